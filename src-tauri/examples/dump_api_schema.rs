@@ -0,0 +1,15 @@
+//! Write the IPC schema to the path given as the first argument, or to stdout.
+//!
+//! cargo run --example dump_api_schema --features api-schema-dump -- api-schema.json
+
+fn main() {
+    let schema = serde_json::to_string_pretty(&opcode_lib::api_schema::generate())
+        .expect("failed to serialize API schema");
+    match std::env::args().nth(1) {
+        Some(path) => {
+            std::fs::write(&path, schema).expect("failed to write API schema");
+            eprintln!("API schema written to {}", path);
+        }
+        None => println!("{}", schema),
+    }
+}
@@ -0,0 +1,1060 @@
+//! Machine-readable description of the IPC surface.
+//!
+//! Commands are registered once, in [`with_registered_commands!`]: `main.rs`
+//! builds the invoke handler from that list and [`generate`] describes the
+//! same commands, so the schema can't drift from what the frontend can
+//! actually invoke. Commands are marked `#[apply(api_command!)]` and payload
+//! types `#[apply(api_type!)]`; both macros keep the item as written and
+//! record its signature or fields next to it. Parameter names use the
+//! camelCase keys Tauri expects on the wire, and payload types carry their
+//! serde renames.
+
+use serde_json::{json, Map, Value};
+
+/// Schema layout version, bumped when the JSON shape changes
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Pass every registered command, as a path from the crate root, to `$callback!`
+macro_rules! with_registered_commands {
+    ($callback:ident) => {
+        $callback! {
+            // Claude & Project Management
+            commands::claude::list_projects,
+            commands::claude::create_project,
+            commands::claude::resolve_project_path,
+            commands::claude::get_project_sessions,
+            commands::claude::get_home_directory,
+            commands::claude::get_claude_settings,
+            commands::claude_config::get_claude_config,
+            commands::claude_config::set_claude_config,
+            commands::claude::open_new_session,
+            commands::claude::get_system_prompt,
+            commands::claude::check_claude_version,
+            commands::claude::check_claude_dir_health,
+            commands::claude::get_claude_dir_settings,
+            commands::claude::switch_claude_dir,
+            commands::claude::save_system_prompt,
+            commands::claude::save_claude_settings,
+            commands::claude::find_claude_md_files,
+            commands::claude::read_claude_md_file,
+            commands::claude::save_claude_md_file,
+            commands::claude::load_session_history,
+            commands::claude::execute_claude_code,
+            commands::claude::continue_claude_code,
+            commands::claude::resume_claude_code,
+            commands::claude::cancel_claude_execution,
+            commands::claude::send_session_input,
+            commands::claude::close_session_input,
+            commands::claude::list_running_claude_sessions,
+            commands::claude::get_claude_session_output,
+            commands::claude::list_directory_contents,
+            commands::claude::search_files,
+            commands::claude::get_recently_modified_files,
+            commands::claude::get_hooks_config,
+            commands::claude::update_hooks_config,
+            commands::claude::validate_hook_command,
+            commands::compaction::estimate_session_context,
+            commands::compaction::compact_session,
+            commands::session_meta::get_session_metadata,
+            commands::session_meta::set_session_metadata,
+            commands::session_meta::generate_session_title,
+            commands::session_meta::export_session_metadata,
+            commands::session_meta::import_session_metadata,
+            commands::session_export::export_session,
+            commands::prompt_analysis::analyze_agent_prompt,
+            commands::prompt_analysis::get_prompt_soft_limits,
+            commands::prompt_analysis::set_prompt_soft_limits,
+            commands::prompt_analysis::analyze_prompt_risk,
+            commands::prompt_analysis::get_prompt_risk_keywords,
+            commands::prompt_analysis::set_prompt_risk_keywords,
+            commands::execution_profiles::list_execution_profiles,
+            commands::execution_profiles::create_execution_profile,
+            commands::execution_profiles::update_execution_profile,
+            commands::execution_profiles::delete_execution_profile,
+            commands::session_import::import_session_file,
+            commands::session_import::get_session_import_max_bytes,
+            commands::session_import::set_session_import_max_bytes,
+            commands::connectivity::run_connectivity_doctor,
+            commands::usage_index::build_usage_index,
+            commands::usage_index::cancel_usage_index_build,
+            commands::usage_index::get_usage_index_status,
+            commands::project_merge::find_duplicate_projects,
+            commands::project_merge::merge_projects,
+            commands::workspace_backup::export_workspace,
+            commands::workspace_backup::import_workspace,
+            commands::tool_stats::get_run_mcp_stats,
+            commands::tool_stats::get_agent_tool_stats,
+            commands::terminal::open_terminal_at,
+            commands::editor::open_in_editor,
+            commands::models::get_available_models,
+            commands::models::get_model_capabilities,
+            commands::performance::get_agent_performance,
+            commands::performance::get_agents_leaderboard,
+            commands::jobs::list_jobs,
+            commands::jobs::get_job,
+            commands::jobs::cancel_job,
+            commands::agent_watchers::create_agent_watcher,
+            commands::agent_watchers::set_agent_watcher_enabled,
+            commands::agent_watchers::list_agent_watchers,
+            commands::agent_watchers::delete_agent_watcher,
+
+            // Checkpoint Management
+            commands::claude::create_checkpoint,
+            commands::claude::restore_checkpoint,
+            commands::claude::list_checkpoints,
+            commands::claude::fork_from_checkpoint,
+            commands::claude::get_session_timeline,
+            commands::claude::update_checkpoint_settings,
+            commands::claude::get_checkpoint_diff,
+            commands::claude::get_file_diff,
+            commands::claude::find_checkpoints_touching_file,
+            commands::claude::get_content_pool_stats,
+            commands::claude::gc_content_pool,
+            commands::claude::find_orphaned_checkpoint_data,
+            commands::claude::purge_orphaned_checkpoint_data,
+            commands::claude::track_checkpoint_message,
+            commands::claude::track_session_messages,
+            commands::claude::check_auto_checkpoint,
+            commands::claude::cleanup_old_checkpoints,
+            commands::claude::get_checkpoint_settings,
+            commands::claude::clear_checkpoint_manager,
+            commands::claude::cancel_checkpoint_creation,
+            commands::claude::get_checkpoint_state_stats,
+            commands::claude::repair_timeline,
+
+            // Agent Management
+            commands::agents::list_agents,
+            commands::agents::create_agent,
+            commands::agents::update_agent,
+            commands::agents::delete_agent,
+            commands::agents::delete_agent_run,
+            commands::agents::delete_agent_runs,
+            commands::agents::get_agent,
+            commands::agents::get_agent_task_template,
+            commands::agents::execute_agent,
+            commands::agents::validate_agent_execution,
+            commands::agents::list_agent_runs,
+            commands::agents::get_agent_run,
+            commands::agents::list_agent_runs_with_metrics,
+            commands::agents::get_agent_run_with_real_time_metrics,
+            commands::agents::list_running_sessions,
+            commands::agents::get_execution_status_summary,
+            commands::agents::kill_agent_session,
+            commands::agents::pause_agent_run,
+            commands::agents::resume_agent_run,
+            commands::agents::retry_agent_run,
+            commands::agents::get_session_status,
+            commands::agents::cleanup_finished_processes,
+            commands::agents::get_session_output,
+            commands::agents::get_session_output_delta,
+            commands::agents::save_output_cursor,
+            commands::agents::get_live_session_output,
+            commands::agents::stream_session_output,
+            commands::agents::load_agent_session_history,
+            commands::agents::get_claude_binary_path,
+            commands::agents::set_claude_binary_path,
+            commands::agents::get_stall_warning_seconds,
+            commands::agents::set_stall_warning_seconds,
+            commands::agents::get_startup_timeout_seconds,
+            commands::agents::set_startup_timeout_seconds,
+            commands::agents::list_claude_installations,
+            commands::agents::export_agent,
+            commands::agents::export_agent_to_file,
+            commands::agents::import_agent,
+            commands::agents::import_agent_from_file,
+            commands::agents::fetch_github_agents,
+            commands::agents::fetch_github_agent_content,
+            commands::agents::import_agent_from_github,
+            commands::agents::list_agent_sources,
+            commands::agents::add_agent_source,
+            commands::agents::remove_agent_source,
+            commands::agents::has_github_token,
+            commands::agents::set_github_token,
+            commands::worktrees::get_worktree_changes,
+            commands::worktrees::apply_worktree_changes,
+            commands::worktrees::cleanup_agent_worktrees,
+
+            // Project Environment
+            commands::project_env::get_project_env,
+            commands::project_env::set_project_env,
+            commands::project_env::preview_claude_env,
+
+            // Notices
+            commands::notices::get_app_notices,
+            commands::notices::dismiss_app_notice,
+
+            // Output Redaction
+            commands::redaction::get_redaction_settings,
+            commands::redaction::set_redaction_patterns,
+
+            // Usage & Analytics
+            commands::usage::get_usage_stats,
+            commands::usage::get_usage_by_date_range,
+            commands::usage::get_usage_details,
+            commands::usage::get_session_stats,
+            commands::activity::get_project_activity,
+            commands::saved_queries::list_saved_queries,
+            commands::saved_queries::save_saved_query,
+            commands::saved_queries::delete_saved_query,
+            commands::saved_queries::run_saved_query,
+
+            // MCP (Model Context Protocol)
+            commands::mcp::mcp_add,
+            commands::mcp::mcp_list,
+            commands::mcp::mcp_get,
+            commands::mcp::mcp_remove,
+            commands::mcp::mcp_add_json,
+            commands::mcp::mcp_add_from_claude_desktop,
+            commands::mcp::mcp_preview_claude_desktop_import,
+            commands::mcp::mcp_apply_claude_desktop_import,
+            commands::mcp::mcp_serve,
+            commands::mcp::mcp_test_connection,
+            commands::mcp::mcp_reset_project_choices,
+            commands::mcp::mcp_get_server_status,
+            commands::mcp::mcp_read_project_config,
+            commands::mcp::mcp_save_project_config,
+            commands::mcp::get_agent_mcp_settings,
+            commands::mcp::set_agent_mcp_settings,
+
+            // Storage Management
+            commands::storage::storage_list_tables,
+            commands::storage::storage_read_table,
+            commands::storage::storage_update_row,
+            commands::storage::storage_delete_row,
+            commands::storage::storage_insert_row,
+            commands::storage::storage_execute_sql,
+            commands::storage::storage_reset_database,
+
+            // Project Templates
+            commands::templates::list_project_templates,
+            commands::templates::save_project_template,
+            commands::templates::create_project_from_template,
+            commands::templates::inspect_project_directory,
+            commands::templates::initialize_project_claude_dir,
+
+            // Slash Commands
+            commands::slash_commands::slash_commands_list,
+            commands::slash_commands::slash_command_get,
+            commands::slash_commands::slash_command_save,
+            commands::slash_commands::slash_command_delete,
+
+            // Proxy Settings
+            commands::proxy::get_proxy_settings,
+            commands::proxy::save_proxy_settings,
+
+            // Localization
+            commands::i18n::get_backend_locale,
+            commands::i18n::set_backend_locale,
+
+            // API Schema
+            commands::schema::generate_api_schema,
+
+            // Startup
+            startup::get_startup_status,
+        }
+    };
+}
+pub(crate) use with_registered_commands;
+
+/// Keep a command as written and record its signature for the schema.
+///
+/// Goes above `#[tauri::command]`. The record is a `COMMAND` const in a
+/// hidden module named after the function, which doesn't clash with it
+/// because modules and functions live in different namespaces.
+macro_rules! api_command {
+    ($($item:tt)*) => {
+        $($item)*
+        $crate::api_schema::describe_command! { $($item)* }
+    };
+}
+pub(crate) use api_command;
+
+macro_rules! describe_command {
+    (
+        $(#[$attr:meta])*
+        $vis:vis $(async)? fn $name:ident($($param:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $body:block
+    ) => {
+        #[doc(hidden)]
+        $vis mod $name {
+            pub const COMMAND: $crate::api_schema::CommandDescriptor =
+                $crate::api_schema::CommandDescriptor {
+                    name: stringify!($name),
+                    module_path: module_path!(),
+                    attrs: &[$(stringify!($attr)),*],
+                    params: &[$((stringify!($param), stringify!($ty))),*],
+                    returns: stringify!($($ret)?),
+                };
+        }
+    };
+}
+pub(crate) use describe_command;
+
+/// Keep a serialized struct or enum as written and record its fields for the schema.
+///
+/// Goes above `#[derive(...)]` so the serde attributes are part of the record.
+macro_rules! api_type {
+    ($($item:tt)*) => {
+        $($item)*
+        $crate::api_schema::describe_type! { $($item)* }
+    };
+}
+pub(crate) use api_type;
+
+macro_rules! describe_type {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        impl $crate::api_schema::ApiType for $name {
+            const DESCRIPTOR: $crate::api_schema::TypeDescriptor = $crate::api_schema::TypeDescriptor {
+                name: stringify!($name),
+                module_path: module_path!(),
+                attrs: &[$(stringify!($attr)),*],
+                shape: $crate::api_schema::TypeShape::Struct(&[$(
+                    $crate::api_schema::MemberDescriptor {
+                        name: stringify!($field),
+                        ty: stringify!($ty),
+                        attrs: &[$(stringify!($field_attr)),*],
+                    }
+                ),*]),
+            };
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident $(($($tuple:tt)*))? $({$($fields:tt)*})? $(= $discriminant:expr)?
+            ),* $(,)?
+        }
+    ) => {
+        impl $crate::api_schema::ApiType for $name {
+            const DESCRIPTOR: $crate::api_schema::TypeDescriptor = $crate::api_schema::TypeDescriptor {
+                name: stringify!($name),
+                module_path: module_path!(),
+                attrs: &[$(stringify!($attr)),*],
+                shape: $crate::api_schema::TypeShape::Enum(&[$(
+                    $crate::api_schema::MemberDescriptor {
+                        name: stringify!($variant),
+                        ty: stringify!($(($($tuple)*))? $({$($fields)*})?),
+                        attrs: &[$(stringify!($variant_attr)),*],
+                    }
+                ),*]),
+            };
+        }
+    };
+}
+pub(crate) use describe_type;
+
+/// Records of the registered commands, in registration order
+macro_rules! describe_commands {
+    ($($($segment:ident)::+),* $(,)?) => {
+        [$(&crate::$($segment)::+::COMMAND),*]
+    };
+}
+
+/// Records of the given payload types
+macro_rules! describe_types {
+    ($($($segment:ident)::+),* $(,)?) => {
+        [$(&<crate::$($segment)::+ as ApiType>::DESCRIPTOR),*]
+    };
+}
+
+/// What `api_command!` records about a command
+pub struct CommandDescriptor {
+    pub name: &'static str,
+    /// Path of the module holding the record, `<module>::<command>`
+    pub module_path: &'static str,
+    /// Attributes as written, doc comments included
+    pub attrs: &'static [&'static str],
+    /// Parameter names and types
+    pub params: &'static [(&'static str, &'static str)],
+    /// Return type, empty for `()`
+    pub returns: &'static str,
+}
+
+/// What `api_type!` records about a payload type
+pub struct TypeDescriptor {
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub attrs: &'static [&'static str],
+    pub shape: TypeShape,
+}
+
+pub enum TypeShape {
+    Struct(&'static [MemberDescriptor]),
+    Enum(&'static [MemberDescriptor]),
+}
+
+/// A struct field, or an enum variant with its payload as `ty`
+pub struct MemberDescriptor {
+    pub name: &'static str,
+    pub ty: &'static str,
+    pub attrs: &'static [&'static str],
+}
+
+/// Implemented by `api_type!` for every payload type it marks
+pub trait ApiType {
+    const DESCRIPTOR: TypeDescriptor;
+}
+
+/// Parameter types injected by Tauri rather than sent by the frontend
+const INJECTED_TYPES: &[&str] = &[
+    "State<",
+    "tauri::State<",
+    "AppHandle",
+    "tauri::AppHandle",
+    "Window",
+    "tauri::Window",
+    "WebviewWindow",
+    "tauri::WebviewWindow",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    name: String,
+    rust_name: String,
+    ty: String,
+    optional: bool,
+}
+
+impl Field {
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "rustName": self.rust_name,
+            "type": self.ty,
+            "optional": self.optional,
+        })
+    }
+}
+
+/// Lay out a stringified type the way it is usually written
+fn normalize_type(ty: &str) -> String {
+    let compact: String = ty.split_whitespace().collect::<Vec<_>>().join(" ");
+    let chars: Vec<char> = compact.chars().collect();
+    let mut out = String::with_capacity(compact.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c == ' ' {
+            let after_open = i > 0 && "<([&".contains(chars[i - 1]);
+            let before_close = chars.get(i + 1).is_some_and(|next| "<>,)]".contains(*next));
+            if after_open || before_close {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out.replace(" :: ", "::").replace(",}", "}").replace(",)", ")")
+}
+
+/// Split on commas that aren't nested in brackets
+fn split_top_level(list: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut current = String::new();
+    for c in list.chars() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current);
+    parts
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// `Option<T>` parameters and fields may be left out
+fn is_optional(ty: &str) -> bool {
+    ty.starts_with("Option<")
+}
+
+fn to_camel_case(snake: &str) -> String {
+    let mut out = String::with_capacity(snake.len());
+    for (i, part) in snake.split('_').filter(|p| !p.is_empty()).enumerate() {
+        if i == 0 {
+            out.push_str(part);
+        } else {
+            let mut chars = part.chars();
+            if let Some(first) = chars.next() {
+                out.extend(first.to_uppercase());
+                out.push_str(chars.as_str());
+            }
+        }
+    }
+    out
+}
+
+fn pascal_to_snake(pascal: &str) -> String {
+    let mut out = String::with_capacity(pascal.len() + 4);
+    for (i, c) in pascal.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Apply a serde `rename_all` rule to a field (snake_case) or variant (PascalCase)
+fn apply_rename_all(ident: &str, rule: Option<&str>, is_variant: bool) -> String {
+    let snake = if is_variant {
+        pascal_to_snake(ident)
+    } else {
+        ident.to_string()
+    };
+    match rule {
+        Some("camelCase") => to_camel_case(&snake),
+        Some("snake_case") => snake,
+        Some("lowercase") => snake.replace('_', ""),
+        Some("UPPERCASE") => snake.replace('_', "").to_uppercase(),
+        Some("kebab-case") => snake.replace('_', "-"),
+        Some("SCREAMING_SNAKE_CASE") => snake.to_uppercase(),
+        Some("PascalCase") => {
+            let camel = to_camel_case(&snake);
+            let mut chars = camel.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+        _ => ident.to_string(),
+    }
+}
+
+/// Options of the `#[serde(...)]` attributes on an item
+#[derive(Debug, Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    tag: Option<String>,
+    untagged: bool,
+    skip: bool,
+    default: bool,
+    flatten: bool,
+}
+
+impl SerdeAttrs {
+    fn absorb(&mut self, attr: &str) {
+        let Some(inner) = attr
+            .trim()
+            .strip_prefix("serde(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        else {
+            return;
+        };
+        for option in split_top_level(inner) {
+            let (key, value) = match option.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim().trim_matches('"').to_string())),
+                None => (option.trim(), None),
+            };
+            match key {
+                "rename" => self.rename = value,
+                "rename_all" => self.rename_all = value,
+                "tag" => self.tag = value,
+                "untagged" => self.untagged = true,
+                "skip" | "skip_serializing" | "skip_deserializing" => self.skip = true,
+                "default" | "skip_serializing_if" => self.default = true,
+                "flatten" => self.flatten = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Text of the doc comments among recorded attributes
+fn doc_comment(attrs: &[&str]) -> String {
+    let docs: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| attr.strip_prefix("doc"))
+        .filter_map(|rest| rest.trim_start().strip_prefix('='))
+        .map(|literal| {
+            literal
+                .trim()
+                .strip_prefix('"')
+                .and_then(|literal| literal.strip_suffix('"'))
+                .unwrap_or(literal)
+                .replace("\\\"", "\"")
+                .replace("\\'", "'")
+                .replace("\\\\", "\\")
+                .trim()
+                .to_string()
+        })
+        .collect();
+    docs.join(" ").trim().to_string()
+}
+
+/// serde options among recorded attributes
+fn serde_attrs(attrs: &[&str]) -> SerdeAttrs {
+    let mut serde = SerdeAttrs::default();
+    for attr in attrs {
+        serde.absorb(attr);
+    }
+    serde
+}
+
+/// Last segment of a module path
+fn module_name(module_path: &str) -> &str {
+    module_path.rsplit("::").next().unwrap_or(module_path)
+}
+
+fn command_json(command: &CommandDescriptor) -> Value {
+    let params: Vec<Value> = command
+        .params
+        .iter()
+        .map(|(ident, ty)| (*ident, normalize_type(ty)))
+        .filter(|(_, ty)| !INJECTED_TYPES.iter().any(|injected| ty.starts_with(injected)))
+        .map(|(ident, ty)| {
+            Field {
+                name: to_camel_case(ident),
+                rust_name: ident.to_string(),
+                optional: is_optional(&ty),
+                ty,
+            }
+            .to_json()
+        })
+        .collect();
+
+    let returns = match normalize_type(command.returns) {
+        returns if returns.is_empty() => "()".to_string(),
+        returns => returns,
+    };
+    let (returns, error) = match returns
+        .strip_prefix("Result<")
+        .and_then(|inner| inner.strip_suffix('>'))
+        .map(split_top_level)
+    {
+        Some(parts) if parts.len() == 2 => (parts[0].clone(), Some(parts[1].clone())),
+        _ => (returns, None),
+    };
+
+    // The record sits in a module named after the command, next to it
+    let module = command
+        .module_path
+        .rsplit("::")
+        .nth(1)
+        .unwrap_or(command.module_path);
+
+    json!({
+        "name": command.name,
+        "module": module,
+        "description": doc_comment(command.attrs),
+        "params": params,
+        "returns": returns,
+        "error": error,
+    })
+}
+
+fn type_json(descriptor: &TypeDescriptor) -> Value {
+    let serde = serde_attrs(descriptor.attrs);
+    let mut item = json!({
+        "module": module_name(descriptor.module_path),
+        "renameAll": serde.rename_all,
+    });
+    match descriptor.shape {
+        TypeShape::Struct(fields) => {
+            let fields: Vec<Value> = fields
+                .iter()
+                .filter_map(|field| {
+                    let attrs = serde_attrs(field.attrs);
+                    if attrs.skip {
+                        return None;
+                    }
+                    let ty = normalize_type(field.ty);
+                    let mut json = Field {
+                        name: attrs.rename.unwrap_or_else(|| {
+                            apply_rename_all(field.name, serde.rename_all.as_deref(), false)
+                        }),
+                        rust_name: field.name.to_string(),
+                        optional: is_optional(&ty) || attrs.default,
+                        ty,
+                    }
+                    .to_json();
+                    if attrs.flatten {
+                        json["flatten"] = Value::Bool(true);
+                    }
+                    Some(json)
+                })
+                .collect();
+            item["kind"] = json!("struct");
+            item["fields"] = Value::Array(fields);
+        }
+        TypeShape::Enum(variants) => {
+            let variants: Vec<Value> = variants
+                .iter()
+                .filter_map(|variant| {
+                    let attrs = serde_attrs(variant.attrs);
+                    if attrs.skip {
+                        return None;
+                    }
+                    let payload = normalize_type(variant.ty);
+                    Some(json!({
+                        "name": attrs.rename.unwrap_or_else(|| {
+                            apply_rename_all(variant.name, serde.rename_all.as_deref(), true)
+                        }),
+                        "rustName": variant.name,
+                        "payload": if payload.is_empty() { Value::Null } else { Value::String(payload) },
+                    }))
+                })
+                .collect();
+            item["kind"] = json!("enum");
+            item["variants"] = Value::Array(variants);
+            if let Some(tag) = serde.tag {
+                item["tag"] = Value::String(tag);
+            }
+            if serde.untagged {
+                item["untagged"] = Value::Bool(true);
+            }
+        }
+    }
+    item
+}
+
+/// Build the schema of every registered command and the payload types they use.
+///
+/// Commands are listed in registration order.
+pub fn generate() -> Value {
+    let commands: Vec<Value> = self::with_registered_commands!(describe_commands)
+        .into_iter()
+        .map(command_json)
+        .collect();
+
+    let payload_types = describe_types![
+        checkpoint::changes::RunChangeSummary,
+        checkpoint::file_index::FileChangeType,
+        checkpoint::file_index::CheckpointFileMatch,
+        checkpoint::Checkpoint,
+        checkpoint::CheckpointKind,
+        checkpoint::CheckpointRetention,
+        checkpoint::CheckpointMetadata,
+        checkpoint::TimelineNode,
+        checkpoint::SessionTimeline,
+        checkpoint::CheckpointStrategy,
+        checkpoint::CheckpointResult,
+        checkpoint::TimelineRepairSource,
+        checkpoint::TimelineRepairReport,
+        checkpoint::OrphanedCheckpointData,
+        checkpoint::ContentPoolStats,
+        checkpoint::CheckpointDiff,
+        checkpoint::FileDiff,
+        claude_binary::InstallationType,
+        claude_binary::ClaudeInstallation,
+        claude_dir::ClaudeDirSettings,
+        commands::activity::ActivityKind,
+        commands::activity::ActivityRange,
+        commands::activity::ActivityEntry,
+        commands::agent_watchers::AgentWatcher,
+        commands::agents::Agent,
+        commands::agents::AgentRun,
+        commands::agents::AgentRunMetrics,
+        commands::agents::AgentRunWithMetrics,
+        commands::agents::MalformedLine,
+        commands::agents::JsonlParseReport,
+        commands::agents::AgentExport,
+        commands::agents::AgentData,
+        commands::agents::TaskTemplateVariable,
+        commands::agents::TaskTemplate,
+        commands::agents::ValidationCheck,
+        commands::agents::OutputSource,
+        commands::agents::SessionOutputDelta,
+        commands::agents::GitHubAgentFile,
+        commands::agents::AgentSource,
+        commands::claude::ClaudeOutputFormat,
+        commands::claude::Project,
+        commands::claude::ProjectListDetail,
+        commands::claude::Session,
+        commands::claude::ClaudeSettings,
+        commands::claude::ClaudeVersionStatus,
+        commands::claude::ClaudeDirProblem,
+        commands::claude::ClaudeDirHealth,
+        commands::claude::ClaudeMdFile,
+        commands::claude::FileEntry,
+        commands::claude::NormalizedProjectPath,
+        commands::claude_config::ClaudeConfigEntry,
+        commands::compaction::EstimateMethod,
+        commands::compaction::ContextEstimate,
+        commands::compaction::CompactionResult,
+        commands::connectivity::CheckStatus,
+        commands::connectivity::ConnectivityCheck,
+        commands::connectivity::ProxyVariable,
+        commands::connectivity::ConnectivityReport,
+        commands::execution_profiles::ExecutionProfile,
+        commands::execution_profiles::ExecutionProfileInput,
+        commands::i18n::Locale,
+        commands::jobs::JobStatus,
+        commands::jobs::Job,
+        commands::mcp::MCPServer,
+        commands::mcp::ServerStatus,
+        commands::mcp::MCPProjectConfig,
+        commands::mcp::MCPServerConfig,
+        commands::mcp::AddServerResult,
+        commands::mcp::ImportResult,
+        commands::mcp::ImportServerResult,
+        commands::mcp::ImportConflictStrategy,
+        commands::mcp::DesktopImportCandidate,
+        commands::mcp::DesktopImportPreview,
+        commands::mcp::McpInjectionMode,
+        commands::mcp::AgentMcpSettings,
+        commands::models::ModelCapabilities,
+        commands::notices::NoticeKind,
+        commands::notices::AppNotice,
+        commands::performance::PerformanceBucket,
+        commands::performance::LeaderboardSort,
+        commands::performance::RunPerformance,
+        commands::performance::AgentPerformancePoint,
+        commands::performance::AgentLeaderboardEntry,
+        commands::project_env::EnvSource,
+        commands::project_env::EnvEntry,
+        commands::project_merge::DuplicateProjectGroup,
+        commands::project_merge::MergedItemKind,
+        commands::project_merge::MergedItem,
+        commands::project_merge::ProjectMergeReport,
+        commands::prompt_analysis::PromptSoftLimits,
+        commands::prompt_analysis::PromptComponentKind,
+        commands::prompt_analysis::PromptComponent,
+        commands::prompt_analysis::PromptLimitWarning,
+        commands::prompt_analysis::PromptAnalysis,
+        commands::prompt_analysis::PromptRiskLevel,
+        commands::prompt_analysis::PromptRiskReason,
+        commands::prompt_analysis::PromptRisk,
+        commands::proxy::ProxySettings,
+        commands::redaction::RedactionSettings,
+        commands::saved_queries::SavedQuery,
+        commands::saved_queries::SavedQueryResult,
+        commands::session_export::SessionExportFilters,
+        commands::session_export::SessionExportFormat,
+        commands::session_export::SessionExportRemoved,
+        commands::session_export::SessionExportManifest,
+        commands::session_import::SessionImport,
+        commands::session_meta::SessionMetadata,
+        commands::slash_commands::SlashCommand,
+        commands::storage::TableInfo,
+        commands::storage::ColumnInfo,
+        commands::storage::TableData,
+        commands::storage::QueryResult,
+        commands::templates::TemplateIncludes,
+        commands::templates::ProjectTemplate,
+        commands::templates::TemplateApplyResult,
+        commands::templates::ProjectDirectoryInfo,
+        commands::templates::ProjectInitOptions,
+        commands::templates::ProjectInitResult,
+        commands::tool_stats::McpToolStats,
+        commands::usage::UsageEntry,
+        commands::usage::UsageStats,
+        commands::usage::ModelUsage,
+        commands::usage::DailyUsage,
+        commands::usage::ProjectUsage,
+        commands::usage::UsageDetailsPage,
+        commands::usage_index::UsageIndexProgress,
+        commands::usage_index::UsageIndexStatus,
+        commands::workspace_backup::WorkspaceSections,
+        commands::workspace_backup::SectionReport,
+        commands::worktrees::WorktreeChangeStatus,
+        commands::worktrees::WorktreeChange,
+        commands::worktrees::WorktreeDiff,
+        commands::worktrees::WorktreeApplyResult,
+        commands::worktrees::WorktreeCleanupResult,
+        process::registry::ProcessType,
+        process::registry::ProcessInfo,
+        process::registry::ExecutionStatusSummary,
+        startup::StartupPhase,
+        startup::StartupProgress,
+        startup::StartupStatus,
+    ];
+    let mut types = Map::new();
+    for descriptor in payload_types {
+        types.insert(descriptor.name.to_string(), type_json(descriptor));
+    }
+
+    json!({
+        "version": SCHEMA_VERSION,
+        "commands": commands,
+        "types": types,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macro_rules_attribute::apply;
+    use serde::Serialize;
+    use std::collections::{HashMap, HashSet};
+
+    fn command<'a>(schema: &'a Value, name: &str) -> &'a Value {
+        schema["commands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == name)
+            .unwrap_or_else(|| panic!("{} missing from schema", name))
+    }
+
+    #[test]
+    fn schema_covers_every_registered_command_in_wire_format() {
+        let schema = generate();
+        let registered = self::with_registered_commands!(describe_commands);
+        assert_eq!(schema["commands"].as_array().unwrap().len(), registered.len());
+        for module in ["agents", "claude", "usage", "mcp"] {
+            assert!(schema["commands"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|c| c["module"] == module));
+        }
+
+        // Injected state is left out and names are camelCased
+        let execute = command(&schema, "execute_claude_code");
+        assert!(execute["description"].as_str().unwrap().starts_with("Execute"));
+        let params: Vec<_> = execute["params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert!(params.contains(&"projectPath"));
+        assert!(params.contains(&"envOverrides"));
+        assert!(!params.contains(&"app"));
+
+        let details = command(&schema, "get_usage_details");
+        assert_eq!(details["params"][0]["optional"], true);
+        assert_eq!(details["error"], "String");
+
+        // Payload types keep serde renames
+        let timeline = &schema["types"]["SessionTimeline"];
+        assert_eq!(timeline["module"], "checkpoint");
+        assert_eq!(timeline["renameAll"], "camelCase");
+        assert_eq!(timeline["fields"][0]["name"], "sessionId");
+        let format = &schema["types"]["ClaudeOutputFormat"]["variants"];
+        assert_eq!(format[0]["name"], "stream-json");
+    }
+
+    #[test]
+    fn every_payload_type_is_described() {
+        // Names that are std, dependency types or aliases rather than our payloads
+        let external: HashSet<&str> = [
+            "Option", "Vec", "String", "HashMap", "BTreeMap", "Map", "Value", "JsonValue",
+            "PathBuf", "DateTime", "Utc", "PromptRiskKeywords",
+        ]
+        .into_iter()
+        .collect();
+        let schema = generate();
+        let types = schema["types"].as_object().unwrap();
+
+        let mut referenced = Vec::new();
+        for command in schema["commands"].as_array().unwrap() {
+            referenced.push(command["returns"].as_str().unwrap().to_string());
+            for param in command["params"].as_array().unwrap() {
+                referenced.push(param["type"].as_str().unwrap().to_string());
+            }
+        }
+        for item in types.values() {
+            for field in item["fields"].as_array().into_iter().flatten() {
+                referenced.push(field["type"].as_str().unwrap().to_string());
+            }
+            for variant in item["variants"].as_array().into_iter().flatten() {
+                referenced.extend(variant["payload"].as_str().map(str::to_string));
+            }
+        }
+
+        for ty in referenced {
+            for name in ty.split(|c: char| !c.is_alphanumeric() && c != '_') {
+                if name.starts_with(|c: char| c.is_ascii_uppercase()) && !external.contains(name) {
+                    assert!(types.contains_key(name), "{} is not marked #[apply(api_type!)]", name);
+                }
+            }
+        }
+    }
+
+    #[allow(dead_code, unused_variables)]
+    mod fixture {
+        use super::*;
+
+        /// Do a thing
+        /// with "quotes"
+        #[apply(api_command!)]
+        pub async fn do_thing(
+            app: tauri::AppHandle,
+            item_path: String,
+            limits: Option<HashMap<String, Vec<u32>>>,
+        ) -> Result<Vec<(String, i64)>, String> {
+            Ok(vec![])
+        }
+
+        #[apply(api_command!)]
+        pub fn do_nothing() {}
+
+        #[apply(api_type!)]
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct Thing {
+            pub item_path: String,
+            #[serde(rename = "type")]
+            pub kind: Kind,
+            #[serde(skip)]
+            pub cache: Vec<u8>,
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            pub tags: Vec<String>,
+        }
+
+        #[apply(api_type!)]
+        #[derive(Serialize)]
+        #[serde(rename_all = "snake_case", tag = "type")]
+        pub enum Kind {
+            /// Repaired
+            TimelineRepair,
+            Failed { reason: String },
+            #[serde(rename = "other")]
+            Unknown,
+        }
+    }
+
+    #[test]
+    fn macros_record_signatures_and_renames() {
+        let cmd = command_json(&fixture::do_thing::COMMAND);
+        assert_eq!(cmd["module"], "fixture");
+        assert_eq!(cmd["description"], "Do a thing with \"quotes\"");
+        assert_eq!(cmd["returns"], "Vec<(String, i64)>");
+        assert_eq!(cmd["error"], "String");
+        let params = cmd["params"].as_array().unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0]["name"], "itemPath");
+        assert_eq!(params[0]["rustName"], "item_path");
+        assert_eq!(params[1]["type"], "Option<HashMap<String, Vec<u32>>>");
+        assert_eq!(params[1]["optional"], true);
+
+        let nothing = command_json(&fixture::do_nothing::COMMAND);
+        assert_eq!(nothing["returns"], "()");
+        assert_eq!(nothing["error"], Value::Null);
+
+        let thing = type_json(&<fixture::Thing as ApiType>::DESCRIPTOR);
+        assert_eq!(thing["kind"], "struct");
+        let fields = thing["fields"].as_array().unwrap();
+        let names: Vec<_> = fields.iter().map(|f| f["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["itemPath", "type", "tags"]);
+        assert_eq!(fields[2]["optional"], true);
+
+        let kind = type_json(&<fixture::Kind as ApiType>::DESCRIPTOR);
+        assert_eq!(kind["tag"], "type");
+        let variants = kind["variants"].as_array().unwrap();
+        assert_eq!(variants[0]["name"], "timeline_repair");
+        assert_eq!(variants[0]["payload"], Value::Null);
+        assert_eq!(variants[1]["payload"], "{reason: String}");
+        assert_eq!(variants[2]["name"], "other");
+        assert_eq!(apply_rename_all("allowed_tools", Some("kebab-case"), false), "allowed-tools");
+    }
+}
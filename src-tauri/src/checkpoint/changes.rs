@@ -0,0 +1,207 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::api_schema::api_type;
+use macro_rules_attribute::apply;
+
+use super::manager::resolve_within_roots;
+use super::storage::CheckpointStorage;
+use super::{FileState, FileTracker, RootedPath};
+
+/// Tools whose `file_path` (or `notebook_path`) input is about to be written
+const WRITING_TOOLS: &[&str] = &["write", "edit", "multiedit", "notebookedit"];
+
+/// What a run did to the files it touched
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunChangeSummary {
+    pub files_modified: usize,
+    pub files_added: usize,
+    pub files_deleted: usize,
+    /// Lines after the run minus lines before, over all changed files
+    pub line_delta: i64,
+}
+
+/// Remembers the state of each file a run touches, as it first touches it,
+/// so the run can be summarized without a checkpoint.
+///
+/// Nothing is written to disk and a run that never writes a file costs
+/// nothing beyond looking at its tool calls.
+pub struct RunChangeTracker {
+    /// The project, then the run's additional directories
+    roots: Vec<PathBuf>,
+    before: FileTracker,
+    lines_before: HashMap<RootedPath, usize>,
+}
+
+impl RunChangeTracker {
+    pub fn new(project_path: impl Into<PathBuf>) -> Self {
+        Self {
+            roots: vec![project_path.into()],
+            before: FileTracker {
+                tracked_files: HashMap::new(),
+            },
+            lines_before: HashMap::new(),
+        }
+    }
+
+    /// Also track files in directories the run was given with `--add-dir`
+    pub fn with_additional_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.roots.extend(dirs);
+        self
+    }
+
+    /// Look at a stream message and record the files its tool calls write.
+    ///
+    /// claude prints the tool call before running it, so this sees the
+    /// files as they were.
+    pub fn observe_message(&mut self, message: &JsonValue) {
+        if message.get("type").and_then(|t| t.as_str()) != Some("assistant") {
+            return;
+        }
+        let blocks = message
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array());
+        for block in blocks.into_iter().flatten() {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                continue;
+            }
+            let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            if !WRITING_TOOLS.contains(&name.to_lowercase().as_str()) {
+                continue;
+            }
+            let input = &block["input"];
+            if let Some(path) = input
+                .get("file_path")
+                .or_else(|| input.get("notebook_path"))
+                .and_then(|p| p.as_str())
+            {
+                self.touch(path);
+            }
+        }
+    }
+
+    /// Record a file's state unless it was already recorded during this run
+    pub fn touch(&mut self, file_path: &str) {
+        let Ok(relative_path) = resolve_within_roots(&self.roots, Path::new(file_path)) else {
+            return;
+        };
+        if self.before.tracked_files.contains_key(&relative_path) {
+            return;
+        }
+        let full_path = self.roots[relative_path.root_index].join(&relative_path.path);
+        let (state, lines) = read_state(&full_path);
+        self.lines_before.insert(relative_path.clone(), lines);
+        self.before.tracked_files.insert(relative_path, state);
+    }
+
+    /// Compare the touched files with their current state, `None` when the
+    /// run touched no files
+    pub fn summarize(&self) -> Option<RunChangeSummary> {
+        if self.before.tracked_files.is_empty() {
+            return None;
+        }
+        let mut summary = RunChangeSummary::default();
+        for (relative_path, before) in &self.before.tracked_files {
+            let root = &self.roots[relative_path.root_index];
+            let (after, lines_after) = read_state(&root.join(&relative_path.path));
+            match (before.exists, after.exists) {
+                (false, true) => summary.files_added += 1,
+                (true, false) => summary.files_deleted += 1,
+                (true, true) if before.last_hash != after.last_hash => {
+                    summary.files_modified += 1
+                }
+                _ => continue,
+            }
+            let lines_before = self.lines_before.get(relative_path).copied().unwrap_or(0);
+            summary.line_delta += lines_after as i64 - lines_before as i64;
+        }
+        Some(summary)
+    }
+}
+
+/// Hash and line count of a file, or an absent state when it doesn't exist
+fn read_state(full_path: &Path) -> (FileState, usize) {
+    let content = fs::read(full_path).ok();
+    let lines = content.as_deref().map(count_lines).unwrap_or(0);
+    let state = FileState {
+        last_hash: content
+            .as_deref()
+            .and_then(|bytes| CheckpointStorage::hash_reader(bytes).ok())
+            .unwrap_or_default(),
+        is_modified: false,
+        last_modified: Utc::now(),
+        exists: content.is_some(),
+    };
+    (state, lines)
+}
+
+/// Number of lines, counting a last line without a newline
+fn count_lines(content: &[u8]) -> usize {
+    let newlines = content.iter().filter(|&&b| b == b'\n').count();
+    match content.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn tool_use(name: &str, path: &Path) -> JsonValue {
+        json!({
+            "type": "assistant",
+            "message": {"content": [
+                {"type": "tool_use", "name": name, "input": {"file_path": path}}
+            ]}
+        })
+    }
+
+    #[test]
+    fn summarizes_touched_files_without_writing_anything() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        fs::write(project.join("edited.rs"), "a\nb\n").unwrap();
+        fs::write(project.join("removed.rs"), "a\nb\nc\n").unwrap();
+        fs::write(project.join("untouched.rs"), "a\n").unwrap();
+
+        let mut tracker = RunChangeTracker::new(project);
+        tracker.observe_message(&json!({"type": "user", "message": {"content": "hi"}}));
+        assert_eq!(tracker.summarize(), None);
+
+        tracker.observe_message(&tool_use("Edit", &project.join("edited.rs")));
+        tracker.observe_message(&tool_use("Write", &project.join("new.rs")));
+        tracker.observe_message(&tool_use("Read", &project.join("untouched.rs")));
+        tracker.observe_message(&tool_use("Edit", Path::new("removed.rs")));
+        fs::write(project.join("edited.rs"), "a\nb\nc\nd").unwrap();
+        // A later call on the same file keeps the state from before the run
+        tracker.observe_message(&tool_use("Edit", &project.join("edited.rs")));
+        fs::write(project.join("new.rs"), "x\ny\n").unwrap();
+        fs::remove_file(project.join("removed.rs")).unwrap();
+        fs::write(project.join("untouched.rs"), "changed\n").unwrap();
+
+        let summary = tracker.summarize().unwrap();
+        assert_eq!(
+            summary,
+            RunChangeSummary {
+                files_modified: 1,
+                files_added: 1,
+                files_deleted: 1,
+                line_delta: 2 + 2 - 3,
+            }
+        );
+        let mut entries: Vec<_> = fs::read_dir(project)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, ["edited.rs", "new.rs", "untouched.rs"]);
+    }
+}
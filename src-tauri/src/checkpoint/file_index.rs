@@ -0,0 +1,203 @@
+//! Which checkpoints of a session touched which files.
+//!
+//! The index lives next to timeline.json as file_index.json and is kept up to
+//! date as checkpoints are saved and removed. It only repeats what the
+//! checkpoints' file references say, so it can always be rebuilt from them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::api_schema::api_type;
+use macro_rules_attribute::apply;
+
+use super::{Checkpoint, FileSnapshot};
+
+/// How a checkpoint changed a file
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeType {
+    /// First snapshot of the file, or it came back after being deleted
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A checkpoint that touched a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileIndexEntry {
+    pub checkpoint_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub change: FileChangeType,
+}
+
+/// A match of `find_checkpoints_touching_file`
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointFileMatch {
+    pub checkpoint_id: String,
+    pub timestamp: DateTime<Utc>,
+    /// Path relative to the project root, with `/` separators; files in an
+    /// additional directory start with `@{root_index}/`
+    pub file_path: String,
+    pub change: FileChangeType,
+}
+
+/// File path → the checkpoints that touched it, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileIndex {
+    pub files: BTreeMap<String, Vec<FileIndexEntry>>,
+}
+
+/// Index key of a snapshot's path, the same on every platform
+fn index_key(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+impl FileIndex {
+    /// Build the index of a session from all its checkpoints
+    pub fn build<'a>(checkpoints: impl IntoIterator<Item = (&'a Checkpoint, Vec<FileSnapshot>)>) -> Self {
+        let mut checkpoints: Vec<_> = checkpoints.into_iter().collect();
+        checkpoints.sort_by_key(|(checkpoint, _)| checkpoint.timestamp);
+        let mut index = Self::default();
+        for (checkpoint, snapshots) in checkpoints {
+            index.add_checkpoint(checkpoint, &snapshots);
+        }
+        index
+    }
+
+    /// Record the files a checkpoint snapshotted
+    pub fn add_checkpoint(&mut self, checkpoint: &Checkpoint, snapshots: &[FileSnapshot]) {
+        for snapshot in snapshots {
+            let entries = self.files.entry(index_key(&snapshot.rooted_path().label())).or_default();
+            entries.retain(|entry| entry.checkpoint_id != checkpoint.id);
+            let position = entries.partition_point(|entry| entry.timestamp <= checkpoint.timestamp);
+            let change = if snapshot.is_deleted {
+                FileChangeType::Deleted
+            } else {
+                match position.checked_sub(1).map(|previous| entries[previous].change) {
+                    None | Some(FileChangeType::Deleted) => FileChangeType::Added,
+                    Some(_) => FileChangeType::Modified,
+                }
+            };
+            entries.insert(
+                position,
+                FileIndexEntry {
+                    checkpoint_id: checkpoint.id.clone(),
+                    timestamp: checkpoint.timestamp,
+                    change,
+                },
+            );
+        }
+    }
+
+    /// Forget a removed checkpoint
+    pub fn remove_checkpoint(&mut self, checkpoint_id: &str) {
+        self.files.retain(|_, entries| {
+            entries.retain(|entry| entry.checkpoint_id != checkpoint_id);
+            !entries.is_empty()
+        });
+    }
+
+    /// Checkpoints that touched a file matching `pattern`, oldest first
+    pub fn find(&self, pattern: &glob::Pattern) -> Vec<CheckpointFileMatch> {
+        let mut matches: Vec<CheckpointFileMatch> = self
+            .files
+            .iter()
+            .filter(|(path, _)| pattern.matches(path))
+            .flat_map(|(path, entries)| {
+                entries.iter().map(move |entry| CheckpointFileMatch {
+                    checkpoint_id: entry.checkpoint_id.clone(),
+                    timestamp: entry.timestamp,
+                    file_path: path.clone(),
+                    change: entry.change,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.file_path.cmp(&b.file_path)));
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointKind, CheckpointMetadata};
+    use std::path::PathBuf;
+
+    fn checkpoint(id: &str, offset_secs: i64) -> Checkpoint {
+        Checkpoint {
+            id: id.to_string(),
+            session_id: "session".to_string(),
+            project_id: "project".to_string(),
+            message_index: 0,
+            timestamp: DateTime::from_timestamp(1_700_000_000 + offset_secs, 0).unwrap(),
+            description: None,
+            parent_checkpoint_id: None,
+            kind: CheckpointKind::Manual,
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "test".to_string(),
+                user_prompt: String::new(),
+                file_changes: 0,
+                snapshot_size: 0,
+                total_cost_usd: None,
+            },
+        }
+    }
+
+    fn snapshot(path: &str, is_deleted: bool) -> FileSnapshot {
+        FileSnapshot {
+            checkpoint_id: String::new(),
+            file_path: PathBuf::from(path),
+            root_index: 0,
+            content: String::new(),
+            source_path: None,
+            hash: String::new(),
+            is_deleted,
+            permissions: None,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn index_tracks_change_types_and_matches_globs() {
+        let (first, second, third) = (checkpoint("c1", 0), checkpoint("c2", 10), checkpoint("c3", 20));
+        // Checkpoints arrive out of order when the index is rebuilt
+        let index = FileIndex::build([
+            (&third, vec![snapshot("src/auth.rs", false)]),
+            (&first, vec![snapshot("src/auth.rs", false), snapshot("README.md", false)]),
+            (&second, vec![snapshot(r"src\auth.rs", true), snapshot("README.md", false)]),
+        ]);
+
+        let changes = |index: &FileIndex, glob: &str| {
+            index
+                .find(&glob::Pattern::new(glob).unwrap())
+                .into_iter()
+                .map(|m| (m.checkpoint_id, m.change))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            changes(&index, "src/auth.rs"),
+            [
+                ("c1".to_string(), FileChangeType::Added),
+                ("c2".to_string(), FileChangeType::Deleted),
+                ("c3".to_string(), FileChangeType::Added),
+            ]
+        );
+        assert_eq!(changes(&index, "*.md")[1], ("c2".to_string(), FileChangeType::Modified));
+        assert_eq!(changes(&index, "**/*.rs").len(), 3);
+
+        let mut pruned = index.clone();
+        pruned.remove_checkpoint("c2");
+        pruned.remove_checkpoint("c1");
+        assert_eq!(changes(&pruned, "*"), [("c3".to_string(), FileChangeType::Added)]);
+        assert_eq!(pruned.files.len(), 1);
+
+        let json = serde_json::to_string(&index).unwrap();
+        assert_eq!(serde_json::from_str::<FileIndex>(&json).unwrap(), index);
+    }
+}
@@ -3,25 +3,621 @@ use chrono::{DateTime, TimeZone, Utc};
 use log;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::commands::usage::cost_of_usage;
 
 use super::{
     storage::{self, CheckpointStorage},
-    Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
-    FileSnapshot, FileState, FileTracker, SessionTimeline,
+    Checkpoint, CheckpointKind, CheckpointMetadata, CheckpointPaths, CheckpointPhase,
+    CheckpointProgress, CheckpointResult, CheckpointRetention, CheckpointStrategy, FileSnapshot,
+    FileState, FileTracker, ProgressNotifier, RootedPath, SessionTimeline, TimelineNotifier,
+    TimelineUpdate,
 };
 
+/// Resolve `.` and `..` components without touching the filesystem
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Resolve a tool-reported path to a path relative to the project root
+///
+/// Relative paths are taken relative to the root and absolute paths must point
+/// inside it. Fails for anything that escapes the root, either through `..`
+/// components or through a symlink in the part of the path that exists.
+pub fn resolve_within_root(root: &Path, file_path: &Path) -> Result<PathBuf> {
+    let root = normalize_lexically(root);
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.clone());
+
+    let joined = if file_path.is_absolute() {
+        file_path.to_path_buf()
+    } else {
+        root.join(file_path)
+    };
+    let normalized = normalize_lexically(&joined);
+
+    // Absolute inputs may spell the root in its canonical form (e.g. /private/var on macOS)
+    let relative = normalized
+        .strip_prefix(&root)
+        .or_else(|_| normalized.strip_prefix(&canonical_root))
+        .map_err(|_| anyhow::anyhow!("Path is outside the project: {}", file_path.display()))?
+        .to_path_buf();
+
+    if relative.as_os_str().is_empty() {
+        anyhow::bail!("Path refers to the project root: {}", file_path.display());
+    }
+
+    // Follow symlinks in the deepest existing part of the path
+    let full_path = root.join(&relative);
+    for ancestor in full_path.ancestors() {
+        if !ancestor.starts_with(&root) {
+            break;
+        }
+        if fs::symlink_metadata(ancestor).is_err() {
+            continue;
+        }
+
+        let resolved = ancestor.canonicalize().with_context(|| {
+            format!("Failed to resolve {} (dangling symlink?)", ancestor.display())
+        })?;
+        if !resolved.starts_with(&canonical_root) {
+            anyhow::bail!(
+                "Path escapes the project through a symlink: {}",
+                file_path.display()
+            );
+        }
+        break;
+    }
+
+    Ok(relative)
+}
+
+/// Resolve a tool-reported path against the roots of a session, the project
+/// first and then its additional directories.
+///
+/// Relative paths are always taken relative to the project. An absolute path
+/// belongs to the first root it lies in; when it is in none of them the
+/// project's error is returned.
+pub fn resolve_within_roots(roots: &[PathBuf], file_path: &Path) -> Result<RootedPath> {
+    let (project, additional) = roots
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Session has no project root"))?;
+    let in_project = resolve_within_root(project, file_path);
+    if in_project.is_ok() || !file_path.is_absolute() {
+        return in_project.map(RootedPath::in_project);
+    }
+    for (index, root) in additional.iter().enumerate() {
+        if let Ok(path) = resolve_within_root(root, file_path) {
+            return Ok(RootedPath {
+                root_index: index + 1,
+                path,
+            });
+        }
+    }
+    in_project.map(RootedPath::in_project)
+}
+
+/// A file's state on disk, as compared by the file tracker
+struct DiskState {
+    hash: String,
+    exists: bool,
+    size: u64,
+    modified: DateTime<Utc>,
+}
+
+impl DiskState {
+    fn read(full_path: &Path) -> Result<Self> {
+        if !full_path.exists() {
+            return Ok(Self {
+                hash: String::new(),
+                exists: false,
+                size: 0,
+                modified: Utc::now(),
+            });
+        }
+
+        let metadata = fs::metadata(full_path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| {
+                Utc.timestamp_opt(d.as_secs() as i64, d.subsec_nanos())
+                    .unwrap()
+            })
+            .unwrap_or_else(Utc::now);
+
+        Ok(Self {
+            hash: storage::CheckpointStorage::hash_file(full_path).unwrap_or_default(),
+            exists: true,
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// Record a file's current state in the tracker
+fn record_file_state(tracker: &mut FileTracker, relative_path: RootedPath, state: DiskState) {
+    // Check if file has actually changed
+    let is_modified = if let Some(existing_state) = tracker.tracked_files.get(&relative_path) {
+        // File is modified if:
+        // 1. Hash has changed
+        // 2. Existence state has changed
+        // 3. It was already marked as modified
+        existing_state.last_hash != state.hash
+            || existing_state.exists != state.exists
+            || existing_state.is_modified
+    } else {
+        // New file is always considered modified
+        true
+    };
+
+    tracker.tracked_files.insert(
+        relative_path,
+        FileState {
+            last_hash: state.hash,
+            is_modified,
+            last_modified: state.modified,
+            exists: state.exists,
+        },
+    );
+}
+
+/// Recursively collect the files of a project, relative to its root
+fn collect_project_files(
+    dir: &Path,
+    base: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), std::io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            // Skip hidden directories like .git
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                if name.starts_with('.') {
+                    continue;
+                }
+            }
+            collect_project_files(&path, base, files)?;
+        } else if path.is_file() {
+            // Compute relative path from project root
+            if let Ok(rel) = path.strip_prefix(base) {
+                files.push(rel.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove directories left empty below `base`; returns whether `dir` was removed
+fn remove_empty_dirs(dir: &Path, base: &Path) -> Result<bool, std::io::Error> {
+    if dir == base {
+        return Ok(false); // Don't remove the base directory
+    }
+
+    let mut is_empty = true;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            if !remove_empty_dirs(&path, base)? {
+                is_empty = false;
+            }
+        } else {
+            is_empty = false;
+        }
+    }
+
+    if is_empty {
+        fs::remove_dir(dir)?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Fail once `cancel_checkpoint_creation` has been called
+fn check_cancelled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::SeqCst) {
+        anyhow::bail!("Checkpoint creation cancelled");
+    }
+    Ok(())
+}
+
+/// Cancel flags of the creates that are running or waiting for the pool
+/// lock. Each create has its own flag, so one that starts later can't clear
+/// a cancel meant for an earlier one
+#[derive(Clone, Default)]
+struct CreateCancels(Arc<std::sync::Mutex<Vec<Arc<AtomicBool>>>>);
+
+impl CreateCancels {
+    /// A flag for a new create, registered until the guard is dropped
+    fn register(&self) -> CancelGuard {
+        let flag = Arc::new(AtomicBool::new(false));
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(Arc::clone(&flag));
+        CancelGuard {
+            cancels: self.clone(),
+            flag,
+        }
+    }
+
+    fn cancel_all(&self) {
+        for flag in self.0.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            flag.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+struct CancelGuard {
+    cancels: CreateCancels,
+    flag: Arc<AtomicBool>,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.cancels
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|flag| !Arc::ptr_eq(flag, &self.flag));
+    }
+}
+
+/// Sends progress of one checkpoint phase, at most every 100 ms
+struct ProgressReporter {
+    session_id: String,
+    notifier: Option<ProgressNotifier>,
+    phase: Option<CheckpointPhase>,
+    last_sent: Option<Instant>,
+}
+
+impl ProgressReporter {
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn report(
+        &mut self,
+        phase: CheckpointPhase,
+        files_done: usize,
+        files_total: usize,
+        bytes_done: u64,
+    ) {
+        let Some(notifier) = &self.notifier else {
+            return;
+        };
+
+        // Phase changes and the last file always go out
+        let due = self.phase != Some(phase)
+            || files_done >= files_total
+            || self.last_sent.is_none_or(|sent| sent.elapsed() >= Self::INTERVAL);
+        if !due {
+            return;
+        }
+
+        self.phase = Some(phase);
+        self.last_sent = Some(Instant::now());
+        notifier(CheckpointProgress {
+            session_id: self.session_id.clone(),
+            phase,
+            files_done,
+            files_total,
+            bytes_done,
+        });
+    }
+}
+
+/// Project files and storage of a session, cheap to move onto the blocking pool
+#[derive(Clone)]
+struct SessionFiles {
+    project_id: String,
+    session_id: String,
+    /// The project, then the session's additional directories
+    roots: Vec<PathBuf>,
+    storage: Arc<CheckpointStorage>,
+}
+
+/// Checkpoint data and outcome of rewriting the project files
+struct RestoredCheckpoint {
+    checkpoint: Checkpoint,
+    file_snapshots: Vec<FileSnapshot>,
+    messages: String,
+    files_processed: usize,
+    warnings: Vec<String>,
+}
+
+impl SessionFiles {
+    /// Files of every root, relative to their root
+    fn collect_files(&self) -> Vec<(usize, PathBuf)> {
+        let mut all_files = Vec::new();
+        for (root_index, root) in self.roots.iter().enumerate() {
+            let mut files = Vec::new();
+            let _ = collect_project_files(root, root, &mut files);
+            all_files.extend(files.into_iter().map(|file| (root_index, file)));
+        }
+        all_files
+    }
+
+    /// Directory of a snapshot's root; fails for a root the session no longer knows
+    fn root(&self, root_index: usize) -> Result<&Path> {
+        self.roots
+            .get(root_index)
+            .map(PathBuf::as_path)
+            .ok_or_else(|| anyhow::anyhow!("Unknown root {} of session {}", root_index, self.session_id))
+    }
+
+    /// Walk the project and additional directories and read the state of every file
+    fn scan(
+        &self,
+        cancel: &AtomicBool,
+        reporter: &mut ProgressReporter,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<(RootedPath, DiskState)>> {
+        let all_files = self.collect_files();
+
+        let files_total = all_files.len();
+        let mut scanned = Vec::with_capacity(files_total);
+        let mut bytes_done = 0;
+        for (index, (root_index, rel)) in all_files.into_iter().enumerate() {
+            check_cancelled(cancel)?;
+            let root = &self.roots[root_index];
+            match resolve_within_root(root, &rel) {
+                Ok(path) => {
+                    match DiskState::read(&root.join(&path)) {
+                        Ok(state) => {
+                            bytes_done += state.size;
+                            scanned.push((RootedPath { root_index, path }, state));
+                        }
+                        Err(e) => log::warn!("Not tracking {}: {}", rel.display(), e),
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Not tracking {}: {}", rel.display(), e);
+                    warnings.push(format!("Skipped file outside the project: {}", rel.display()));
+                }
+            }
+            reporter.report(CheckpointPhase::Scanning, index + 1, files_total, bytes_done);
+        }
+
+        Ok(scanned)
+    }
+
+    /// Create file snapshots for the given modified files
+    fn snapshot(
+        &self,
+        checkpoint_id: &str,
+        modified_paths: Vec<RootedPath>,
+        cancel: &AtomicBool,
+        reporter: &mut ProgressReporter,
+    ) -> Result<Vec<FileSnapshot>> {
+        let files_total = modified_paths.len();
+        let mut snapshots = Vec::with_capacity(files_total);
+        let mut bytes_done = 0;
+
+        for (index, rel_path) in modified_paths.into_iter().enumerate() {
+            check_cancelled(cancel)?;
+            let full_path = self.root(rel_path.root_index)?.join(&rel_path.path);
+
+            let (source_path, exists, permissions, size, current_hash) = if full_path.exists() {
+                // Content is streamed from disk when the checkpoint is saved
+                let current_hash = storage::CheckpointStorage::hash_file(&full_path)
+                    .with_context(|| format!("Failed to hash {}", full_path.display()))?;
+
+                // Don't skip based on hash - if is_modified is true, we should snapshot it
+                // The hash check in record_file_state already determined if it changed
+
+                let metadata = fs::metadata(&full_path)?;
+                let permissions = {
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+                        Some(metadata.permissions().mode())
+                    }
+                    #[cfg(not(unix))]
+                    {
+                        None
+                    }
+                };
+                (Some(full_path), true, permissions, metadata.len(), current_hash)
+            } else {
+                (None, false, None, 0, String::new())
+            };
+
+            bytes_done += size;
+            snapshots.push(FileSnapshot {
+                checkpoint_id: checkpoint_id.to_string(),
+                file_path: rel_path.path,
+                root_index: rel_path.root_index,
+                content: String::new(),
+                source_path,
+                hash: current_hash,
+                is_deleted: !exists,
+                permissions,
+                size,
+            });
+            reporter.report(CheckpointPhase::Snapshotting, index + 1, files_total, bytes_done);
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Bring the project files back to the state of a checkpoint
+    fn restore(
+        &self,
+        checkpoint_id: &str,
+        reporter: &mut ProgressReporter,
+    ) -> Result<RestoredCheckpoint> {
+        // Load checkpoint data
+        let (checkpoint, file_snapshots, messages) =
+            self.storage
+                .load_checkpoint_streamed(&self.project_id, &self.session_id, checkpoint_id)?;
+
+        // Create a set of files that should exist after restore
+        let mut checkpoint_files = std::collections::HashSet::new();
+        for snapshot in &file_snapshots {
+            if !snapshot.is_deleted {
+                checkpoint_files.insert(snapshot.rooted_path());
+            }
+        }
+
+        // Files that exist now but shouldn't exist in the checkpoint. An
+        // additional directory the checkpoint has no snapshot of was added
+        // after it, and is left alone rather than emptied.
+        let snapshotted_roots: std::collections::HashSet<usize> =
+            file_snapshots.iter().map(|snapshot| snapshot.root_index).collect();
+        let stale_files: Vec<RootedPath> = self
+            .collect_files()
+            .into_iter()
+            .filter(|(root_index, _)| *root_index == 0 || snapshotted_roots.contains(root_index))
+            .map(|(root_index, path)| RootedPath { root_index, path })
+            .filter(|file| !checkpoint_files.contains(file))
+            .collect();
+        let files_total = stale_files.len() + file_snapshots.len();
+        let mut files_done = 0;
+        let mut bytes_done = 0;
+
+        let mut warnings = Vec::new();
+        let mut files_processed = 0;
+
+        for current_file in stale_files {
+            let full_path = self.roots[current_file.root_index].join(&current_file.path);
+            match fs::remove_file(&full_path) {
+                Ok(_) => {
+                    files_processed += 1;
+                    log::info!("Deleted file not in checkpoint: {:?}", full_path);
+                }
+                Err(e) => {
+                    warnings.push(format!(
+                        "Failed to delete {}: {}",
+                        current_file.label().display(),
+                        e
+                    ));
+                }
+            }
+            files_done += 1;
+            reporter.report(CheckpointPhase::Restoring, files_done, files_total, bytes_done);
+        }
+
+        // Clean up any empty directories left after file deletion
+        for root in &self.roots {
+            let _ = remove_empty_dirs(root, root);
+        }
+
+        // Restore files from checkpoint
+        for snapshot in &file_snapshots {
+            match self.restore_file_snapshot(snapshot) {
+                Ok(_) => files_processed += 1,
+                Err(e) => warnings.push(format!(
+                    "Failed to restore {}: {}",
+                    snapshot.rooted_path().label().display(),
+                    e
+                )),
+            }
+            files_done += 1;
+            bytes_done += snapshot.size;
+            reporter.report(CheckpointPhase::Restoring, files_done, files_total, bytes_done);
+        }
+
+        Ok(RestoredCheckpoint {
+            checkpoint,
+            file_snapshots,
+            messages,
+            files_processed,
+            warnings,
+        })
+    }
+    /// Restore a single file from snapshot
+    fn restore_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
+        // Never write outside the snapshot's root, whatever the snapshot says
+        let root = self.root(snapshot.root_index)?;
+        let relative_path = resolve_within_root(root, &snapshot.file_path)?;
+        let full_path = root.join(relative_path);
+
+        if snapshot.is_deleted {
+            // Delete the file if it exists
+            if full_path.exists() {
+                fs::remove_file(&full_path).context("Failed to delete file")?;
+            }
+        } else {
+            // Create parent directories if needed
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent).context("Failed to create parent directories")?;
+            }
+
+            // Write file content, streaming it when it isn't in memory
+            if let Some(source_path) = &snapshot.source_path {
+                fs::copy(source_path, &full_path).context("Failed to write file")?;
+            } else if snapshot.content.is_empty() && !snapshot.hash.is_empty() {
+                self.storage.restore_content_to(
+                    &self.project_id,
+                    &self.session_id,
+                    &snapshot.hash,
+                    &full_path,
+                )?;
+            } else {
+                fs::write(&full_path, &snapshot.content).context("Failed to write file")?;
+            }
+
+            // Restore permissions if available
+            #[cfg(unix)]
+            if let Some(mode) = snapshot.permissions {
+                use std::os::unix::fs::PermissionsExt;
+                let permissions = std::fs::Permissions::from_mode(mode);
+                fs::set_permissions(&full_path, permissions)
+                    .context("Failed to set file permissions")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Size and modification time of a session JSONL file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SessionFileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl SessionFileStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
 /// Manages checkpoint operations for a session
 pub struct CheckpointManager {
     project_id: String,
     session_id: String,
     project_path: PathBuf,
+    additional_roots: Arc<std::sync::RwLock<Vec<PathBuf>>>, // Directories added with --add-dir
     file_tracker: Arc<RwLock<FileTracker>>,
     pub storage: Arc<CheckpointStorage>,
     timeline: Arc<RwLock<SessionTimeline>>,
     current_messages: Arc<RwLock<Vec<String>>>, // JSONL messages
+    pending_warnings: Arc<RwLock<Vec<String>>>, // Reported with the next checkpoint result
+    notifier: Option<TimelineNotifier>,         // Told about timeline changes, if set
+    progress: Option<ProgressNotifier>,         // Told about create/restore progress, if set
+    cancel_create: CreateCancels,               // Set to abort running and waiting creates
+    checkpoint_lock: Arc<Mutex<()>>,            // Held while the content pool changes
+    session_stamp: Arc<RwLock<Option<SessionFileStamp>>>, // Session file when last synced
 }
 
 impl CheckpointManager {
@@ -39,12 +635,17 @@ impl CheckpointManager {
 
         // Load or create timeline
         let paths = CheckpointPaths::new(&claude_dir, &project_id, &session_id);
-        let timeline = if paths.timeline_file.exists() {
-            storage.load_timeline(&paths.timeline_file)?
+        let (timeline, recovery_warning) = if paths.timeline_file.exists() {
+            storage.load_timeline_with_recovery(&paths.timeline_file)?
         } else {
-            SessionTimeline::new(session_id.clone())
+            (SessionTimeline::new(session_id.clone()), None)
         };
 
+        let additional_roots = CheckpointStorage::load_additional_roots(&paths).unwrap_or_else(|e| {
+            log::warn!("Checkpoints of {} only cover the project: {}", session_id, e);
+            Vec::new()
+        });
+
         let file_tracker = FileTracker {
             tracked_files: HashMap::new(),
         };
@@ -53,13 +654,156 @@ impl CheckpointManager {
             project_id,
             session_id,
             project_path,
+            additional_roots: Arc::new(std::sync::RwLock::new(additional_roots)),
             file_tracker: Arc::new(RwLock::new(file_tracker)),
             storage,
             timeline: Arc::new(RwLock::new(timeline)),
             current_messages: Arc::new(RwLock::new(Vec::new())),
+            pending_warnings: Arc::new(RwLock::new(recovery_warning.into_iter().collect())),
+            notifier: None,
+            progress: None,
+            cancel_create: CreateCancels::default(),
+            checkpoint_lock: Arc::new(Mutex::new(())),
+            session_stamp: Arc::new(RwLock::new(None)),
         })
     }
 
+    /// Report timeline changes (checkpoint created, restored, removed or
+    /// settings updated) to the given callback
+    pub fn with_notifier(mut self, notifier: Option<TimelineNotifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Report create/restore progress to the given callback
+    pub fn with_progress(mut self, progress: Option<ProgressNotifier>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Share the checkpoint lock of the session with content pool commands
+    pub fn with_checkpoint_lock(mut self, checkpoint_lock: Arc<Mutex<()>>) -> Self {
+        self.checkpoint_lock = checkpoint_lock;
+        self
+    }
+
+    fn progress_reporter(&self) -> ProgressReporter {
+        ProgressReporter {
+            session_id: self.session_id.clone(),
+            notifier: self.progress.clone(),
+            phase: None,
+            last_sent: None,
+        }
+    }
+
+    /// The project, then the session's additional directories
+    fn roots(&self) -> Vec<PathBuf> {
+        let additional = self.additional_roots.read().unwrap_or_else(|e| e.into_inner());
+        std::iter::once(self.project_path.clone())
+            .chain(additional.iter().cloned())
+            .collect()
+    }
+
+    /// Also checkpoint the files of `dirs`, directories outside the project
+    /// the session was given with `--add-dir`. The roots are stored with the
+    /// session's timeline, so they outlive this manager.
+    pub fn add_additional_roots(&self, dirs: &[PathBuf]) -> Result<()> {
+        let dirs: Vec<PathBuf> = dirs
+            .iter()
+            .filter(|dir| **dir != self.project_path)
+            .cloned()
+            .collect();
+        if dirs.is_empty() {
+            return Ok(());
+        }
+        let paths = CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+        let roots = CheckpointStorage::register_additional_roots(&paths, &dirs)?;
+        *self.additional_roots.write().unwrap_or_else(|e| e.into_inner()) = roots;
+        Ok(())
+    }
+
+    fn session_files(&self) -> SessionFiles {
+        SessionFiles {
+            project_id: self.project_id.clone(),
+            session_id: self.session_id.clone(),
+            roots: self.roots(),
+            storage: Arc::clone(&self.storage),
+        }
+    }
+
+    /// Send the current checkpoint count and position to the notifier
+    async fn notify_timeline_changed(&self) {
+        if let Some(notifier) = &self.notifier {
+            let timeline = self.timeline.read().await;
+            notifier(TimelineUpdate {
+                session_id: self.session_id.clone(),
+                total_checkpoints: timeline.total_checkpoints,
+                current_checkpoint_id: timeline.current_checkpoint_id.clone(),
+            });
+        }
+    }
+
+    /// Take warnings that were raised outside of a checkpoint operation
+    /// (e.g. a timeline recovered from backup) so they can be reported
+    async fn take_pending_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.pending_warnings.write().await)
+    }
+
+    /// The session's JSONL file in ~/.claude/projects
+    fn session_file(&self) -> PathBuf {
+        self.storage
+            .claude_dir
+            .join("projects")
+            .join(&self.project_id)
+            .join(format!("{}.jsonl", self.session_id))
+    }
+
+    /// Whether the session file changed since the manager last synced with it,
+    /// e.g. because claude ran against the session in a terminal
+    pub async fn session_file_changed(&self) -> bool {
+        match *self.session_stamp.read().await {
+            Some(synced) => SessionFileStamp::read(&self.session_file()) != Some(synced),
+            None => false,
+        }
+    }
+
+    /// Record the session file as seen, after tracking messages the app wrote itself
+    pub async fn mark_session_file_synced(&self) {
+        *self.session_stamp.write().await = SessionFileStamp::read(&self.session_file());
+    }
+
+    /// Replace the tracked messages with the session file, up to `message_index`.
+    ///
+    /// Returns whether the file had changed outside the manager since the
+    /// last sync; the next checkpoint result then carries a warning.
+    pub async fn sync_session_file(&self, message_index: Option<usize>) -> Result<bool> {
+        let path = self.session_file();
+        let externally_modified = self.session_file_changed().await;
+        if !path.exists() {
+            return Ok(externally_modified);
+        }
+
+        let stamp = SessionFileStamp::read(&path);
+        let content = fs::read_to_string(&path).context("Failed to read session file")?;
+        self.current_messages.write().await.clear();
+        for (index, line) in content.lines().enumerate() {
+            if message_index.is_some_and(|max| index > max) {
+                break;
+            }
+            self.track_message(line.to_string()).await?;
+        }
+        *self.session_stamp.write().await = stamp;
+
+        if externally_modified {
+            log::warn!("Session {} was modified outside the app; reloaded it", self.session_id);
+            self.pending_warnings.write().await.push(
+                "The session file was modified outside the app; the conversation was reloaded from disk"
+                    .to_string(),
+            );
+        }
+        Ok(externally_modified)
+    }
+
     /// Track a new message in the session
     pub async fn track_message(&self, jsonl_message: String) -> Result<()> {
         let mut messages = self.current_messages.write().await;
@@ -106,57 +850,22 @@ impl CheckpointManager {
 
     /// Track a file modification
     pub async fn track_file_modification(&self, file_path: &str) -> Result<()> {
-        let mut tracker = self.file_tracker.write().await;
-        let full_path = self.project_path.join(file_path);
-
-        // Read current file state
-        let (hash, exists, _size, modified) = if full_path.exists() {
-            let content = fs::read_to_string(&full_path).unwrap_or_default();
-            let metadata = fs::metadata(&full_path)?;
-            let modified = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| {
-                    Utc.timestamp_opt(d.as_secs() as i64, d.subsec_nanos())
-                        .unwrap()
-                })
-                .unwrap_or_else(Utc::now);
-
-            (
-                storage::CheckpointStorage::calculate_file_hash(&content),
-                true,
-                metadata.len(),
-                modified,
-            )
-        } else {
-            (String::new(), false, 0, Utc::now())
+        let roots = self.roots();
+        let relative_path = match resolve_within_roots(&roots, Path::new(file_path)) {
+            Ok(relative_path) => relative_path,
+            Err(e) => {
+                log::warn!("Not tracking {}: {}", file_path, e);
+                self.pending_warnings
+                    .write()
+                    .await
+                    .push(format!("Skipped file outside the project: {}", file_path));
+                return Ok(());
+            }
         };
 
-        // Check if file has actually changed
-        let is_modified =
-            if let Some(existing_state) = tracker.tracked_files.get(&PathBuf::from(file_path)) {
-                // File is modified if:
-                // 1. Hash has changed
-                // 2. Existence state has changed
-                // 3. It was already marked as modified
-                existing_state.last_hash != hash
-                    || existing_state.exists != exists
-                    || existing_state.is_modified
-            } else {
-                // New file is always considered modified
-                true
-            };
-
-        tracker.tracked_files.insert(
-            PathBuf::from(file_path),
-            FileState {
-                last_hash: hash,
-                is_modified,
-                last_modified: modified,
-                exists,
-            },
-        );
+        let state = DiskState::read(&roots[relative_path.root_index].join(&relative_path.path))?;
+        let mut tracker = self.file_tracker.write().await;
+        record_file_state(&mut tracker, relative_path, state);
 
         Ok(())
     }
@@ -185,60 +894,106 @@ impl CheckpointManager {
     }
 
     /// Create a checkpoint
+    ///
+    /// The project walk, hashing and writes run on the blocking pool and are
+    /// reported through the progress notifier. `cancel_checkpoint_creation`
+    /// stops the operation before anything is added to the timeline.
     pub async fn create_checkpoint(
         &self,
         description: Option<String>,
         parent_checkpoint_id: Option<String>,
     ) -> Result<CheckpointResult> {
+        self.create_checkpoint_of_kind(description, parent_checkpoint_id, CheckpointKind::Manual)
+            .await
+    }
+
+    /// Create a checkpoint recording why it was taken. After an automatic
+    /// checkpoint the session's retention policy is applied.
+    pub async fn create_checkpoint_of_kind(
+        &self,
+        description: Option<String>,
+        parent_checkpoint_id: Option<String>,
+        kind: CheckpointKind,
+    ) -> Result<CheckpointResult> {
+        let result = self
+            .save_new_checkpoint(description, parent_checkpoint_id, kind)
+            .await?;
+
+        if kind.is_auto() {
+            let retention = self.timeline.read().await.retention.clone();
+            if !retention.keeps_all() {
+                match self.cleanup_old_checkpoints(None, &retention).await {
+                    Ok(removed) if removed > 0 => {
+                        log::info!("Retention policy removed {} checkpoints", removed)
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to apply checkpoint retention: {}", e),
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn save_new_checkpoint(
+        &self,
+        description: Option<String>,
+        parent_checkpoint_id: Option<String>,
+        kind: CheckpointKind,
+    ) -> Result<CheckpointResult> {
+        // Registered before waiting for the lock, so a cancel issued
+        // meanwhile still applies to this create
+        let cancel_guard = self.cancel_create.register();
+        let _pool_guard = self.checkpoint_lock.lock().await;
         let messages = self.current_messages.read().await;
         let message_index = messages.len().saturating_sub(1);
 
         // Extract metadata from the last user message
-        let (user_prompt, model_used, total_tokens) =
+        let (user_prompt, model_used, total_tokens, total_cost_usd) =
             self.extract_checkpoint_metadata(&messages).await?;
 
         // Ensure every file in the project is tracked so new checkpoints include all files
-        // Recursively walk the project directory and track each file
-        fn collect_files(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-            files: &mut Vec<std::path::PathBuf>,
-        ) -> Result<(), std::io::Error> {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // Skip hidden directories like .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') {
-                            continue;
-                        }
-                    }
-                    collect_files(&path, base, files)?;
-                } else if path.is_file() {
-                    // Compute relative path from project root
-                    if let Ok(rel) = path.strip_prefix(base) {
-                        files.push(rel.to_path_buf());
-                    }
-                }
-            }
-            Ok(())
-        }
-        let mut all_files = Vec::new();
-        let project_dir = &self.project_path;
-        let _ = collect_files(project_dir.as_path(), project_dir.as_path(), &mut all_files);
-        for rel in all_files {
-            if let Some(p) = rel.to_str() {
-                // Track each file for snapshot
-                let _ = self.track_file_modification(p).await;
+        let files = self.session_files();
+        let cancel = Arc::clone(&cancel_guard.flag);
+        let mut reporter = self.progress_reporter();
+        let (scanned, scan_warnings) = tokio::task::spawn_blocking(move || {
+            let mut warnings = Vec::new();
+            files
+                .scan(&cancel, &mut reporter, &mut warnings)
+                .map(|scanned| (scanned, warnings))
+        })
+        .await
+        .context("Checkpoint scan task failed")??;
+        {
+            let mut tracker = self.file_tracker.write().await;
+            for (relative_path, state) in scanned {
+                record_file_state(&mut tracker, relative_path, state);
             }
         }
+        self.pending_warnings.write().await.extend(scan_warnings);
 
         // Generate checkpoint ID early so snapshots reference it
         let checkpoint_id = storage::CheckpointStorage::generate_checkpoint_id();
 
-        // Create file snapshots
-        let file_snapshots = self.create_file_snapshots(&checkpoint_id).await?;
+        // Create file snapshots for all tracked modified files
+        let modified_paths: Vec<RootedPath> = self
+            .file_tracker
+            .read()
+            .await
+            .tracked_files
+            .iter()
+            .filter(|(_, state)| state.is_modified)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let files = self.session_files();
+        let cancel = Arc::clone(&cancel_guard.flag);
+        let mut reporter = self.progress_reporter();
+        let snapshot_checkpoint_id = checkpoint_id.clone();
+        let file_snapshots = tokio::task::spawn_blocking(move || {
+            files.snapshot(&snapshot_checkpoint_id, modified_paths, &cancel, &mut reporter)
+        })
+        .await
+        .context("Checkpoint snapshot task failed")??;
 
         // Generate checkpoint struct
         let checkpoint = Checkpoint {
@@ -257,6 +1012,7 @@ impl CheckpointManager {
                     timeline.current_checkpoint_id.clone()
                 }
             },
+            kind,
             metadata: CheckpointMetadata {
                 total_tokens,
                 model_used,
@@ -266,49 +1022,88 @@ impl CheckpointManager {
                     &messages.join("\n"),
                     &file_snapshots,
                 ),
+                total_cost_usd: Some(total_cost_usd),
             },
         };
 
         // Save checkpoint
         let messages_content = messages.join("\n");
-        let result = self.storage.save_checkpoint(
-            &self.project_id,
-            &self.session_id,
-            &checkpoint,
-            file_snapshots,
-            &messages_content,
-        )?;
-
-        // Reload timeline from disk so in-memory timeline has updated nodes and total_checkpoints
-        let claude_dir = self.storage.claude_dir.clone();
-        let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
-        let updated_timeline = self.storage.load_timeline(&paths.timeline_file)?;
-        {
-            let mut timeline_lock = self.timeline.write().await;
-            *timeline_lock = updated_timeline;
+        let storage = Arc::clone(&self.storage);
+        let project_id = self.project_id.clone();
+        let session_id = self.session_id.clone();
+        let cancel = Arc::clone(&cancel_guard.flag);
+        let mut reporter = self.progress_reporter();
+        let mut result = tokio::task::spawn_blocking(move || {
+            check_cancelled(&cancel)?;
+            let files_total = file_snapshots.len();
+            let (mut files_done, mut bytes_done) = (0, 0);
+            storage.save_checkpoint(
+                &project_id,
+                &session_id,
+                &checkpoint,
+                file_snapshots,
+                &messages_content,
+                &mut |snapshot| {
+                    files_done += 1;
+                    bytes_done += snapshot.size;
+                    reporter.report(CheckpointPhase::Saving, files_done, files_total, bytes_done);
+                    !cancel.load(Ordering::SeqCst)
+                },
+            )
+        })
+        .await
+        .context("Checkpoint save task failed")??;
+
+        // Append the saved node to the in-memory timeline; only reload it from
+        // disk when it has drifted from timeline.json
+        let followed = match result.timeline_append.take() {
+            Some(append) => self.timeline.write().await.follow_append(append),
+            None => false,
+        };
+        if !followed {
+            let claude_dir = self.storage.claude_dir.clone();
+            let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
+            let updated_timeline = self.storage.load_timeline(&paths.timeline_file)?;
+            *self.timeline.write().await = updated_timeline;
         }
 
         // Update timeline (current checkpoint only)
-        let mut timeline = self.timeline.write().await;
-        timeline.current_checkpoint_id = Some(checkpoint_id);
+        self.timeline.write().await.current_checkpoint_id = Some(checkpoint_id);
 
         // Reset file tracker
         let mut tracker = self.file_tracker.write().await;
         for (_, state) in tracker.tracked_files.iter_mut() {
             state.is_modified = false;
         }
+        drop(tracker);
+
+        result.warnings.extend(self.take_pending_warnings().await);
+        self.progress_reporter().report(
+            CheckpointPhase::Done,
+            result.files_processed,
+            result.files_processed,
+            result.checkpoint.metadata.snapshot_size,
+        );
+        self.notify_timeline_changed().await;
 
         Ok(result)
     }
 
+    /// Ask a running `create_checkpoint`, and any waiting for it, to stop;
+    /// they then fail without adding a checkpoint to the timeline
+    pub fn cancel_checkpoint_creation(&self) {
+        self.cancel_create.cancel_all();
+    }
+
     /// Extract metadata from messages for checkpoint
     async fn extract_checkpoint_metadata(
         &self,
         messages: &[String],
-    ) -> Result<(String, String, u64)> {
+    ) -> Result<(String, String, u64, f64)> {
         let mut user_prompt = String::new();
         let mut model_used = String::from("unknown");
         let mut total_tokens = 0u64;
+        let mut total_cost_usd = 0.0;
 
         // Iterate through messages in reverse to find the last user prompt
         for msg_str in messages.iter().rev() {
@@ -347,6 +1142,8 @@ impl CheckpointManager {
                 // First check for usage in message.usage (assistant messages)
                 if let Some(message) = msg.get("message") {
                     if let Some(usage) = message.get("usage") {
+                        let model = message.get("model").and_then(|m| m.as_str()).unwrap_or("");
+                        total_cost_usd += cost_of_usage(model, usage);
                         if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
                             total_tokens += input;
                         }
@@ -371,6 +1168,8 @@ impl CheckpointManager {
 
                 // Then check for top-level usage (result messages)
                 if let Some(usage) = msg.get("usage") {
+                    let model = msg.get("model").and_then(|m| m.as_str()).unwrap_or("");
+                    total_cost_usd += cost_of_usage(model, usage);
                     if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
                         total_tokens += input;
                     }
@@ -394,174 +1193,24 @@ impl CheckpointManager {
             }
         }
 
-        Ok((user_prompt, model_used, total_tokens))
-    }
-
-    /// Create file snapshots for all tracked modified files
-    async fn create_file_snapshots(&self, checkpoint_id: &str) -> Result<Vec<FileSnapshot>> {
-        let tracker = self.file_tracker.read().await;
-        let mut snapshots = Vec::new();
-
-        for (rel_path, state) in &tracker.tracked_files {
-            // Skip files that haven't been modified
-            if !state.is_modified {
-                continue;
-            }
-
-            let full_path = self.project_path.join(rel_path);
-
-            let (content, exists, permissions, size, current_hash) = if full_path.exists() {
-                let content = fs::read_to_string(&full_path).unwrap_or_default();
-                let current_hash = storage::CheckpointStorage::calculate_file_hash(&content);
-
-                // Don't skip based on hash - if is_modified is true, we should snapshot it
-                // The hash check in track_file_modification already determined if it changed
-
-                let metadata = fs::metadata(&full_path)?;
-                let permissions = {
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        Some(metadata.permissions().mode())
-                    }
-                    #[cfg(not(unix))]
-                    {
-                        None
-                    }
-                };
-                (content, true, permissions, metadata.len(), current_hash)
-            } else {
-                (String::new(), false, None, 0, String::new())
-            };
-
-            snapshots.push(FileSnapshot {
-                checkpoint_id: checkpoint_id.to_string(),
-                file_path: rel_path.clone(),
-                content,
-                hash: current_hash,
-                is_deleted: !exists,
-                permissions,
-                size,
-            });
-        }
-
-        Ok(snapshots)
+        Ok((user_prompt, model_used, total_tokens, total_cost_usd))
     }
 
     /// Restore a checkpoint
     pub async fn restore_checkpoint(&self, checkpoint_id: &str) -> Result<CheckpointResult> {
-        // Load checkpoint data
-        let (checkpoint, file_snapshots, messages) =
-            self.storage
-                .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
-
-        // First, collect all files currently in the project to handle deletions
-        fn collect_all_project_files(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-            files: &mut Vec<std::path::PathBuf>,
-        ) -> Result<(), std::io::Error> {
-            for entry in std::fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    // Skip hidden directories like .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') {
-                            continue;
-                        }
-                    }
-                    collect_all_project_files(&path, base, files)?;
-                } else if path.is_file() {
-                    // Compute relative path from project root
-                    if let Ok(rel) = path.strip_prefix(base) {
-                        files.push(rel.to_path_buf());
-                    }
-                }
-            }
-            Ok(())
-        }
-
-        let mut current_files = Vec::new();
-        let _ =
-            collect_all_project_files(&self.project_path, &self.project_path, &mut current_files);
-
-        // Create a set of files that should exist after restore
-        let mut checkpoint_files = std::collections::HashSet::new();
-        for snapshot in &file_snapshots {
-            if !snapshot.is_deleted {
-                checkpoint_files.insert(snapshot.file_path.clone());
-            }
-        }
-
-        // Delete files that exist now but shouldn't exist in the checkpoint
-        let mut warnings = Vec::new();
-        let mut files_processed = 0;
-
-        for current_file in current_files {
-            if !checkpoint_files.contains(&current_file) {
-                // This file exists now but not in the checkpoint, so delete it
-                let full_path = self.project_path.join(&current_file);
-                match fs::remove_file(&full_path) {
-                    Ok(_) => {
-                        files_processed += 1;
-                        log::info!("Deleted file not in checkpoint: {:?}", current_file);
-                    }
-                    Err(e) => {
-                        warnings.push(format!(
-                            "Failed to delete {}: {}",
-                            current_file.display(),
-                            e
-                        ));
-                    }
-                }
-            }
-        }
-
-        // Clean up empty directories
-        fn remove_empty_dirs(
-            dir: &std::path::Path,
-            base: &std::path::Path,
-        ) -> Result<bool, std::io::Error> {
-            if dir == base {
-                return Ok(false); // Don't remove the base directory
-            }
-
-            let mut is_empty = true;
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    if !remove_empty_dirs(&path, base)? {
-                        is_empty = false;
-                    }
-                } else {
-                    is_empty = false;
-                }
-            }
-
-            if is_empty {
-                fs::remove_dir(dir)?;
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        }
-
-        // Clean up any empty directories left after file deletion
-        let _ = remove_empty_dirs(&self.project_path, &self.project_path);
-
-        // Restore files from checkpoint
-        for snapshot in &file_snapshots {
-            match self.restore_file_snapshot(snapshot).await {
-                Ok(_) => files_processed += 1,
-                Err(e) => warnings.push(format!(
-                    "Failed to restore {}: {}",
-                    snapshot.file_path.display(),
-                    e
-                )),
-            }
-        }
+        // Rewrite the project on the blocking pool; large trees take a while
+        let files = self.session_files();
+        let mut reporter = self.progress_reporter();
+        let restore_id = checkpoint_id.to_string();
+        let RestoredCheckpoint {
+            checkpoint,
+            file_snapshots,
+            messages,
+            files_processed,
+            mut warnings,
+        } = tokio::task::spawn_blocking(move || files.restore(&restore_id, &mut reporter))
+            .await
+            .context("Checkpoint restore task failed")??;
 
         // Update current messages
         let mut current_messages = self.current_messages.write().await;
@@ -571,8 +1220,7 @@ impl CheckpointManager {
         }
 
         // Update timeline
-        let mut timeline = self.timeline.write().await;
-        timeline.current_checkpoint_id = Some(checkpoint_id.to_string());
+        self.timeline.write().await.current_checkpoint_id = Some(checkpoint_id.to_string());
 
         // Update file tracker
         let mut tracker = self.file_tracker.write().await;
@@ -580,7 +1228,7 @@ impl CheckpointManager {
         for snapshot in &file_snapshots {
             if !snapshot.is_deleted {
                 tracker.tracked_files.insert(
-                    snapshot.file_path.clone(),
+                    snapshot.rooted_path(),
                     FileState {
                         last_hash: snapshot.hash.clone(),
                         is_modified: false,
@@ -590,45 +1238,21 @@ impl CheckpointManager {
                 );
             }
         }
+        drop(tracker);
+
+        warnings.extend(self.take_pending_warnings().await);
+        self.progress_reporter()
+            .report(CheckpointPhase::Done, files_processed, files_processed, 0);
+        self.notify_timeline_changed().await;
 
         Ok(CheckpointResult {
             checkpoint: checkpoint.clone(),
             files_processed,
             warnings,
+            timeline_append: None,
         })
     }
 
-    /// Restore a single file from snapshot
-    async fn restore_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
-        let full_path = self.project_path.join(&snapshot.file_path);
-
-        if snapshot.is_deleted {
-            // Delete the file if it exists
-            if full_path.exists() {
-                fs::remove_file(&full_path).context("Failed to delete file")?;
-            }
-        } else {
-            // Create parent directories if needed
-            if let Some(parent) = full_path.parent() {
-                fs::create_dir_all(parent).context("Failed to create parent directories")?;
-            }
-
-            // Write file content
-            fs::write(&full_path, &snapshot.content).context("Failed to write file")?;
-
-            // Restore permissions if available
-            #[cfg(unix)]
-            if let Some(mode) = snapshot.permissions {
-                use std::os::unix::fs::PermissionsExt;
-                let permissions = std::fs::Permissions::from_mode(mode);
-                fs::set_permissions(&full_path, permissions)
-                    .context("Failed to set file permissions")?;
-            }
-        }
-
-        Ok(())
-    }
-
     /// Get the current timeline
     pub async fn get_timeline(&self) -> SessionTimeline {
         self.timeline.read().await.clone()
@@ -666,7 +1290,7 @@ impl CheckpointManager {
         // Load the checkpoint to fork from
         let (_base_checkpoint, _, _) =
             self.storage
-                .load_checkpoint(&self.project_id, &self.session_id, checkpoint_id)?;
+                .load_checkpoint_streamed(&self.project_id, &self.session_id, checkpoint_id)?;
 
         // Restore to that checkpoint first
         self.restore_checkpoint(checkpoint_id).await?;
@@ -751,27 +1375,71 @@ impl CheckpointManager {
         auto_checkpoint_enabled: bool,
         checkpoint_strategy: CheckpointStrategy,
     ) -> Result<()> {
-        let mut timeline = self.timeline.write().await;
-        timeline.auto_checkpoint_enabled = auto_checkpoint_enabled;
-        timeline.checkpoint_strategy = checkpoint_strategy;
+        {
+            let mut timeline = self.timeline.write().await;
+            timeline.auto_checkpoint_enabled = auto_checkpoint_enabled;
+            timeline.checkpoint_strategy = checkpoint_strategy;
 
-        // Save updated timeline
-        let claude_dir = self.storage.claude_dir.clone();
-        let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
-        self.storage
-            .save_timeline(&paths.timeline_file, &timeline)?;
+            // Save updated timeline
+            let claude_dir = self.storage.claude_dir.clone();
+            let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
+            self.storage
+                .save_timeline(&paths.timeline_file, &timeline)?;
+        }
 
+        self.notify_timeline_changed().await;
         Ok(())
     }
 
-    /// Get files modified since a given timestamp
+    /// Set the retention policy applied after each automatic checkpoint
+    pub async fn update_retention(&self, retention: CheckpointRetention) -> Result<()> {
+        {
+            let mut timeline = self.timeline.write().await;
+            timeline.retention = retention;
+
+            let paths =
+                CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+            self.storage.save_timeline(&paths.timeline_file, &timeline)?;
+        }
+
+        self.notify_timeline_changed().await;
+        Ok(())
+    }
+
+    /// Remove the oldest checkpoints beyond `keep_count` and the per-kind
+    /// limits of `retention`, then reload the timeline
+    pub async fn cleanup_old_checkpoints(
+        &self,
+        keep_count: Option<usize>,
+        retention: &CheckpointRetention,
+    ) -> Result<usize> {
+        let _pool_guard = self.checkpoint_lock.lock().await;
+        let removed = self.storage.cleanup_old_checkpoints(
+            &self.project_id,
+            &self.session_id,
+            keep_count,
+            retention,
+        )?;
+
+        if removed > 0 {
+            let paths =
+                CheckpointPaths::new(&self.storage.claude_dir, &self.project_id, &self.session_id);
+            let updated_timeline = self.storage.load_timeline(&paths.timeline_file)?;
+            *self.timeline.write().await = updated_timeline;
+            self.notify_timeline_changed().await;
+        }
+
+        Ok(removed)
+    }
+
+    /// Get files modified since a given timestamp, by their labels
     pub async fn get_files_modified_since(&self, since: DateTime<Utc>) -> Vec<PathBuf> {
         let tracker = self.file_tracker.read().await;
         tracker
             .tracked_files
             .iter()
             .filter(|(_, state)| state.last_modified > since && state.is_modified)
-            .map(|(path, _)| path.clone())
+            .map(|(path, _)| path.label())
             .collect()
     }
 
@@ -785,3 +1453,327 @@ impl CheckpointManager {
             .max()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn test_manager(temp_dir: &TempDir) -> (CheckpointManager, PathBuf) {
+        let project_path = temp_dir.path().join("project");
+        fs::create_dir_all(project_path.join("src")).unwrap();
+        let manager = CheckpointManager::new(
+            "project".to_string(),
+            "session".to_string(),
+            project_path.clone(),
+            temp_dir.path().join("claude"),
+        )
+        .await
+        .unwrap();
+        (manager, project_path)
+    }
+
+    #[test]
+    fn test_resolve_within_root_rejects_parent_traversal() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        assert_eq!(
+            resolve_within_root(root, Path::new("src/../lib.rs")).unwrap(),
+            PathBuf::from("lib.rs")
+        );
+        assert!(resolve_within_root(root, Path::new("../../../etc/hosts")).is_err());
+        assert!(resolve_within_root(root, Path::new("src/../../outside.txt")).is_err());
+        assert!(resolve_within_root(root, Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_resolve_within_root_handles_absolute_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root).unwrap();
+
+        assert_eq!(
+            resolve_within_root(&root, &root.join("src/main.rs")).unwrap(),
+            PathBuf::from("src/main.rs")
+        );
+        assert_eq!(
+            resolve_within_root(&root, &root.canonicalize().unwrap().join("a.txt")).unwrap(),
+            PathBuf::from("a.txt")
+        );
+        assert!(resolve_within_root(&root, &temp_dir.path().join("other.txt")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_within_root_rejects_symlink_escapes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("project");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path().join("missing"), root.join("dangling"))
+            .unwrap();
+        std::os::unix::fs::symlink(root.join("inside.txt"), root.join("alias.txt")).unwrap();
+        fs::write(root.join("inside.txt"), "ok").unwrap();
+
+        assert!(resolve_within_root(&root, Path::new("escape/secret.txt")).is_err());
+        assert!(resolve_within_root(&root, Path::new("dangling")).is_err());
+        assert_eq!(
+            resolve_within_root(&root, Path::new("alias.txt")).unwrap(),
+            PathBuf::from("alias.txt")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_external_session_changes_are_detected_and_reloaded() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, _) = test_manager(&temp_dir).await;
+        let session_file = manager.session_file();
+        fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+        fs::write(&session_file, "{\"type\":\"user\"}\n").unwrap();
+
+        // First sync establishes the baseline
+        assert!(!manager.sync_session_file(None).await.unwrap());
+        assert!(!manager.session_file_changed().await);
+
+        // claude in a terminal appends to the same session
+        fs::write(
+            &session_file,
+            "{\"type\":\"user\"}\n{\"type\":\"assistant\"}\n{\"type\":\"user\"}\n",
+        )
+        .unwrap();
+        assert!(manager.session_file_changed().await);
+        assert!(manager.sync_session_file(None).await.unwrap());
+        assert_eq!(manager.current_messages.read().await.len(), 3);
+        assert_eq!(manager.take_pending_warnings().await.len(), 1);
+        assert!(!manager.session_file_changed().await);
+    }
+
+    #[tokio::test]
+    async fn test_track_file_modification_skips_outside_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, project_path) = test_manager(&temp_dir).await;
+        fs::write(project_path.join("src/lib.rs"), "fn main() {}").unwrap();
+
+        manager
+            .track_file_modification(project_path.join("src/lib.rs").to_str().unwrap())
+            .await
+            .unwrap();
+        manager
+            .track_file_modification("../../../etc/hosts")
+            .await
+            .unwrap();
+
+        let tracker = manager.file_tracker.read().await;
+        let tracked: Vec<_> = tracker.tracked_files.keys().cloned().collect();
+        assert_eq!(tracked, vec![RootedPath::in_project("src/lib.rs")]);
+        drop(tracker);
+
+        assert_eq!(manager.take_pending_warnings().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_refuses_to_write_outside_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, project_path) = test_manager(&temp_dir).await;
+
+        let snapshot = FileSnapshot {
+            checkpoint_id: "checkpoint".to_string(),
+            file_path: PathBuf::from("../escaped.txt"),
+            root_index: 0,
+            content: "pwned".to_string(),
+            source_path: None,
+            hash: String::new(),
+            is_deleted: false,
+            permissions: None,
+            size: 5,
+        };
+
+        assert!(manager.session_files().restore_file_snapshot(&snapshot).is_err());
+        assert!(!project_path.join("../escaped.txt").exists());
+
+        let inside = FileSnapshot {
+            file_path: PathBuf::from("src/restored.txt"),
+            ..snapshot
+        };
+        manager.session_files().restore_file_snapshot(&inside).unwrap();
+        assert_eq!(
+            fs::read_to_string(project_path.join("src/restored.txt")).unwrap(),
+            "pwned"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_additional_roots_are_checkpointed_and_restored() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, project_path) = test_manager(&temp_dir).await;
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(project_path.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(shared.join("lib.rs"), "v1").unwrap();
+        manager.add_additional_roots(std::slice::from_ref(&shared)).unwrap();
+
+        let roots = manager.roots();
+        assert_eq!(
+            resolve_within_roots(&roots, &shared.join("lib.rs")).unwrap(),
+            RootedPath { root_index: 1, path: PathBuf::from("lib.rs") }
+        );
+        assert_eq!(
+            resolve_within_roots(&roots, Path::new("lib.rs")).unwrap(),
+            RootedPath::in_project("lib.rs")
+        );
+        assert!(resolve_within_roots(&roots, &temp_dir.path().join("elsewhere.rs")).is_err());
+
+        let first = manager.create_checkpoint(None, None).await.unwrap().checkpoint.id;
+        fs::write(shared.join("lib.rs"), "v2").unwrap();
+        fs::write(shared.join("added.rs"), "new").unwrap();
+        let second = manager.create_checkpoint(None, None).await.unwrap().checkpoint.id;
+
+        let diff = manager
+            .storage
+            .diff_checkpoints("project", "session", &first, &second)
+            .unwrap();
+        assert_eq!(diff.added_files, [PathBuf::from("@1/added.rs")]);
+        assert_eq!(diff.modified_files[0].path, PathBuf::from("@1/lib.rs"));
+
+        // The roots are stored with the session, so a new manager knows them
+        drop(manager);
+        let (manager, _) = test_manager(&temp_dir).await;
+        manager.restore_checkpoint(&first).await.unwrap();
+        assert_eq!(fs::read_to_string(shared.join("lib.rs")).unwrap(), "v1");
+        assert!(!shared.join("added.rs").exists());
+        assert!(project_path.join("src/main.rs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_timeline_changes_are_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, project_path) = test_manager(&temp_dir).await;
+
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&updates);
+        let manager = manager.with_notifier(Some(Arc::new(move |update: TimelineUpdate| {
+            sink.lock().unwrap().push(update)
+        })));
+
+        fs::write(project_path.join("src/main.rs"), "fn main() {}").unwrap();
+        let first = manager.create_checkpoint(None, None).await.unwrap();
+        fs::write(project_path.join("src/main.rs"), "fn main() { run() }").unwrap();
+        manager.create_checkpoint(None, None).await.unwrap();
+        manager
+            .update_settings(false, CheckpointStrategy::Manual)
+            .await
+            .unwrap();
+        assert_eq!(
+            manager
+                .cleanup_old_checkpoints(Some(1), &CheckpointRetention::default())
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(!manager.get_timeline().await.auto_checkpoint_enabled);
+
+        let updates = updates.lock().unwrap();
+        let totals: Vec<usize> = updates.iter().map(|u| u.total_checkpoints).collect();
+        assert_eq!(totals, vec![1, 2, 2, 1]);
+        assert_eq!(
+            updates[0].current_checkpoint_id.as_deref(),
+            Some(first.checkpoint.id.as_str())
+        );
+        assert!(updates.iter().all(|u| u.session_id == "session"));
+    }
+
+    #[tokio::test]
+    async fn test_appended_timeline_matches_a_fresh_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, project_path) = test_manager(&temp_dir).await;
+
+        for i in 0..100 {
+            fs::write(project_path.join("src/main.rs"), format!("fn main() {{ {} }}", i)).unwrap();
+            manager.create_checkpoint(None, None).await.unwrap();
+        }
+
+        let in_memory = manager.get_timeline().await;
+        let paths = CheckpointPaths::new(&manager.storage.claude_dir, "project", "session");
+        let loaded = manager.storage.load_timeline(&paths.timeline_file).unwrap();
+        assert_eq!(in_memory.total_checkpoints, 100);
+        assert_eq!(
+            serde_json::to_value(&in_memory).unwrap(),
+            serde_json::to_value(&loaded).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_reported_and_cancel_leaves_no_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, project_path) = test_manager(&temp_dir).await;
+        for i in 0..3 {
+            fs::write(project_path.join(format!("src/{}.rs", i)), "fn f() {}").unwrap();
+        }
+
+        // Cancel the first create as soon as it starts saving files
+        let cancel = manager.cancel_create.clone();
+        let cancelled_once = Arc::new(AtomicBool::new(false));
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let manager = manager.with_progress(Some(Arc::new(move |progress: CheckpointProgress| {
+            if progress.phase == CheckpointPhase::Saving && !cancelled_once.swap(true, Ordering::SeqCst) {
+                cancel.cancel_all();
+            }
+            sink.lock().unwrap().push(progress);
+        })));
+
+        let err = manager.create_checkpoint(None, None).await.unwrap_err();
+        assert!(err.to_string().contains("cancelled"));
+        assert_eq!(manager.get_timeline().await.total_checkpoints, 0);
+        let paths = CheckpointPaths::new(&temp_dir.path().join("claude"), "project", "session");
+        assert_eq!(fs::read_dir(&paths.checkpoints_dir).unwrap().count(), 0);
+
+        // The next create starts with a cleared flag and completes
+        events.lock().unwrap().clear();
+        let result = manager.create_checkpoint(None, None).await.unwrap();
+        assert_eq!(result.files_processed, 3);
+        assert_eq!(manager.get_timeline().await.total_checkpoints, 1);
+
+        let events = events.lock().unwrap();
+        let mut phases: Vec<CheckpointPhase> = events.iter().map(|e| e.phase).collect();
+        phases.dedup();
+        assert_eq!(
+            phases,
+            vec![
+                CheckpointPhase::Scanning,
+                CheckpointPhase::Snapshotting,
+                CheckpointPhase::Saving,
+                CheckpointPhase::Done,
+            ]
+        );
+        let last_scan = events
+            .iter()
+            .rfind(|e| e.phase == CheckpointPhase::Scanning)
+            .unwrap();
+        assert_eq!((last_scan.files_done, last_scan.files_total), (3, 3));
+        assert_eq!(last_scan.bytes_done, 27);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_reaches_a_create_waiting_for_the_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, _) = test_manager(&temp_dir).await;
+
+        // Another create holds the lock while this one is cancelled
+        let pool_guard = manager.checkpoint_lock.lock().await;
+        let cancel = async {
+            manager.cancel_checkpoint_creation();
+            drop(pool_guard);
+        };
+        let (result, _) = tokio::join!(manager.create_checkpoint(None, None), cancel);
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+        assert_eq!(manager.get_timeline().await.total_checkpoints, 0);
+
+        // The cancel was used up by that create
+        manager.create_checkpoint(None, None).await.unwrap();
+    }
+}
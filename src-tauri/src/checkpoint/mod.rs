@@ -1,13 +1,21 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::api_schema::api_type;
+use macro_rules_attribute::apply;
 
+pub mod changes;
+pub mod file_index;
 pub mod manager;
 pub mod state;
 pub mod storage;
 
 /// Represents a checkpoint in the session timeline
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Checkpoint {
@@ -25,11 +33,71 @@ pub struct Checkpoint {
     pub description: Option<String>,
     /// Parent checkpoint ID for fork tracking
     pub parent_checkpoint_id: Option<String>,
+    /// What created the checkpoint; older checkpoints count as manual
+    #[serde(default)]
+    pub kind: CheckpointKind,
     /// Metadata about the checkpoint
     pub metadata: CheckpointMetadata,
 }
 
+/// What created a checkpoint, for retention
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointKind {
+    /// Created by the user, including forks
+    #[default]
+    Manual,
+    /// Created by the per-prompt strategy
+    AutoPrompt,
+    /// Created by the per-tool-use strategy
+    AutoTool,
+    /// Created by the smart strategy
+    AutoSmart,
+    /// Taken by the app before a risky operation
+    AutoSafety,
+}
+
+impl CheckpointKind {
+    /// Kind of the automatic checkpoints a strategy creates
+    pub fn for_strategy(strategy: &CheckpointStrategy) -> Self {
+        match strategy {
+            CheckpointStrategy::Manual => Self::Manual,
+            CheckpointStrategy::PerPrompt => Self::AutoPrompt,
+            CheckpointStrategy::PerToolUse => Self::AutoTool,
+            CheckpointStrategy::Smart => Self::AutoSmart,
+        }
+    }
+
+    pub fn is_auto(self) -> bool {
+        self != Self::Manual
+    }
+}
+
+/// How many checkpoints of each kind a session keeps; `None` keeps all.
+///
+/// The current checkpoint and branch points (checkpoints with more than one
+/// child) are never pruned.
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CheckpointRetention {
+    pub keep_manual: Option<usize>,
+    /// Counted over all automatic kinds together
+    pub keep_auto: Option<usize>,
+    /// Estimated snapshot size above which the oldest automatic checkpoints go
+    pub max_auto_bytes: Option<u64>,
+}
+
+impl CheckpointRetention {
+    /// Whether the policy never prunes anything
+    pub fn keeps_all(&self) -> bool {
+        self.keep_manual.is_none() && self.keep_auto.is_none() && self.max_auto_bytes.is_none()
+    }
+}
+
 /// Metadata associated with a checkpoint
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CheckpointMetadata {
@@ -43,6 +111,9 @@ pub struct CheckpointMetadata {
     pub file_changes: usize,
     /// Size of all file snapshots in bytes
     pub snapshot_size: u64,
+    /// Cost in USD of all messages up to this point (absent in older checkpoints)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cost_usd: Option<f64>,
 }
 
 /// Represents a snapshot of a file at a checkpoint
@@ -51,10 +122,18 @@ pub struct CheckpointMetadata {
 pub struct FileSnapshot {
     /// Checkpoint this snapshot belongs to
     pub checkpoint_id: String,
-    /// Relative path from project root
+    /// Relative path from the root the file is in
     pub file_path: PathBuf,
-    /// Full content of the file (will be compressed)
+    /// Which root of the session `file_path` is relative to: 0 for the
+    /// project, 1.. for the directories added with `--add-dir`
+    #[serde(default)]
+    pub root_index: usize,
+    /// Full content of the file (will be compressed). Empty when the content
+    /// is streamed from `source_path` or from the content pool instead.
     pub content: String,
+    /// File on disk to stream the content from instead of `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
     /// SHA-256 hash for integrity verification
     pub hash: String,
     /// Whether this file was deleted at this checkpoint
@@ -65,7 +144,44 @@ pub struct FileSnapshot {
     pub size: u64,
 }
 
+impl FileSnapshot {
+    pub fn rooted_path(&self) -> RootedPath {
+        RootedPath {
+            root_index: self.root_index,
+            path: self.file_path.clone(),
+        }
+    }
+}
+
+/// A file below one of a session's roots
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RootedPath {
+    /// 0 for the project, 1.. for the session's additional directories
+    pub root_index: usize,
+    /// Relative to the root
+    pub path: PathBuf,
+}
+
+impl RootedPath {
+    pub fn in_project(path: impl Into<PathBuf>) -> Self {
+        Self {
+            root_index: 0,
+            path: path.into(),
+        }
+    }
+
+    /// How the file is named in diffs and the file index: its relative path
+    /// in the project, `@{root_index}/{path}` in an additional directory
+    pub fn label(&self) -> PathBuf {
+        match self.root_index {
+            0 => self.path.clone(),
+            index => Path::new(&format!("@{}", index)).join(&self.path),
+        }
+    }
+}
+
 /// Represents a node in the timeline tree
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TimelineNode {
@@ -75,9 +191,45 @@ pub struct TimelineNode {
     pub children: Vec<TimelineNode>,
     /// IDs of file snapshots associated with this checkpoint
     pub file_snapshot_ids: Vec<String>,
+    /// Tokens used since the parent checkpoint (filled in when the timeline is served)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_delta: Option<i64>,
+    /// Cost in USD since the parent checkpoint, when both costs are known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_delta: Option<f64>,
+}
+
+impl TimelineNode {
+    /// Fill in the token and cost deltas of this node and its descendants.
+    ///
+    /// Each node is compared with its parent in the tree, so the first node of
+    /// a fork is measured against the checkpoint it branched from, not its
+    /// previous sibling.
+    fn compute_deltas(&mut self, parent: Option<&CheckpointMetadata>) {
+        let metadata = &self.checkpoint.metadata;
+        let (parent_tokens, parent_cost) = match parent {
+            Some(parent) => (parent.total_tokens, parent.total_cost_usd),
+            None => (0, Some(0.0)),
+        };
+        self.tokens_delta = Some(metadata.total_tokens as i64 - parent_tokens as i64);
+        self.cost_delta = metadata
+            .total_cost_usd
+            .zip(parent_cost)
+            .map(|(cost, parent_cost)| cost - parent_cost);
+
+        let Self {
+            checkpoint,
+            children,
+            ..
+        } = self;
+        for child in children {
+            child.compute_deltas(Some(&checkpoint.metadata));
+        }
+    }
 }
 
 /// The complete timeline for a session
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionTimeline {
@@ -91,29 +243,80 @@ pub struct SessionTimeline {
     pub auto_checkpoint_enabled: bool,
     /// Strategy for automatic checkpoints
     pub checkpoint_strategy: CheckpointStrategy,
+    /// Applied after every automatic checkpoint
+    #[serde(default)]
+    pub retention: CheckpointRetention,
     /// Total number of checkpoints in timeline
     pub total_checkpoints: usize,
 }
 
 /// Strategy for automatic checkpoint creation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serialized in snake_case; `FromStr` also accepts camelCase in any case.
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CheckpointStrategy {
     /// Only create checkpoints manually
     Manual,
     /// Create checkpoint after each user prompt
+    #[serde(alias = "perPrompt")]
     PerPrompt,
     /// Create checkpoint after each tool use
+    #[serde(alias = "perToolUse")]
     PerToolUse,
     /// Create checkpoint after destructive operations
     Smart,
 }
 
+impl CheckpointStrategy {
+    pub const ALL: [Self; 4] = [Self::Manual, Self::PerPrompt, Self::PerToolUse, Self::Smart];
+
+    /// Canonical name, the same as the serialized form
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::PerPrompt => "per_prompt",
+            Self::PerToolUse => "per_tool_use",
+            Self::Smart => "smart",
+        }
+    }
+}
+
+impl fmt::Display for CheckpointStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CheckpointStrategy {
+    type Err = String;
+
+    /// Parse `per_tool_use`, `perToolUse`, `PER_TOOL_USE` and so on
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let folded: String = s
+            .trim()
+            .chars()
+            .filter(|c| *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.as_str().replace('_', "") == folded)
+            .ok_or_else(|| {
+                format!(
+                    "unknown checkpoint strategy '{}', expected one of manual, per_prompt, per_tool_use, smart",
+                    s
+                )
+            })
+    }
+}
+
 /// Tracks the state of files for checkpointing
 #[derive(Debug, Clone)]
 pub struct FileTracker {
     /// Map of file paths to their current state
-    pub tracked_files: HashMap<PathBuf, FileState>,
+    pub tracked_files: HashMap<RootedPath, FileState>,
 }
 
 /// State of a tracked file
@@ -130,6 +333,7 @@ pub struct FileState {
 }
 
 /// Result of a checkpoint operation
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CheckpointResult {
     /// The created/restored checkpoint
@@ -138,10 +342,129 @@ pub struct CheckpointResult {
     pub files_processed: usize,
     /// Any warnings during the operation
     pub warnings: Vec<String>,
+    /// What a save added to the timeline on disk; not sent to the frontend
+    #[serde(skip)]
+    pub timeline_append: Option<TimelineAppend>,
+}
+
+/// A checkpoint node a save appended to timeline.json, with the counters
+/// after it, so the in-memory timeline can follow without a reload
+#[derive(Debug, Clone)]
+pub struct TimelineAppend {
+    pub node: TimelineNode,
+    pub current_checkpoint_id: Option<String>,
+    pub total_checkpoints: usize,
+}
+
+/// Where a repaired timeline was recovered from
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineRepairSource {
+    /// timeline.json was readable, nothing to repair
+    Primary,
+    /// Restored from timeline.json.bak
+    Backup,
+    /// Rebuilt from the checkpoint metadata on disk
+    Rebuilt,
+}
+
+/// Result of repairing a session timeline
+#[apply(api_type!)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineRepairReport {
+    /// Session whose timeline was repaired
+    pub session_id: String,
+    /// Where the timeline came from
+    pub source: TimelineRepairSource,
+    /// Number of checkpoints in the repaired timeline
+    pub recovered_checkpoints: usize,
+    /// Checkpoints that were skipped or re-parented during a rebuild
+    pub warnings: Vec<String>,
+}
+
+/// Checkpoint data left behind by a session whose JSONL no longer exists
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanedCheckpointData {
+    pub project_id: String,
+    pub session_id: String,
+    /// Size of the session's `.timelines` directory
+    pub size_bytes: u64,
+}
+
+/// Deduplication statistics of the content pool of a session
+#[apply(api_type!)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentPoolStats {
+    /// Blobs in the pool
+    pub blob_count: usize,
+    /// Compressed size of all blobs on disk
+    pub total_bytes: u64,
+    /// Blobs referenced by at least one checkpoint
+    pub referenced_blobs: usize,
+    /// Blobs no checkpoint refers to; removed by `gc_content_pool`
+    pub unreferenced_blobs: usize,
+    /// Compressed size of the unreferenced blobs
+    pub unreferenced_bytes: u64,
+    /// Uncompressed size of every file snapshot, counted once per checkpoint
+    pub logical_bytes: u64,
+    /// `logical_bytes / total_bytes`, or 0 for an empty pool
+    pub dedup_ratio: f64,
+}
+
+/// Payload of the `timeline-updated:{session_id}` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineUpdate {
+    pub session_id: String,
+    pub total_checkpoints: usize,
+    pub current_checkpoint_id: Option<String>,
 }
 
+/// Callback invoked whenever a session timeline changes
+pub type TimelineNotifier = Arc<dyn Fn(TimelineUpdate) + Send + Sync>;
+
+/// Stage of a checkpoint create or restore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointPhase {
+    /// Walking the project and hashing files
+    Scanning,
+    /// Hashing the files that changed since the last checkpoint
+    Snapshotting,
+    /// Writing file contents into the checkpoint
+    Saving,
+    /// Removing and rewriting project files
+    Restoring,
+    /// The operation finished
+    Done,
+}
+
+/// Payload of the `checkpoint-progress:{session_id}` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointProgress {
+    pub session_id: String,
+    pub phase: CheckpointPhase,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+}
+
+/// Callback invoked as long checkpoint operations make progress
+pub type ProgressNotifier = Arc<dyn Fn(CheckpointProgress) + Send + Sync>;
+
+/// Files larger than this (in either checkpoint) are not diffed line by line
+pub const MAX_DIFF_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
 /// Diff between two checkpoints
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CheckpointDiff {
     /// Source checkpoint ID
     pub from_checkpoint_id: String,
@@ -158,7 +481,9 @@ pub struct CheckpointDiff {
 }
 
 /// Diff for a single file
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct FileDiff {
     /// File path
     pub path: PathBuf,
@@ -166,8 +491,11 @@ pub struct FileDiff {
     pub additions: usize,
     /// Number of deletions
     pub deletions: usize,
-    /// Unified diff content (optional)
+    /// Unified diff content, only filled in by `get_file_diff`
     pub diff_content: Option<String>,
+    /// Above `MAX_DIFF_FILE_SIZE`; counts and content were skipped
+    #[serde(default)]
+    pub too_large: bool,
 }
 
 impl Default for CheckpointStrategy {
@@ -185,10 +513,74 @@ impl SessionTimeline {
             current_checkpoint_id: None,
             auto_checkpoint_enabled: false,
             checkpoint_strategy: CheckpointStrategy::default(),
+            retention: CheckpointRetention::default(),
             total_checkpoints: 0,
         }
     }
 
+    /// Fill in the per-node token and cost deltas against each parent checkpoint
+    pub fn compute_deltas(&mut self) {
+        if let Some(root) = &mut self.root_node {
+            root.compute_deltas(None);
+        }
+    }
+
+    /// Time of the newest checkpoint on any branch
+    pub fn latest_checkpoint_at(&self) -> Option<DateTime<Utc>> {
+        let mut stack: Vec<&TimelineNode> = self.root_node.iter().collect();
+        let mut latest = None;
+        while let Some(node) = stack.pop() {
+            latest = latest.max(Some(node.checkpoint.timestamp));
+            stack.extend(node.children.iter());
+        }
+        latest
+    }
+
+    /// Add a new checkpoint node under its parent, or as the root of an empty
+    /// timeline, make it current and count it
+    pub fn append_node(&mut self, node: TimelineNode) -> anyhow::Result<()> {
+        let id = node.checkpoint.id.clone();
+        if self.root_node.is_none() {
+            self.root_node = Some(node);
+            self.current_checkpoint_id = Some(id);
+        } else if let Some(parent_id) = node.checkpoint.parent_checkpoint_id.clone() {
+            let parent = self
+                .find_checkpoint_mut(&parent_id)
+                .ok_or_else(|| anyhow::anyhow!("Parent checkpoint not found: {}", parent_id))?;
+            parent.children.push(node);
+            self.current_checkpoint_id = Some(id);
+        }
+        self.total_checkpoints += 1;
+        Ok(())
+    }
+
+    /// Apply a save made to the on-disk copy of this timeline.
+    ///
+    /// Returns false, leaving the timeline untouched, when this copy no
+    /// longer matches the one on disk and has to be reloaded instead.
+    pub fn follow_append(&mut self, append: TimelineAppend) -> bool {
+        if self.total_checkpoints + 1 != append.total_checkpoints {
+            return false;
+        }
+        if self.append_node(append.node).is_err() {
+            return false;
+        }
+        self.current_checkpoint_id = append.current_checkpoint_id;
+        true
+    }
+
+    fn find_checkpoint_mut(&mut self, checkpoint_id: &str) -> Option<&mut TimelineNode> {
+        fn find<'a>(node: &'a mut TimelineNode, checkpoint_id: &str) -> Option<&'a mut TimelineNode> {
+            if node.checkpoint.id == checkpoint_id {
+                return Some(node);
+            }
+            node.children
+                .iter_mut()
+                .find_map(|child| find(child, checkpoint_id))
+        }
+        self.root_node.as_mut().and_then(|root| find(root, checkpoint_id))
+    }
+
     /// Find a checkpoint by ID in the timeline tree
     pub fn find_checkpoint(&self, checkpoint_id: &str) -> Option<&TimelineNode> {
         self.root_node
@@ -214,6 +606,10 @@ impl SessionTimeline {
 /// Checkpoint storage paths
 pub struct CheckpointPaths {
     pub timeline_file: PathBuf,
+    /// Which checkpoints touched which files, next to the timeline
+    pub file_index_file: PathBuf,
+    /// Directories outside the project the session's checkpoints cover
+    pub additional_roots_file: PathBuf,
     pub checkpoints_dir: PathBuf,
     pub files_dir: PathBuf,
 }
@@ -228,6 +624,8 @@ impl CheckpointPaths {
 
         Self {
             timeline_file: base_dir.join("timeline.json"),
+            file_index_file: base_dir.join("file_index.json"),
+            additional_roots_file: base_dir.join("additional_roots.json"),
             checkpoints_dir: base_dir.join("checkpoints"),
             files_dir: base_dir.join("files"),
         }
@@ -260,3 +658,97 @@ impl CheckpointPaths {
             .join(format!("{}.json", safe_filename))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategy_parses_every_spelling_and_round_trips() {
+        let spellings = [
+            (CheckpointStrategy::Manual, ["manual", "Manual", "MANUAL"]),
+            (CheckpointStrategy::PerPrompt, ["per_prompt", "perPrompt", "PER_PROMPT"]),
+            (CheckpointStrategy::PerToolUse, ["per_tool_use", "perToolUse", "PerToolUse"]),
+            (CheckpointStrategy::Smart, ["smart", "Smart", " SMART "]),
+        ];
+        for (strategy, names) in spellings {
+            for name in names {
+                assert_eq!(name.parse::<CheckpointStrategy>(), Ok(strategy.clone()), "{}", name);
+            }
+            assert_eq!(strategy.to_string().parse::<CheckpointStrategy>(), Ok(strategy.clone()));
+
+            // serde, Display and the frontend's type all use the same name
+            let json = serde_json::to_string(&strategy).unwrap();
+            assert_eq!(json, format!("\"{}\"", strategy));
+            assert_eq!(serde_json::from_str::<CheckpointStrategy>(&json).unwrap(), strategy);
+        }
+        assert_eq!(
+            serde_json::from_str::<CheckpointStrategy>("\"perToolUse\"").unwrap(),
+            CheckpointStrategy::PerToolUse
+        );
+        for bad in ["", "per-prompt", "auto", "per_prompts"] {
+            assert!(bad.parse::<CheckpointStrategy>().is_err(), "{}", bad);
+        }
+    }
+
+    fn node(
+        id: &str,
+        total_tokens: u64,
+        total_cost_usd: Option<f64>,
+        children: Vec<TimelineNode>,
+    ) -> TimelineNode {
+        TimelineNode {
+            checkpoint: Checkpoint {
+                id: id.to_string(),
+                session_id: "session".to_string(),
+                project_id: "project".to_string(),
+                message_index: 0,
+                timestamp: Utc::now(),
+                description: None,
+                parent_checkpoint_id: None,
+                kind: CheckpointKind::Manual,
+                metadata: CheckpointMetadata {
+                    total_tokens,
+                    model_used: "sonnet".to_string(),
+                    user_prompt: String::new(),
+                    file_changes: 0,
+                    snapshot_size: 0,
+                    total_cost_usd,
+                },
+            },
+            children,
+            file_snapshot_ids: Vec::new(),
+            tokens_delta: None,
+            cost_delta: None,
+        }
+    }
+
+    #[test]
+    fn deltas_are_measured_against_the_parent_of_a_fork() {
+        //   root(100) ── a(250) ── a2(300)
+        //            └─ b(180)            (fork from root)
+        //                 └─ b2(400, legacy checkpoint without cost)
+        let mut timeline = SessionTimeline::new("session".to_string());
+        timeline.root_node = Some(node(
+            "root",
+            100,
+            Some(0.5),
+            vec![
+                node("a", 250, Some(1.25), vec![node("a2", 300, Some(1.5), vec![])]),
+                node("b", 180, Some(0.75), vec![node("b2", 400, None, vec![])]),
+            ],
+        ));
+
+        timeline.compute_deltas();
+
+        let delta = |id: &str| {
+            let node = timeline.find_checkpoint(id).unwrap();
+            (node.tokens_delta, node.cost_delta)
+        };
+        assert_eq!(delta("root"), (Some(100), Some(0.5)));
+        assert_eq!(delta("a"), (Some(150), Some(0.75)));
+        assert_eq!(delta("a2"), (Some(50), Some(0.25)));
+        assert_eq!(delta("b"), (Some(80), Some(0.25)));
+        assert_eq!(delta("b2"), (Some(220), None));
+    }
+}
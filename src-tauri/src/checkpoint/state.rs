@@ -2,9 +2,10 @@ use anyhow::Result;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
 use super::manager::CheckpointManager;
+use super::{ProgressNotifier, TimelineNotifier};
 
 /// Manages checkpoint managers for active sessions
 ///
@@ -18,6 +19,19 @@ pub struct CheckpointState {
     managers: Arc<RwLock<HashMap<String, Arc<CheckpointManager>>>>,
     /// The Claude directory path for consistent access
     claude_dir: Arc<RwLock<Option<PathBuf>>>,
+    /// Handed to every new manager so timeline changes reach the UI;
+    /// left unset in unit tests where no app exists
+    notifier: Arc<RwLock<Option<TimelineNotifier>>>,
+    /// Handed to every new manager so create/restore progress reaches the UI
+    progress: Arc<RwLock<Option<ProgressNotifier>>>,
+    /// Per-session lock held while checkpoints are created or the content
+    /// pool is inspected or collected, whether or not a manager exists
+    checkpoint_locks: Arc<RwLock<HashMap<String, Arc<Mutex<()>>>>>,
+    /// Number of claude processes running per project key
+    active_runs: Arc<std::sync::Mutex<HashMap<String, usize>>>,
+    /// Sessions whose auto-checkpoint waits for their project's runs to end:
+    /// session_id -> (project_id, project key)
+    deferred_checkpoints: Arc<std::sync::Mutex<HashMap<String, (String, String)>>>,
 }
 
 impl CheckpointState {
@@ -26,17 +40,117 @@ impl CheckpointState {
         Self {
             managers: Arc::new(RwLock::new(HashMap::new())),
             claude_dir: Arc::new(RwLock::new(None)),
+            notifier: Arc::new(RwLock::new(None)),
+            progress: Arc::new(RwLock::new(None)),
+            checkpoint_locks: Arc::new(RwLock::new(HashMap::new())),
+            active_runs: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            deferred_checkpoints: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
     }
 
     /// Sets the Claude directory path
     ///
-    /// This should be called once during application initialization
+    /// Called during application initialization, and again when the app is
+    /// switched to another directory, which drops the managers of the old one
     pub async fn set_claude_dir(&self, claude_dir: PathBuf) {
         let mut dir = self.claude_dir.write().await;
+        if dir.as_ref().is_some_and(|current| *current != claude_dir) {
+            self.managers.write().await.clear();
+        }
         *dir = Some(claude_dir);
     }
 
+    /// Sets the callback that is told about timeline changes
+    ///
+    /// Only managers created after this call report their changes
+    pub async fn set_notifier(&self, notifier: TimelineNotifier) {
+        let mut current = self.notifier.write().await;
+        *current = Some(notifier);
+    }
+
+    /// Sets the callback that is told about checkpoint create/restore progress
+    ///
+    /// Only managers created after this call report their progress
+    pub async fn set_progress_notifier(&self, progress: ProgressNotifier) {
+        let mut current = self.progress.write().await;
+        *current = Some(progress);
+    }
+
+    /// Gets the checkpoint lock of a session, creating it on first use
+    pub async fn checkpoint_lock(&self, session_id: &str) -> Arc<Mutex<()>> {
+        let mut locks = self.checkpoint_locks.write().await;
+        Arc::clone(locks.entry(session_id.to_string()).or_default())
+    }
+
+    /// Records that a claude process started in the project with this key
+    pub fn begin_run(&self, project_key: &str) {
+        let mut runs = self.active_runs.lock().unwrap_or_else(|e| e.into_inner());
+        *runs.entry(project_key.to_string()).or_default() += 1;
+    }
+
+    /// Records that a claude process in the project ended
+    ///
+    /// When it was the project's last one, returns the sessions whose
+    /// auto-checkpoint was deferred, as (session_id, project_id) pairs.
+    pub fn end_run(&self, project_key: &str) -> Vec<(String, String)> {
+        {
+            let mut runs = self.active_runs.lock().unwrap_or_else(|e| e.into_inner());
+            match runs.get_mut(project_key) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    return Vec::new();
+                }
+                _ => {
+                    runs.remove(project_key);
+                }
+            }
+        }
+
+        let mut deferred = self
+            .deferred_checkpoints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let sessions: Vec<String> = deferred
+            .iter()
+            .filter(|(_, (_, key))| key == project_key)
+            .map(|(session_id, _)| session_id.clone())
+            .collect();
+        sessions
+            .into_iter()
+            .filter_map(|session_id| {
+                let (project_id, _) = deferred.remove(&session_id)?;
+                Some((session_id, project_id))
+            })
+            .collect()
+    }
+
+    /// Whether a claude process is running in the project
+    pub fn has_active_run(&self, project_key: &str) -> bool {
+        let runs = self.active_runs.lock().unwrap_or_else(|e| e.into_inner());
+        runs.contains_key(project_key)
+    }
+
+    /// Remembers to create a session's auto-checkpoint once the project's runs end
+    pub fn defer_auto_checkpoint(&self, session_id: &str, project_id: &str, project_key: &str) {
+        let mut deferred = self
+            .deferred_checkpoints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        deferred.insert(
+            session_id.to_string(),
+            (project_id.to_string(), project_key.to_string()),
+        );
+    }
+
+    /// Whether a session has an auto-checkpoint waiting for a run to end
+    pub fn has_deferred_checkpoint(&self, session_id: &str) -> bool {
+        let deferred = self
+            .deferred_checkpoints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        deferred.contains_key(session_id)
+    }
+
     /// Gets or creates a CheckpointManager for a session
     ///
     /// If a manager already exists for the session, it returns the existing one.
@@ -71,9 +185,15 @@ impl CheckpointState {
         };
 
         // Create new manager
+        let notifier = self.notifier.read().await.clone();
+        let progress = self.progress.read().await.clone();
+        let checkpoint_lock = self.checkpoint_lock(&session_id).await;
         let manager =
             CheckpointManager::new(project_id, session_id.clone(), project_path, claude_dir)
-                .await?;
+                .await?
+                .with_notifier(notifier)
+                .with_progress(progress)
+                .with_checkpoint_lock(checkpoint_lock);
 
         let manager_arc = Arc::new(manager);
         managers.insert(session_id, Arc::clone(&manager_arc));
@@ -81,10 +201,26 @@ impl CheckpointState {
         Ok(manager_arc)
     }
 
+    /// Makes the session's checkpoints also cover `dirs`, the directories
+    /// outside the project it was given with `--add-dir`
+    pub async fn add_additional_roots(
+        &self,
+        session_id: String,
+        project_id: String,
+        project_path: PathBuf,
+        dirs: &[PathBuf],
+    ) -> Result<()> {
+        if dirs.is_empty() {
+            return Ok(());
+        }
+        self.get_or_create_manager(session_id, project_id, project_path)
+            .await?
+            .add_additional_roots(dirs)
+    }
+
     /// Gets an existing CheckpointManager for a session
     ///
     /// Returns None if no manager exists for the session
-    #[allow(dead_code)]
     pub async fn get_manager(&self, session_id: &str) -> Option<Arc<CheckpointManager>> {
         let managers = self.managers.read().await;
         managers.get(session_id).map(Arc::clone)
@@ -181,4 +317,26 @@ mod tests {
 
         assert!(!Arc::ptr_eq(&manager1, &manager3));
     }
+
+    #[test]
+    fn auto_checkpoints_wait_for_the_last_run_of_the_project() {
+        let state = CheckpointState::new();
+        state.begin_run("/work/app");
+        state.begin_run("/work/app");
+        assert!(state.has_active_run("/work/app"));
+        assert!(!state.has_active_run("/work/other"));
+
+        state.defer_auto_checkpoint("s1", "-work-app", "/work/app");
+        state.defer_auto_checkpoint("s2", "-work-other", "/work/other");
+        assert!(state.has_deferred_checkpoint("s1"));
+
+        assert!(state.end_run("/work/app").is_empty());
+        assert_eq!(
+            state.end_run("/work/app"),
+            vec![("s1".to_string(), "-work-app".to_string())]
+        );
+        assert!(!state.has_active_run("/work/app"));
+        assert!(!state.has_deferred_checkpoint("s1"));
+        assert!(state.has_deferred_checkpoint("s2"));
+    }
 }
@@ -2,13 +2,18 @@ use anyhow::Result;
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use crate::api_schema::api_type;
+use macro_rules_attribute::apply;
 /// Shared module for detecting Claude Code binary installations
 /// Supports NVM installations, aliased paths, and version-based selection
 use std::path::PathBuf;
 use std::process::Command;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+
+use crate::commands::i18n::{self, ErrorKind};
 
 /// Type of Claude installation
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum InstallationType {
     /// System-installed binary
@@ -18,6 +23,7 @@ pub enum InstallationType {
 }
 
 /// Represents a Claude installation with metadata
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaudeInstallation {
     /// Full path to the Claude binary
@@ -30,30 +36,74 @@ pub struct ClaudeInstallation {
     pub installation_type: InstallationType,
 }
 
+/// Payload of the `claude-binary-changed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeBinaryChanged {
+    /// The configured path that is no longer usable
+    pub previous_path: String,
+    /// The installation picked instead
+    pub new_path: String,
+    pub version: Option<String>,
+    pub source: String,
+}
+
+/// Whether a path points at an existing file that can be executed
+pub fn is_executable(path: &std::path::Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+
+    #[cfg(not(unix))]
+    {
+        true
+    }
+}
+
+/// Path stored in the `claude_binary_path` setting, whether or not it still exists
+pub fn stored_claude_binary_path(conn: &rusqlite::Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = 'claude_binary_path'",
+        [],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+}
+
 /// Main function to find the Claude binary
-/// Checks database first for stored path and preference, then prioritizes accordingly
+/// Checks database first for stored path and preference, then prioritizes accordingly.
+///
+/// When the stored path is gone or no longer executable, the best discovered
+/// installation replaces it in the database and `claude-binary-changed` is emitted.
 pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, String> {
     info!("Searching for claude binary...");
 
+    let mut stale_path: Option<(PathBuf, String)> = None;
+
     // First check if we have a stored path and preference in the database
     if let Ok(app_data_dir) = app_handle.path().app_data_dir() {
         let db_path = app_data_dir.join("agents.db");
         if db_path.exists() {
             if let Ok(conn) = rusqlite::Connection::open(&db_path) {
                 // Check for stored path first
-                if let Ok(stored_path) = conn.query_row(
-                    "SELECT value FROM app_settings WHERE key = 'claude_binary_path'",
-                    [],
-                    |row| row.get::<_, String>(0),
-                ) {
+                if let Some(stored_path) = stored_claude_binary_path(&conn) {
                     info!("Found stored claude path in database: {}", stored_path);
                     
-                    // Check if the path still exists
+                    // Check if the path still exists and can be run
                     let path_buf = PathBuf::from(&stored_path);
-                    if path_buf.exists() && path_buf.is_file() {
+                    if is_executable(&path_buf) {
                         return Ok(stored_path);
                     } else {
-                        warn!("Stored claude path no longer exists: {}", stored_path);
+                        warn!("Stored claude path is missing or not executable: {}", stored_path);
+                        stale_path = Some((db_path.clone(), stored_path));
                     }
                 }
                 
@@ -74,7 +124,10 @@ pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, Strin
 
     if installations.is_empty() {
         error!("Could not find claude binary in any location");
-        return Err("Claude Code not found. Please ensure it's installed in one of these locations: PATH, /usr/local/bin, /opt/homebrew/bin, ~/.nvm/versions/node/*/bin, ~/.claude/local, ~/.local/bin".to_string());
+        return Err(i18n::error(
+            ErrorKind::ClaudeNotInstalled,
+            "searched PATH, /usr/local/bin, /opt/homebrew/bin, ~/.nvm/versions/node/*/bin, ~/.claude/local, ~/.local/bin",
+        ));
     }
 
     // Log all found installations
@@ -88,10 +141,45 @@ pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, Strin
             "Selected Claude installation: path={}, version={:?}, source={}",
             best.path, best.version, best.source
         );
+        if let Some((db_path, previous_path)) = stale_path {
+            replace_stored_binary(app_handle, &db_path, previous_path, &best);
+        }
         Ok(best.path)
     } else {
-        Err("No valid Claude installation found".to_string())
+        Err(i18n::error(ErrorKind::ClaudeNotFound, "no valid installation"))
+    }
+}
+
+/// Persist a fallback installation in place of a stale stored path and notify the UI
+fn replace_stored_binary(
+    app_handle: &tauri::AppHandle,
+    db_path: &std::path::Path,
+    previous_path: String,
+    installation: &ClaudeInstallation,
+) {
+    match rusqlite::Connection::open(db_path).and_then(|conn| {
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES ('claude_binary_path', ?1)
+             ON CONFLICT(key) DO UPDATE SET value = ?1",
+            rusqlite::params![installation.path],
+        )
+    }) {
+        Ok(_) => info!(
+            "Replaced stale claude path {} with {}",
+            previous_path, installation.path
+        ),
+        Err(e) => warn!("Failed to store fallback claude path: {}", e),
     }
+
+    let _ = app_handle.emit(
+        "claude-binary-changed",
+        ClaudeBinaryChanged {
+            previous_path,
+            new_path: installation.path.clone(),
+            version: installation.version.clone(),
+            source: installation.source.clone(),
+        },
+    );
 }
 
 /// Discovers all available Claude installations and returns them for selection
@@ -331,7 +419,7 @@ fn find_standard_installations() -> Vec<ClaudeInstallation> {
 }
 
 /// Get Claude version by running --version command
-fn get_claude_version(path: &str) -> Result<Option<String>, String> {
+pub fn get_claude_version(path: &str) -> Result<Option<String>, String> {
     match Command::new(path).arg("--version").output() {
         Ok(output) => {
             if output.status.success() {
@@ -409,7 +497,7 @@ fn select_best_installation(installations: Vec<ClaudeInstallation>) -> Option<Cl
 }
 
 /// Compare two version strings
-fn compare_versions(a: &str, b: &str) -> Ordering {
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
     // Simple semantic version comparison
     let a_parts: Vec<u32> = a
         .split('.')
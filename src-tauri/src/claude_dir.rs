@@ -0,0 +1,157 @@
+//! Where the app reads claude's data from.
+//!
+//! This is ~/.claude unless the user switched to another directory, e.g. a
+//! copy from someone else's machine. Such a directory can be opened
+//! read-only, in which case writes into it fail with `read_only_mode`.
+//! claude processes started by the app get the same directory through
+//! `CLAUDE_CONFIG_DIR`.
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use crate::api_schema::api_type;
+use macro_rules_attribute::apply;
+
+use crate::commands::i18n::{self, ErrorKind};
+
+/// app_settings key of the directory used instead of ~/.claude
+pub const CLAUDE_DIR_SETTING_KEY: &str = "claude_dir_override";
+
+/// app_settings key that is "true" while the claude directory is read-only
+pub const READ_ONLY_SETTING_KEY: &str = "claude_dir_read_only";
+
+/// Which claude directory the app uses
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaudeDirSettings {
+    /// Directory used instead of ~/.claude
+    pub path: Option<PathBuf>,
+    /// Refuse writes into the directory
+    pub read_only: bool,
+}
+
+impl ClaudeDirSettings {
+    /// Whether a write to `path` must be refused, given the resolved `claude_dir`
+    fn refuses_write(&self, claude_dir: &Path, path: &Path) -> bool {
+        self.read_only
+            && (path.starts_with(claude_dir)
+                || claude_dir.canonicalize().is_ok_and(|dir| path.starts_with(dir)))
+    }
+}
+
+static CURRENT: RwLock<ClaudeDirSettings> = RwLock::new(ClaudeDirSettings {
+    path: None,
+    read_only: false,
+});
+
+/// Settings currently in effect
+pub fn current() -> ClaudeDirSettings {
+    CURRENT.read().map(|settings| settings.clone()).unwrap_or_default()
+}
+
+/// Re-point the resolver; callers persist the settings with `save`
+pub fn apply(settings: ClaudeDirSettings) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = settings;
+    }
+}
+
+/// Settings saved by a previous `switch_claude_dir`
+pub fn load(conn: &Connection) -> ClaudeDirSettings {
+    let setting = |key: &str| {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+    };
+    ClaudeDirSettings {
+        path: setting(CLAUDE_DIR_SETTING_KEY)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from),
+        read_only: setting(READ_ONLY_SETTING_KEY).is_some_and(|value| value == "true"),
+    }
+}
+
+pub fn save(conn: &Connection, settings: &ClaudeDirSettings) -> rusqlite::Result<()> {
+    let path = settings
+        .path
+        .as_ref()
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_default();
+    for (key, value) in [
+        (CLAUDE_DIR_SETTING_KEY, path),
+        (READ_ONLY_SETTING_KEY, settings.read_only.to_string()),
+    ] {
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![key, value],
+        )?;
+    }
+    Ok(())
+}
+
+/// The claude directory: the override if set, else ~/.claude
+pub fn claude_dir() -> Result<PathBuf, String> {
+    match current().path {
+        Some(path) => Ok(path),
+        None => Ok(dirs::home_dir()
+            .ok_or_else(|| i18n::error(ErrorKind::HomeDirNotFound, ""))?
+            .join(".claude")),
+    }
+}
+
+/// Session directories of the claude directory
+pub fn projects_dir() -> Result<PathBuf, String> {
+    Ok(claude_dir()?.join("projects"))
+}
+
+/// Fail with `read_only_mode` when `path` is inside a claude directory
+/// opened read-only
+pub fn check_writable(path: &Path) -> Result<(), String> {
+    let settings = current();
+    if !settings.read_only {
+        return Ok(());
+    }
+    if settings.refuses_write(&claude_dir()?, path) {
+        return Err(i18n::error(ErrorKind::ReadOnlyMode, path.display()));
+    }
+    Ok(())
+}
+
+/// Fail with `read_only_mode` while the claude directory is open read-only,
+/// for commands that write somewhere inside it
+pub fn check_dir_writable() -> Result<(), String> {
+    check_writable(&claude_dir()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_schema;
+
+    #[test]
+    fn settings_round_trip_and_only_guard_the_claude_dir() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        assert_eq!(load(&conn), ClaudeDirSettings::default());
+
+        let settings = ClaudeDirSettings {
+            path: Some(PathBuf::from("/backups/alice/.claude")),
+            read_only: true,
+        };
+        save(&conn, &settings).unwrap();
+        assert_eq!(load(&conn), settings);
+        save(&conn, &ClaudeDirSettings::default()).unwrap();
+        assert_eq!(load(&conn), ClaudeDirSettings::default());
+
+        let dir = Path::new("/backups/alice/.claude");
+        assert!(settings.refuses_write(dir, &dir.join("projects/-work/s1.jsonl")));
+        assert!(!settings.refuses_write(dir, Path::new("/work/app/CLAUDE.md")));
+        let writable = ClaudeDirSettings { read_only: false, ..settings };
+        assert!(!writable.refuses_write(dir, &dir.join("settings.json")));
+    }
+}
@@ -0,0 +1,371 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{self, AgentDb};
+use super::claude::{extract_first_user_message, find_project_dir};
+use super::i18n::{self, ErrorKind};
+use super::usage;
+use crate::checkpoint::storage::CheckpointStorage;
+use crate::checkpoint::{CheckpointPaths, TimelineNode};
+
+/// Entries returned when no limit is given
+const DEFAULT_ACTIVITY_LIMIT: usize = 200;
+
+/// Maximum length of a session's first prompt in the feed
+const ACTIVITY_TITLE_CHARS: usize = 200;
+
+/// What an activity entry describes
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    AgentRun,
+    Session,
+    Checkpoint,
+}
+
+/// How far back the activity feed goes
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityRange {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl ActivityRange {
+    /// Start of the range, `None` for all time
+    pub fn since(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Day => Some(now - chrono::Duration::days(1)),
+            Self::Week => Some(now - chrono::Duration::weeks(1)),
+            Self::Month => Some(now - chrono::Duration::days(30)),
+            Self::All => None,
+        }
+    }
+}
+
+/// One entry of a project's activity feed
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub kind: ActivityKind,
+    /// RFC 3339 time the run, session or checkpoint started
+    pub timestamp: String,
+    /// Agent task, first prompt of the session, or checkpoint description
+    pub title: String,
+    pub session_id: Option<String>,
+    /// Status of an agent run
+    pub status: Option<String>,
+    pub cost_usd: Option<f64>,
+    pub run_id: Option<i64>,
+    pub agent_name: Option<String>,
+    pub checkpoint_id: Option<String>,
+    /// Checkpoint this one was created from; a parent with several children is a fork
+    pub parent_checkpoint_id: Option<String>,
+}
+
+impl ActivityEntry {
+    fn new(kind: ActivityKind, timestamp: DateTime<Utc>, title: String) -> Self {
+        Self {
+            kind,
+            timestamp: timestamp.to_rfc3339(),
+            title,
+            session_id: None,
+            status: None,
+            cost_usd: None,
+            run_id: None,
+            agent_name: None,
+            checkpoint_id: None,
+            parent_checkpoint_id: None,
+        }
+    }
+}
+
+/// Parse RFC 3339 timestamps from JSONL and SQLite's `CURRENT_TIMESTAMP` (UTC)
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc())
+        })
+        .ok()
+}
+
+fn file_created_at(path: &Path) -> Option<DateTime<Utc>> {
+    let metadata = fs::metadata(path).ok()?;
+    let created = metadata.created().or_else(|_| metadata.modified()).ok()?;
+    (created >= SystemTime::UNIX_EPOCH).then(|| DateTime::<Utc>::from(created))
+}
+
+fn collect_checkpoint_entries(node: &TimelineNode, entries: &mut Vec<ActivityEntry>) {
+    let checkpoint = &node.checkpoint;
+    let title = checkpoint
+        .description
+        .clone()
+        .filter(|d| !d.trim().is_empty())
+        .unwrap_or_else(|| checkpoint.metadata.user_prompt.clone());
+    let mut entry = ActivityEntry::new(ActivityKind::Checkpoint, checkpoint.timestamp, title);
+    entry.session_id = Some(checkpoint.session_id.clone());
+    entry.cost_usd = checkpoint.metadata.total_cost_usd;
+    entry.checkpoint_id = Some(checkpoint.id.clone());
+    entry.parent_checkpoint_id = checkpoint.parent_checkpoint_id.clone();
+    entries.push(entry);
+
+    for child in &node.children {
+        collect_checkpoint_entries(child, entries);
+    }
+}
+
+/// Merge a project's agent runs, interactive sessions and checkpoints into
+/// one feed, newest first.
+///
+/// Sessions are found through claude's project directory for the path, and
+/// session costs come from the same usage parsing as the usage dashboard.
+/// Sessions started by agent runs only appear as the run.
+pub fn collect_activity(
+    conn: &Connection,
+    claude_dir: &Path,
+    project_path: &str,
+    since: Option<DateTime<Utc>>,
+    limit: usize,
+) -> Result<Vec<ActivityEntry>, String> {
+    let project_dir = find_project_dir(&claude_dir.join("projects"), Path::new(project_path));
+    let costs = usage::session_costs(&project_dir);
+    let mut entries = Vec::new();
+
+    let runs = agents::query_project_runs(conn, project_path)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let run_sessions: HashSet<String> = runs
+        .iter()
+        .map(|run| run.session_id.clone())
+        .filter(|sid| !sid.is_empty())
+        .collect();
+    for run in runs {
+        let Some(timestamp) = parse_timestamp(&run.created_at) else {
+            continue;
+        };
+        let cost_usd = costs.get(&run.session_id).copied().or_else(|| {
+            run.id
+                .and_then(|id| agents::load_run_metrics(conn, id))
+                .and_then(|(metrics, _)| metrics.cost_usd)
+        });
+        let mut entry = ActivityEntry::new(ActivityKind::AgentRun, timestamp, run.task);
+        entry.session_id = Some(run.session_id).filter(|sid| !sid.is_empty());
+        entry.status = Some(run.status);
+        entry.cost_usd = cost_usd;
+        entry.run_id = run.id;
+        entry.agent_name = Some(run.agent_name);
+        entries.push(entry);
+    }
+
+    if let Ok(dir_entries) = fs::read_dir(&project_dir) {
+        for dir_entry in dir_entries.flatten() {
+            let path = dir_entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(session_id) = path.file_stem().map(|s| s.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+            if run_sessions.contains(&session_id) {
+                continue;
+            }
+
+            let (first_message, message_timestamp) = extract_first_user_message(&path);
+            let Some(timestamp) = message_timestamp
+                .as_deref()
+                .and_then(parse_timestamp)
+                .or_else(|| file_created_at(&path))
+            else {
+                continue;
+            };
+            let title = first_message
+                .map(|m| m.trim().chars().take(ACTIVITY_TITLE_CHARS).collect())
+                .unwrap_or_default();
+            let mut entry = ActivityEntry::new(ActivityKind::Session, timestamp, title);
+            entry.cost_usd = costs.get(&session_id).copied();
+            entry.session_id = Some(session_id);
+            entries.push(entry);
+        }
+    }
+
+    // Sessions with a timeline contribute their checkpoints, including forks
+    let project_id = project_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let storage = CheckpointStorage::new(claude_dir.to_path_buf());
+    if let Ok(timelines) = fs::read_dir(project_dir.join(".timelines")) {
+        for timeline_dir in timelines.flatten() {
+            let session_id = timeline_dir.file_name().to_string_lossy().to_string();
+            let paths = CheckpointPaths::new(&PathBuf::from(claude_dir), &project_id, &session_id);
+            if !paths.timeline_file.exists() {
+                continue;
+            }
+            match storage.load_timeline(&paths.timeline_file) {
+                Ok(timeline) => {
+                    if let Some(root) = &timeline.root_node {
+                        collect_checkpoint_entries(root, &mut entries);
+                    }
+                }
+                Err(e) => log::warn!("Skipping unreadable timeline of {}: {}", session_id, e),
+            }
+        }
+    }
+
+    if let Some(since) = since {
+        entries.retain(|entry| parse_timestamp(&entry.timestamp).is_some_and(|t| t >= since));
+    }
+    // RFC 3339 in UTC sorts chronologically as text
+    entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+/// Chronological feed of agent runs, sessions and checkpoints for a project
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_project_activity(
+    db: State<'_, AgentDb>,
+    project_path: String,
+    range: Option<ActivityRange>,
+    limit: Option<usize>,
+) -> Result<Vec<ActivityEntry>, String> {
+    let claude_dir = crate::claude_dir::claude_dir()?;
+    let since = range.unwrap_or(ActivityRange::All).since(Utc::now());
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    collect_activity(
+        &conn,
+        &claude_dir,
+        &project_path,
+        since,
+        limit.unwrap_or(DEFAULT_ACTIVITY_LIMIT),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{Checkpoint, CheckpointKind, CheckpointMetadata, SessionTimeline};
+    use rusqlite::params;
+
+    fn checkpoint(id: &str, parent: Option<&str>, timestamp: &str) -> Checkpoint {
+        Checkpoint {
+            id: id.to_string(),
+            session_id: "s-cp".to_string(),
+            project_id: String::new(),
+            message_index: 0,
+            timestamp: parse_timestamp(timestamp).unwrap(),
+            description: Some(format!("checkpoint {}", id)),
+            parent_checkpoint_id: parent.map(|p| p.to_string()),
+            kind: CheckpointKind::Manual,
+            metadata: CheckpointMetadata {
+                total_tokens: 0,
+                model_used: "sonnet".to_string(),
+                user_prompt: String::new(),
+                file_changes: 0,
+                snapshot_size: 0,
+                total_cost_usd: None,
+            },
+        }
+    }
+
+    #[test]
+    fn activity_merges_runs_sessions_and_checkpoints() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp.path();
+        let project_path = "/work/app";
+        let project_dir = claude_dir
+            .join("projects")
+            .join(crate::commands::claude::encode_project_path(Path::new(project_path)));
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        agents::init_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt) VALUES ('Reviewer', 'bot', 'Review')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, created_at)
+             VALUES (?1, 'Reviewer', 'bot', 'Review the diff', 'sonnet', ?2, 's-run', 'completed', '2025-03-02 10:00:00')",
+            params![conn.last_insert_rowid(), project_path],
+        )
+        .unwrap();
+
+        let usage_line = |session: &str, ts: &str, id: &str| {
+            format!(
+                r#"{{"type":"assistant","sessionId":"{}","timestamp":"{}","requestId":"r-{}","message":{{"id":"{}","model":"claude-sonnet-4","usage":{{"input_tokens":1000000,"output_tokens":0}}}}}}"#,
+                session, ts, id, id
+            )
+        };
+        fs::write(
+            project_dir.join("s-run.jsonl"),
+            usage_line("s-run", "2025-03-02T10:00:05Z", "m1"),
+        )
+        .unwrap();
+        fs::write(
+            project_dir.join("s-chat.jsonl"),
+            [
+                r#"{"type":"user","timestamp":"2025-03-01T09:00:00Z","message":{"role":"user","content":"Fix the login bug"}}"#.to_string(),
+                usage_line("s-chat", "2025-03-01T09:00:10Z", "m2"),
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let project_id = project_dir.file_name().unwrap().to_string_lossy().to_string();
+        let paths = CheckpointPaths::new(&claude_dir.to_path_buf(), &project_id, "s-cp");
+        fs::create_dir_all(paths.timeline_file.parent().unwrap()).unwrap();
+        let mut timeline = SessionTimeline::new("s-cp".to_string());
+        timeline.root_node = Some(TimelineNode {
+            checkpoint: checkpoint("a", None, "2025-03-03T08:00:00Z"),
+            children: vec![TimelineNode {
+                checkpoint: checkpoint("b", Some("a"), "2025-03-03T09:00:00Z"),
+                children: Vec::new(),
+                file_snapshot_ids: Vec::new(),
+                tokens_delta: None,
+                cost_delta: None,
+            }],
+            file_snapshot_ids: Vec::new(),
+            tokens_delta: None,
+            cost_delta: None,
+        });
+        fs::write(&paths.timeline_file, serde_json::to_string(&timeline).unwrap()).unwrap();
+
+        let feed = collect_activity(&conn, claude_dir, project_path, None, 10).unwrap();
+        let kinds: Vec<_> = feed.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ActivityKind::Checkpoint,
+                ActivityKind::Checkpoint,
+                ActivityKind::AgentRun,
+                ActivityKind::Session
+            ]
+        );
+        assert_eq!(feed[0].parent_checkpoint_id.as_deref(), Some("a"));
+        assert_eq!(feed[2].title, "Review the diff");
+        assert_eq!(feed[2].cost_usd, Some(3.0));
+        assert_eq!(feed[3].title, "Fix the login bug");
+        assert_eq!(feed[3].cost_usd, Some(3.0));
+
+        let since = parse_timestamp("2025-03-02T00:00:00Z");
+        let recent = collect_activity(&conn, claude_dir, project_path, since, 1).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].checkpoint_id.as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,647 @@
+use glob::Pattern;
+use log::{error, info, warn};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{execute_agent, query_agent, AgentDb};
+use super::claude::normalize_project_path;
+use super::i18n::{self, ErrorKind};
+use crate::process::ProcessRegistryState;
+
+/// Quiet time after the last change before a watcher triggers
+const DEFAULT_DEBOUNCE_MS: u64 = 2_000;
+
+/// Shortest time between two runs started by the same watcher
+const DEFAULT_MIN_INTERVAL_SECS: u64 = 60;
+
+/// Changed files listed in the task; the rest are only counted
+const MAX_LISTED_CHANGES: usize = 50;
+
+/// Template variable that receives the changed file list
+const CHANGED_FILES_VARIABLE: &str = "changed_files";
+
+/// Task used when neither the watcher nor the agent has one
+const DEFAULT_WATCH_TASK: &str = "These files changed:\n{{changed_files}}";
+
+/// Directories whose changes never trigger a watcher
+const IGNORED_DIRS: &[&str] = &[".git"];
+
+/// Project files the app writes itself, e.g. while starting a run
+const APP_WRITTEN_FILES: &[&str] = &[".mcp.json", ".claude/settings.json"];
+
+/// How often a watcher checks whether the run it started is still going
+const RUN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs an agent in a project when files matching its patterns change
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentWatcher {
+    pub id: i64,
+    pub agent_id: i64,
+    pub project_path: String,
+    /// Globs relative to the project, e.g. `src/**/*.ts`
+    pub patterns: Vec<String>,
+    pub debounce_ms: u64,
+    pub min_interval_secs: u64,
+    /// Task for the triggered runs; `{{changed_files}}` receives the changes.
+    /// Unset uses the agent's default task.
+    pub task_template: Option<String>,
+    pub enabled: bool,
+    pub last_triggered_at: Option<String>,
+    pub last_run_id: Option<i64>,
+    pub created_at: String,
+}
+
+/// Sent as `agent-watcher-triggered` when a watcher starts a run
+#[derive(Debug, Clone, Serialize)]
+pub struct WatcherTriggered {
+    pub watcher_id: i64,
+    pub run_id: i64,
+    pub changed_files: Vec<String>,
+}
+
+/// File watchers of the enabled agent watchers, by watcher ID
+#[derive(Default)]
+pub struct AgentWatcherState(Mutex<HashMap<i64, RecommendedWatcher>>);
+
+const WATCHER_COLUMNS: &str = "id, agent_id, project_path, patterns, debounce_ms, min_interval_secs, task_template, enabled, last_triggered_at, last_run_id, created_at";
+
+fn watcher_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentWatcher> {
+    let patterns: String = row.get(3)?;
+    Ok(AgentWatcher {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        project_path: row.get(2)?,
+        patterns: serde_json::from_str(&patterns).unwrap_or_default(),
+        debounce_ms: row.get::<_, i64>(4)?.max(0) as u64,
+        min_interval_secs: row.get::<_, i64>(5)?.max(0) as u64,
+        task_template: row.get(6)?,
+        enabled: row.get(7)?,
+        last_triggered_at: row.get(8)?,
+        last_run_id: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+/// Watchers of one agent, or all of them, oldest first
+pub fn query_agent_watchers(
+    conn: &Connection,
+    agent_id: Option<i64>,
+) -> rusqlite::Result<Vec<AgentWatcher>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM agent_watchers WHERE ?1 IS NULL OR agent_id = ?1 ORDER BY id",
+        WATCHER_COLUMNS
+    ))?;
+    let watchers = stmt.query_map(params![agent_id], watcher_from_row)?;
+    watchers.collect()
+}
+
+pub fn query_agent_watcher(conn: &Connection, id: i64) -> rusqlite::Result<Option<AgentWatcher>> {
+    conn.query_row(
+        &format!("SELECT {} FROM agent_watchers WHERE id = ?1", WATCHER_COLUMNS),
+        params![id],
+        watcher_from_row,
+    )
+    .optional()
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, String> {
+    if patterns.is_empty() {
+        return Err(i18n::error(ErrorKind::InvalidInput, "a watcher needs at least one pattern"));
+    }
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern.trim()).map_err(|e| {
+                i18n::error(ErrorKind::InvalidInput, format!("invalid pattern '{}': {}", pattern, e))
+            })
+        })
+        .collect()
+}
+
+/// Lock files, write checks, temp files and files the app writes itself,
+/// none of which are edits a watcher should react to
+fn is_ignored_file(relative: &str) -> bool {
+    let name = relative.rsplit('/').next().unwrap_or(relative);
+    APP_WRITTEN_FILES.contains(&relative)
+        || name.ends_with(".lock")
+        || name.contains(".lock.")
+        || name.starts_with(".write-check")
+        || name.starts_with(".tmp")
+        || name.starts_with(".#")
+        || name.ends_with(".tmp")
+        || name.ends_with(".swp")
+        || name.ends_with('~')
+}
+
+/// The path of a changed file relative to the project, if it matches one of
+/// the patterns
+fn matching_change(root: &Path, patterns: &[Pattern], path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let ignored = relative
+        .components()
+        .any(|c| IGNORED_DIRS.iter().any(|dir| c.as_os_str() == *dir));
+    if ignored {
+        return None;
+    }
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    if is_ignored_file(&relative) {
+        return None;
+    }
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches(&relative))
+        .then_some(relative)
+}
+
+/// How long to hold back a trigger so a watcher starts at most one run per
+/// `min_interval`
+fn trigger_delay(last_trigger: Option<Instant>, now: Instant, min_interval: Duration) -> Duration {
+    last_trigger
+        .map(|last| (last + min_interval).saturating_duration_since(now))
+        .unwrap_or_default()
+}
+
+/// The changed file list as it goes into the task
+fn format_changed_files(changes: &BTreeSet<String>) -> String {
+    let mut lines: Vec<String> = changes
+        .iter()
+        .take(MAX_LISTED_CHANGES)
+        .map(|path| format!("- {}", path))
+        .collect();
+    if changes.len() > MAX_LISTED_CHANGES {
+        lines.push(format!("- ... and {} more", changes.len() - MAX_LISTED_CHANGES));
+    }
+    lines.join("\n")
+}
+
+/// The task for a triggered run, with the changed file list appended when
+/// the template doesn't place it
+fn watch_task_template(watcher: &AgentWatcher, default_task: Option<&str>) -> String {
+    let template = watcher
+        .task_template
+        .as_deref()
+        .or(default_task)
+        .filter(|task| !task.trim().is_empty())
+        .unwrap_or(DEFAULT_WATCH_TASK);
+    if super::agents::template_variables(template).iter().any(|name| name == CHANGED_FILES_VARIABLE) {
+        template.to_string()
+    } else {
+        format!("{}\n\nChanged files:\n{{{{{}}}}}", template, CHANGED_FILES_VARIABLE)
+    }
+}
+
+/// Start a run of the watcher's agent for the collected changes
+async fn trigger_watcher(app: &AppHandle, watcher_id: i64, changes: &BTreeSet<String>) -> Result<Option<i64>, String> {
+    let (watcher, agent) = {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        let Some(watcher) = query_agent_watcher(&conn, watcher_id)
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?
+            .filter(|watcher| watcher.enabled)
+        else {
+            return Ok(None);
+        };
+        let agent = query_agent(&conn, watcher.agent_id)?;
+        (watcher, agent)
+    };
+
+    let task = watch_task_template(&watcher, agent.default_task.as_deref());
+    let variables = HashMap::from([(
+        CHANGED_FILES_VARIABLE.to_string(),
+        format_changed_files(changes),
+    )]);
+    let run_id = execute_agent(
+        app.clone(),
+        watcher.agent_id,
+        watcher.project_path.clone(),
+        task,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(variables),
+        None,
+        None,
+        app.state::<AgentDb>(),
+        app.state::<ProcessRegistryState>(),
+    )
+    .await?;
+
+    {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        conn.execute(
+            "UPDATE agent_watchers SET last_triggered_at = CURRENT_TIMESTAMP, last_run_id = ?1 WHERE id = ?2",
+            params![run_id, watcher_id],
+        )
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    }
+    let _ = app.emit(
+        "agent-watcher-triggered",
+        &WatcherTriggered {
+            watcher_id,
+            run_id,
+            changed_files: changes.iter().cloned().collect(),
+        },
+    );
+    Ok(Some(run_id))
+}
+
+/// Drop changes while `run_id` is going and for `settle` after it ends.
+///
+/// Returns false once the watcher is stopped.
+async fn skip_run_changes(
+    events: &mut UnboundedReceiver<Vec<PathBuf>>,
+    run_id: Option<i64>,
+    settle: Duration,
+    run_is_active: &impl Fn(i64) -> bool,
+) -> bool {
+    if let Some(run_id) = run_id {
+        while run_is_active(run_id) {
+            if let Ok(None) = tokio::time::timeout(RUN_POLL_INTERVAL, events.recv()).await {
+                return false;
+            }
+        }
+    }
+    let settled = tokio::time::Instant::now() + settle;
+    loop {
+        match tokio::time::timeout_at(settled, events.recv()).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return false,
+            Err(_) => return true,
+        }
+    }
+}
+
+/// Collect matching changes and call `trigger` with them until the watcher
+/// is stopped, which closes the channel.
+///
+/// Everything that changes from a trigger until the run it started has
+/// finished, plus the debounce window, is the run's own doing and dropped.
+async fn watch_changes<T, F>(
+    watcher: &AgentWatcher,
+    patterns: &[Pattern],
+    mut events: UnboundedReceiver<Vec<PathBuf>>,
+    run_is_active: impl Fn(i64) -> bool,
+    mut trigger: T,
+) where
+    T: FnMut(BTreeSet<String>) -> F,
+    F: Future<Output = Result<Option<i64>, String>>,
+{
+    let root = PathBuf::from(&watcher.project_path);
+    let debounce = Duration::from_millis(watcher.debounce_ms);
+    let min_interval = Duration::from_secs(watcher.min_interval_secs);
+    let mut last_trigger: Option<Instant> = None;
+
+    // A run started before the watcher was (re)started is still its own
+    if watcher.last_run_id.is_some_and(&run_is_active)
+        && !skip_run_changes(&mut events, watcher.last_run_id, debounce, &run_is_active).await
+    {
+        return;
+    }
+
+    while let Some(paths) = events.recv().await {
+        let mut changes: BTreeSet<String> =
+            paths.iter().filter_map(|path| matching_change(&root, patterns, path)).collect();
+        if changes.is_empty() {
+            continue;
+        }
+
+        // Wait for a quiet period, and for the trigger interval to pass
+        loop {
+            let wait = debounce.max(trigger_delay(last_trigger, Instant::now(), min_interval));
+            match tokio::time::timeout(wait, events.recv()).await {
+                Ok(Some(paths)) => {
+                    changes.extend(paths.iter().filter_map(|path| matching_change(&root, patterns, path)))
+                }
+                Ok(None) => return,
+                Err(_) if trigger_delay(last_trigger, Instant::now(), min_interval).is_zero() => break,
+                Err(_) => {}
+            }
+        }
+
+        let changed = changes.len();
+        let run_id = match trigger(changes).await {
+            Ok(Some(run_id)) => {
+                info!("Watcher {} started run {} for {} changed files", watcher.id, run_id, changed);
+                Some(run_id)
+            }
+            Ok(None) => return,
+            Err(e) => {
+                error!("Watcher {} failed to start a run: {}", watcher.id, e);
+                None
+            }
+        };
+        last_trigger = Some(Instant::now());
+
+        // Even a failed start may have written into the project
+        if !skip_run_changes(&mut events, run_id, debounce, &run_is_active).await {
+            return;
+        }
+    }
+}
+
+/// Trigger runs of the watcher's agent for its changes until it is stopped
+async fn watch_loop(
+    app: AppHandle,
+    watcher: AgentWatcher,
+    patterns: Vec<Pattern>,
+    events: UnboundedReceiver<Vec<PathBuf>>,
+) {
+    let run_is_active = |run_id: i64| {
+        app.state::<ProcessRegistryState>()
+            .0
+            .get_process(run_id)
+            .is_ok_and(|process| process.is_some())
+    };
+    let trigger = |changes: BTreeSet<String>| {
+        let app = app.clone();
+        async move { trigger_watcher(&app, watcher.id, &changes).await }
+    };
+    watch_changes(&watcher, &patterns, events, run_is_active, trigger).await;
+}
+
+/// Start watching the watcher's project, replacing a watcher already running
+pub fn start_watcher(app: &AppHandle, watcher: &AgentWatcher) -> Result<(), String> {
+    let patterns = compile_patterns(&watcher.patterns)?;
+    let (sender, events) = unbounded_channel();
+    let mut fs_watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(event) => {
+                let _ = sender.send(event.paths);
+            }
+            Err(e) => warn!("File watcher error: {}", e),
+        }
+    })
+    .map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+    fs_watcher
+        .watch(Path::new(&watcher.project_path), RecursiveMode::Recursive)
+        .map_err(|e| i18n::error(ErrorKind::FileRead, format!("{}: {}", watcher.project_path, e)))?;
+
+    app.state::<AgentWatcherState>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(watcher.id, fs_watcher);
+    tauri::async_runtime::spawn(watch_loop(app.clone(), watcher.clone(), patterns, events));
+    Ok(())
+}
+
+/// Stop watching; the watch loop ends once its file watcher is gone
+fn stop_watcher(app: &AppHandle, id: i64) {
+    if let Ok(mut watchers) = app.state::<AgentWatcherState>().0.lock() {
+        watchers.remove(&id);
+    }
+}
+
+/// Start the enabled watchers at app startup
+pub fn start_enabled_watchers(app: &AppHandle) {
+    let watchers = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to lock database to start agent watchers: {}", e);
+                return;
+            }
+        };
+        query_agent_watchers(&conn, None).unwrap_or_else(|e| {
+            error!("Failed to load agent watchers: {}", e);
+            Vec::new()
+        })
+    };
+    for watcher in watchers.iter().filter(|watcher| watcher.enabled) {
+        if let Err(e) = start_watcher(app, watcher) {
+            warn!("Failed to start watcher {}: {}", watcher.id, e);
+        }
+    }
+}
+
+/// Watch a project and run an agent when matching files change.
+///
+/// Runs are at least `min_interval_secs` apart (default 60). Changes from
+/// a trigger until its run has finished and the debounce window has passed
+/// are ignored so the agent's own edits don't trigger it again, and so are
+/// lock, temp and config files the app writes into the project.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn create_agent_watcher(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    project_path: String,
+    patterns: Vec<String>,
+    debounce_ms: Option<u64>,
+    min_interval_secs: Option<u64>,
+    task_template: Option<String>,
+) -> Result<AgentWatcher, String> {
+    let project_path = normalize_project_path(&project_path)?.path;
+    compile_patterns(&patterns)?;
+    let patterns_json = serde_json::to_string(&patterns).map_err(|e| e.to_string())?;
+    let watcher = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        query_agent(&conn, agent_id)?;
+        let db_error = |e: rusqlite::Error| i18n::error(ErrorKind::Database, e);
+        conn.execute(
+            "INSERT INTO agent_watchers (agent_id, project_path, patterns, debounce_ms, min_interval_secs, task_template) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                agent_id,
+                project_path,
+                patterns_json,
+                debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS) as i64,
+                min_interval_secs.unwrap_or(DEFAULT_MIN_INTERVAL_SECS) as i64,
+                task_template
+            ],
+        )
+        .map_err(db_error)?;
+        query_agent_watcher(&conn, conn.last_insert_rowid())
+            .map_err(db_error)?
+            .ok_or_else(|| i18n::error(ErrorKind::Database, "watcher not saved"))?
+    };
+    start_watcher(&app, &watcher)?;
+    Ok(watcher)
+}
+
+/// Turn a watcher on or off
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_agent_watcher_enabled(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    id: i64,
+    enabled: bool,
+) -> Result<AgentWatcher, String> {
+    let watcher = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        let db_error = |e: rusqlite::Error| i18n::error(ErrorKind::Database, e);
+        conn.execute(
+            "UPDATE agent_watchers SET enabled = ?1 WHERE id = ?2",
+            params![enabled, id],
+        )
+        .map_err(db_error)?;
+        query_agent_watcher(&conn, id)
+            .map_err(db_error)?
+            .ok_or_else(|| i18n::error(ErrorKind::InvalidInput, format!("no agent watcher {}", id)))?
+    };
+    stop_watcher(&app, id);
+    if enabled {
+        start_watcher(&app, &watcher)?;
+    }
+    Ok(watcher)
+}
+
+/// Watchers of an agent, or of all agents
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn list_agent_watchers(
+    db: State<'_, AgentDb>,
+    agent_id: Option<i64>,
+) -> Result<Vec<AgentWatcher>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    query_agent_watchers(&conn, agent_id).map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Stop and remove a watcher; the runs it started are kept
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn delete_agent_watcher(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    id: i64,
+) -> Result<(), String> {
+    stop_watcher(&app, id);
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    conn.execute("DELETE FROM agent_watchers WHERE id = ?1", params![id])
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn test_watcher(root: &Path) -> AgentWatcher {
+        AgentWatcher {
+            id: 1,
+            agent_id: 1,
+            project_path: root.to_string_lossy().to_string(),
+            patterns: vec![],
+            debounce_ms: DEFAULT_DEBOUNCE_MS,
+            min_interval_secs: DEFAULT_MIN_INTERVAL_SECS,
+            task_template: None,
+            enabled: true,
+            last_triggered_at: None,
+            last_run_id: None,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn changes_are_matched_relative_to_the_project_and_triggers_spaced_out() {
+        let root = Path::new("/work/app");
+        let patterns = compile_patterns(&["src/**/*.ts".to_string(), "*.json".to_string()]).unwrap();
+        let changed = |path: &str| matching_change(root, &patterns, Path::new(path));
+        assert_eq!(changed("/work/app/src/lib/api.ts"), Some("src/lib/api.ts".to_string()));
+        assert_eq!(changed("/work/app/package.json"), Some("package.json".to_string()));
+        assert_eq!(changed("/work/app/src/lib/api.rs"), None);
+        assert_eq!(changed("/work/app/.git/config.json"), None);
+        assert_eq!(changed("/work/app/.mcp.json"), None);
+        assert_eq!(changed("/work/app/.claude/settings.json"), None);
+        assert_eq!(changed("/work/app/src/.tmpX2bq9a.ts"), None);
+        assert_eq!(changed("/elsewhere/src/a.ts"), None);
+        assert!(is_ignored_file("src/.write-check4Fq1"));
+        assert!(is_ignored_file("Cargo.lock"));
+        assert!(is_ignored_file(".mcp.json.lock.5b1c"));
+        assert!(!is_ignored_file("src/lock.rs"));
+        assert!(compile_patterns(&[]).is_err());
+        assert!(compile_patterns(&["src/[".to_string()]).is_err());
+
+        let now = Instant::now();
+        let interval = Duration::from_secs(60);
+        assert_eq!(trigger_delay(None, now, interval), Duration::ZERO);
+        assert_eq!(trigger_delay(Some(now), now + Duration::from_secs(45), interval), Duration::from_secs(15));
+        assert_eq!(trigger_delay(Some(now), now + Duration::from_secs(90), interval), Duration::ZERO);
+
+        let watcher = test_watcher(root);
+        assert_eq!(watch_task_template(&watcher, Some("Fix lint")), "Fix lint\n\nChanged files:\n{{changed_files}}");
+        assert_eq!(watch_task_template(&watcher, None), DEFAULT_WATCH_TASK);
+        let changes: BTreeSet<String> = (0..MAX_LISTED_CHANGES + 2).map(|i| format!("f{:03}.ts", i)).collect();
+        let listed = format_changed_files(&changes);
+        assert!(listed.starts_with("- f000.ts\n"));
+        assert!(listed.ends_with("- ... and 2 more"));
+    }
+
+    #[tokio::test]
+    async fn a_triggered_run_does_not_trigger_its_watcher_again() {
+        let root = Path::new("/work/app");
+        let watcher = AgentWatcher {
+            debounce_ms: 50,
+            min_interval_secs: 0,
+            ..test_watcher(root)
+        };
+        let patterns = compile_patterns(&["**/*".to_string()]).unwrap();
+        let (sender, events) = unbounded_channel();
+        let running = Arc::new(AtomicBool::new(false));
+        let triggered = Arc::new(Mutex::new(Vec::new()));
+
+        let run_is_active = {
+            let running = running.clone();
+            move |_run_id: i64| running.load(Ordering::SeqCst)
+        };
+        let trigger = {
+            let (sender, running, triggered) = (sender.downgrade(), running.clone(), triggered.clone());
+            move |changes: BTreeSet<String>| {
+                triggered.lock().unwrap().push(changes);
+                // Starting the run merges its MCP servers and the agent edits a file
+                running.store(true, Ordering::SeqCst);
+                let sender = sender.upgrade().unwrap();
+                sender.send(vec![root.join(".mcp.json")]).unwrap();
+                sender.send(vec![root.join("src/generated.ts")]).unwrap();
+                async { Ok(Some(7)) }
+            }
+        };
+        let watching = tokio::spawn(async move {
+            watch_changes(&watcher, &patterns, events, run_is_active, trigger).await
+        });
+
+        sender.send(vec![root.join("src/main.ts")]).unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(triggered.lock().unwrap().len(), 1);
+
+        // Finishing restores .mcp.json; late events of the run still arrive
+        running.store(false, Ordering::SeqCst);
+        sender.send(vec![root.join(".mcp.json")]).unwrap();
+        sender.send(vec![root.join("src/generated.ts")]).unwrap();
+        tokio::time::sleep(RUN_POLL_INTERVAL + Duration::from_millis(300)).await;
+        assert_eq!(triggered.lock().unwrap().len(), 1);
+
+        // A change after the run has settled triggers again
+        sender.send(vec![root.join("src/other.ts")]).unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        {
+            let triggered = triggered.lock().unwrap();
+            assert_eq!(triggered.len(), 2);
+            assert_eq!(triggered[1], BTreeSet::from(["src/other.ts".to_string()]));
+        }
+
+        drop(sender);
+        tokio::time::timeout(Duration::from_secs(2), watching).await.unwrap().unwrap();
+    }
+}
@@ -1,14 +1,29 @@
 use anyhow::{Context, Result};
+use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter, Manager};
 use tokio::process::{Child, Command};
 use tokio::sync::Mutex;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{read_session_history, AgentDb, JsonlParseReport};
+use super::i18n::{self, ErrorKind};
+use super::jobs::run_as_job;
+use super::notices;
+use super::project_env;
+use super::redaction::{self, Redactor};
+use super::execution_profiles::{resolve_execution_profile, ExecutionProfile};
+use crate::file_lock::{
+    create_dirs, ensure_writable, update_locked, write_error, write_locked, CreatedDirs,
+};
 
 
 /// Global state to track current Claude process
@@ -24,22 +39,363 @@ impl Default for ClaudeProcessState {
     }
 }
 
+/// Output format passed to `claude --output-format`
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClaudeOutputFormat {
+    /// One JSON message per line while claude is running
+    #[default]
+    StreamJson,
+    /// A single result object once claude finishes
+    Json,
+    /// Plain text of the final answer
+    Text,
+}
+
+impl ClaudeOutputFormat {
+    /// Whether output arrives line by line while the process runs
+    pub fn is_streaming(&self) -> bool {
+        matches!(self, ClaudeOutputFormat::StreamJson)
+    }
+
+    /// CLI arguments selecting this format
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            // stream-json requires --verbose in print mode
+            ClaudeOutputFormat::StreamJson => vec![
+                "--output-format".to_string(),
+                "stream-json".to_string(),
+                "--verbose".to_string(),
+            ],
+            // --verbose would turn the json result into an array of every message
+            ClaudeOutputFormat::Json => {
+                vec!["--output-format".to_string(), "json".to_string()]
+            }
+            ClaudeOutputFormat::Text => {
+                vec!["--output-format".to_string(), "text".to_string()]
+            }
+        }
+    }
+}
+
+/// Complete output of a non-streaming (json/text) claude invocation
+#[derive(Debug, Clone)]
+pub struct ClaudeFinalOutput {
+    /// Payload emitted to the frontend (the result object for json, raw text otherwise)
+    pub payload: String,
+    /// Session ID reported in the result object
+    pub session_id: Option<String>,
+}
+
+impl ClaudeFinalOutput {
+    /// Parse the full stdout of a json/text mode run
+    pub fn parse(format: ClaudeOutputFormat, stdout: &str) -> Self {
+        let trimmed = stdout.trim();
+
+        if format == ClaudeOutputFormat::Json {
+            if let Ok(value) = serde_json::from_str::<serde_json::Value>(trimmed) {
+                // Older CLI versions print every message as an array; keep the result entry
+                let result = match value {
+                    serde_json::Value::Array(messages) => messages
+                        .into_iter()
+                        .rev()
+                        .find(|m| m.get("type").and_then(|t| t.as_str()) == Some("result")),
+                    other => Some(other),
+                };
+
+                if let Some(result) = result {
+                    return Self {
+                        session_id: session_id_from_message(&result)
+                            .map(|s| s.to_string())
+                            .or_else(|| find_session_id(trimmed)),
+                        payload: result.to_string(),
+                    };
+                }
+            }
+        }
+
+        Self {
+            payload: trimmed.to_string(),
+            session_id: None,
+        }
+    }
+}
+
+/// Print-mode arguments carrying the prompt
+///
+/// Interactive runs read prompts as stream-json messages from stdin instead,
+/// which keeps the process alive for follow-up input after each result.
+pub fn prompt_args(prompt: &str, interactive: bool) -> Vec<String> {
+    if interactive {
+        vec![
+            "-p".to_string(),
+            "--input-format".to_string(),
+            "stream-json".to_string(),
+        ]
+    } else {
+        vec!["-p".to_string(), prompt.to_string()]
+    }
+}
+
+/// Encode text as a stream-json user message for an interactive run's stdin
+pub fn user_input_line(text: &str) -> String {
+    serde_json::json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": [{ "type": "text", "text": text }]
+        }
+    })
+    .to_string()
+}
+
+/// Session ID carried by a stream-json message
+///
+/// The system/init message normally reports it first, but a CLI that fails
+/// fast may only print a result (or assistant) message, so any message type
+/// is accepted.
+pub fn session_id_from_message(msg: &serde_json::Value) -> Option<&str> {
+    msg.get("session_id")
+        .and_then(|s| s.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+/// Find the session ID in captured output, preferring the init message
+///
+/// Accepts stream-json lines as well as a single json result object or the
+/// message array printed by older CLI versions.
+pub fn find_session_id(output: &str) -> Option<String> {
+    let messages: Vec<serde_json::Value> = match serde_json::from_str(output.trim()) {
+        Ok(serde_json::Value::Array(messages)) => messages,
+        Ok(message) => vec![message],
+        Err(_) => output
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+    };
+
+    messages
+        .iter()
+        .find(|msg| msg["type"] == "system" && msg["subtype"] == "init")
+        .and_then(session_id_from_message)
+        .or_else(|| messages.iter().find_map(session_id_from_message))
+        .map(|s| s.to_string())
+}
+
+/// Whether a stream-json line is the result message that ends a turn
+fn is_result_line(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line)
+        .map(|msg| msg["type"] == "result")
+        .unwrap_or(false)
+}
+
+/// Check that the requested mode can be driven interactively
+pub fn validate_interactive(output_format: ClaudeOutputFormat, interactive: bool) -> Result<(), String> {
+    if interactive && !output_format.is_streaming() {
+        return Err("Interactive mode requires the stream-json output format".to_string());
+    }
+    Ok(())
+}
+
 /// Represents a project in the ~/.claude/projects directory
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     /// The project ID (derived from the directory name)
     pub id: String,
     /// The original project path (decoded from the directory name)
     pub path: String,
-    /// List of session IDs (JSONL file names without extension)
+    /// List of session IDs (JSONL file names without extension); empty in a counts listing
     pub sessions: Vec<String>,
+    /// Number of sessions in the project
+    #[serde(default)]
+    pub session_count: usize,
     /// Unix timestamp when the project directory was created
     pub created_at: u64,
     /// Unix timestamp of the most recent session (if any)
     pub most_recent_session: Option<u64>,
+    /// Number of agent runs that targeted this project (None when not requested)
+    #[serde(default)]
+    pub agent_run_count: Option<i64>,
+    /// Creation time of the latest agent run in this project
+    #[serde(default)]
+    pub last_agent_run_at: Option<String>,
+}
+
+/// How much `list_projects` returns per project
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectListDetail {
+    /// Session count and activity only; `sessions` is left empty
+    Counts,
+    /// Every session ID
+    #[default]
+    Full,
+}
+
+/// What a counts listing needs from a project directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectDirSummary {
+    pub project_path: String,
+    pub session_files: Vec<PathBuf>,
+}
+
+/// Project directory summaries, keyed by directory and valid while its mtime is unchanged.
+///
+/// Adding or removing a session changes the directory's mtime; appending to one
+/// does not, so activity times are still read from the session files.
+#[derive(Default)]
+pub struct ProjectListCache(
+    std::sync::Mutex<std::collections::HashMap<PathBuf, (SystemTime, ProjectDirSummary)>>,
+);
+
+impl ProjectListCache {
+    /// Summary of `dir`, read again only when `dir_modified` differs from the cached one
+    pub fn summary(
+        &self,
+        dir: &Path,
+        dir_name: &str,
+        dir_modified: SystemTime,
+    ) -> ProjectDirSummary {
+        let mut cache = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((modified, summary)) = cache.get(dir) {
+            if *modified == dir_modified {
+                return summary.clone();
+            }
+        }
+        let summary = summarize_project_dir(dir, dir_name);
+        cache.insert(dir.to_path_buf(), (dir_modified, summary.clone()));
+        summary
+    }
+}
+
+/// How much of a session file the background summary job reads
+const SESSION_SUMMARY_READ_LIMIT: u64 = 32 * 1024;
+
+/// First user message and its timestamp
+type FirstMessage = (Option<String>, Option<String>);
+
+/// First messages of session files, filled in the background.
+///
+/// A found message never changes; a file without one is read again once
+/// its mtime changes.
+#[derive(Default)]
+pub struct SessionSummaryCache {
+    summaries: std::sync::Mutex<HashMap<PathBuf, (SystemTime, FirstMessage)>>,
+    /// Sessions waiting for the warming job, most urgent first
+    queue: std::sync::Mutex<VecDeque<(PathBuf, String)>>,
+    warming: std::sync::atomic::AtomicBool,
+}
+
+impl SessionSummaryCache {
+    /// Cached first message of `path`, if still valid for `modified`
+    pub fn get(&self, path: &Path, modified: SystemTime) -> Option<FirstMessage> {
+        let summaries = self.summaries.lock().unwrap_or_else(|e| e.into_inner());
+        summaries
+            .get(path)
+            .filter(|(cached, summary)| *cached == modified || summary.0.is_some())
+            .map(|(_, summary)| summary.clone())
+    }
+
+    fn insert(&self, path: PathBuf, modified: SystemTime, summary: FirstMessage) {
+        let mut summaries = self.summaries.lock().unwrap_or_else(|e| e.into_inner());
+        summaries.insert(path, (modified, summary));
+    }
+
+    /// Queue sessions ahead of earlier requests, keeping their order.
+    /// Returns true when no warming job is running and one must be started.
+    fn enqueue(&self, sessions: Vec<(PathBuf, String)>) -> bool {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.retain(|(queued, _)| !sessions.iter().any(|(path, _)| path == queued));
+        for session in sessions.into_iter().rev() {
+            queue.push_front(session);
+        }
+        !self.warming.swap(true, std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Next session to warm; marks the job finished when the queue is empty
+    fn next(&self) -> Option<(PathBuf, String)> {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let next = queue.pop_front();
+        if next.is_none() {
+            self.warming.store(false, std::sync::atomic::Ordering::SeqCst);
+        }
+        next
+    }
+}
+
+/// Read the first messages of `sessions` in the background, emitting
+/// `session-summary-ready:{session_id}` as each one is known
+fn warm_session_summaries(app: &AppHandle, sessions: Vec<(PathBuf, String)>) {
+    if sessions.is_empty() || !app.state::<SessionSummaryCache>().enqueue(sessions) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let cache = app.state::<SessionSummaryCache>();
+        while let Some((path, session_id)) = cache.next() {
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let summary = match cache.get(&path, modified) {
+                Some(summary) => summary,
+                None => {
+                    let summary = read_first_user_message(&path, SESSION_SUMMARY_READ_LIMIT);
+                    cache.insert(path, modified, summary.clone());
+                    summary
+                }
+            };
+            let payload = serde_json::json!({
+                "sessionId": session_id,
+                "firstMessage": summary.0,
+                "messageTimestamp": summary.1,
+            });
+            if let Err(e) = app.emit(&format!("session-summary-ready:{}", session_id), &payload) {
+                log::warn!("Failed to emit summary of session {}: {}", session_id, e);
+            }
+        }
+    });
+}
+
+/// Session files and project path of a project directory
+fn summarize_project_dir(dir: &Path, dir_name: &str) -> ProjectDirSummary {
+    let session_files = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("jsonl"))
+                .collect()
+        })
+        .unwrap_or_default();
+    // A directory without sessions has no cwd to read
+    let project_path = get_project_path_from_sessions(&dir.to_path_buf())
+        .unwrap_or_else(|_| decode_project_path(dir_name));
+    ProjectDirSummary {
+        project_path,
+        session_files,
+    }
+}
+
+/// Modification time of the newest session file
+fn newest_session_time(session_files: &[PathBuf]) -> Option<u64> {
+    session_files
+        .iter()
+        .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+        .map(|modified| {
+            modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        })
 }
 
 /// Represents a session with its metadata
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     /// The session ID (UUID)
@@ -56,6 +412,9 @@ pub struct Session {
     pub first_message: Option<String>,
     /// Timestamp of the first user message (if available)
     pub message_timestamp: Option<String>,
+    /// Title generated by `generate_session_title`, if any
+    #[serde(default)]
+    pub title: Option<String>,
 }
 
 /// Represents a message entry in the JSONL file
@@ -76,6 +435,7 @@ struct MessageContent {
 }
 
 /// Represents the settings from ~/.claude/settings.json
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaudeSettings {
     #[serde(flatten)]
@@ -91,6 +451,7 @@ impl Default for ClaudeSettings {
 }
 
 /// Represents the Claude Code version status
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaudeVersionStatus {
     /// Whether Claude Code is installed and working
@@ -101,7 +462,29 @@ pub struct ClaudeVersionStatus {
     pub output: String,
 }
 
+/// A location in ~/.claude that cannot be written
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeDirProblem {
+    pub path: String,
+    /// Coded error, e.g. `[claude_dir_readonly] ...`
+    pub error: String,
+}
+
+/// Whether the app can write to ~/.claude
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeDirHealth {
+    pub claude_dir: String,
+    pub exists: bool,
+    /// Whether every checked location accepts writes
+    pub writable: bool,
+    pub problems: Vec<ClaudeDirProblem>,
+    pub checked_at: String,
+}
+
 /// Represents a CLAUDE.md file found in the project
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaudeMdFile {
     /// Relative path from the project root
@@ -115,6 +498,7 @@ pub struct ClaudeMdFile {
 }
 
 /// Represents a file or directory entry
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
     /// The name of the file or directory
@@ -135,17 +519,257 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
     crate::claude_binary::find_claude_binary(app_handle)
 }
 
-/// Gets the path to the ~/.claude directory
+/// Gets the path to the claude directory, ~/.claude unless switched
 fn get_claude_dir() -> Result<PathBuf> {
-    dirs::home_dir()
-        .context("Could not find home directory")?
-        .join(".claude")
+    crate::claude_dir::claude_dir()
+        .map_err(anyhow::Error::msg)?
         .canonicalize()
         .context("Could not find ~/.claude directory")
 }
 
+/// Encodes a project path the way Claude Code names its ~/.claude/projects directories
+///
+/// Every character that isn't an ASCII letter or digit becomes '-'. Claude applies a
+/// JavaScript regex, so characters outside the BMP count twice (one per UTF-16 unit).
+/// Windows verbatim prefixes (`\\?\`) are dropped since claude never sees them in its cwd.
+pub fn encode_project_path(project_path: &Path) -> String {
+    let path = strip_verbatim_prefix(&project_path.as_os_str().to_string_lossy());
+
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_ascii_alphanumeric() {
+            encoded.push(c);
+        } else {
+            encoded.extend(std::iter::repeat_n('-', c.len_utf16()));
+        }
+    }
+    encoded
+}
+
+/// Finds the ~/.claude/projects directory holding the sessions of a project path
+///
+/// Prefers claude's own encoding and falls back to the slash-only encoding that
+/// older versions of this app used when creating projects.
+pub fn find_project_dir(projects_dir: &Path, project_path: &Path) -> PathBuf {
+    let encoded_dir = projects_dir.join(encode_project_path(project_path));
+    if encoded_dir.exists() {
+        return encoded_dir;
+    }
+
+    let legacy_dir = projects_dir.join(project_path.to_string_lossy().replace('/', "-"));
+    if legacy_dir.exists() {
+        return legacy_dir;
+    }
+
+    encoded_dir
+}
+
+/// Finds the JSONL file of a session, looking in the project's own directory
+/// first and then in every other project directory
+pub fn find_session_file(
+    projects_dir: &Path,
+    session_id: &str,
+    project_path: &Path,
+) -> Option<PathBuf> {
+    let file_name = format!("{}.jsonl", session_id);
+    let expected = find_project_dir(projects_dir, project_path).join(&file_name);
+    if expected.is_file() {
+        return Some(expected);
+    }
+
+    fs::read_dir(projects_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Check that a session to resume belongs to `project_path`.
+///
+/// A session found under another project fails with
+/// `session_in_other_project` and that project's path as the detail, so the
+/// UI can offer to switch.
+fn check_session_project(
+    projects_dir: &Path,
+    session_id: &str,
+    project_path: &str,
+) -> Result<(), String> {
+    let session_file = find_session_file(projects_dir, session_id, Path::new(project_path))
+        .ok_or_else(|| i18n::error(ErrorKind::SessionNotFound, session_id))?;
+    let session_dir = session_file.parent().unwrap_or(projects_dir).to_path_buf();
+    if session_dir == find_project_dir(projects_dir, Path::new(project_path)) {
+        return Ok(());
+    }
+
+    // Another directory for the same path, e.g. one claude encoded differently
+    let actual_path = get_project_path_from_sessions(&session_dir).unwrap_or_else(|_| {
+        decode_project_path(&session_dir.file_name().unwrap_or_default().to_string_lossy())
+    });
+    if canonical_project_key(&actual_path) == canonical_project_key(project_path) {
+        return Ok(());
+    }
+    Err(i18n::error(ErrorKind::SessionInOtherProject, actual_path))
+}
+
+/// Drops the Windows verbatim prefix (`\\?\` or `\\?\UNC\`) from a path string
+fn strip_verbatim_prefix(raw: &str) -> String {
+    if let Some(unc) = raw.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        raw.strip_prefix(r"\\?\").unwrap_or(raw).to_string()
+    }
+}
+
+/// A project path cleaned up by `normalize_project_path`
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalizedProjectPath {
+    pub path: String,
+    /// Whether the directory is inside the user's home directory
+    pub inside_home: bool,
+}
+
+/// Expands `$VAR` and `${VAR}` references, and `%VAR%` on Windows; unknown
+/// variables are left as-is. Elsewhere `%` is an ordinary path character.
+fn expand_env_vars(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(pos) = rest.find(|c| c == '$' || (cfg!(windows) && c == '%')) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        let (name, consumed) = if let Some(braced) = tail.strip_prefix("${") {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 3),
+                None => ("", 0),
+            }
+        } else if let Some(percent) = tail.strip_prefix('%') {
+            match percent.find('%') {
+                Some(end) => (&percent[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let name_len = tail[1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(tail.len() - 1);
+            (&tail[1..1 + name_len], name_len + 1)
+        };
+
+        match (consumed, std::env::var(name)) {
+            (n, Ok(value)) if n > 0 && !name.is_empty() => {
+                out.push_str(&value);
+                rest = &tail[n..];
+            }
+            _ => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// The path of a `file://` URL with the scheme already stripped: drops an
+/// empty or `localhost` host and percent-decodes the rest
+fn file_url_path(url: &str) -> String {
+    let url = match url.get(..9) {
+        Some(host) if host.eq_ignore_ascii_case("localhost") && url[9..].starts_with('/') => &url[9..],
+        _ => url,
+    };
+    let path = percent_decode_str(url).decode_utf8_lossy();
+    // file:///C:/dir on Windows keeps a leading slash before the drive letter
+    if cfg!(windows) {
+        path.trim_start_matches('/').to_string()
+    } else {
+        path.into_owned()
+    }
+}
+
+/// Cleans up a project path typed, pasted or dropped by the user.
+///
+/// Trims whitespace and surrounding quotes, strips a `file://` scheme, expands `~`
+/// and environment variables, then canonicalizes and checks it is a directory.
+pub fn normalize_project_path(input: &str) -> Result<NormalizedProjectPath, String> {
+    let mut raw = input.trim();
+    for quote in ['"', '\''] {
+        if raw.len() >= 2 && raw.starts_with(quote) && raw.ends_with(quote) {
+            raw = raw[1..raw.len() - 1].trim();
+        }
+    }
+    if raw.is_empty() {
+        return Err(i18n::error(ErrorKind::InvalidInput, "project path is empty"));
+    }
+
+    let mut path = match raw.strip_prefix("file://") {
+        Some(url) => file_url_path(url),
+        None => raw.to_string(),
+    };
+    path = expand_env_vars(&path);
+
+    let home = dirs::home_dir();
+    if path == "~" || path.starts_with("~/") || path.starts_with("~\\") {
+        let home = home
+            .as_ref()
+            .ok_or_else(|| i18n::error(ErrorKind::HomeDirNotFound, ""))?;
+        path = format!("{}{}", home.display(), &path[1..]);
+    }
+
+    let candidate = PathBuf::from(&path);
+    if !candidate.exists() {
+        return Err(i18n::error(ErrorKind::PathNotFound, &path));
+    }
+    if !candidate.is_dir() {
+        return Err(i18n::error(ErrorKind::NotADirectory, &path));
+    }
+
+    let canonical = candidate
+        .canonicalize()
+        .map_err(|e| i18n::error(ErrorKind::PathNotFound, e))?;
+    let canonical = PathBuf::from(strip_verbatim_prefix(&canonical.to_string_lossy()));
+
+    let inside_home = home
+        .and_then(|h| h.canonicalize().ok())
+        .map(|h| canonical.starts_with(PathBuf::from(strip_verbatim_prefix(&h.to_string_lossy()))))
+        .unwrap_or(false);
+
+    Ok(NormalizedProjectPath {
+        path: canonical.to_string_lossy().to_string(),
+        inside_home,
+    })
+}
+
+/// Validate directories given for `--add-dir` and normalize them like
+/// project paths, dropping repeats
+pub fn normalize_additional_dirs(dirs: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        let dir = normalize_project_path(dir)?.path;
+        if !normalized.contains(&dir) {
+            normalized.push(dir);
+        }
+    }
+    Ok(normalized)
+}
+
+/// `--add-dir` flags giving claude access to directories outside the project
+pub fn add_dir_args(dirs: &[String]) -> Vec<String> {
+    dirs.iter()
+        .flat_map(|dir| ["--add-dir".to_string(), dir.clone()])
+        .collect()
+}
+
+/// Normalize and validate a project path (e.g. a dropped or pasted directory)
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn resolve_project_path(input: String) -> Result<NormalizedProjectPath, String> {
+    normalize_project_path(&input)
+}
+
 /// Gets the actual project path by reading the cwd from the first JSONL entry
-fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, String> {
+pub fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, String> {
     // Try to read any JSONL file in the directory
     let entries = fs::read_dir(project_dir)
         .map_err(|e| format!("Failed to read project directory: {}", e))?;
@@ -158,8 +782,9 @@ fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, Strin
                 if let Ok(file) = fs::File::open(&path) {
                     let reader = BufReader::new(file);
                     if let Some(Ok(first_line)) = reader.lines().next() {
-                        // Parse the JSON and extract cwd
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&first_line) {
+                        // Parse the JSON and extract cwd, ignoring a UTF-8 BOM
+                        let first_line = first_line.trim_start_matches('\u{feff}');
+                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(first_line) {
                             if let Some(cwd) = json.get("cwd").and_then(|v| v.as_str()) {
                                 return Ok(cwd.to_string());
                             }
@@ -184,17 +809,23 @@ fn decode_project_path(encoded: &str) -> String {
 }
 
 /// Extracts the first valid user message from a JSONL file
-fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<String>) {
+pub fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<String>) {
+    read_first_user_message(jsonl_path, u64::MAX)
+}
+
+/// Like `extract_first_user_message`, reading at most `max_bytes` of the file
+fn read_first_user_message(jsonl_path: &Path, max_bytes: u64) -> FirstMessage {
     let file = match fs::File::open(jsonl_path) {
         Ok(file) => file,
         Err(_) => return (None, None),
     };
 
-    let reader = BufReader::new(file);
+    let reader = BufReader::new(file.take(max_bytes));
 
     for line in reader.lines() {
         if let Ok(line) = line {
-            if let Ok(entry) = serde_json::from_str::<JsonlEntry>(&line) {
+            let line = line.trim_start_matches('\u{feff}');
+            if let Ok(entry) = serde_json::from_str::<JsonlEntry>(line) {
                 if let Some(message) = entry.message {
                     if message.role.as_deref() == Some("user") {
                         if let Some(content) = message.content {
@@ -251,6 +882,11 @@ fn create_command_with_env(program: &str) -> Command {
         }
     }
 
+    // Sessions go to the claude directory the app was switched to
+    if let Some(claude_dir) = crate::claude_dir::current().path {
+        tokio_cmd.env("CLAUDE_CONFIG_DIR", claude_dir);
+    }
+
     // Add NVM support if the program is in an NVM directory
     if program.contains("/.nvm/versions/node/") {
         if let Some(node_bin_dir) = std::path::Path::new(program).parent() {
@@ -280,10 +916,10 @@ fn create_command_with_env(program: &str) -> Command {
 }
 
 /// Creates a system binary command with the given arguments
-fn create_system_command(
+pub(crate) fn create_system_command(
     claude_path: &str,
     args: Vec<String>,
-    project_path: &str,
+    project_path: &Path,
 ) -> Command {
     let mut cmd = create_command_with_env(claude_path);
     
@@ -300,6 +936,7 @@ fn create_system_command(
 }
 
 /// Gets the user's home directory path
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_home_directory() -> Result<String, String> {
     dirs::home_dir()
@@ -308,9 +945,18 @@ pub async fn get_home_directory() -> Result<String, String> {
 }
 
 
-/// Lists all projects in the ~/.claude/projects directory
+/// Lists all projects in the ~/.claude/projects directory.
+///
+/// `detail` defaults to `full`; `counts` skips listing session IDs, for views
+/// that only show how many sessions a project has.
+#[apply(api_command!)]
 #[tauri::command]
-pub async fn list_projects() -> Result<Vec<Project>, String> {
+pub async fn list_projects(
+    db: tauri::State<'_, AgentDb>,
+    cache: tauri::State<'_, ProjectListCache>,
+    include_run_info: Option<bool>,
+    detail: Option<ProjectListDetail>,
+) -> Result<Vec<Project>, String> {
     log::info!("Listing projects from ~/.claude/projects");
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
@@ -349,6 +995,25 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
                 .unwrap_or_default()
                 .as_secs();
 
+            if detail.unwrap_or_default() == ProjectListDetail::Counts {
+                let summary = cache.summary(
+                    &path,
+                    dir_name,
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                );
+                projects.push(Project {
+                    id: dir_name.to_string(),
+                    path: summary.project_path,
+                    sessions: Vec::new(),
+                    session_count: summary.session_files.len(),
+                    created_at,
+                    most_recent_session: newest_session_time(&summary.session_files),
+                    agent_run_count: None,
+                    last_agent_run_at: None,
+                });
+                continue;
+            }
+
             // Get the actual project path from JSONL files
             let project_path = match get_project_path_from_sessions(&path) {
                 Ok(path) => path,
@@ -394,13 +1059,33 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
             projects.push(Project {
                 id: dir_name.to_string(),
                 path: project_path,
+                session_count: sessions.len(),
                 sessions,
                 created_at,
                 most_recent_session,
+                agent_run_count: None,
+                last_agent_run_at: None,
             });
         }
     }
 
+    if include_run_info.unwrap_or(true) {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        match agent_run_summary_by_project(&conn) {
+            Ok(summary) => {
+                for project in projects.iter_mut() {
+                    let (count, last_run) = summary
+                        .get(&canonical_project_key(&project.path))
+                        .cloned()
+                        .unwrap_or((0, None));
+                    project.agent_run_count = Some(count);
+                    project.last_agent_run_at = last_run;
+                }
+            }
+            Err(e) => log::warn!("Failed to load agent run counts for projects: {}", e),
+        }
+    }
+
     // Sort projects by most recent session activity, then by creation time
     projects.sort_by(|a, b| {
         // First compare by most recent session
@@ -416,17 +1101,86 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
     Ok(projects)
 }
 
+/// Normalizes a project path for comparison: trailing separators are ignored, Windows
+/// separators are folded, and letter case is folded on Windows and macOS since their
+/// default filesystems are case-insensitive.
+pub fn project_path_key(path: &str) -> String {
+    let path = strip_verbatim_prefix(path.trim());
+    let key = if cfg!(windows) {
+        path.replace('\\', "/").to_lowercase()
+    } else if cfg!(target_os = "macos") {
+        path.to_lowercase()
+    } else {
+        path
+    };
+    let trimmed = key.trim_end_matches('/');
+    if trimmed.is_empty() || (cfg!(windows) && trimmed.ends_with(':')) {
+        // Keep the root ("/" or "c:/") intact
+        format!("{}/", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Resolves symlinks in a project path that still exists; other paths are returned as-is
+pub fn canonical_project_path(path: &str) -> String {
+    match Path::new(path.trim()).canonicalize() {
+        Ok(canonical) => strip_verbatim_prefix(&canonical.to_string_lossy()),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// Comparison key of a project path after resolving symlinks, so that
+/// `/Users/me/code` and the `/Volumes/Dev/code` it links to are the same project
+pub fn canonical_project_key(path: &str) -> String {
+    project_path_key(&canonical_project_path(path))
+}
+
+/// Counts agent runs and their latest creation time per normalized project path
+fn agent_run_summary_by_project(
+    conn: &rusqlite::Connection,
+) -> rusqlite::Result<std::collections::HashMap<String, (i64, Option<String>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT project_path, COUNT(*), MAX(created_at) FROM agent_runs GROUP BY project_path",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+
+    // Several stored spellings can map to the same project, so merge them
+    let mut summary: std::collections::HashMap<String, (i64, Option<String>)> =
+        std::collections::HashMap::new();
+    for row in rows {
+        let (path, count, last_run) = row?;
+        let entry = summary.entry(project_path_key(&path)).or_insert((0, None));
+        entry.0 += count;
+        if last_run > entry.1 {
+            entry.1 = last_run;
+        }
+    }
+    Ok(summary)
+}
+
 /// Creates a new project for the given directory path
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn create_project(path: String) -> Result<Project, String> {
     log::info!("Creating project for path: {}", path);
     
-    // Encode the path to create a project ID
-    let project_id = path.replace('/', "-");
-    
     // Get claude directory
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let projects_dir = claude_dir.join("projects");
+    crate::claude_dir::check_writable(&projects_dir)?;
+
+    // Encode the path to create a project ID (reusing an existing directory if any)
+    let project_id = find_project_dir(&projects_dir, Path::new(&path))
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| encode_project_path(Path::new(&path)));
     
     // Create projects directory if it doesn't exist
     if !projects_dir.exists() {
@@ -458,14 +1212,25 @@ pub async fn create_project(path: String) -> Result<Project, String> {
         id: project_id,
         path,
         sessions: Vec::new(),
+        session_count: 0,
         created_at,
         most_recent_session: None,
+        agent_run_count: None,
+        last_agent_run_at: None,
     })
 }
 
 /// Gets sessions for a specific project
+///
+/// First messages not yet cached are `None`; they are read in the background
+/// and delivered as `session-summary-ready:{session_id}` events.
+#[apply(api_command!)]
 #[tauri::command]
-pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, String> {
+pub async fn get_project_sessions(
+    app: AppHandle,
+    summaries: tauri::State<'_, SessionSummaryCache>,
+    project_id: String,
+) -> Result<Vec<Session>, String> {
     log::info!("Getting sessions for project: {}", project_id);
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
@@ -473,7 +1238,7 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
     let todos_dir = claude_dir.join("todos");
 
     if !project_dir.exists() {
-        return Err(format!("Project directory not found: {}", project_id));
+        return Err(i18n::error(ErrorKind::ProjectNotFound, project_id));
     }
 
     // Get the actual project path from JSONL files
@@ -490,6 +1255,8 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
     };
 
     let mut sessions = Vec::new();
+    let mut missing_summaries = Vec::new();
+    let mut titles = super::session_meta::load_sidecar_titles(&project_dir);
 
     // Read all JSONL files in the project directory
     let entries = fs::read_dir(&project_dir)
@@ -513,8 +1280,14 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
                     .unwrap_or_default()
                     .as_secs();
 
-                // Extract first user message and timestamp
-                let (first_message, message_timestamp) = extract_first_user_message(&path);
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                let (first_message, message_timestamp) = match summaries.get(&path, modified) {
+                    Some(summary) => summary,
+                    None => {
+                        missing_summaries.push((created_at, path.clone(), session_id.to_string()));
+                        (None, None)
+                    }
+                };
 
                 // Try to load associated todo data
                 let todo_path = todos_dir.join(format!("{}.json", session_id));
@@ -534,6 +1307,7 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
                     created_at,
                     first_message,
                     message_timestamp,
+                    title: titles.remove(session_id),
                 });
             }
         }
@@ -542,6 +1316,16 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
     // Sort sessions by creation time (newest first)
     sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
 
+    // Newest sessions are at the top of the list, so they are read first
+    missing_summaries.sort_by(|a, b| b.0.cmp(&a.0));
+    warm_session_summaries(
+        &app,
+        missing_summaries
+            .into_iter()
+            .map(|(_, path, session_id)| (path, session_id))
+            .collect(),
+    );
+
     log::info!(
         "Found {} sessions for project {}",
         sessions.len(),
@@ -551,6 +1335,7 @@ pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, St
 }
 
 /// Reads the Claude settings file
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_claude_settings() -> Result<ClaudeSettings, String> {
     log::info!("Reading Claude settings");
@@ -566,7 +1351,7 @@ pub async fn get_claude_settings() -> Result<ClaudeSettings, String> {
     }
 
     let content = fs::read_to_string(&settings_path)
-        .map_err(|e| format!("Failed to read settings file: {}", e))?;
+        .map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
 
     let data: serde_json::Value = serde_json::from_str(&content)
         .map_err(|e| format!("Failed to parse settings JSON: {}", e))?;
@@ -575,6 +1360,7 @@ pub async fn get_claude_settings() -> Result<ClaudeSettings, String> {
 }
 
 /// Opens a new Claude Code session by executing the claude command
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn open_new_session(app: AppHandle, path: Option<String>) -> Result<String, String> {
     log::info!("Opening new Claude Code session at path: {:?}", path);
@@ -599,7 +1385,7 @@ pub async fn open_new_session(app: AppHandle, path: Option<String>) -> Result<St
 
         // If a path is provided, use it; otherwise use current directory
         if let Some(project_path) = path {
-            cmd.current_dir(&project_path);
+            cmd.current_dir(normalize_project_path(&project_path)?.path);
         }
 
         // Execute the command
@@ -617,6 +1403,7 @@ pub async fn open_new_session(app: AppHandle, path: Option<String>) -> Result<St
 }
 
 /// Reads the CLAUDE.md system prompt file
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_system_prompt() -> Result<String, String> {
     log::info!("Reading CLAUDE.md system prompt");
@@ -629,10 +1416,113 @@ pub async fn get_system_prompt() -> Result<String, String> {
         return Ok(String::new());
     }
 
-    fs::read_to_string(&claude_md_path).map_err(|e| format!("Failed to read CLAUDE.md: {}", e))
+    fs::read_to_string(&claude_md_path).map_err(|e| i18n::error(ErrorKind::FileRead, e))
+}
+
+/// Locations under ~/.claude the app writes to
+const CLAUDE_DIR_WRITE_TARGETS: &[&str] = &["", "settings.json", "CLAUDE.md", "projects"];
+
+/// Check that ~/.claude and the files the app saves in it are writable
+pub fn claude_dir_health(claude_dir: &Path) -> ClaudeDirHealth {
+    let exists = claude_dir.is_dir();
+    let problems: Vec<ClaudeDirProblem> = if exists {
+        CLAUDE_DIR_WRITE_TARGETS
+            .iter()
+            .map(|name| claude_dir.join(name))
+            .filter(|path| path.exists())
+            .filter_map(|path| {
+                ensure_writable(&path).err().map(|error| ClaudeDirProblem {
+                    path: path.to_string_lossy().to_string(),
+                    error,
+                })
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    ClaudeDirHealth {
+        claude_dir: claude_dir.to_string_lossy().to_string(),
+        exists,
+        writable: exists && problems.is_empty(),
+        problems,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+/// Report whether ~/.claude can be written, for the UI to poll and warn about
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn check_claude_dir_health() -> Result<ClaudeDirHealth, String> {
+    let claude_dir = crate::claude_dir::claude_dir()?;
+    let health = tokio::task::spawn_blocking(move || claude_dir_health(&claude_dir))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !health.writable {
+        log::warn!("~/.claude is not fully writable: {:?}", health.problems);
+    }
+    Ok(health)
+}
+
+/// The claude directory the app uses and whether it is read-only
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_claude_dir_settings() -> Result<crate::claude_dir::ClaudeDirSettings, String> {
+    Ok(crate::claude_dir::current())
+}
+
+/// Point the app at another claude directory, e.g. a copy of a colleague's
+/// ~/.claude, or back at ~/.claude with no `path`.
+///
+/// With `read_only`, commands that would write into the directory fail with
+/// `read_only_mode`. Emits `claude-dir-changed` so open views reload.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn switch_claude_dir(
+    app: AppHandle,
+    db: tauri::State<'_, AgentDb>,
+    checkpoint_state: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    path: Option<String>,
+    read_only: bool,
+) -> Result<crate::claude_dir::ClaudeDirSettings, String> {
+    let path = match path.filter(|path| !path.trim().is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path.trim());
+            if !path.is_dir() {
+                return Err(i18n::error(ErrorKind::NotADirectory, path.display()));
+            }
+            Some(path.canonicalize().map_err(|e| i18n::error(ErrorKind::PathNotFound, e))?)
+        }
+        None => None,
+    };
+    // Running claude processes keep writing to the directory they started with
+    if !registry.0.get_running_processes()?.is_empty() {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            "stop running sessions and agents before switching the claude directory",
+        ));
+    }
+
+    let settings = crate::claude_dir::ClaudeDirSettings { path, read_only };
+    {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        crate::claude_dir::save(&conn, &settings).map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    }
+    crate::claude_dir::apply(settings.clone());
+    if let Ok(claude_dir) = get_claude_dir() {
+        checkpoint_state.set_claude_dir(claude_dir).await;
+    }
+    log::info!(
+        "Switched claude directory to {:?} (read-only: {})",
+        settings.path,
+        settings.read_only
+    );
+    let _ = app.emit("claude-dir-changed", &settings);
+    Ok(settings)
 }
 
 /// Checks if Claude Code is installed and gets its version
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn check_claude_version(app: AppHandle) -> Result<ClaudeVersionStatus, String> {
     log::info!("Checking Claude Code version");
@@ -721,6 +1611,7 @@ pub async fn check_claude_version(app: AppHandle) -> Result<ClaudeVersionStatus,
 }
 
 /// Saves the CLAUDE.md system prompt file
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn save_system_prompt(content: String) -> Result<String, String> {
     log::info!("Saving CLAUDE.md system prompt");
@@ -728,12 +1619,13 @@ pub async fn save_system_prompt(content: String) -> Result<String, String> {
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let claude_md_path = claude_dir.join("CLAUDE.md");
 
-    fs::write(&claude_md_path, content).map_err(|e| format!("Failed to write CLAUDE.md: {}", e))?;
+    write_locked(&claude_md_path, content.as_bytes()).await?;
 
     Ok("System prompt saved successfully".to_string())
 }
 
 /// Saves the Claude settings file
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn save_claude_settings(settings: serde_json::Value) -> Result<String, String> {
     log::info!("Saving Claude settings");
@@ -745,13 +1637,13 @@ pub async fn save_claude_settings(settings: serde_json::Value) -> Result<String,
     let json_string = serde_json::to_string_pretty(&settings)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
 
-    fs::write(&settings_path, json_string)
-        .map_err(|e| format!("Failed to write settings file: {}", e))?;
+    write_locked(&settings_path, json_string.as_bytes()).await?;
 
     Ok("Settings saved successfully".to_string())
 }
 
 /// Recursively finds all CLAUDE.md files in a project directory
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn find_claude_md_files(project_path: String) -> Result<Vec<ClaudeMdFile>, String> {
     log::info!("Finding CLAUDE.md files in project: {}", project_path);
@@ -771,6 +1663,28 @@ pub async fn find_claude_md_files(project_path: String) -> Result<Vec<ClaudeMdFi
     Ok(claude_files)
 }
 
+/// Memory files claude loads for a session started in `project_path`, in the
+/// order they are read: the user's ~/.claude/CLAUDE.md, then CLAUDE.md,
+/// .claude/CLAUDE.md and CLAUDE.local.md of every directory from the
+/// filesystem root down to the project
+pub fn effective_claude_md_files(project_path: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(claude_dir) = get_claude_dir() {
+        files.push(claude_dir.join("CLAUDE.md"));
+    }
+    let mut dirs: Vec<&Path> = project_path.ancestors().collect();
+    dirs.reverse();
+    for dir in dirs {
+        files.push(dir.join("CLAUDE.md"));
+        files.push(dir.join(".claude").join("CLAUDE.md"));
+        files.push(dir.join("CLAUDE.local.md"));
+    }
+    // The user file is also the project's .claude/CLAUDE.md when run from home
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|file| file.is_file() && seen.insert(file.clone()));
+    files
+}
+
 /// Helper function to recursively find CLAUDE.md files
 fn find_claude_md_recursive(
     current_path: &PathBuf,
@@ -838,6 +1752,7 @@ fn find_claude_md_recursive(
 }
 
 /// Reads a specific CLAUDE.md file by its absolute path
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn read_claude_md_file(file_path: String) -> Result<String, String> {
     log::info!("Reading CLAUDE.md file: {}", file_path);
@@ -847,32 +1762,41 @@ pub async fn read_claude_md_file(file_path: String) -> Result<String, String> {
         return Err(format!("File does not exist: {}", file_path));
     }
 
-    fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))
+    fs::read_to_string(&path).map_err(|e| i18n::error(ErrorKind::FileRead, e))
 }
 
 /// Saves a specific CLAUDE.md file by its absolute path
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn save_claude_md_file(file_path: String, content: String) -> Result<String, String> {
     log::info!("Saving CLAUDE.md file: {}", file_path);
 
     let path = PathBuf::from(&file_path);
+    ensure_writable(&path)?;
 
-    // Ensure the parent directory exists
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-    }
+    // Ensure the parent directory exists, without leaving it behind on failure
+    let created_dir = match path.parent() {
+        Some(parent) => Some(create_dirs(parent)?),
+        None => None,
+    };
 
-    fs::write(&path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+    fs::write(&path, content).map_err(|e| write_error(&path, e))?;
+    if let Some(created_dir) = created_dir {
+        created_dir.keep();
+    }
 
     Ok("File saved successfully".to_string())
 }
 
 /// Loads the JSONL history for a specific session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn load_session_history(
+    app: AppHandle,
+    db: tauri::State<'_, AgentDb>,
     session_id: String,
     project_id: String,
+    strict: Option<bool>,
 ) -> Result<Vec<serde_json::Value>, String> {
     log::info!(
         "Loading session history for session: {} in project: {}",
@@ -887,66 +1811,111 @@ pub async fn load_session_history(
         .join(format!("{}.jsonl", session_id));
 
     if !session_path.exists() {
-        return Err(format!("Session file not found: {}", session_id));
+        return Err(i18n::error(ErrorKind::SessionNotFound, session_id));
     }
 
-    let file =
-        fs::File::open(&session_path).map_err(|e| format!("Failed to open session file: {}", e))?;
+    // Transcripts are written by the CLI itself, so mask them on the way out
+    let redactor = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        redaction::stored_redactor(&conn)
+    };
+    let (messages, report) = read_session_history(&session_path, &redactor, strict.unwrap_or(false))?;
+    emit_parse_warnings(&app, &session_id, &report);
 
-    let reader = BufReader::new(file);
-    let mut messages = Vec::new();
+    Ok(messages)
+}
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                messages.push(json);
-            }
-        }
+/// Report skipped JSONL lines of a session on `session-parse-warnings:{session_id}`
+pub fn emit_parse_warnings(app: &AppHandle, session_id: &str, report: &JsonlParseReport) {
+    if !report.has_skipped() {
+        return;
     }
-
-    Ok(messages)
+    log::warn!(
+        "Skipped {} of {} lines in session {}",
+        report.skipped_lines,
+        report.total_lines,
+        session_id
+    );
+    let _ = app.emit(&format!("session-parse-warnings:{}", session_id), report);
 }
 
 
 
 /// Execute a new interactive Claude Code session with streaming output
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn execute_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
-    model: String,
+    model: Option<String>,
+    output_format: Option<ClaudeOutputFormat>,
+    interactive: Option<bool>,
+    env_overrides: Option<std::collections::HashMap<String, String>>,
+    disable_redaction: Option<bool>,
+    execution_profile_id: Option<i64>,
+    additional_dirs: Option<Vec<String>>,
 ) -> Result<(), String> {
+    // Explicit parameters override the profile
+    let profile = resolve_execution_profile(&app, execution_profile_id)?;
+    let model = model
+        .or_else(|| profile.as_ref().and_then(|p| p.model.clone()))
+        .ok_or_else(|| i18n::error(ErrorKind::InvalidInput, "no model given"))?;
+    let env_overrides = ExecutionProfile::merged_env(profile.as_ref(), env_overrides);
     log::info!(
         "Starting new Claude Code session in: {} with model: {}",
         project_path,
         model
     );
 
+    let project_path = normalize_project_path(&project_path)?.path;
+    let additional_dirs = normalize_additional_dirs(&additional_dirs.unwrap_or_default())?;
     let claude_path = find_claude_binary(&app)?;
     
-    let args = vec![
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
+    let output_format = output_format.unwrap_or_default();
+    let interactive = interactive.unwrap_or(false);
+    validate_interactive(output_format, interactive)?;
+
+    let mut args = prompt_args(&prompt, interactive);
+    args.extend(["--model".to_string(), model.clone()]);
+    args.extend(output_format.args());
+    args.extend(add_dir_args(&additional_dirs));
+    args.extend(ExecutionProfile::permission_args(profile.as_ref()));
+    if let Some(profile) = &profile {
+        args.extend(profile.extra_args.iter().cloned());
+    }
+
+    // Text output never reports the session ID, so pick it ourselves
+    let session_id = if output_format == ClaudeOutputFormat::Text {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        args.push("--session-id".to_string());
+        args.push(session_id.clone());
+        Some(session_id)
+    } else {
+        None
+    };
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    let mut cmd = create_system_command(&claude_path, args, Path::new(&project_path));
+    cmd.envs(project_env::resolve_env(&app, &project_path, env_overrides.as_ref())?);
+    if interactive {
+        cmd.stdin(Stdio::piped());
+    }
+    let redactor = redaction::load_redactor(&app, disable_redaction.unwrap_or(false));
+    spawn_claude_process(app, cmd, prompt, model, project_path, output_format, interactive, session_id, redactor, additional_dirs).await
 }
 
 /// Continue an existing Claude Code conversation with streaming output
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn continue_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
     model: String,
+    output_format: Option<ClaudeOutputFormat>,
+    interactive: Option<bool>,
+    env_overrides: Option<std::collections::HashMap<String, String>>,
+    disable_redaction: Option<bool>,
 ) -> Result<(), String> {
     log::info!(
         "Continuing Claude Code conversation in: {} with model: {}",
@@ -954,25 +1923,32 @@ pub async fn continue_claude_code(
         model
     );
 
+    let project_path = normalize_project_path(&project_path)?.path;
     let claude_path = find_claude_binary(&app)?;
     
-    let args = vec![
+    let output_format = output_format.unwrap_or_default();
+    let interactive = interactive.unwrap_or(false);
+    validate_interactive(output_format, interactive)?;
+
+    let mut args = vec![
         "-c".to_string(), // Continue flag
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
     ];
-
-    let cmd = create_system_command(&claude_path, args, &project_path);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    args.extend(prompt_args(&prompt, interactive));
+    args.extend(["--model".to_string(), model.clone()]);
+    args.extend(output_format.args());
+    args.push("--dangerously-skip-permissions".to_string());
+
+    let mut cmd = create_system_command(&claude_path, args, Path::new(&project_path));
+    cmd.envs(project_env::resolve_env(&app, &project_path, env_overrides.as_ref())?);
+    if interactive {
+        cmd.stdin(Stdio::piped());
+    }
+    let redactor = redaction::load_redactor(&app, disable_redaction.unwrap_or(false));
+    spawn_claude_process(app, cmd, prompt, model, project_path, output_format, interactive, None, redactor, Vec::new()).await
 }
 
 /// Resume an existing Claude Code session by ID with streaming output
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn resume_claude_code(
     app: AppHandle,
@@ -980,6 +1956,12 @@ pub async fn resume_claude_code(
     session_id: String,
     prompt: String,
     model: String,
+    output_format: Option<ClaudeOutputFormat>,
+    interactive: Option<bool>,
+    env_overrides: Option<std::collections::HashMap<String, String>>,
+    disable_redaction: Option<bool>,
+    force: Option<bool>,
+    additional_dirs: Option<Vec<String>>,
 ) -> Result<(), String> {
     log::info!(
         "Resuming Claude Code session: {} in: {} with model: {}",
@@ -988,26 +1970,47 @@ pub async fn resume_claude_code(
         model
     );
 
+    let project_path = normalize_project_path(&project_path)?.path;
+    // `force` skips the check, e.g. for a session file that was moved by hand
+    if !force.unwrap_or(false) {
+        let projects_dir = get_claude_dir().map_err(|e| e.to_string())?.join("projects");
+        check_session_project(&projects_dir, &session_id, &project_path)?;
+    }
+    let additional_dirs = normalize_additional_dirs(&additional_dirs.unwrap_or_default())?;
     let claude_path = find_claude_binary(&app)?;
     
-    let args = vec![
+    let output_format = output_format.unwrap_or_default();
+    let interactive = interactive.unwrap_or(false);
+    validate_interactive(output_format, interactive)?;
+
+    let mut args = vec![
         "--resume".to_string(),
         session_id.clone(),
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
     ];
+    args.extend(prompt_args(&prompt, interactive));
+    args.extend(["--model".to_string(), model.clone()]);
+    args.extend(output_format.args());
+    args.extend(add_dir_args(&additional_dirs));
+    args.push("--dangerously-skip-permissions".to_string());
+
+    // Text output never reports the session ID; events are keyed by the resumed one
+    let known_session_id = if output_format == ClaudeOutputFormat::Text {
+        Some(session_id)
+    } else {
+        None
+    };
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    let mut cmd = create_system_command(&claude_path, args, Path::new(&project_path));
+    cmd.envs(project_env::resolve_env(&app, &project_path, env_overrides.as_ref())?);
+    if interactive {
+        cmd.stdin(Stdio::piped());
+    }
+    let redactor = redaction::load_redactor(&app, disable_redaction.unwrap_or(false));
+    spawn_claude_process(app, cmd, prompt, model, project_path, output_format, interactive, known_session_id, redactor, additional_dirs).await
 }
 
 /// Cancel the currently running Claude Code execution
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn cancel_claude_execution(
     app: AppHandle,
@@ -1131,7 +2134,50 @@ pub async fn cancel_claude_execution(
     Ok(())
 }
 
+/// Resolve a run ID or Claude session ID to a registry run ID
+fn resolve_run_id(
+    registry: &crate::process::ProcessRegistry,
+    run_or_session_id: &str,
+) -> Result<i64, String> {
+    if let Ok(run_id) = run_or_session_id.parse::<i64>() {
+        return Ok(run_id);
+    }
+
+    registry
+        .get_claude_session_by_id(run_or_session_id)?
+        .map(|info| info.run_id)
+        .ok_or_else(|| i18n::error(ErrorKind::ProcessNotFound, run_or_session_id))
+}
+
+/// Send a follow-up message to an interactive agent run or Claude session
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn send_session_input(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    run_or_session_id: String,
+    text: String,
+) -> Result<(), String> {
+    log::info!("Sending input to session: {}", run_or_session_id);
+
+    let run_id = resolve_run_id(&registry.0, &run_or_session_id)?;
+    registry.0.write_stdin(run_id, &user_input_line(&text)).await
+}
+
+/// Close stdin of an interactive run so claude finishes after the current turn
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn close_session_input(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    run_or_session_id: String,
+) -> Result<(), String> {
+    log::info!("Closing input for session: {}", run_or_session_id);
+
+    let run_id = resolve_run_id(&registry.0, &run_or_session_id)?;
+    registry.0.close_stdin(run_id).await
+}
+
 /// Get all running Claude sessions
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn list_running_claude_sessions(
     registry: tauri::State<'_, crate::process::ProcessRegistryState>,
@@ -1140,6 +2186,7 @@ pub async fn list_running_claude_sessions(
 }
 
 /// Get live output from a Claude session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_claude_session_output(
     registry: tauri::State<'_, crate::process::ProcessRegistryState>,
@@ -1154,32 +2201,112 @@ pub async fn get_claude_session_output(
 }
 
 /// Helper function to spawn Claude process and handle streaming
-async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String, model: String, project_path: String) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
+/// Let the checkpoints of a session cover the directories it was given with
+/// `--add-dir`, in the background
+fn register_additional_roots(app: &AppHandle, session_id: &str, project_path: &str, additional_dirs: &[String]) {
+    if additional_dirs.is_empty() {
+        return;
+    }
+    let state = app.state::<crate::checkpoint::state::CheckpointState>().inner().clone();
+    let session_id = session_id.to_string();
+    let project_path = PathBuf::from(project_path);
+    let project_id = encode_project_path(&project_path);
+    let dirs: Vec<PathBuf> = additional_dirs.iter().map(PathBuf::from).collect();
+    tokio::spawn(async move {
+        if let Err(e) = state
+            .add_additional_roots(session_id.clone(), project_id, project_path, &dirs)
+            .await
+        {
+            log::warn!("Checkpoints of {} won't cover its additional directories: {}", session_id, e);
+        }
+    });
+}
+
+/// Register a session with the ProcessRegistry under claude's session ID and
+/// remember the run ID it got
+fn register_session(
+    registry: &crate::process::ProcessRegistry,
+    session_id: &str,
+    pid: u32,
+    project_path: &str,
+    prompt: &str,
+    model: &str,
+    run_id_holder: &std::sync::Mutex<Option<i64>>,
+) {
+    match registry.register_claude_session(
+        session_id.to_string(),
+        pid,
+        project_path.to_string(),
+        prompt.to_string(),
+        model.to_string(),
+    ) {
+        Ok(run_id) => {
+            log::info!("Registered Claude session with run_id: {}", run_id);
+            *run_id_holder.lock().unwrap() = Some(run_id);
+        }
+        Err(e) => {
+            log::error!("Failed to register Claude session: {}", e);
+        }
+    }
+}
+
+async fn spawn_claude_process(
+    app: AppHandle,
+    mut cmd: Command,
+    prompt: String,
+    model: String,
+    project_path: String,
+    output_format: ClaudeOutputFormat,
+    interactive: bool,
+    known_session_id: Option<String>,
+    redactor: Arc<Redactor>,
+    additional_dirs: Vec<String>,
+) -> Result<(), String> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
     use std::sync::Mutex;
 
+    // The session is written into the claude directory
+    crate::claude_dir::check_dir_writable()?;
+
     // Spawn the process
     let mut child = cmd
         .spawn()
-        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
+        .map_err(|e| i18n::error(ErrorKind::ProcessSpawn, e))?;
 
     // Get stdout and stderr
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
     let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
 
+    // Interactive runs get their first prompt through stdin
+    let mut stdin = if interactive {
+        let mut stdin = child.stdin.take().ok_or("Failed to get stdin")?;
+        stdin
+            .write_all(format!("{}\n", user_input_line(&prompt)).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send prompt to Claude: {}", e))?;
+        Some(stdin)
+    } else {
+        None
+    };
+
     // Get the child PID for logging
     let pid = child.id().unwrap_or(0);
     log::info!(
         "Spawned Claude process with PID: {:?}",
         pid
     );
+    let run_guard = ProjectRunGuard::new(&app, &project_path);
 
     // Create readers first (before moving child)
     let stdout_reader = BufReader::new(stdout);
     let stderr_reader = BufReader::new(stderr);
 
-    // We'll extract the session ID from Claude's init message
-    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+    if let Some(session_id) = &known_session_id {
+        register_additional_roots(&app, session_id, &project_path, &additional_dirs);
+    }
+
+    // We'll extract the session ID from Claude's init message (or the json result)
+    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(known_session_id));
     let run_id_holder: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
 
     // Store the child process in the global state (for backward compatibility)
@@ -1203,45 +2330,93 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
     let project_path_clone = project_path.clone();
     let prompt_clone = prompt.clone();
     let model_clone = model.clone();
+    let redactor_stderr = redactor.clone();
     let stdout_task = tokio::spawn(async move {
+        if !output_format.is_streaming() {
+            // json/text modes print everything at exit; collect it and emit once.
+            // A session ID picked up front is registered now so the run is
+            // listed while it works
+            let known_session_id = session_id_holder_clone.lock().unwrap().clone();
+            if let Some(session_id) = &known_session_id {
+                register_session(&registry_clone, session_id, pid, &project_path_clone, &prompt_clone, &model_clone, &run_id_holder_clone);
+            }
+
+            let mut stdout_reader = stdout_reader;
+            let mut stdout = String::new();
+            if let Err(e) = stdout_reader.read_to_string(&mut stdout).await {
+                log::error!("Failed to read Claude stdout: {}", e);
+            }
+
+            let stdout = redactor.redact(&stdout);
+            let output = ClaudeFinalOutput::parse(output_format, &stdout);
+            log::debug!("Claude final output: {}", output.payload);
+
+            if let Some(claude_session_id) = output.session_id {
+                if known_session_id.is_none() {
+                    register_additional_roots(&app_handle, &claude_session_id, &project_path_clone, &additional_dirs);
+                    register_session(&registry_clone, &claude_session_id, pid, &project_path_clone, &prompt_clone, &model_clone, &run_id_holder_clone);
+                }
+                *session_id_holder_clone.lock().unwrap() = Some(claude_session_id);
+            }
+            if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
+                let _ = registry_clone.append_live_output(run_id, &output.payload);
+            }
+
+            if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
+                let _ = app_handle.emit(&format!("claude-output:{}", session_id), &output.payload);
+            }
+            let _ = app_handle.emit("claude-output", &output.payload);
+            return;
+        }
+
         let mut lines = stdout_reader.lines();
+        // Output seen before the session could be registered
+        let mut pending_output: Vec<String> = Vec::new();
         while let Ok(Some(line)) = lines.next_line().await {
+            // Secrets are masked before the line is stored or emitted
+            let line = redactor.redact(&line).into_owned();
             log::debug!("Claude stdout: {}", line);
             
-            // Parse the line to check for init message with session ID
+            // Take the session ID from the init message, or from whichever
+            // message reports it first when the CLI exits before init
             if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
-                if msg["type"] == "system" && msg["subtype"] == "init" {
-                    if let Some(claude_session_id) = msg["session_id"].as_str() {
-                        let mut session_id_guard = session_id_holder_clone.lock().unwrap();
-                        if session_id_guard.is_none() {
-                            *session_id_guard = Some(claude_session_id.to_string());
-                            log::info!("Extracted Claude session ID: {}", claude_session_id);
-                            
-                            // Now register with ProcessRegistry using Claude's session ID
-                            match registry_clone.register_claude_session(
-                                claude_session_id.to_string(),
-                                pid,
-                                project_path_clone.clone(),
-                                prompt_clone.clone(),
-                                model_clone.clone(),
-                            ) {
-                                Ok(run_id) => {
-                                    log::info!("Registered Claude session with run_id: {}", run_id);
-                                    let mut run_id_guard = run_id_holder_clone.lock().unwrap();
-                                    *run_id_guard = Some(run_id);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to register Claude session: {}", e);
-                                }
-                            }
-                        }
+                if let Some(claude_session_id) = session_id_from_message(&msg) {
+                    let mut session_id_guard = session_id_holder_clone.lock().unwrap();
+                    if session_id_guard.is_none() {
+                        *session_id_guard = Some(claude_session_id.to_string());
+                        log::info!("Extracted Claude session ID: {}", claude_session_id);
+                        register_additional_roots(&app_handle, claude_session_id, &project_path_clone, &additional_dirs);
+                        
+                        // Now register with ProcessRegistry using Claude's session ID
+                        register_session(&registry_clone, claude_session_id, pid, &project_path_clone, &prompt_clone, &model_clone, &run_id_holder_clone);
                     }
                 }
             }
             
             // Store live output in registry if we have a run_id
-            if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
+            let run_id = *run_id_holder_clone.lock().unwrap();
+            if let Some(run_id) = run_id {
+                for pending in pending_output.drain(..) {
+                    let _ = registry_clone.append_live_output(run_id, &pending);
+                }
                 let _ = registry_clone.append_live_output(run_id, &line);
+
+                // Hand stdin to the registry so send_session_input can reach it
+                if let Some(stdin) = stdin.take() {
+                    if let Err(e) = registry_clone.attach_stdin(run_id, stdin).await {
+                        log::error!("Failed to attach stdin: {}", e);
+                    }
+                }
+            } else {
+                pending_output.push(line.clone());
+            }
+
+            // An interactive run waits for the next stdin message after each result
+            if interactive && is_result_line(&line) {
+                if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
+                    let _ = app_handle.emit(&format!("claude-awaiting-input:{}", session_id), true);
+                }
+                let _ = app_handle.emit("claude-awaiting-input", true);
             }
             
             // Emit the line to the frontend with session isolation if we have session ID
@@ -1258,6 +2433,12 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
     let stderr_task = tokio::spawn(async move {
         let mut lines = stderr_reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
+            let line = redactor_stderr.redact(&line).into_owned();
+            // Upgrade/deprecation/auth notices go to the notices feed instead
+            let session_id = session_id_holder_clone2.lock().unwrap().clone();
+            if notices::capture_stderr_notice(&app_handle_stderr, &line, None, session_id.as_deref()) {
+                continue;
+            }
             log::error!("Claude stderr: {}", line);
             // Emit error lines to the frontend with session isolation if we have session ID
             if let Some(ref session_id) = *session_id_holder_clone2.lock().unwrap() {
@@ -1277,6 +2458,8 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
     tokio::spawn(async move {
         let _ = stdout_task.await;
         let _ = stderr_task.await;
+        // The output is done; end the run before the UI hears about completion
+        drop(run_guard);
 
         // Get the child from the state to wait on it
         let mut current_process = claude_state_wait.lock().await;
@@ -1310,7 +2493,9 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
         }
 
         // Unregister from ProcessRegistry if we have a run_id
-        if let Some(run_id) = *run_id_holder_clone2.lock().unwrap() {
+        let run_id = *run_id_holder_clone2.lock().unwrap();
+        if let Some(run_id) = run_id {
+            let _ = registry_clone2.close_stdin(run_id).await;
             let _ = registry_clone2.unregister_process(run_id);
         }
 
@@ -1323,6 +2508,7 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
 
 
 /// Lists files and directories in a given path
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn list_directory_contents(directory_path: String) -> Result<Vec<FileEntry>, String> {
     log::info!("Listing directory contents: '{}'", directory_path);
@@ -1400,6 +2586,7 @@ pub async fn list_directory_contents(directory_path: String) -> Result<Vec<FileE
 }
 
 /// Search for files and directories matching a pattern
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn search_files(base_path: String, query: String) -> Result<Vec<FileEntry>, String> {
     log::info!("Searching files in '{}' for: '{}'", base_path, query);
@@ -1517,15 +2704,19 @@ fn search_files_recursive(
 }
 
 /// Creates a checkpoint for the current session state
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn create_checkpoint(
+    app_handle: AppHandle,
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
     session_id: String,
     project_id: String,
     project_path: String,
     message_index: Option<usize>,
     description: Option<String>,
+    kind: Option<crate::checkpoint::CheckpointKind>,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
+    crate::claude_dir::check_dir_writable()?;
     log::info!(
         "Creating checkpoint for session: {} in project: {}",
         session_id,
@@ -1542,41 +2733,121 @@ pub async fn create_checkpoint(
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
     // Always load current session messages from the JSONL file
-    let session_path = get_claude_dir()
-        .map_err(|e| e.to_string())?
-        .join("projects")
-        .join(&project_id)
-        .join(format!("{}.jsonl", session_id));
+    let externally_modified = manager
+        .sync_session_file(message_index)
+        .await
+        .map_err(|e| format!("Failed to track message: {}", e))?;
+    if externally_modified {
+        emit_session_externally_modified(&app_handle, &session_id, &project_id);
+    }
 
-    if session_path.exists() {
-        let file = fs::File::open(&session_path)
-            .map_err(|e| format!("Failed to open session file: {}", e))?;
-        let reader = BufReader::new(file);
+    manager
+        .create_checkpoint_of_kind(description, None, kind.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+}
 
-        let mut line_count = 0;
-        for line in reader.lines() {
-            if let Some(index) = message_index {
-                if line_count > index {
-                    break;
-                }
-            }
-            if let Ok(line) = line {
-                manager
-                    .track_message(line)
-                    .await
-                    .map_err(|e| format!("Failed to track message: {}", e))?;
+/// Marks a claude process as running in a project until dropped.
+///
+/// Auto-checkpoints of the project's sessions are deferred meanwhile, so they
+/// don't snapshot files claude is still writing; when the project's last run
+/// ends, the deferred ones are created from the final state.
+pub struct ProjectRunGuard {
+    app: AppHandle,
+    project_key: String,
+}
+
+impl ProjectRunGuard {
+    pub fn new(app: &AppHandle, project_path: &str) -> Self {
+        let project_key = canonical_project_key(project_path);
+        app.state::<crate::checkpoint::state::CheckpointState>()
+            .begin_run(&project_key);
+        Self {
+            app: app.clone(),
+            project_key,
+        }
+    }
+}
+
+impl Drop for ProjectRunGuard {
+    fn drop(&mut self) {
+        let deferred = self
+            .app
+            .state::<crate::checkpoint::state::CheckpointState>()
+            .end_run(&self.project_key);
+        if deferred.is_empty() {
+            return;
+        }
+        let app = self.app.clone();
+        tauri::async_runtime::spawn(async move {
+            create_deferred_checkpoints(&app, deferred).await;
+        });
+    }
+}
+
+/// Create the auto-checkpoints deferred while a run was active
+async fn create_deferred_checkpoints(app: &AppHandle, sessions: Vec<(String, String)>) {
+    let state = app.state::<crate::checkpoint::state::CheckpointState>();
+    for (session_id, project_id) in sessions {
+        let Some(manager) = state.get_manager(&session_id).await else {
+            continue;
+        };
+        match manager.sync_session_file(None).await {
+            Ok(true) => emit_session_externally_modified(app, &session_id, &project_id),
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!("Failed to reload session {} for its deferred checkpoint: {}", session_id, e);
+                continue;
             }
-            line_count += 1;
         }
+        let kind = crate::checkpoint::CheckpointKind::for_strategy(
+            &manager.get_timeline().await.checkpoint_strategy,
+        );
+        match manager
+            .create_checkpoint_of_kind(Some("Auto checkpoint after run".to_string()), None, kind)
+            .await
+        {
+            Ok(result) => log::info!(
+                "Created deferred auto-checkpoint {} for session {}",
+                result.checkpoint.id,
+                session_id
+            ),
+            Err(e) => log::warn!("Failed to create deferred checkpoint for session {}: {}", session_id, e),
+        }
+    }
+}
+
+/// Tell the UI a session file changed outside the app so it reloads the conversation
+fn emit_session_externally_modified(app: &AppHandle, session_id: &str, project_id: &str) {
+    let payload = serde_json::json!({ "sessionId": session_id, "projectId": project_id });
+    let event = format!("session-externally-modified:{}", session_id);
+    if let Err(e) = app.emit(&event, &payload) {
+        log::warn!("Failed to emit {}: {}", event, e);
     }
+}
 
-    manager
-        .create_checkpoint(description, None)
-        .await
-        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+/// Cancels a checkpoint being created for a session
+///
+/// The running create fails and leaves no checkpoint in the timeline.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn cancel_checkpoint_creation(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+) -> Result<bool, String> {
+    log::info!("Cancelling checkpoint creation for session: {}", session_id);
+
+    match app.get_manager(&session_id).await {
+        Some(manager) => {
+            manager.cancel_checkpoint_creation();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
 }
 
 /// Restores a session to a specific checkpoint
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn restore_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1585,6 +2856,7 @@ pub async fn restore_checkpoint(
     project_id: String,
     project_path: String,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
+    crate::claude_dir::check_dir_writable()?;
     log::info!(
         "Restoring checkpoint: {} for session: {}",
         checkpoint_id,
@@ -1614,9 +2886,9 @@ pub async fn restore_checkpoint(
 
     // The manager has already restored the messages internally,
     // but we need to update the actual session file
-    let (_, _, messages) = manager
+    let messages = manager
         .storage
-        .load_checkpoint(&result.checkpoint.project_id, &session_id, &checkpoint_id)
+        .load_checkpoint_messages(&result.checkpoint.project_id, &session_id, &checkpoint_id)
         .map_err(|e| format!("Failed to load checkpoint data: {}", e))?;
 
     fs::write(&session_path, messages)
@@ -1626,6 +2898,7 @@ pub async fn restore_checkpoint(
 }
 
 /// Lists all checkpoints for a session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn list_checkpoints(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1648,6 +2921,7 @@ pub async fn list_checkpoints(
 }
 
 /// Forks a new timeline branch from a checkpoint
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn fork_from_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1658,6 +2932,7 @@ pub async fn fork_from_checkpoint(
     new_session_id: String,
     description: Option<String>,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
+    crate::claude_dir::check_dir_writable()?;
     log::info!(
         "Forking from checkpoint: {} to new session: {}",
         checkpoint_id,
@@ -1698,6 +2973,7 @@ pub async fn fork_from_checkpoint(
 }
 
 /// Gets the timeline for a session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_session_timeline(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1716,10 +2992,13 @@ pub async fn get_session_timeline(
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    Ok(manager.get_timeline().await)
+    let mut timeline = manager.get_timeline().await;
+    timeline.compute_deltas();
+    Ok(timeline)
 }
 
 /// Updates checkpoint settings for a session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn update_checkpoint_settings(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1728,23 +3007,16 @@ pub async fn update_checkpoint_settings(
     project_path: String,
     auto_checkpoint_enabled: bool,
     checkpoint_strategy: String,
+    retention: Option<crate::checkpoint::CheckpointRetention>,
 ) -> Result<(), String> {
+    crate::claude_dir::check_dir_writable()?;
     use crate::checkpoint::CheckpointStrategy;
 
     log::info!("Updating checkpoint settings for session: {}", session_id);
 
-    let strategy = match checkpoint_strategy.as_str() {
-        "manual" => CheckpointStrategy::Manual,
-        "per_prompt" => CheckpointStrategy::PerPrompt,
-        "per_tool_use" => CheckpointStrategy::PerToolUse,
-        "smart" => CheckpointStrategy::Smart,
-        _ => {
-            return Err(format!(
-                "Invalid checkpoint strategy: {}",
-                checkpoint_strategy
-            ))
-        }
-    };
+    let strategy = checkpoint_strategy
+        .parse::<CheckpointStrategy>()
+        .map_err(|e| i18n::error(ErrorKind::InvalidInput, e))?;
 
     let manager = app
         .get_or_create_manager(session_id, project_id, PathBuf::from(&project_path))
@@ -1754,10 +3026,22 @@ pub async fn update_checkpoint_settings(
     manager
         .update_settings(auto_checkpoint_enabled, strategy)
         .await
-        .map_err(|e| format!("Failed to update settings: {}", e))
+        .map_err(|e| format!("Failed to update settings: {}", e))?;
+    if let Some(retention) = retention {
+        manager
+            .update_retention(retention)
+            .await
+            .map_err(|e| format!("Failed to update settings: {}", e))?;
+    }
+    Ok(())
 }
 
 /// Gets diff between two checkpoints
+///
+/// Only line counts are returned; fetch a file's content diff with
+/// `get_file_diff`. Runs off the async runtime since large checkpoints take
+/// a while to read.
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_checkpoint_diff(
     from_checkpoint_id: String,
@@ -1774,77 +3058,180 @@ pub async fn get_checkpoint_diff(
     );
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let storage = CheckpointStorage::new(claude_dir);
+    tokio::task::spawn_blocking(move || {
+        CheckpointStorage::new(claude_dir).diff_checkpoints(
+            &project_id,
+            &session_id,
+            &from_checkpoint_id,
+            &to_checkpoint_id,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to diff checkpoints: {}", e))
+}
+
+/// Finds the checkpoints of a session that touched files matching
+/// `path_glob` (e.g. `src/auth.rs` or `**/*.sql`), oldest first, with how
+/// each changed them. Pass `rebuild` to re-index timelines created before the
+/// file index existed.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn find_checkpoints_touching_file(
+    session_id: String,
+    project_id: String,
+    path_glob: String,
+    rebuild: Option<bool>,
+) -> Result<Vec<crate::checkpoint::file_index::CheckpointFileMatch>, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
 
-    // Load both checkpoints
-    let (from_checkpoint, from_files, _) = storage
-        .load_checkpoint(&project_id, &session_id, &from_checkpoint_id)
-        .map_err(|e| format!("Failed to load source checkpoint: {}", e))?;
-    let (to_checkpoint, to_files, _) = storage
-        .load_checkpoint(&project_id, &session_id, &to_checkpoint_id)
-        .map_err(|e| format!("Failed to load target checkpoint: {}", e))?;
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || {
+        CheckpointStorage::new(claude_dir).find_checkpoints_touching_file(
+            &project_id,
+            &session_id,
+            &path_glob,
+            rebuild.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to search checkpoints: {}", e))
+}
 
-    // Build file maps
-    let mut from_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &from_files {
-        from_map.insert(file.file_path.clone(), file);
-    }
+/// Gets the content diff of one file between two checkpoints
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_file_diff(
+    from_checkpoint_id: String,
+    to_checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    path: String,
+) -> Result<crate::checkpoint::FileDiff, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
 
-    let mut to_map: std::collections::HashMap<PathBuf, &crate::checkpoint::FileSnapshot> =
-        std::collections::HashMap::new();
-    for file in &to_files {
-        to_map.insert(file.file_path.clone(), file);
-    }
-
-    // Calculate differences
-    let mut modified_files = Vec::new();
-    let mut added_files = Vec::new();
-    let mut deleted_files = Vec::new();
-
-    // Check for modified and deleted files
-    for (path, from_file) in &from_map {
-        if let Some(to_file) = to_map.get(path) {
-            if from_file.hash != to_file.hash {
-                // File was modified
-                let additions = to_file.content.lines().count();
-                let deletions = from_file.content.lines().count();
-
-                modified_files.push(crate::checkpoint::FileDiff {
-                    path: path.clone(),
-                    additions,
-                    deletions,
-                    diff_content: None, // TODO: Generate actual diff
-                });
-            }
-        } else {
-            // File was deleted
-            deleted_files.push(path.clone());
-        }
-    }
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || {
+        CheckpointStorage::new(claude_dir).diff_file(
+            &project_id,
+            &session_id,
+            &from_checkpoint_id,
+            &to_checkpoint_id,
+            Path::new(&path),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to diff file: {}", e))
+}
 
-    // Check for added files
-    for (path, _) in &to_map {
-        if !from_map.contains_key(path) {
-            added_files.push(path.clone());
-        }
-    }
+/// Reports how much the content pool of a session saves through deduplication
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_content_pool_stats(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    project_id: String,
+    session_id: String,
+) -> Result<crate::checkpoint::ContentPoolStats, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
 
-    // Calculate token delta
-    let token_delta = (to_checkpoint.metadata.total_tokens as i64)
-        - (from_checkpoint.metadata.total_tokens as i64);
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let checkpoint_lock = app.checkpoint_lock(&session_id).await;
+    let _guard = checkpoint_lock.lock().await;
+    tokio::task::spawn_blocking(move || {
+        CheckpointStorage::new(claude_dir).content_pool_stats(&project_id, &session_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to read content pool: {}", e))
+}
 
-    Ok(crate::checkpoint::CheckpointDiff {
-        from_checkpoint_id,
-        to_checkpoint_id,
-        modified_files,
-        added_files,
-        deleted_files,
-        token_delta,
+/// Removes content pool blobs no checkpoint refers to, returning how many
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn gc_content_pool(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    project_id: String,
+    session_id: String,
+) -> Result<usize, String> {
+    crate::claude_dir::check_dir_writable()?;
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let checkpoint_lock = app.checkpoint_lock(&session_id).await;
+    let _guard = checkpoint_lock.lock().await;
+    let removed = tokio::task::spawn_blocking(move || {
+        CheckpointStorage::new(claude_dir).garbage_collect_content(&project_id, &session_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to collect content pool: {}", e))?;
+    log::info!("Garbage collected {} orphaned content files", removed);
+    Ok(removed)
+}
+
+/// Finds checkpoint data of sessions whose JSONL no longer exists, with sizes
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn find_orphaned_checkpoint_data(
+) -> Result<Vec<crate::checkpoint::OrphanedCheckpointData>, String> {
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || CheckpointStorage::new(claude_dir).find_orphaned_timelines())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to scan timelines: {}", e))
+}
+
+/// Deletes the selected orphaned checkpoint data, returning what was deleted.
+///
+/// Sessions whose file has reappeared or that have an active checkpoint
+/// manager are left alone. Runs as a job; cancelling it stops after the
+/// current session and returns what was deleted so far.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn purge_orphaned_checkpoint_data(
+    app_handle: AppHandle,
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    entries: Vec<crate::checkpoint::OrphanedCheckpointData>,
+) -> Result<Vec<crate::checkpoint::OrphanedCheckpointData>, String> {
+    crate::claude_dir::check_dir_writable()?;
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let params = serde_json::json!({ "entries": entries.len() });
+    run_as_job(&app_handle, "purge_orphaned_checkpoint_data", params, |job| async move {
+        let total = entries.len();
+        let mut purged = Vec::new();
+        for (done, entry) in entries.into_iter().enumerate() {
+            if job.should_stop() {
+                break;
+            }
+            job.progress(done, total, &entry.session_id);
+            if app.has_active_manager(&entry.session_id).await {
+                log::info!("Keeping timeline of active session {}", entry.session_id);
+                continue;
+            }
+            let checkpoint_lock = app.checkpoint_lock(&entry.session_id).await;
+            let _guard = checkpoint_lock.lock().await;
+            let storage = CheckpointStorage::new(claude_dir.clone());
+            let removed = tokio::task::spawn_blocking(move || storage.purge_orphaned_timelines(&[entry]))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| format!("Failed to remove timeline: {}", e))?;
+            purged.extend(removed);
+        }
+        let freed: u64 = purged.iter().map(|entry| entry.size_bytes).sum();
+        log::info!("Removed {} orphaned timelines, freeing {} bytes", purged.len(), freed);
+        Ok(purged)
     })
+    .await
 }
 
 /// Tracks a message for checkpointing
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn track_checkpoint_message(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1863,12 +3250,16 @@ pub async fn track_checkpoint_message(
     manager
         .track_message(message)
         .await
-        .map_err(|e| format!("Failed to track message: {}", e))
+        .map_err(|e| format!("Failed to track message: {}", e))?;
+    manager.mark_session_file_synced().await;
+    Ok(())
 }
 
 /// Checks if auto-checkpoint should be triggered
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn check_auto_checkpoint(
+    app_handle: AppHandle,
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
     session_id: String,
     project_id: String,
@@ -1878,44 +3269,68 @@ pub async fn check_auto_checkpoint(
     log::info!("Checking auto-checkpoint for session: {}", session_id);
 
     let manager = app
-        .get_or_create_manager(session_id.clone(), project_id, PathBuf::from(project_path))
+        .get_or_create_manager(session_id.clone(), project_id.clone(), PathBuf::from(&project_path))
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    Ok(manager.should_auto_checkpoint(&message).await)
+    // Decide on the conversation as it is on disk, not a stale copy
+    if manager.session_file_changed().await {
+        manager
+            .sync_session_file(None)
+            .await
+            .map_err(|e| format!("Failed to reload session: {}", e))?;
+        emit_session_externally_modified(&app_handle, &session_id, &project_id);
+    }
+
+    let should_checkpoint = manager.should_auto_checkpoint(&message).await;
+    let project_key = canonical_project_key(&project_path);
+    if should_checkpoint && app.has_active_run(&project_key) {
+        // Created once the run ends, see ProjectRunGuard
+        log::info!("Deferring auto-checkpoint of session {} until the project's run ends", session_id);
+        app.defer_auto_checkpoint(&session_id, &project_id, &project_key);
+        return Ok(false);
+    }
+    Ok(should_checkpoint)
 }
 
 /// Triggers cleanup of old checkpoints
+///
+/// Without `retention` the session's stored policy is applied; the current
+/// checkpoint and branch points are always kept.
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn cleanup_old_checkpoints(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
     session_id: String,
     project_id: String,
     project_path: String,
-    keep_count: usize,
+    keep_count: Option<usize>,
+    retention: Option<crate::checkpoint::CheckpointRetention>,
 ) -> Result<usize, String> {
+    crate::claude_dir::check_dir_writable()?;
     log::info!(
-        "Cleaning up old checkpoints for session: {}, keeping {}",
+        "Cleaning up old checkpoints for session: {}, keeping {:?}",
         session_id,
         keep_count
     );
 
     let manager = app
-        .get_or_create_manager(
-            session_id.clone(),
-            project_id.clone(),
-            PathBuf::from(project_path),
-        )
+        .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
+    let retention = match retention {
+        Some(retention) => retention,
+        None => manager.get_timeline().await.retention,
+    };
     manager
-        .storage
-        .cleanup_old_checkpoints(&project_id, &session_id, keep_count)
+        .cleanup_old_checkpoints(keep_count, &retention)
+        .await
         .map_err(|e| format!("Failed to cleanup checkpoints: {}", e))
 }
 
 /// Gets checkpoint settings for a session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_checkpoint_settings(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1925,22 +3340,32 @@ pub async fn get_checkpoint_settings(
 ) -> Result<serde_json::Value, String> {
     log::info!("Getting checkpoint settings for session: {}", session_id);
 
+    let auto_checkpoint_pending = app.has_deferred_checkpoint(&session_id);
     let manager = app
         .get_or_create_manager(session_id, project_id, PathBuf::from(project_path))
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
     let timeline = manager.get_timeline().await;
+    let last_checkpoint_at = timeline.latest_checkpoint_at();
 
     Ok(serde_json::json!({
         "auto_checkpoint_enabled": timeline.auto_checkpoint_enabled,
-        "checkpoint_strategy": timeline.checkpoint_strategy,
+        "checkpoint_strategy": timeline.checkpoint_strategy.to_string(),
+        "retention": timeline.retention,
         "total_checkpoints": timeline.total_checkpoints,
         "current_checkpoint_id": timeline.current_checkpoint_id,
+        // How recent the newest checkpoint is, e.g. before a risky prompt
+        "last_checkpoint_at": last_checkpoint_at,
+        "seconds_since_last_checkpoint": last_checkpoint_at
+            .map(|at| (chrono::Utc::now() - at).num_seconds().max(0)),
+        // An auto-checkpoint is scheduled for when the current run ends
+        "auto_checkpoint_pending": auto_checkpoint_pending,
     }))
 }
 
 /// Clears checkpoint manager for a session (cleanup on session end)
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn clear_checkpoint_manager(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1952,7 +3377,37 @@ pub async fn clear_checkpoint_manager(
     Ok(())
 }
 
+/// Repairs a session timeline whose timeline.json is unreadable
+///
+/// Falls back to timeline.json.bak, and rebuilds the timeline from the
+/// checkpoints directory when both copies are broken.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn repair_timeline(
+    app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    session_id: String,
+    project_id: String,
+) -> Result<crate::checkpoint::TimelineRepairReport, String> {
+    crate::claude_dir::check_dir_writable()?;
+    use crate::checkpoint::storage::CheckpointStorage;
+
+    log::info!("Repairing timeline for session: {}", session_id);
+
+    let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+    let storage = CheckpointStorage::new(claude_dir);
+
+    let report = storage
+        .repair_timeline(&project_id, &session_id)
+        .map_err(|e| format!("Failed to repair timeline: {}", e))?;
+
+    // Drop any cached manager so the repaired timeline is picked up on next use
+    app.remove_manager(&session_id).await;
+
+    Ok(report)
+}
+
 /// Gets checkpoint state statistics (for debugging/monitoring)
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_checkpoint_state_stats(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -1967,6 +3422,7 @@ pub async fn get_checkpoint_state_stats(
 }
 
 /// Gets files modified in the last N minutes for a session
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_recently_modified_files(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -2003,6 +3459,7 @@ pub async fn get_recently_modified_files(
 }
 
 /// Track session messages from the frontend for checkpointing
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn track_session_messages(
     state: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
@@ -2032,11 +3489,13 @@ pub async fn track_session_messages(
             .await
             .map_err(|e| format!("Failed to track message: {}", e))?;
     }
+    manager.mark_session_file_synced().await;
 
     Ok(())
 }
 
 /// Gets hooks configuration from settings at specified scope
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_hooks_config(scope: String, project_path: Option<String>) -> Result<serde_json::Value, String> {
     log::info!("Getting hooks config for scope: {}, project: {:?}", scope, project_path);
@@ -2073,6 +3532,7 @@ pub async fn get_hooks_config(scope: String, project_path: Option<String>) -> Re
 }
 
 /// Updates hooks configuration in settings at specified scope
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn update_hooks_config(
     scope: String, 
@@ -2081,53 +3541,53 @@ pub async fn update_hooks_config(
 ) -> Result<String, String> {
     log::info!("Updating hooks config for scope: {}, project: {:?}", scope, project_path);
 
-    let settings_path = match scope.as_str() {
+    // A .claude directory created here is removed again if the write fails
+    let (settings_path, created_dir): (PathBuf, Option<CreatedDirs>) = match scope.as_str() {
         "user" => {
-            get_claude_dir()
-                .map_err(|e| e.to_string())?
-                .join("settings.json")
+            let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
+            (claude_dir.join("settings.json"), None)
         },
         "project" => {
             let path = project_path.ok_or("Project path required for project scope")?;
             let claude_dir = PathBuf::from(path).join(".claude");
-            fs::create_dir_all(&claude_dir)
-                .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
-            claude_dir.join("settings.json")
+            ensure_writable(&claude_dir)?;
+            (claude_dir.join("settings.json"), Some(create_dirs(&claude_dir)?))
         },
         "local" => {
             let path = project_path.ok_or("Project path required for local scope")?;
             let claude_dir = PathBuf::from(path).join(".claude");
-            fs::create_dir_all(&claude_dir)
-                .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
-            claude_dir.join("settings.local.json")
+            ensure_writable(&claude_dir)?;
+            (claude_dir.join("settings.local.json"), Some(create_dirs(&claude_dir)?))
         },
         _ => return Err("Invalid scope".to_string())
     };
 
-    // Read existing settings or create new
-    let mut settings = if settings_path.exists() {
-        let content = fs::read_to_string(&settings_path)
-            .map_err(|e| format!("Failed to read settings: {}", e))?;
-        serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse settings: {}", e))?
-    } else {
-        serde_json::json!({})
-    };
+    // Read, update and write back under the lock so concurrent writers don't clobber it
+    update_locked(&settings_path, |content| {
+        let mut settings = match content {
+            Some(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse settings: {}", e))?,
+            None => serde_json::json!({}),
+        };
 
-    // Update hooks section
-    settings["hooks"] = hooks;
+        // Update hooks section
+        settings["hooks"] = hooks;
 
-    // Write back with pretty formatting
-    let json_string = serde_json::to_string_pretty(&settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-    
-    fs::write(&settings_path, json_string)
-        .map_err(|e| format!("Failed to write settings: {}", e))?;
+        // Write back with pretty formatting
+        serde_json::to_string_pretty(&settings)
+            .map(Some)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))
+    })
+    .await?;
+    if let Some(created_dir) = created_dir {
+        created_dir.keep();
+    }
 
     Ok("Hooks configuration updated successfully".to_string())
 }
 
 /// Validates a hook command by dry-running it
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn validate_hook_command(command: String) -> Result<serde_json::Value, String> {
     log::info!("Validating hook command syntax");
@@ -2156,3 +3616,274 @@ pub async fn validate_hook_command(command: String) -> Result<serde_json::Value,
         Err(e) => Err(format!("Failed to validate command: {}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encode_project_path_matches_claude() {
+        assert_eq!(
+            encode_project_path(Path::new("/Users/me/dev/my_app.v2")),
+            "-Users-me-dev-my-app-v2"
+        );
+        assert_eq!(
+            encode_project_path(Path::new("/home/me/My Project")),
+            "-home-me-My-Project"
+        );
+    }
+
+    #[test]
+    fn test_encode_project_path_cjk_and_spaces() {
+        // Each CJK character is one UTF-16 unit and becomes a single dash
+        assert_eq!(encode_project_path(Path::new(r"D:\项目\我的应用")), "D---------");
+        assert_eq!(
+            encode_project_path(Path::new(r"C:\Users\张 三\My Project")),
+            "C--Users-----My-Project"
+        );
+        // Characters outside the BMP take two UTF-16 units
+        assert_eq!(encode_project_path(Path::new("/tmp/𠀀")), "-tmp---");
+    }
+
+    #[test]
+    fn test_encode_project_path_strips_verbatim_prefix() {
+        assert_eq!(
+            encode_project_path(Path::new(r"\\?\D:\项目\我的应用")),
+            encode_project_path(Path::new(r"D:\项目\我的应用"))
+        );
+        assert_eq!(
+            encode_project_path(Path::new(r"\\?\UNC\server\share\项目")),
+            encode_project_path(Path::new(r"\\server\share\项目"))
+        );
+    }
+
+    #[test]
+    fn test_find_project_dir_falls_back_to_legacy_encoding() {
+        let temp_dir = TempDir::new().unwrap();
+        let projects_dir = temp_dir.path();
+        let project_path = Path::new("/home/me/my.app");
+
+        // Nothing exists yet: claude's encoding is used
+        assert_eq!(
+            find_project_dir(projects_dir, project_path),
+            projects_dir.join("-home-me-my-app")
+        );
+
+        // A directory created with the old slash-only encoding is still found
+        fs::create_dir_all(projects_dir.join("-home-me-my.app")).unwrap();
+        assert_eq!(
+            find_project_dir(projects_dir, project_path),
+            projects_dir.join("-home-me-my.app")
+        );
+    }
+
+    #[test]
+    fn test_resume_rejects_session_from_another_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let projects_dir = temp_dir.path();
+        let session_id = "0b6c3a1e-5d2f-4f7a-9c1e-2a3b4c5d6e7f";
+        let other_dir = projects_dir.join("-home-me-other");
+        fs::create_dir_all(&other_dir).unwrap();
+        fs::write(
+            other_dir.join(format!("{}.jsonl", session_id)),
+            "{\"type\":\"user\",\"cwd\":\"/home/me/other\"}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_session_file(projects_dir, session_id, Path::new("/home/me/app")),
+            Some(other_dir.join(format!("{}.jsonl", session_id)))
+        );
+        let err = check_session_project(projects_dir, session_id, "/home/me/app").unwrap_err();
+        assert!(err.starts_with("[session_in_other_project]"), "{}", err);
+        assert!(err.ends_with("/home/me/other"), "{}", err);
+
+        assert!(check_session_project(projects_dir, session_id, "/home/me/other").is_ok());
+        let err = check_session_project(projects_dir, "missing", "/home/me/app").unwrap_err();
+        assert!(err.starts_with("[session_not_found]"), "{}", err);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_cjk_project_dir_with_spaces() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path().join("我的 项目");
+        fs::create_dir_all(&project_path).unwrap();
+
+        // canonicalize() adds a verbatim prefix on Windows; the encoding must not change
+        let canonical = project_path.canonicalize().unwrap();
+        assert_eq!(
+            encode_project_path(&canonical),
+            encode_project_path(&project_path)
+        );
+
+        let projects_dir = temp_dir.path().join("projects");
+        let encoded_dir = projects_dir.join(encode_project_path(&project_path));
+        fs::create_dir_all(&encoded_dir).unwrap();
+        assert_eq!(find_project_dir(&projects_dir, &canonical), encoded_dir);
+
+        // The spawned process accepts the non-ASCII working directory
+        let status = std::process::Command::new("cmd")
+            .args(["/C", "cd"])
+            .current_dir(&project_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_normalize_project_path_cleans_pasted_input() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("my project");
+        fs::create_dir(&dir).unwrap();
+        let expected = dir.canonicalize().unwrap();
+        let expected = strip_verbatim_prefix(&expected.to_string_lossy());
+
+        let quoted = format!("  \"{}\"\n", dir.display());
+        assert_eq!(normalize_project_path(&quoted).unwrap().path, expected);
+
+        if cfg!(unix) {
+            let url = format!("file://{}", dir.display()).replace(' ', "%20");
+            assert_eq!(normalize_project_path(&url).unwrap().path, expected);
+            let url = format!("file://localhost{}", dir.display()).replace(' ', "%20");
+            assert_eq!(normalize_project_path(&url).unwrap().path, expected);
+
+            let accented = temp.path().join("caf\u{e9}");
+            fs::create_dir(&accented).unwrap();
+            let url = format!("file://{}/caf%C3%A9", temp.path().display());
+            assert_eq!(
+                normalize_project_path(&url).unwrap().path,
+                strip_verbatim_prefix(&accented.canonicalize().unwrap().to_string_lossy())
+            );
+            // Only URLs are decoded
+            let literal = temp.path().join("100%20done");
+            fs::create_dir(&literal).unwrap();
+            assert_eq!(
+                normalize_project_path(&literal.to_string_lossy()).unwrap().path,
+                strip_verbatim_prefix(&literal.canonicalize().unwrap().to_string_lossy())
+            );
+
+            std::env::set_var("OPCODE_TEST_PROJECT_ROOT", temp.path());
+            let with_var = "${OPCODE_TEST_PROJECT_ROOT}/my project";
+            assert_eq!(normalize_project_path(with_var).unwrap().path, expected);
+            // %VAR% is only a variable on Windows
+            let percent = expand_env_vars("%OPCODE_TEST_PROJECT_ROOT%/my project");
+            if cfg!(windows) {
+                assert_eq!(percent, format!("{}/my project", temp.path().display()));
+            } else {
+                assert_eq!(percent, "%OPCODE_TEST_PROJECT_ROOT%/my project");
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_project_path_rejects_files_and_missing_paths() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("notes.txt");
+        fs::write(&file, "x").unwrap();
+
+        let err = normalize_project_path(&file.to_string_lossy()).unwrap_err();
+        assert!(err.starts_with("[not_a_directory]"));
+
+        let missing = temp.path().join("missing");
+        let err = normalize_project_path(&missing.to_string_lossy()).unwrap_err();
+        assert!(err.starts_with("[path_not_found]"));
+
+        assert!(normalize_project_path("  ''  ").is_err());
+    }
+
+
+    #[test]
+    fn test_project_path_key_ignores_trailing_separators() {
+        assert_eq!(project_path_key("/home/user/app/"), project_path_key("/home/user/app"));
+        assert_eq!(project_path_key("/"), "/");
+        if cfg!(windows) {
+            assert_eq!(project_path_key(r"C:\Users\App\"), project_path_key("c:/users/app"));
+        } else if cfg!(target_os = "macos") {
+            assert_eq!(project_path_key("/Users/Me/App"), project_path_key("/users/me/app"));
+        } else {
+            assert_ne!(project_path_key("/home/User"), project_path_key("/home/user"));
+        }
+    }
+
+    #[test]
+    fn test_find_session_id_without_init_message() {
+        // The CLI failed before init and only printed its result
+        let short_circuit = r#"{"type":"result","subtype":"error_during_execution","is_error":true,"session_id":"5a1e-result","result":"API Error: 401"}"#;
+        assert_eq!(find_session_id(short_circuit).as_deref(), Some("5a1e-result"));
+
+        let stream = "warming up\n{\"type\":\"assistant\",\"session_id\":\"5a1e-assistant\"}\n";
+        assert_eq!(find_session_id(stream).as_deref(), Some("5a1e-assistant"));
+
+        // Init still wins when it shows up after another message
+        let stream = "{\"type\":\"assistant\",\"session_id\":\"other\"}\n{\"type\":\"system\",\"subtype\":\"init\",\"session_id\":\"5a1e-init\"}\n";
+        assert_eq!(find_session_id(stream).as_deref(), Some("5a1e-init"));
+
+        assert_eq!(find_session_id("{\"type\":\"result\",\"session_id\":\"\"}"), None);
+
+        let output = ClaudeFinalOutput::parse(
+            ClaudeOutputFormat::Json,
+            r#"[{"type":"assistant","session_id":"5a1e-array"},{"type":"result","is_error":true}]"#,
+        );
+        assert_eq!(output.session_id.as_deref(), Some("5a1e-array"));
+    }
+
+    #[test]
+    fn test_session_summary_reads_bounded_prefix_and_queues_newest_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("s1.jsonl");
+        let padding = format!("{{\"type\":\"summary\",\"summary\":\"{}\"}}\n", "x".repeat(100));
+        let user = "{\"type\":\"user\",\"timestamp\":\"t1\",\"message\":{\"role\":\"user\",\"content\":\"Fix the build\"}}\n";
+        fs::write(&path, format!("{}{}", padding, user)).unwrap();
+
+        assert_eq!(
+            read_first_user_message(&path, 1024),
+            (Some("Fix the build".to_string()), Some("t1".to_string()))
+        );
+        // The message lies beyond the limit
+        assert_eq!(read_first_user_message(&path, 64), (None, None));
+
+        let cache = SessionSummaryCache::default();
+        let t0 = SystemTime::UNIX_EPOCH;
+        let t1 = t0 + std::time::Duration::from_secs(1);
+        cache.insert(path.clone(), t0, (None, None));
+        assert!(cache.get(&path, t0).is_some());
+        assert!(cache.get(&path, t1).is_none());
+        cache.insert(path.clone(), t0, (Some("Fix the build".to_string()), None));
+        assert!(cache.get(&path, t1).is_some());
+
+        // A later request goes ahead of the queue without duplicating entries
+        let session = |id: &str| (temp_dir.path().join(id), id.to_string());
+        assert!(cache.enqueue(vec![session("a"), session("b")]));
+        assert!(!cache.enqueue(vec![session("c"), session("b")]));
+        let order: Vec<String> = std::iter::from_fn(|| cache.next()).map(|(_, id)| id).collect();
+        assert_eq!(order, vec!["c", "b", "a"]);
+        assert!(cache.enqueue(vec![session("d")]));
+    }
+
+    #[test]
+    fn test_project_list_cache_follows_dir_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("-work-app");
+        fs::create_dir(&dir).unwrap();
+        let cache = ProjectListCache::default();
+        let t0 = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100);
+
+        // No sessions yet: the path is decoded from the directory name
+        let summary = cache.summary(&dir, "-work-app", t0);
+        assert!(summary.session_files.is_empty());
+        assert_eq!(summary.project_path, "/work/app");
+        assert_eq!(newest_session_time(&summary.session_files), None);
+
+        fs::write(dir.join("s1.jsonl"), "{\"cwd\":\"/work/my-app\"}\n").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        assert!(cache.summary(&dir, "-work-app", t0).session_files.is_empty());
+
+        let t1 = t0 + std::time::Duration::from_secs(1);
+        let summary = cache.summary(&dir, "-work-app", t1);
+        assert_eq!(summary.session_files.len(), 1);
+        assert_eq!(summary.project_path, "/work/my-app");
+        assert!(newest_session_time(&summary.session_files).is_some());
+    }
+}
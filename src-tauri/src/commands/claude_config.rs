@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::AppHandle;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::claude::{create_system_command, normalize_project_path};
+use super::i18n::{self, ErrorKind};
+use crate::claude_binary::find_claude_binary;
+
+/// How long a `claude config` invocation may take before it is killed
+const CLAUDE_CONFIG_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A key of the claude CLI config and its value
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaudeConfigEntry {
+    pub key: String,
+    pub value: JsonValue,
+}
+
+/// Parse a value printed by `claude config get`: JSON when it parses, else text
+pub fn parse_config_value(output: &str) -> JsonValue {
+    let output = output.trim();
+    if output.is_empty() {
+        return JsonValue::Null;
+    }
+    serde_json::from_str(output).unwrap_or_else(|_| JsonValue::String(output.to_string()))
+}
+
+/// Parse the output of `claude config list`.
+///
+/// Current versions print a JSON object; older ones printed `key: value` lines.
+pub fn parse_config_list(output: &str) -> Vec<ClaudeConfigEntry> {
+    if let Ok(JsonValue::Object(map)) = serde_json::from_str::<JsonValue>(output.trim()) {
+        return map
+            .into_iter()
+            .map(|(key, value)| ClaudeConfigEntry { key, value })
+            .collect();
+    }
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':').or_else(|| line.split_once('='))?;
+            let key = key.trim();
+            (!key.is_empty()).then(|| ClaudeConfigEntry {
+                key: key.to_string(),
+                value: parse_config_value(value),
+            })
+        })
+        .collect()
+}
+
+/// Reject keys the CLI would read as flags
+fn validate_config_key(key: &str) -> Result<(), String> {
+    if key.trim().is_empty() || key.starts_with('-') || key.chars().any(char::is_whitespace) {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("invalid config key '{}'", key),
+        ));
+    }
+    Ok(())
+}
+
+/// Run `claude config <args>` and return its stdout.
+///
+/// Project-scoped keys are resolved against the working directory, so it is
+/// the project when one is given and the home directory otherwise.
+async fn run_claude_config(
+    app: &AppHandle,
+    args: Vec<String>,
+    project_path: Option<String>,
+) -> Result<String, String> {
+    let claude_path = find_claude_binary(app)?;
+    let cwd = match project_path {
+        Some(path) => PathBuf::from(normalize_project_path(&path)?.path),
+        None => dirs::home_dir().ok_or_else(|| i18n::error(ErrorKind::HomeDirNotFound, ""))?,
+    };
+
+    let mut full_args = vec!["config".to_string()];
+    full_args.extend(args);
+    log::info!("Running claude {}", full_args.join(" "));
+    let mut cmd = create_system_command(&claude_path, full_args, &cwd);
+    cmd.stdin(Stdio::null()).kill_on_drop(true);
+
+    let output = tokio::time::timeout(CLAUDE_CONFIG_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| {
+            i18n::error(
+                ErrorKind::ClaudeConfig,
+                format!("no response after {:?}", CLAUDE_CONFIG_TIMEOUT),
+            )
+        })?
+        .map_err(|e| i18n::error(ErrorKind::ProcessSpawn, e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.status.success() {
+        // Unknown keys and bad values: pass the CLI's own message through
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() };
+        return Err(i18n::error(ErrorKind::ClaudeConfig, message));
+    }
+    Ok(stdout)
+}
+
+/// Read the claude CLI config: every key, or just `key`
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_claude_config(
+    app: AppHandle,
+    key: Option<String>,
+    project_path: Option<String>,
+) -> Result<Vec<ClaudeConfigEntry>, String> {
+    match key {
+        Some(key) => {
+            validate_config_key(&key)?;
+            let output =
+                run_claude_config(&app, vec!["get".to_string(), key.clone()], project_path).await?;
+            Ok(vec![ClaudeConfigEntry {
+                key,
+                value: parse_config_value(&output),
+            }])
+        }
+        None => {
+            let output = run_claude_config(&app, vec!["list".to_string()], project_path).await?;
+            Ok(parse_config_list(&output))
+        }
+    }
+}
+
+/// Set a claude CLI config key, globally or for the project, and return its new value
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_claude_config(
+    app: AppHandle,
+    key: String,
+    value: String,
+    global: bool,
+    project_path: Option<String>,
+) -> Result<Vec<ClaudeConfigEntry>, String> {
+    validate_config_key(&key)?;
+    let mut args = vec!["set".to_string()];
+    if global {
+        args.push("--global".to_string());
+    }
+    args.extend([key.clone(), value]);
+    run_claude_config(&app, args, project_path.clone()).await?;
+
+    let mut get_args = vec!["get".to_string()];
+    if global {
+        get_args.push("--global".to_string());
+    }
+    get_args.push(key.clone());
+    let output = run_claude_config(&app, get_args, project_path).await?;
+    Ok(vec![ClaudeConfigEntry {
+        key,
+        value: parse_config_value(&output),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn config_output_is_parsed_as_json_or_lines() {
+        let entries = parse_config_list(r#"{"allowedTools":[],"theme":"dark"}"#);
+        assert_eq!(
+            entries,
+            vec![
+                ClaudeConfigEntry { key: "allowedTools".to_string(), value: json!([]) },
+                ClaudeConfigEntry { key: "theme".to_string(), value: json!("dark") },
+            ]
+        );
+
+        let entries = parse_config_list("autoUpdates: false\ntheme: light\n\n");
+        assert_eq!(entries[0].value, json!(false));
+        assert_eq!(entries[1].value, json!("light"));
+
+        assert_eq!(parse_config_value("  \n"), JsonValue::Null);
+        assert_eq!(parse_config_value("claude-sonnet-4\n"), json!("claude-sonnet-4"));
+        assert!(validate_config_key("--global").is_err());
+    }
+}
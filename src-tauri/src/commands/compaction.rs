@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tauri::AppHandle;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::claude::{create_system_command, prompt_args, ClaudeFinalOutput, ClaudeOutputFormat};
+use super::i18n::{self, ErrorKind};
+use super::project_env;
+
+/// Context window assumed for current Claude models
+const DEFAULT_CONTEXT_WINDOW: u64 = 200_000;
+
+/// Rough characters per token for messages without usage data
+pub const CHARS_PER_TOKEN: u64 = 4;
+
+/// How a context estimate was computed
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateMethod {
+    /// Token usage the API reported for the latest assistant message
+    Usage,
+    /// Message length divided by `CHARS_PER_TOKEN`
+    Characters,
+}
+
+/// Approximate context consumption of a session
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextEstimate {
+    pub session_id: String,
+    pub estimated_tokens: u64,
+    pub context_window: u64,
+    pub percent_used: f64,
+    /// Messages after the last compaction boundary
+    pub messages_since_compaction: usize,
+    /// Compaction boundaries found in the transcript
+    pub compaction_count: usize,
+    pub method: EstimateMethod,
+    /// Always true; the CLI's own count may differ
+    pub is_estimate: bool,
+}
+
+/// Outcome of compacting a session
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionResult {
+    /// Session the compacted conversation lives in (claude may fork a new one)
+    pub session_id: String,
+    pub before: ContextEstimate,
+    /// Estimate after compaction, if the resulting transcript could be read
+    pub after: Option<ContextEstimate>,
+    /// Estimated tokens freed (negative if the context grew)
+    pub tokens_saved: Option<i64>,
+}
+
+/// Whether a transcript line marks the start of a compacted context.
+///
+/// Newer CLIs write a `compact_boundary` system message followed by the
+/// summary; older ones only flag the summary message. (`summary` lines are
+/// session titles, not compactions.)
+fn is_compaction_boundary(msg: &Value) -> bool {
+    match msg.get("type").and_then(|t| t.as_str()) {
+        Some("system") => msg.get("subtype").and_then(|s| s.as_str()) == Some("compact_boundary"),
+        _ => msg.get("isCompactSummary").and_then(|v| v.as_bool()) == Some(true),
+    }
+}
+
+/// Context size the API reported for an assistant message
+fn usage_tokens(msg: &Value) -> Option<u64> {
+    let usage = msg.get("message")?.get("usage")?;
+    let field = |name: &str| usage.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+    let total = field("input_tokens")
+        + field("cache_creation_input_tokens")
+        + field("cache_read_input_tokens")
+        + field("output_tokens");
+    (total > 0).then_some(total)
+}
+
+/// Character-based token guess for a user or assistant message
+fn estimated_message_tokens(msg: &Value) -> u64 {
+    let content = msg.get("message").and_then(|m| m.get("content"));
+    let chars = match content {
+        Some(Value::String(text)) => text.chars().count(),
+        Some(other) => other.to_string().chars().count(),
+        None => 0,
+    };
+    (chars as u64).div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimate how much context a session transcript currently uses.
+///
+/// Only messages after the last compaction boundary count. The usage of
+/// the latest assistant message is the best signal; messages after it (or
+/// all of them, without usage data) are estimated from their length.
+pub fn estimate_context(session_id: &str, content: &str) -> ContextEstimate {
+    let mut compaction_count = 0;
+    let mut messages = 0;
+    let mut last_usage: Option<u64> = None;
+    let mut tokens_after_usage = 0;
+    let mut after_boundary = false;
+
+    for line in content.lines() {
+        let Ok(msg) = serde_json::from_str::<Value>(line.trim_start_matches('\u{feff}')) else {
+            continue;
+        };
+        if is_compaction_boundary(&msg) {
+            // A summary right after its boundary belongs to the same compaction
+            if !after_boundary {
+                compaction_count += 1;
+            }
+            after_boundary = true;
+            messages = 0;
+            last_usage = None;
+            tokens_after_usage = 0;
+            // The summary itself is what the compacted context starts from
+            if msg.get("type").and_then(|t| t.as_str()) != Some("user") {
+                continue;
+            }
+        } else {
+            after_boundary = false;
+        }
+
+        match msg.get("type").and_then(|t| t.as_str()) {
+            Some("assistant") => {
+                messages += 1;
+                match usage_tokens(&msg) {
+                    Some(tokens) => {
+                        last_usage = Some(tokens);
+                        tokens_after_usage = 0;
+                    }
+                    None => tokens_after_usage += estimated_message_tokens(&msg),
+                }
+            }
+            Some("user") => {
+                messages += 1;
+                tokens_after_usage += estimated_message_tokens(&msg);
+            }
+            _ => {}
+        }
+    }
+
+    let (estimated_tokens, method) = match last_usage {
+        Some(tokens) => (tokens + tokens_after_usage, EstimateMethod::Usage),
+        None => (tokens_after_usage, EstimateMethod::Characters),
+    };
+    ContextEstimate {
+        session_id: session_id.to_string(),
+        estimated_tokens,
+        context_window: DEFAULT_CONTEXT_WINDOW,
+        percent_used: estimated_tokens as f64 * 100.0 / DEFAULT_CONTEXT_WINDOW as f64,
+        messages_since_compaction: messages,
+        compaction_count,
+        method,
+        is_estimate: true,
+    }
+}
+
+/// Working directory recorded in a transcript
+fn session_cwd(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let msg: Value = serde_json::from_str(line.trim_start_matches('\u{feff}')).ok()?;
+        msg.get("cwd").and_then(|v| v.as_str()).map(|s| s.to_string())
+    })
+}
+
+fn session_file(project_id: &str, session_id: &str) -> Result<PathBuf, String> {
+    let path = crate::claude_dir::claude_dir()?
+        .join("projects")
+        .join(project_id)
+        .join(format!("{}.jsonl", session_id));
+    if path.exists() {
+        Ok(path)
+    } else {
+        Err(i18n::error(ErrorKind::SessionNotFound, session_id))
+    }
+}
+
+fn read_session(project_id: &str, session_id: &str) -> Result<String, String> {
+    let path = session_file(project_id, session_id)?;
+    std::fs::read_to_string(path).map_err(|e| i18n::error(ErrorKind::FileRead, e))
+}
+
+/// Estimate the context a session currently uses
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn estimate_session_context(
+    project_id: String,
+    session_id: String,
+) -> Result<ContextEstimate, String> {
+    let content = read_session(&project_id, &session_id)?;
+    Ok(estimate_context(&session_id, &content))
+}
+
+/// Compact a session with `/compact` and report how much context it freed
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn compact_session(
+    app: AppHandle,
+    project_id: String,
+    session_id: String,
+    instructions: Option<String>,
+) -> Result<CompactionResult, String> {
+    crate::claude_dir::check_dir_writable()?;
+    let content = read_session(&project_id, &session_id)?;
+    let before = estimate_context(&session_id, &content);
+    let project_path = session_cwd(&content)
+        .ok_or_else(|| i18n::error(ErrorKind::ProjectNotFound, &project_id))?;
+    if !Path::new(&project_path).is_dir() {
+        return Err(i18n::error(ErrorKind::PathNotFound, &project_path));
+    }
+
+    let prompt = match instructions.as_deref().map(str::trim) {
+        Some(instructions) if !instructions.is_empty() => format!("/compact {}", instructions),
+        _ => "/compact".to_string(),
+    };
+    log::info!(
+        "Compacting session {} (~{} tokens)",
+        session_id,
+        before.estimated_tokens
+    );
+
+    let claude_path = crate::claude_binary::find_claude_binary(&app)?;
+    let mut args = vec!["--resume".to_string(), session_id.clone()];
+    args.extend(prompt_args(&prompt, false));
+    args.extend(ClaudeOutputFormat::Json.args());
+    let mut cmd = create_system_command(&claude_path, args, Path::new(&project_path));
+    cmd.envs(project_env::resolve_env(&app, &project_path, None)?);
+    cmd.stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| i18n::error(ErrorKind::ProcessSpawn, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("claude /compact failed: {}", stderr.trim()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = ClaudeFinalOutput::parse(ClaudeOutputFormat::Json, &stdout);
+    let compacted_id = result.session_id.unwrap_or(session_id);
+    let after = match read_session(&project_id, &compacted_id) {
+        Ok(content) => Some(estimate_context(&compacted_id, &content)),
+        Err(e) => {
+            log::warn!("Failed to read compacted session {}: {}", compacted_id, e);
+            None
+        }
+    };
+    let tokens_saved = after
+        .as_ref()
+        .map(|after| before.estimated_tokens as i64 - after.estimated_tokens as i64);
+
+    Ok(CompactionResult {
+        session_id: compacted_id,
+        before,
+        after,
+        tokens_saved,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_starts_at_the_last_compaction_boundary() {
+        let transcript = [
+            r#"{"type":"user","cwd":"/work/app","message":{"role":"user","content":"hello there"}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"hi"}],"usage":{"input_tokens":90000,"output_tokens":500}}}"#,
+            r#"{"type":"system","subtype":"compact_boundary","content":"Conversation compacted"}"#,
+            r#"{"type":"user","isCompactSummary":true,"message":{"role":"user","content":"Summary of the work so far"}}"#,
+            r#"{"type":"assistant","message":{"content":"ok","usage":{"input_tokens":10,"cache_read_input_tokens":12000,"output_tokens":90}}}"#,
+            r#"{"type":"user","message":{"role":"user","content":"12345678"}}"#,
+        ]
+        .join("\n");
+
+        let estimate = estimate_context("s1", &transcript);
+        assert_eq!(estimate.method, EstimateMethod::Usage);
+        assert_eq!(estimate.estimated_tokens, 12_100 + 2);
+        assert_eq!(estimate.compaction_count, 1);
+        assert_eq!(estimate.messages_since_compaction, 3);
+        assert!(estimate.is_estimate);
+        assert_eq!(session_cwd(&transcript).as_deref(), Some("/work/app"));
+
+        // Without usage data only message lengths are left
+        let estimate = estimate_context("s2", r#"{"type":"user","message":{"content":"abcdefgh"}}"#);
+        assert_eq!(estimate.method, EstimateMethod::Characters);
+        assert_eq!(estimate.estimated_tokens, 2);
+    }
+}
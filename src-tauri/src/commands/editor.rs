@@ -0,0 +1,218 @@
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tauri::State;
+use crate::api_schema::api_command;
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::claude::get_project_path_from_sessions;
+use super::i18n::{self, ErrorKind};
+
+/// Setting with the command that opens a file in the user's editor, e.g.
+/// `code -g {path}:{line}:{column}`; unset uses the OS default opener
+pub const EDITOR_COMMAND_KEY: &str = "editor_command";
+
+/// A program invocation that opens a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EditorLaunch {
+    program: String,
+    args: Vec<String>,
+}
+
+/// Split a command template into words. Single and double quotes group
+/// words with spaces; there is no other shell syntax.
+fn split_template(template: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for ch in template.chars() {
+        match (quote, ch) {
+            (Some(q), ch) if ch == q => quote = None,
+            (Some(_), ch) => word.push(ch),
+            (None, '"' | '\'') => {
+                quote = Some(ch);
+                in_word = true;
+            }
+            (None, ch) if ch.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, ch) => {
+                word.push(ch);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(i18n::error(ErrorKind::InvalidInput, format!("unclosed quote in '{}'", template)));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// How to open `path` with the editor command `template`.
+///
+/// Placeholders are filled in after the template is split, so a path with
+/// spaces or quotes stays a single argument and is never seen by a shell. A
+/// template without `{path}` gets the path appended.
+fn editor_launch(template: &str, path: &str, line: Option<u32>, column: Option<u32>) -> Result<EditorLaunch, String> {
+    let mut words = split_template(template)?;
+    if words.is_empty() {
+        return Err(i18n::error(ErrorKind::InvalidInput, "editor command is empty"));
+    }
+    if !words.iter().any(|word| word.contains("{path}")) {
+        words.push("{path}".to_string());
+    }
+    let line = line.unwrap_or(1).to_string();
+    let column = column.unwrap_or(1).to_string();
+    let mut words = words.into_iter().map(|word| {
+        word.replace("{line}", &line)
+            .replace("{column}", &column)
+            .replace("{path}", path)
+    });
+    Ok(EditorLaunch {
+        program: words.next().unwrap_or_default(),
+        args: words.collect(),
+    })
+}
+
+/// How the OS opens `path` with its default application
+fn default_open_launch(os: &str, path: &str) -> EditorLaunch {
+    let program = match os {
+        "macos" => "open",
+        "windows" => "explorer",
+        _ => "xdg-open",
+    };
+    EditorLaunch {
+        program: program.to_string(),
+        args: vec![path.to_string()],
+    }
+}
+
+/// Editor command configured in the settings, if any
+fn load_editor_command(conn: &Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![EDITOR_COMMAND_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .map(|value| value.trim().to_string())
+    .filter(|value| !value.is_empty())
+}
+
+/// Directories files may be opened from: the projects of the claude
+/// directory, and the projects and worktrees agents ran in
+fn known_project_roots(conn: &Connection) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    if let Ok(mut stmt) = conn.prepare(
+        "SELECT project_path FROM agent_runs UNION SELECT worktree_path FROM agent_runs WHERE worktree_path IS NOT NULL",
+    ) {
+        if let Ok(rows) = stmt.query_map([], |row| row.get::<_, String>(0)) {
+            roots.extend(rows.flatten().map(PathBuf::from));
+        }
+    }
+    if let Ok(entries) = crate::claude_dir::projects_dir().and_then(|dir| std::fs::read_dir(dir).map_err(|e| e.to_string())) {
+        for entry in entries.flatten() {
+            if let Ok(project_path) = get_project_path_from_sessions(&entry.path()) {
+                roots.push(PathBuf::from(project_path));
+            }
+        }
+    }
+    roots
+}
+
+/// Whether `path` lies inside one of `roots`, following symlinks on both sides
+fn is_inside_roots(path: &Path, roots: &[PathBuf]) -> bool {
+    roots
+        .iter()
+        .filter(|root| !root.as_os_str().is_empty())
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| path.starts_with(root))
+}
+
+/// Open a file in the user's editor at a line and column, e.g. one mentioned
+/// in a run's output.
+///
+/// Only files inside a known project can be opened. Uses the `editor_command`
+/// setting when set, otherwise the OS default opener. Fails with
+/// `editor_not_found` when the configured editor isn't installed, so it can be
+/// set up again.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn open_in_editor(
+    db: State<'_, AgentDb>,
+    path: String,
+    line: Option<u32>,
+    column: Option<u32>,
+) -> Result<(), String> {
+    let file = Path::new(&path)
+        .canonicalize()
+        .map_err(|e| i18n::error(ErrorKind::PathNotFound, format!("{}: {}", path, e)))?;
+    let (template, roots) = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        (load_editor_command(&conn), known_project_roots(&conn))
+    };
+    if !is_inside_roots(&file, &roots) {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("{} is not inside a known project", file.display()),
+        ));
+    }
+
+    let file = file.to_string_lossy();
+    let launch = match &template {
+        Some(template) => editor_launch(template, &file, line, column)?,
+        None => default_open_launch(std::env::consts::OS, &file),
+    };
+    let spawned = Command::new(&launch.program)
+        .args(&launch.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        Ok(_) => {
+            log::info!("Opened {} with {}", file, launch.program);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound && template.is_some() => {
+            Err(i18n::error(ErrorKind::EditorNotFound, &launch.program))
+        }
+        Err(e) => Err(i18n::error(ErrorKind::ProcessSpawn, format!("{}: {}", launch.program, e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editor_templates_keep_the_path_one_argument() {
+        let launch = editor_launch("code -g {path}:{line}:{column}", "/p/it's a \"file\".rs", Some(12), None).unwrap();
+        assert_eq!(launch.program, "code");
+        assert_eq!(launch.args, ["-g", "/p/it's a \"file\".rs:12:1"]);
+
+        let launch = editor_launch("\"/Applications/My Editor.app/bin/edit\" --line {line}", "/p/$(rm -rf ~).rs", None, None).unwrap();
+        assert_eq!(launch.program, "/Applications/My Editor.app/bin/edit");
+        assert_eq!(launch.args, ["--line", "1", "/p/$(rm -rf ~).rs"]);
+
+        assert!(editor_launch("code 'unclosed", "/p/a.rs", None, None).is_err());
+        assert!(editor_launch("  ", "/p/a.rs", None, None).is_err());
+        assert_eq!(default_open_launch("linux", "/p/a.rs").program, "xdg-open");
+
+        let root = tempfile::tempdir().unwrap();
+        let file = root.path().join("src.rs");
+        std::fs::write(&file, "").unwrap();
+        let file = file.canonicalize().unwrap();
+        assert!(is_inside_roots(&file, &[root.path().to_path_buf()]));
+        assert!(!is_inside_roots(&file, &[root.path().join("other"), PathBuf::new()]));
+    }
+}
@@ -0,0 +1,319 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+
+/// Values accepted by `claude --permission-mode`
+pub const PERMISSION_MODES: &[&str] = &["default", "acceptEdits", "plan", "bypassPermissions"];
+
+/// Arguments the app sets itself, which a profile's extra args may not repeat
+const MANAGED_ARGS: &[&str] = &[
+    "-p",
+    "--print",
+    "-r",
+    "--resume",
+    "-c",
+    "--continue",
+    "--model",
+    "--system-prompt",
+    "--output-format",
+    "--input-format",
+    "--session-id",
+    "--mcp-config",
+    "--permission-mode",
+    "--dangerously-skip-permissions",
+];
+
+/// A named bundle of execution settings, e.g. "safe review" or "yolo build"
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionProfile {
+    pub id: Option<i64>,
+    pub name: String,
+    pub model: Option<String>,
+    /// One of `PERMISSION_MODES`; unset keeps `--dangerously-skip-permissions`
+    pub permission_mode: Option<String>,
+    /// Sandbox profile to run under. This build has no sandbox, so a profile
+    /// with one set can't be saved or applied.
+    pub sandbox_profile_id: Option<i64>,
+    /// Environment variables, applied before per-run overrides
+    pub env: HashMap<String, String>,
+    /// Extra claude CLI arguments, appended after the app's own
+    pub extra_args: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Fields of an execution profile the user can edit
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionProfileInput {
+    pub name: String,
+    pub model: Option<String>,
+    pub permission_mode: Option<String>,
+    pub sandbox_profile_id: Option<i64>,
+    pub env: HashMap<String, String>,
+    pub extra_args: Vec<String>,
+}
+
+impl ExecutionProfile {
+    /// Permission arguments for a run under this profile
+    pub fn permission_args(profile: Option<&Self>) -> Vec<String> {
+        match profile.and_then(|p| p.permission_mode.as_deref()) {
+            Some(mode) if mode != "bypassPermissions" => {
+                vec!["--permission-mode".to_string(), mode.to_string()]
+            }
+            _ => vec!["--dangerously-skip-permissions".to_string()],
+        }
+    }
+
+    /// The profile's env with the explicit per-run overrides on top
+    pub fn merged_env(
+        profile: Option<&Self>,
+        overrides: Option<HashMap<String, String>>,
+    ) -> Option<HashMap<String, String>> {
+        let Some(profile) = profile else {
+            return overrides;
+        };
+        let mut env = profile.env.clone();
+        env.extend(overrides.unwrap_or_default());
+        Some(env)
+    }
+}
+
+const PROFILE_COLUMNS: &str =
+    "id, name, model, permission_mode, sandbox_profile_id, env, extra_args, created_at, updated_at";
+
+fn profile_from_row(row: &rusqlite::Row) -> rusqlite::Result<ExecutionProfile> {
+    let env: String = row.get(5)?;
+    let extra_args: String = row.get(6)?;
+    Ok(ExecutionProfile {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        model: row.get(2)?,
+        permission_mode: row.get(3)?,
+        sandbox_profile_id: row.get(4)?,
+        env: serde_json::from_str(&env).unwrap_or_default(),
+        extra_args: serde_json::from_str(&extra_args).unwrap_or_default(),
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+fn validate_profile(input: &ExecutionProfileInput) -> Result<(), String> {
+    let invalid = |detail: String| Err(i18n::error(ErrorKind::InvalidInput, detail));
+    if input.name.trim().is_empty() {
+        return invalid("profile name is empty".to_string());
+    }
+    if let Some(mode) = &input.permission_mode {
+        if !PERMISSION_MODES.contains(&mode.as_str()) {
+            return invalid(format!(
+                "unknown permission mode '{}', expected one of {}",
+                mode,
+                PERMISSION_MODES.join(", ")
+            ));
+        }
+    }
+    for arg in &input.extra_args {
+        let flag = arg.split('=').next().unwrap_or(arg);
+        if MANAGED_ARGS.contains(&flag) {
+            return invalid(format!("'{}' is set by the app and can't be an extra argument", flag));
+        }
+    }
+    check_no_sandbox(input.sandbox_profile_id)
+}
+
+/// Refuse a sandbox profile rather than run without the sandbox it asks for
+fn check_no_sandbox(sandbox_profile_id: Option<i64>) -> Result<(), String> {
+    match sandbox_profile_id {
+        Some(id) => Err(i18n::error(
+            ErrorKind::Unsupported,
+            format!("sandbox profile {} can't be enforced in this build", id),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// All execution profiles, by name
+pub fn query_execution_profiles(conn: &Connection) -> rusqlite::Result<Vec<ExecutionProfile>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM execution_profiles ORDER BY name",
+        PROFILE_COLUMNS
+    ))?;
+    let profiles = stmt.query_map([], profile_from_row)?;
+    profiles.collect()
+}
+
+/// Load one execution profile
+pub fn query_execution_profile(conn: &Connection, id: i64) -> Result<ExecutionProfile, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM execution_profiles WHERE id = ?1", PROFILE_COLUMNS),
+        params![id],
+        profile_from_row,
+    )
+    .optional()
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?
+    .ok_or_else(|| i18n::error(ErrorKind::InvalidInput, format!("no execution profile {}", id)))
+}
+
+/// Insert a profile, or replace the one with `id`
+pub fn save_execution_profile(
+    conn: &Connection,
+    id: Option<i64>,
+    input: ExecutionProfileInput,
+) -> Result<ExecutionProfile, String> {
+    validate_profile(&input)?;
+    let env = serde_json::to_string(&input.env).map_err(|e| e.to_string())?;
+    let extra_args = serde_json::to_string(&input.extra_args).map_err(|e| e.to_string())?;
+    let db_error = |e: rusqlite::Error| i18n::error(ErrorKind::Database, e);
+
+    let id = match id {
+        Some(id) => {
+            let rows = conn
+                .execute(
+                    "UPDATE execution_profiles SET name = ?1, model = ?2, permission_mode = ?3, sandbox_profile_id = ?4, env = ?5, extra_args = ?6, updated_at = CURRENT_TIMESTAMP WHERE id = ?7",
+                    params![input.name, input.model, input.permission_mode, input.sandbox_profile_id, env, extra_args, id],
+                )
+                .map_err(db_error)?;
+            if rows == 0 {
+                return Err(i18n::error(
+                    ErrorKind::InvalidInput,
+                    format!("no execution profile {}", id),
+                ));
+            }
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO execution_profiles (name, model, permission_mode, sandbox_profile_id, env, extra_args) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![input.name, input.model, input.permission_mode, input.sandbox_profile_id, env, extra_args],
+            )
+            .map_err(db_error)?;
+            conn.last_insert_rowid()
+        }
+    };
+    query_execution_profile(conn, id)
+}
+
+/// Load the profile a run asked for, if any
+pub fn resolve_execution_profile(
+    app: &AppHandle,
+    id: Option<i64>,
+) -> Result<Option<ExecutionProfile>, String> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let db = app.state::<AgentDb>();
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let profile = query_execution_profile(&conn, id)?;
+    check_no_sandbox(profile.sandbox_profile_id)?;
+    Ok(Some(profile))
+}
+
+/// List execution profiles
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn list_execution_profiles(
+    db: State<'_, AgentDb>,
+) -> Result<Vec<ExecutionProfile>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    query_execution_profiles(&conn).map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Create an execution profile
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn create_execution_profile(
+    db: State<'_, AgentDb>,
+    profile: ExecutionProfileInput,
+) -> Result<ExecutionProfile, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    save_execution_profile(&conn, None, profile)
+}
+
+/// Replace the settings of an execution profile
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn update_execution_profile(
+    db: State<'_, AgentDb>,
+    id: i64,
+    profile: ExecutionProfileInput,
+) -> Result<ExecutionProfile, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    save_execution_profile(&conn, Some(id), profile)
+}
+
+/// Delete an execution profile; runs keep the ID of the profile they used
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn delete_execution_profile(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    conn.execute("DELETE FROM execution_profiles WHERE id = ?1", params![id])
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_schema;
+
+    #[test]
+    fn profiles_round_trip_and_expand_into_settings() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+
+        let input = ExecutionProfileInput {
+            name: "safe review".to_string(),
+            model: Some("opus".to_string()),
+            permission_mode: Some("plan".to_string()),
+            env: HashMap::from([("LOG".to_string(), "debug".to_string())]),
+            extra_args: vec!["--verbose".to_string()],
+            ..Default::default()
+        };
+        let profile = save_execution_profile(&conn, None, input.clone()).unwrap();
+        assert_eq!(query_execution_profiles(&conn).unwrap().len(), 1);
+        assert_eq!(profile.extra_args, vec!["--verbose"]);
+        assert_eq!(
+            ExecutionProfile::permission_args(Some(&profile)),
+            vec!["--permission-mode", "plan"]
+        );
+        assert_eq!(
+            ExecutionProfile::permission_args(None),
+            vec!["--dangerously-skip-permissions"]
+        );
+
+        // Explicit overrides win over the profile's env
+        let env = ExecutionProfile::merged_env(
+            Some(&profile),
+            Some(HashMap::from([("LOG".to_string(), "info".to_string())])),
+        )
+        .unwrap();
+        assert_eq!(env["LOG"], "info");
+
+        let bad_mode = ExecutionProfileInput {
+            permission_mode: Some("yolo".to_string()),
+            ..input.clone()
+        };
+        assert!(save_execution_profile(&conn, profile.id, bad_mode).is_err());
+        let managed_arg = ExecutionProfileInput {
+            extra_args: vec!["--model=haiku".to_string()],
+            ..input
+        };
+        assert!(save_execution_profile(&conn, profile.id, managed_arg.clone()).is_err());
+        let sandboxed = ExecutionProfileInput {
+            sandbox_profile_id: Some(1),
+            extra_args: Vec::new(),
+            ..managed_arg
+        };
+        let err = save_execution_profile(&conn, profile.id, sandboxed).unwrap_err();
+        assert!(err.starts_with("[unsupported]"), "{}", err);
+        assert_eq!(query_execution_profile(&conn, profile.id.unwrap()).unwrap().sandbox_profile_id, None);
+    }
+}
@@ -0,0 +1,284 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use crate::commands::agents::AgentDb;
+
+/// app_settings key holding the backend locale
+pub const LOCALE_SETTING_KEY: &str = "backend_locale";
+
+/// Locale used for user-facing backend messages
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Locale {
+    #[default]
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+}
+
+impl Locale {
+    /// Parse a locale tag such as "en", "en-US", "zh-CN" or "zh"
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        match tag.as_str() {
+            "en" | "en-us" | "en-gb" => Some(Self::En),
+            "zh" | "zh-cn" | "zh-hans" | "zh-hans-cn" => Some(Self::ZhCn),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::ZhCn => "zh-CN",
+        }
+    }
+}
+
+static CURRENT_LOCALE: AtomicU8 = AtomicU8::new(0);
+
+/// Locale currently used for backend messages
+pub fn current_locale() -> Locale {
+    match CURRENT_LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::ZhCn,
+        _ => Locale::En,
+    }
+}
+
+/// Switch the locale used for backend messages
+pub fn set_locale(locale: Locale) {
+    let value = match locale {
+        Locale::En => 0,
+        Locale::ZhCn => 1,
+    };
+    CURRENT_LOCALE.store(value, Ordering::Relaxed);
+}
+
+/// Load the saved locale from the settings table, if any
+pub fn load_saved_locale(conn: &Connection) {
+    if let Ok(tag) = conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![LOCALE_SETTING_KEY],
+        |row| row.get::<_, String>(0),
+    ) {
+        if let Some(locale) = Locale::parse(&tag) {
+            set_locale(locale);
+        }
+    }
+}
+
+/// Machine-readable kind of a backend error.
+///
+/// The code never changes with the locale, so the frontend can match on it
+/// instead of on the message text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    HomeDirNotFound,
+    ProjectsDirNotFound,
+    ProjectNotFound,
+    SessionNotFound,
+    SessionInOtherProject,
+    AgentNotFound,
+    RunNotFound,
+    ClaudeNotFound,
+    ClaudeNotInstalled,
+    Database,
+    FileRead,
+    FileWrite,
+    ProcessSpawn,
+    ProcessNotFound,
+    InvalidInput,
+    PathNotFound,
+    NotADirectory,
+    Network,
+    ClaudeConfig,
+    FileBusy,
+    ClaudeDirReadonly,
+    FileTooLarge,
+    Unsupported,
+    Cancelled,
+    ReadOnlyMode,
+    EditorNotFound,
+    NotReady,
+    RateLimited,
+}
+
+impl ErrorKind {
+    /// Stable identifier of the error kind
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::HomeDirNotFound => "home_dir_not_found",
+            Self::ProjectsDirNotFound => "projects_dir_not_found",
+            Self::ProjectNotFound => "project_not_found",
+            Self::SessionNotFound => "session_not_found",
+            Self::SessionInOtherProject => "session_in_other_project",
+            Self::AgentNotFound => "agent_not_found",
+            Self::RunNotFound => "run_not_found",
+            Self::ClaudeNotFound => "claude_not_found",
+            Self::ClaudeNotInstalled => "claude_not_installed",
+            Self::Database => "database",
+            Self::FileRead => "file_read",
+            Self::FileWrite => "file_write",
+            Self::ProcessSpawn => "process_spawn",
+            Self::ProcessNotFound => "process_not_found",
+            Self::InvalidInput => "invalid_input",
+            Self::PathNotFound => "path_not_found",
+            Self::NotADirectory => "not_a_directory",
+            Self::Network => "network",
+            Self::ClaudeConfig => "claude_config",
+            Self::FileBusy => "file_busy",
+            Self::ClaudeDirReadonly => "claude_dir_readonly",
+            Self::FileTooLarge => "file_too_large",
+            Self::Unsupported => "unsupported",
+            Self::Cancelled => "cancelled",
+            Self::ReadOnlyMode => "read_only_mode",
+            Self::EditorNotFound => "editor_not_found",
+            Self::NotReady => "not_ready",
+            Self::RateLimited => "rate_limited",
+        }
+    }
+
+    /// User-facing message for this kind in the given locale
+    pub fn message(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Self::HomeDirNotFound, Locale::En) => "Failed to get home directory",
+            (Self::HomeDirNotFound, Locale::ZhCn) => "无法获取用户主目录",
+            (Self::ProjectsDirNotFound, Locale::En) => "Projects directory not found",
+            (Self::ProjectsDirNotFound, Locale::ZhCn) => "未找到项目目录",
+            (Self::ProjectNotFound, Locale::En) => "Project not found",
+            (Self::ProjectNotFound, Locale::ZhCn) => "未找到项目",
+            (Self::SessionNotFound, Locale::En) => "Session file not found",
+            (Self::SessionNotFound, Locale::ZhCn) => "未找到会话文件",
+            (Self::SessionInOtherProject, Locale::En) => "Session belongs to another project",
+            (Self::SessionInOtherProject, Locale::ZhCn) => "会话属于其他项目",
+            (Self::AgentNotFound, Locale::En) => "Agent not found",
+            (Self::AgentNotFound, Locale::ZhCn) => "未找到智能体",
+            (Self::RunNotFound, Locale::En) => "Agent run not found",
+            (Self::RunNotFound, Locale::ZhCn) => "未找到智能体运行记录",
+            (Self::ClaudeNotFound, Locale::En) => "Claude Code binary not found",
+            (Self::ClaudeNotFound, Locale::ZhCn) => "未找到 Claude Code 可执行文件",
+            (Self::ClaudeNotInstalled, Locale::En) => "Claude Code is not installed",
+            (Self::ClaudeNotInstalled, Locale::ZhCn) => "未安装 Claude Code",
+            (Self::Database, Locale::En) => "Database error",
+            (Self::Database, Locale::ZhCn) => "数据库错误",
+            (Self::FileRead, Locale::En) => "Failed to read file",
+            (Self::FileRead, Locale::ZhCn) => "读取文件失败",
+            (Self::FileWrite, Locale::En) => "Failed to write file",
+            (Self::FileWrite, Locale::ZhCn) => "写入文件失败",
+            (Self::ProcessSpawn, Locale::En) => "Failed to start process",
+            (Self::ProcessSpawn, Locale::ZhCn) => "启动进程失败",
+            (Self::ProcessNotFound, Locale::En) => "Process not found",
+            (Self::ProcessNotFound, Locale::ZhCn) => "未找到进程",
+            (Self::InvalidInput, Locale::En) => "Invalid input",
+            (Self::InvalidInput, Locale::ZhCn) => "输入无效",
+            (Self::PathNotFound, Locale::En) => "Path does not exist",
+            (Self::PathNotFound, Locale::ZhCn) => "路径不存在",
+            (Self::NotADirectory, Locale::En) => "Path is not a directory",
+            (Self::NotADirectory, Locale::ZhCn) => "路径不是目录",
+            (Self::Network, Locale::En) => "Network request failed",
+            (Self::Network, Locale::ZhCn) => "网络请求失败",
+            (Self::ClaudeConfig, Locale::En) => "Claude config command failed",
+            (Self::ClaudeConfig, Locale::ZhCn) => "Claude 配置命令失败",
+            (Self::FileBusy, Locale::En) => "File is being written by another window or process",
+            (Self::FileBusy, Locale::ZhCn) => "文件正被其他窗口或进程写入",
+            (Self::ClaudeDirReadonly, Locale::En) => "Claude directory is read-only",
+            (Self::ClaudeDirReadonly, Locale::ZhCn) => "Claude 目录为只读",
+            (Self::FileTooLarge, Locale::En) => "File is larger than the allowed size",
+            (Self::FileTooLarge, Locale::ZhCn) => "文件超过允许的大小",
+            (Self::Unsupported, Locale::En) => "Not supported on this platform",
+            (Self::Unsupported, Locale::ZhCn) => "此平台不支持该操作",
+            (Self::Cancelled, Locale::En) => "Operation was cancelled",
+            (Self::Cancelled, Locale::ZhCn) => "操作已取消",
+            (Self::ReadOnlyMode, Locale::En) => "The Claude directory is open read-only",
+            (Self::ReadOnlyMode, Locale::ZhCn) => "Claude 目录以只读模式打开",
+            (Self::EditorNotFound, Locale::En) => "Editor command not found",
+            (Self::EditorNotFound, Locale::ZhCn) => "未找到编辑器命令",
+            (Self::NotReady, Locale::En) => "The app is still starting, try again shortly",
+            (Self::NotReady, Locale::ZhCn) => "应用仍在启动中，请稍后重试",
+            (Self::RateLimited, Locale::En) => "Rate limit reached",
+            (Self::RateLimited, Locale::ZhCn) => "已达到请求频率限制",
+        }
+    }
+}
+
+/// Build a localized command error: `[code] message: detail`
+pub fn error(kind: ErrorKind, detail: impl Display) -> String {
+    let detail = detail.to_string();
+    let message = kind.message(current_locale());
+    if detail.is_empty() {
+        format!("[{}] {}", kind.code(), message)
+    } else {
+        format!("[{}] {}: {}", kind.code(), message, detail)
+    }
+}
+
+/// Error for looking up a single row: a missing row is `not_found`, any
+/// other failure is a database error
+pub fn lookup_error(not_found: ErrorKind, e: rusqlite::Error) -> String {
+    match e {
+        rusqlite::Error::QueryReturnedNoRows => error(not_found, e),
+        e => error(ErrorKind::Database, e),
+    }
+}
+
+/// Get the locale used for backend messages
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_backend_locale() -> Result<Locale, String> {
+    Ok(current_locale())
+}
+
+/// Set the locale used for backend messages ("en" or "zh-CN") and persist it
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_backend_locale(db: State<'_, AgentDb>, locale: String) -> Result<Locale, String> {
+    let parsed = Locale::parse(&locale)
+        .ok_or_else(|| error(ErrorKind::InvalidInput, format!("unsupported locale '{}'", locale)))?;
+
+    let conn = db.0.lock().map_err(|e| error(ErrorKind::Database, e))?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![LOCALE_SETTING_KEY, parsed.tag()],
+    )
+    .map_err(|e| error(ErrorKind::Database, e))?;
+
+    set_locale(parsed);
+    log::info!("Backend locale set to {}", parsed.tag());
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_tags_parse() {
+        assert_eq!(Locale::parse("zh_CN"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("EN-us"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+    }
+
+    #[test]
+    fn only_missing_rows_are_not_found() {
+        let missing = lookup_error(ErrorKind::AgentNotFound, rusqlite::Error::QueryReturnedNoRows);
+        assert!(missing.starts_with("[agent_not_found] "));
+        let broken = lookup_error(ErrorKind::AgentNotFound, rusqlite::Error::InvalidQuery);
+        assert!(broken.starts_with("[database] "));
+    }
+
+    #[test]
+    fn error_keeps_code_across_locales() {
+        let kind = ErrorKind::SessionNotFound;
+        assert_eq!(kind.message(Locale::En), "Session file not found");
+        assert_eq!(kind.message(Locale::ZhCn), "未找到会话文件");
+        assert!(error(kind, "abc").starts_with("[session_not_found] "));
+    }
+}
@@ -0,0 +1,387 @@
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+
+/// Event carrying a job whenever it starts, progresses or finishes
+pub const JOB_UPDATED_EVENT: &str = "job-updated";
+
+/// Finished jobs kept in the table; older ones are removed as new jobs start
+const JOB_HISTORY_LIMIT: i64 = 200;
+
+/// Jobs returned by `list_jobs` when no limit is given
+const DEFAULT_JOB_LIST_LIMIT: usize = 50;
+
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    /// Stopped by `cancel_job`; the result holds what was done before
+    Cancelled,
+    /// Still running when the app was closed
+    Interrupted,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+            Self::Interrupted => "interrupted",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "running" => Self::Running,
+            "completed" => Self::Completed,
+            "cancelled" => Self::Cancelled,
+            "interrupted" => Self::Interrupted,
+            _ => Self::Failed,
+        }
+    }
+}
+
+/// A long-running operation and its outcome, kept across window reloads
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: i64,
+    /// Command that runs the job, e.g. "export_workspace"
+    pub kind: String,
+    /// Arguments the job was started with
+    pub params: JsonValue,
+    pub status: JobStatus,
+    /// Share of the work done, from 0 to 1
+    pub progress: f64,
+    pub progress_message: Option<String>,
+    /// What the command returned
+    pub result: Option<JsonValue>,
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// Cancel flags of the jobs running in this instance
+#[derive(Default)]
+pub struct JobState(Mutex<HashMap<i64, Arc<AtomicBool>>>);
+
+const JOB_COLUMNS: &str =
+    "id, kind, params, status, progress, progress_message, result, error, started_at, finished_at";
+
+fn row_to_job(row: &rusqlite::Row) -> rusqlite::Result<Job> {
+    let json = |text: Option<String>| text.and_then(|t| serde_json::from_str(&t).ok());
+    Ok(Job {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        params: json(row.get(2)?).unwrap_or(JsonValue::Null),
+        status: JobStatus::parse(&row.get::<_, String>(3)?),
+        progress: row.get(4)?,
+        progress_message: row.get(5)?,
+        result: json(row.get(6)?),
+        error: row.get(7)?,
+        started_at: row.get(8)?,
+        finished_at: row.get(9)?,
+    })
+}
+
+/// Record a started job, dropping the oldest finished ones past the history limit
+pub fn insert_job(conn: &Connection, kind: &str, params: &JsonValue) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO jobs (kind, params) VALUES (?1, ?2)",
+        params![kind, params.to_string()],
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.execute(
+        "DELETE FROM jobs WHERE finished_at IS NOT NULL AND id NOT IN
+             (SELECT id FROM jobs WHERE finished_at IS NOT NULL ORDER BY id DESC LIMIT ?1)",
+        params![JOB_HISTORY_LIMIT],
+    )?;
+    Ok(id)
+}
+
+pub fn update_job_progress(
+    conn: &Connection,
+    id: i64,
+    progress: f64,
+    message: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE jobs SET progress = ?1, progress_message = ?2 WHERE id = ?3 AND status = 'running'",
+        params![progress.clamp(0.0, 1.0), message, id],
+    )?;
+    Ok(())
+}
+
+/// Store the outcome of a job
+pub fn finish_job(
+    conn: &Connection,
+    id: i64,
+    status: JobStatus,
+    result: Option<&JsonValue>,
+    error: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE jobs SET status = ?1, result = ?2, error = ?3, finished_at = CURRENT_TIMESTAMP,
+                progress = CASE WHEN ?1 = 'completed' THEN 1 ELSE progress END
+         WHERE id = ?4",
+        params![status.as_str(), result.map(|r| r.to_string()), error, id],
+    )?;
+    Ok(())
+}
+
+pub fn query_job(conn: &Connection, id: i64) -> rusqlite::Result<Option<Job>> {
+    conn.query_row(
+        &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+        params![id],
+        row_to_job,
+    )
+    .optional()
+}
+
+/// Most recent jobs first
+pub fn query_jobs(conn: &Connection, limit: usize) -> rusqlite::Result<Vec<Job>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM jobs ORDER BY id DESC LIMIT ?1",
+        JOB_COLUMNS
+    ))?;
+    let jobs = stmt.query_map(params![limit as i64], row_to_job)?.collect();
+    jobs
+}
+
+/// Mark jobs a previous instance left running as interrupted
+pub fn interrupt_unfinished_jobs(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE jobs SET status = 'interrupted', finished_at = CURRENT_TIMESTAMP WHERE status = 'running'",
+        [],
+    )
+}
+
+/// Status a job ends with, given its outcome and whether the work stopped
+/// at a cancellation check. Work that ran to the end despite a cancel
+/// request completed.
+fn final_status(succeeded: bool, stopped: bool) -> JobStatus {
+    match (succeeded, stopped) {
+        (_, true) => JobStatus::Cancelled,
+        (true, false) => JobStatus::Completed,
+        (false, false) => JobStatus::Failed,
+    }
+}
+
+/// A running job, handed to the operation so it can report progress and
+/// notice cancellation
+#[derive(Clone)]
+pub struct JobHandle {
+    app: AppHandle,
+    id: i64,
+    cancelled: Arc<AtomicBool>,
+    /// Set once the work stopped at `check_cancelled` or `should_stop`
+    stopped: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    fn start(app: &AppHandle, kind: &str, params: &JsonValue) -> Result<Self, String> {
+        let id = {
+            let db = app.state::<AgentDb>();
+            let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+            insert_job(&conn, kind, params).map_err(|e| i18n::error(ErrorKind::Database, e))?
+        };
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Ok(mut flags) = app.state::<JobState>().0.lock() {
+            flags.insert(id, cancelled.clone());
+        }
+        let job = Self {
+            app: app.clone(),
+            id,
+            cancelled,
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+        job.emit();
+        Ok(job)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Whether to stop early because `cancel_job` was called; for operations
+    /// that return what they did so far. The job then ends as cancelled.
+    pub fn should_stop(&self) -> bool {
+        let stop = self.is_cancelled();
+        if stop {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        stop
+    }
+
+    /// Fail with a cancelled error once `cancel_job` was called; for
+    /// operations that cannot keep what they did so far
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            self.stopped.store(true, Ordering::Relaxed);
+            Err(i18n::error(ErrorKind::Cancelled, format!("job {}", self.id)))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Record that `done` of `total` steps are finished.
+    ///
+    /// Must not be called while holding the database lock.
+    pub fn progress(&self, done: usize, total: usize, message: &str) {
+        let progress = if total == 0 { 0.0 } else { done as f64 / total as f64 };
+        let db = self.app.state::<AgentDb>();
+        let updated = match db.0.lock() {
+            Ok(conn) => update_job_progress(&conn, self.id, progress, Some(message)).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match updated {
+            Ok(()) => self.emit(),
+            Err(e) => warn!("Failed to record progress of job {}: {}", self.id, e),
+        }
+    }
+
+    fn finish<T: Serialize>(&self, outcome: &Result<T, String>) {
+        if let Ok(mut flags) = self.app.state::<JobState>().0.lock() {
+            flags.remove(&self.id);
+        }
+        let status = final_status(outcome.is_ok(), self.stopped.load(Ordering::Relaxed));
+        let (result, error) = match outcome {
+            Ok(value) => (serde_json::to_value(value).ok(), None),
+            Err(e) => (None, Some(e.as_str())),
+        };
+        let db = self.app.state::<AgentDb>();
+        let finished = match db.0.lock() {
+            Ok(conn) => finish_job(&conn, self.id, status, result.as_ref(), error).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match finished {
+            Ok(()) => self.emit(),
+            Err(e) => warn!("Failed to record the outcome of job {}: {}", self.id, e),
+        }
+    }
+
+    fn emit(&self) {
+        let db = self.app.state::<AgentDb>();
+        let job = match db.0.lock() {
+            Ok(conn) => query_job(&conn, self.id).ok().flatten(),
+            Err(_) => None,
+        };
+        if let Some(job) = job {
+            let _ = self.app.emit(JOB_UPDATED_EVENT, &job);
+        }
+    }
+}
+
+/// Run `work` as a job: it is recorded before it starts, its progress and
+/// outcome are stored and emitted, and its result is returned as usual.
+pub async fn run_as_job<T, Fut>(
+    app: &AppHandle,
+    kind: &str,
+    params: JsonValue,
+    work: impl FnOnce(JobHandle) -> Fut,
+) -> Result<T, String>
+where
+    T: Serialize,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let job = JobHandle::start(app, kind, &params)?;
+    let outcome = work(job.clone()).await;
+    job.finish(&outcome);
+    outcome
+}
+
+/// Recent jobs, newest first
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn list_jobs(db: State<'_, AgentDb>, limit: Option<usize>) -> Result<Vec<Job>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    query_jobs(&conn, limit.unwrap_or(DEFAULT_JOB_LIST_LIMIT))
+        .map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_job(db: State<'_, AgentDb>, id: i64) -> Result<Job, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    query_job(&conn, id)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?
+        .ok_or_else(|| i18n::error(ErrorKind::InvalidInput, format!("no job {}", id)))
+}
+
+/// Ask a running job to stop at its next safe point; false if it is not running
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn cancel_job(jobs: State<'_, JobState>, id: i64) -> Result<bool, String> {
+    let flags = jobs.0.lock().map_err(|e| e.to_string())?;
+    Ok(match flags.get(&id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_schema;
+    use serde_json::json;
+
+    #[test]
+    fn jobs_record_progress_and_outcome_and_survive_restarts() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+
+        let id = insert_job(&conn, "export_workspace", &json!({"file_path": "/tmp/w.json"})).unwrap();
+        update_job_progress(&conn, id, 0.5, Some("agents")).unwrap();
+        let job = query_job(&conn, id).unwrap().unwrap();
+        assert_eq!((job.status, job.progress), (JobStatus::Running, 0.5));
+        assert_eq!(job.params["file_path"], "/tmp/w.json");
+
+        finish_job(&conn, id, final_status(true, false), Some(&json!([{"section": "agents"}])), None).unwrap();
+        let job = query_job(&conn, id).unwrap().unwrap();
+        assert_eq!((job.status, job.progress), (JobStatus::Completed, 1.0));
+        assert_eq!(job.result.unwrap()[0]["section"], "agents");
+        assert!(job.finished_at.is_some());
+
+        // Progress of a finished job is left alone
+        update_job_progress(&conn, id, 0.1, None).unwrap();
+        assert_eq!(query_job(&conn, id).unwrap().unwrap().progress, 1.0);
+
+        // Only work that stopped early counts as cancelled; a cancel request
+        // that came too late leaves the job completed
+        assert_eq!(final_status(true, false), JobStatus::Completed);
+        assert_eq!(final_status(true, true), JobStatus::Cancelled);
+        assert_eq!(final_status(false, true), JobStatus::Cancelled);
+        assert_eq!(final_status(false, false), JobStatus::Failed);
+
+        let running = insert_job(&conn, "merge_projects", &json!({})).unwrap();
+        assert_eq!(interrupt_unfinished_jobs(&conn).unwrap(), 1);
+        assert_eq!(query_job(&conn, running).unwrap().unwrap().status, JobStatus::Interrupted);
+        assert_eq!(query_jobs(&conn, 10).unwrap().iter().map(|j| j.id).collect::<Vec<_>>(), [running, id]);
+
+        for _ in 0..JOB_HISTORY_LIMIT + 5 {
+            let id = insert_job(&conn, "purge", &json!({})).unwrap();
+            finish_job(&conn, id, JobStatus::Completed, None, None).unwrap();
+        }
+        let kept: i64 = conn.query_row("SELECT COUNT(*) FROM jobs", [], |row| row.get(0)).unwrap();
+        assert_eq!(kept, JOB_HISTORY_LIMIT + 1);
+    }
+}
@@ -2,11 +2,21 @@ use anyhow::{Context, Result};
 use dirs;
 use log::{error, info};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use rusqlite::{params, Connection};
 use std::process::Command;
-use tauri::AppHandle;
+use tauri::{AppHandle, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+use crate::checkpoint::storage::write_atomically;
+use crate::file_lock::{update_locked, write_error, write_locked, FileLock, LOCK_WAIT};
 
 /// Helper function to create a std::process::Command with proper environment variables
 /// This ensures commands like Claude can find Node.js and other dependencies
@@ -21,6 +31,7 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String> {
 }
 
 /// Represents an MCP server configuration
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPServer {
     /// Server name/identifier
@@ -44,6 +55,7 @@ pub struct MCPServer {
 }
 
 /// Server status information
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerStatus {
     /// Whether the server is running
@@ -55,6 +67,7 @@ pub struct ServerStatus {
 }
 
 /// MCP configuration for project scope (.mcp.json)
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPProjectConfig {
     #[serde(rename = "mcpServers")]
@@ -62,6 +75,7 @@ pub struct MCPProjectConfig {
 }
 
 /// Individual server configuration in .mcp.json
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPServerConfig {
     pub command: String,
@@ -72,6 +86,7 @@ pub struct MCPServerConfig {
 }
 
 /// Result of adding a server
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddServerResult {
     pub success: bool,
@@ -80,19 +95,27 @@ pub struct AddServerResult {
 }
 
 /// Import result for multiple servers
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportResult {
     pub imported_count: u32,
     pub failed_count: u32,
+    /// Servers left alone because the name was already configured
+    #[serde(default)]
+    pub skipped_count: u32,
     pub servers: Vec<ImportServerResult>,
 }
 
 /// Result for individual server import
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportServerResult {
     pub name: String,
     pub success: bool,
     pub error: Option<String>,
+    /// New name when the server was renamed to avoid a conflict
+    #[serde(default)]
+    pub imported_as: Option<String>,
 }
 
 /// Executes a claude mcp command
@@ -117,6 +140,7 @@ fn execute_claude_mcp_command(app_handle: &AppHandle, args: Vec<&str>) -> Result
 }
 
 /// Adds a new MCP server
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_add(
     app: AppHandle,
@@ -209,6 +233,7 @@ pub async fn mcp_add(
 }
 
 /// Lists all configured MCP servers
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
     info!("Listing MCP servers");
@@ -331,6 +356,7 @@ pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
 }
 
 /// Gets details for a specific MCP server
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_get(app: AppHandle, name: String) -> Result<MCPServer, String> {
     info!("Getting MCP server details for: {}", name);
@@ -400,6 +426,7 @@ pub async fn mcp_get(app: AppHandle, name: String) -> Result<MCPServer, String>
 }
 
 /// Removes an MCP server
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_remove(app: AppHandle, name: String) -> Result<String, String> {
     info!("Removing MCP server: {}", name);
@@ -417,6 +444,7 @@ pub async fn mcp_remove(app: AppHandle, name: String) -> Result<String, String>
 }
 
 /// Adds an MCP server from JSON configuration
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_add_json(
     app: AppHandle,
@@ -457,18 +485,44 @@ pub async fn mcp_add_json(
     }
 }
 
-/// Imports MCP servers from Claude Desktop
-#[tauri::command]
-pub async fn mcp_add_from_claude_desktop(
-    app: AppHandle,
-    scope: String,
-) -> Result<ImportResult, String> {
-    info!(
-        "Importing MCP servers from Claude Desktop with scope: {}",
-        scope
-    );
+/// How to handle Claude Desktop servers whose name is already configured
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportConflictStrategy {
+    /// Leave the existing server alone
+    #[default]
+    Skip,
+    /// Replace the existing server
+    Overwrite,
+    /// Import under a new, unused name
+    Rename,
+}
 
-    // Get Claude Desktop config path based on platform
+/// A server found in the Claude Desktop config
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesktopImportCandidate {
+    pub name: String,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    /// Whether a server with this name is already configured
+    pub conflict: bool,
+    /// Why the server cannot be imported, if it can't
+    pub error: Option<String>,
+}
+
+/// Servers that an import from Claude Desktop would bring in
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesktopImportPreview {
+    pub config_path: String,
+    pub servers: Vec<DesktopImportCandidate>,
+}
+
+/// Gets the Claude Desktop config path for this platform
+fn claude_desktop_config_path() -> Result<PathBuf, String> {
     let config_path = if cfg!(target_os = "macos") {
         dirs::home_dir()
             .ok_or_else(|| "Could not find home directory".to_string())?
@@ -496,6 +550,14 @@ pub async fn mcp_add_from_claude_desktop(
         );
     }
 
+    Ok(config_path)
+}
+
+/// Reads the MCP servers defined in the Claude Desktop config
+fn read_claude_desktop_servers(
+) -> Result<(PathBuf, serde_json::Map<String, serde_json::Value>), String> {
+    let config_path = claude_desktop_config_path()?;
+
     // Read and parse the config file
     let config_content = fs::read_to_string(&config_path)
         .map_err(|e| format!("Failed to read Claude Desktop config: {}", e))?;
@@ -509,108 +571,380 @@ pub async fn mcp_add_from_claude_desktop(
         .and_then(|v| v.as_object())
         .ok_or_else(|| "No MCP servers found in Claude Desktop config".to_string())?;
 
-    let mut imported_count = 0;
-    let mut failed_count = 0;
-    let mut server_results = Vec::new();
+    Ok((config_path, mcp_servers.clone()))
+}
 
-    // Import each server using add-json
-    for (name, server_config) in mcp_servers {
-        info!("Importing server: {}", name);
+/// Converts a Claude Desktop server entry to the add-json format
+fn desktop_server_to_json(
+    server_config: &serde_json::Value,
+) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let mut json_config = serde_json::Map::new();
 
-        // Convert Claude Desktop format to add-json format
-        let mut json_config = serde_json::Map::new();
+    // All Claude Desktop servers are stdio type
+    json_config.insert(
+        "type".to_string(),
+        serde_json::Value::String("stdio".to_string()),
+    );
 
-        // All Claude Desktop servers are stdio type
-        json_config.insert(
-            "type".to_string(),
-            serde_json::Value::String("stdio".to_string()),
-        );
+    let command = server_config
+        .get("command")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Missing command field".to_string())?;
+    json_config.insert(
+        "command".to_string(),
+        serde_json::Value::String(command.to_string()),
+    );
 
-        // Add command
-        if let Some(command) = server_config.get("command").and_then(|v| v.as_str()) {
-            json_config.insert(
-                "command".to_string(),
-                serde_json::Value::String(command.to_string()),
-            );
-        } else {
-            failed_count += 1;
-            server_results.push(ImportServerResult {
+    let args = server_config
+        .get("args")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    json_config.insert("args".to_string(), serde_json::Value::Array(args));
+
+    let env = server_config
+        .get("env")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+    json_config.insert("env".to_string(), serde_json::Value::Object(env));
+
+    Ok(json_config)
+}
+
+/// Picks `<name>-<suffix>`, `<name>-<suffix>-2`, ... that is not taken yet
+fn unused_server_name(name: &str, suffix: &str, taken: &HashSet<String>) -> String {
+    let base = format!("{}-{}", name, suffix);
+    if !taken.contains(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !taken.contains(candidate))
+        .expect("unbounded range always yields a free name")
+}
+
+/// Project directory and `~/.claude.json` the claude CLI reads when run from here
+fn cli_config_paths() -> (PathBuf, Option<PathBuf>) {
+    (
+        std::env::current_dir().unwrap_or_default(),
+        dirs::home_dir().map(|home| home.join(".claude.json")),
+    )
+}
+
+/// Names of the MCP servers that are already configured
+///
+/// Reads claude's config files directly and adds what `claude mcp list`
+/// reports; a failing CLI only costs the servers it alone knows about.
+async fn existing_server_names(app: &AppHandle) -> HashSet<String> {
+    let (project_path, claude_json) = cli_config_paths();
+    let mut names: HashSet<String> =
+        match configured_mcp_servers(&project_path, claude_json.as_deref()) {
+            Ok(servers) => servers.into_keys().collect(),
+            Err(e) => {
+                log::warn!("Failed to read configured MCP servers: {}", e);
+                HashSet::new()
+            }
+        };
+    match mcp_list(app.clone()).await {
+        Ok(servers) => names.extend(servers.into_iter().map(|server| server.name)),
+        Err(e) => log::warn!("Listing MCP servers failed, using config files only: {}", e),
+    }
+    names
+}
+
+/// Lists the servers in the Claude Desktop config without importing anything
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn mcp_preview_claude_desktop_import(
+    app: AppHandle,
+) -> Result<DesktopImportPreview, String> {
+    info!("Previewing MCP server import from Claude Desktop");
+
+    let (config_path, mcp_servers) = read_claude_desktop_servers()?;
+    let existing = existing_server_names(&app).await;
+
+    let servers = mcp_servers
+        .iter()
+        .map(|(name, server_config)| {
+            let string_list = |key: &str| -> Vec<String> {
+                server_config
+                    .get(key)
+                    .and_then(|v| v.as_array())
+                    .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                    .unwrap_or_default()
+            };
+            let env = server_config
+                .get("env")
+                .and_then(|v| v.as_object())
+                .map(|o| {
+                    o.iter()
+                        .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            DesktopImportCandidate {
                 name: name.clone(),
-                success: false,
-                error: Some("Missing command field".to_string()),
-            });
-            continue;
-        }
+                command: server_config
+                    .get("command")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                args: string_list("args"),
+                env,
+                conflict: existing.contains(name),
+                error: desktop_server_to_json(server_config).err(),
+            }
+        })
+        .collect();
 
-        // Add args if present
-        if let Some(args) = server_config.get("args").and_then(|v| v.as_array()) {
-            json_config.insert("args".to_string(), args.clone().into());
-        } else {
-            json_config.insert("args".to_string(), serde_json::Value::Array(vec![]));
-        }
+    Ok(DesktopImportPreview {
+        config_path: config_path.to_string_lossy().to_string(),
+        servers,
+    })
+}
 
-        // Add env if present
-        if let Some(env) = server_config.get("env").and_then(|v| v.as_object()) {
-            json_config.insert("env".to_string(), env.clone().into());
-        } else {
-            json_config.insert(
-                "env".to_string(),
-                serde_json::Value::Object(serde_json::Map::new()),
+/// A server replaced by an import: its scope and config entry as claude stored it
+type ReplacedServer = (&'static str, serde_json::Value);
+
+/// Removes the servers added by a failed import and restores the ones it replaced
+async fn rollback_server_import(
+    app: &AppHandle,
+    scope: &str,
+    applied: Vec<(String, Option<ReplacedServer>)>,
+) {
+    for (name, previous) in applied.into_iter().rev() {
+        if let Err(e) = execute_claude_mcp_command(app, vec!["remove", &name, "-s", scope]) {
+            error!("Rollback: failed to remove {}: {}", name, e);
+        }
+        if let Some((previous_scope, config)) = previous {
+            let restored = execute_claude_mcp_command(
+                app,
+                vec!["add-json", &name, &config.to_string(), "-s", previous_scope],
             );
+            if let Err(e) = restored {
+                error!("Rollback: failed to restore {}: {}", name, e);
+            }
         }
+    }
+}
+
+/// Imports the selected Claude Desktop servers.
+///
+/// Every selected server is validated before anything is written. If writing one
+/// of them fails, the servers added so far are removed again and replaced servers
+/// are restored from their original config entries.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn mcp_apply_claude_desktop_import(
+    app: AppHandle,
+    selected_names: Vec<String>,
+    scope: String,
+    conflict_strategy: Option<ImportConflictStrategy>,
+) -> Result<ImportResult, String> {
+    let conflict_strategy = conflict_strategy.unwrap_or_default();
+    info!(
+        "Importing {} MCP servers from Claude Desktop with scope: {} ({:?} on conflict)",
+        selected_names.len(),
+        scope,
+        conflict_strategy
+    );
+
+    let (_, mcp_servers) = read_claude_desktop_servers()?;
+
+    // Validate everything before touching any config
+    let mut servers = Vec::new();
+    let mut seen = HashSet::new();
+    for name in selected_names {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let server_config = mcp_servers
+            .get(&name)
+            .ok_or_else(|| format!("Server not found in Claude Desktop config: {}", name))?;
+        let json_config = desktop_server_to_json(server_config)
+            .map_err(|e| format!("Cannot import {}: {}", name, e))?;
+        servers.push((name, serde_json::Value::Object(json_config)));
+    }
+
+    import_server_configs(&app, servers, &scope, conflict_strategy, "desktop").await
+}
+
+/// Add server configs with `claude mcp add-json`, resolving name conflicts
+/// with `conflict_strategy` (renamed servers get `suffix`).
+///
+/// If adding one of them fails, the servers added so far are removed again
+/// and replaced servers are restored from the config entries backed up
+/// before removing them, env included.
+pub(crate) async fn import_server_configs(
+    app: &AppHandle,
+    servers: Vec<(String, serde_json::Value)>,
+    scope: &str,
+    conflict_strategy: ImportConflictStrategy,
+    suffix: &str,
+) -> Result<ImportResult, String> {
+    let mut taken = existing_server_names(app).await;
 
-        // Convert to JSON string
+    // Plan everything before touching any config
+    let mut plan = Vec::new();
+    let mut server_results = Vec::new();
+    let mut skipped_count = 0;
+    for (name, json_config) in servers {
         let json_str = serde_json::to_string(&json_config)
             .map_err(|e| format!("Failed to serialize config for {}: {}", name, e))?;
 
-        // Call add-json command
-        match mcp_add_json(app.clone(), name.clone(), json_str, scope.clone()).await {
-            Ok(result) => {
-                if result.success {
-                    imported_count += 1;
-                    server_results.push(ImportServerResult {
-                        name: name.clone(),
-                        success: true,
-                        error: None,
-                    });
-                    info!("Successfully imported server: {}", name);
-                } else {
-                    failed_count += 1;
-                    let error_msg = result.message.clone();
+        let (target, replaces) = if !taken.contains(&name) {
+            (name.clone(), false)
+        } else {
+            match conflict_strategy {
+                ImportConflictStrategy::Skip => {
+                    info!("Skipping {}: a server with that name is already configured", name);
+                    skipped_count += 1;
                     server_results.push(ImportServerResult {
-                        name: name.clone(),
+                        name,
                         success: false,
-                        error: Some(result.message),
+                        error: Some("Already configured, skipped".to_string()),
+                        imported_as: None,
                     });
-                    error!("Failed to import server {}: {}", name, error_msg);
+                    continue;
                 }
+                ImportConflictStrategy::Overwrite => (name.clone(), true),
+                ImportConflictStrategy::Rename => (unused_server_name(&name, suffix, &taken), false),
             }
-            Err(e) => {
-                failed_count += 1;
-                let error_msg = e.clone();
+        };
+        taken.insert(target.clone());
+        plan.push((name, target, json_str, replaces));
+    }
+
+    // Write pass: stop and roll back at the first failure
+    let (project_path, claude_json) = cli_config_paths();
+    let mut applied: Vec<(String, Option<ReplacedServer>)> = Vec::new();
+    for (name, target, json_str, replaces) in plan {
+        let previous = if replaces {
+            let backup = configured_server_entry(&project_path, claude_json.as_deref(), &target)
+                .and_then(|entry| {
+                    entry.ok_or_else(|| "not found in claude's config files".to_string())
+                });
+            let previous = match backup {
+                Ok(previous) => previous,
+                Err(e) => {
+                    rollback_server_import(app, scope, applied).await;
+                    return Err(format!("Failed to back up {}: {}", target, e));
+                }
+            };
+            let removed =
+                execute_claude_mcp_command(app, vec!["remove", &target, "-s", previous.0]);
+            if let Err(e) = removed {
+                rollback_server_import(app, scope, applied).await;
+                return Err(format!("Failed to replace {}: {}", target, e));
+            }
+            Some(previous)
+        } else {
+            None
+        };
+
+        let added = mcp_add_json(app.clone(), target.clone(), json_str, scope.to_string()).await;
+        match added {
+            Ok(result) if result.success => {
+                info!("Successfully imported server: {} as {}", name, target);
                 server_results.push(ImportServerResult {
                     name: name.clone(),
-                    success: false,
-                    error: Some(e),
+                    success: true,
+                    error: None,
+                    imported_as: (target != name).then(|| target.clone()),
                 });
-                error!("Error importing server {}: {}", name, error_msg);
+                applied.push((target, previous));
+            }
+            outcome => {
+                let message = match outcome {
+                    Ok(result) => result.message,
+                    Err(e) => e,
+                };
+                error!("Failed to import server {}: {}", name, message);
+                let rolled_back = applied.len();
+                // The failed server was not added, but a replaced one must come back
+                applied.push((target, previous));
+                rollback_server_import(app, scope, applied).await;
+                return Err(format!(
+                    "Failed to import {}: {} (rolled back {} imported servers)",
+                    name, message, rolled_back
+                ));
             }
         }
     }
 
+    let imported_count = server_results.iter().filter(|r| r.success).count() as u32;
     info!(
-        "Import complete: {} imported, {} failed",
-        imported_count, failed_count
+        "Import complete: {} imported, {} skipped",
+        imported_count, skipped_count
     );
 
     Ok(ImportResult {
         imported_count,
-        failed_count,
+        failed_count: 0,
+        skipped_count,
         servers: server_results,
     })
 }
 
+/// Imports MCP servers from Claude Desktop
+///
+/// Kept for compatibility: previews the import and applies every valid server,
+/// skipping names that are already configured. Servers that cannot be imported
+/// are reported as failed.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn mcp_add_from_claude_desktop(
+    app: AppHandle,
+    scope: String,
+) -> Result<ImportResult, String> {
+    info!(
+        "Importing MCP servers from Claude Desktop with scope: {}",
+        scope
+    );
+
+    let preview = mcp_preview_claude_desktop_import(app.clone()).await?;
+    let (selected, invalid) = importable_candidates(preview.servers);
+    let mut result = mcp_apply_claude_desktop_import(
+        app,
+        selected,
+        scope,
+        Some(ImportConflictStrategy::Skip),
+    )
+    .await?;
+
+    result.failed_count += invalid.len() as u32;
+    result.servers.extend(invalid);
+    info!(
+        "Import complete: {} imported, {} failed, {} skipped",
+        result.imported_count, result.failed_count, result.skipped_count
+    );
+    Ok(result)
+}
+
+/// Splits previewed servers into the names to import and results for the
+/// ones that cannot be imported
+fn importable_candidates(
+    candidates: Vec<DesktopImportCandidate>,
+) -> (Vec<String>, Vec<ImportServerResult>) {
+    let mut selected = Vec::new();
+    let mut invalid = Vec::new();
+    for candidate in candidates {
+        match candidate.error {
+            None => selected.push(candidate.name),
+            Some(e) => invalid.push(ImportServerResult {
+                name: candidate.name,
+                success: false,
+                error: Some(e),
+                imported_as: None,
+            }),
+        }
+    }
+    (selected, invalid)
+}
+
 /// Starts Claude Code as an MCP server
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_serve(app: AppHandle) -> Result<String, String> {
     info!("Starting Claude Code as MCP server");
@@ -640,6 +974,7 @@ pub async fn mcp_serve(app: AppHandle) -> Result<String, String> {
 }
 
 /// Tests connection to an MCP server
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_test_connection(app: AppHandle, name: String) -> Result<String, String> {
     info!("Testing connection to MCP server: {}", name);
@@ -652,6 +987,7 @@ pub async fn mcp_test_connection(app: AppHandle, name: String) -> Result<String,
 }
 
 /// Resets project-scoped server approval choices
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_reset_project_choices(app: AppHandle) -> Result<String, String> {
     info!("Resetting MCP project choices");
@@ -669,6 +1005,7 @@ pub async fn mcp_reset_project_choices(app: AppHandle) -> Result<String, String>
 }
 
 /// Gets the status of MCP servers
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_get_server_status() -> Result<HashMap<String, ServerStatus>, String> {
     info!("Getting MCP server status");
@@ -679,6 +1016,7 @@ pub async fn mcp_get_server_status() -> Result<HashMap<String, ServerStatus>, St
 }
 
 /// Reads .mcp.json from the current project
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_read_project_config(project_path: String) -> Result<MCPProjectConfig, String> {
     info!("Reading .mcp.json from project: {}", project_path);
@@ -707,6 +1045,7 @@ pub async fn mcp_read_project_config(project_path: String) -> Result<MCPProjectC
 }
 
 /// Saves .mcp.json to the current project
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn mcp_save_project_config(
     project_path: String,
@@ -719,8 +1058,599 @@ pub async fn mcp_save_project_config(
     let json_content = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("Failed to serialize config: {}", e))?;
 
-    fs::write(&mcp_json_path, json_content)
-        .map_err(|e| format!("Failed to write .mcp.json: {}", e))?;
+    write_locked(&mcp_json_path, json_content.as_bytes()).await?;
 
     Ok("Project MCP configuration saved".to_string())
 }
+
+/// app_settings key holding how agent MCP servers are injected (JSON object)
+pub const AGENT_MCP_SETTINGS_KEY: &str = "agent_mcp_injection";
+
+/// Time allowed for an MCP server health check
+const MCP_HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// An MCP server an agent depends on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AgentMcpServer {
+    /// A server configured for the project or user, by name
+    Named(String),
+    /// A full server definition, as it would appear under `mcpServers`
+    Inline {
+        name: String,
+        #[serde(flatten)]
+        config: serde_json::Map<String, serde_json::Value>,
+    },
+}
+
+/// How an agent's MCP servers reach claude
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpInjectionMode {
+    /// A temporary config file passed with --mcp-config
+    #[default]
+    McpConfig,
+    /// Merged into the project's .mcp.json for the duration of the run
+    MergeProject,
+}
+
+/// Settings for injecting agent MCP servers
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentMcpSettings {
+    #[serde(default)]
+    pub mode: McpInjectionMode,
+    /// Check each server responds (url) or can be found (command) before the run
+    #[serde(default)]
+    pub health_check: bool,
+}
+
+/// Parse the `mcp_servers` JSON stored on an agent
+pub fn parse_agent_mcp_servers(json: &str) -> Result<Vec<AgentMcpServer>, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid agent MCP servers: {}", e))
+}
+
+/// Read a JSON file, treating a missing file as empty
+fn read_json_file(path: &std::path::Path) -> Result<Option<serde_json::Value>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn servers_of(value: Option<&serde_json::Value>) -> impl Iterator<Item = (String, serde_json::Value)> + '_ {
+    value
+        .and_then(|v| v.get("mcpServers"))
+        .and_then(|s| s.as_object())
+        .into_iter()
+        .flatten()
+        .map(|(name, config)| (name.clone(), config.clone()))
+}
+
+/// User-scope servers in `~/.claude.json`
+pub fn user_mcp_servers(
+    claude_json: &std::path::Path,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    Ok(servers_of(read_json_file(claude_json)?.as_ref()).collect())
+}
+
+/// MCP server config entries keyed by name
+type ServerConfigs = HashMap<String, serde_json::Value>;
+
+/// Servers claude would see for a project, by scope from lowest to highest
+/// precedence: user (top-level ~/.claude.json), project (.mcp.json), then
+/// local (per-project entries in ~/.claude.json).
+fn scoped_mcp_servers(
+    project_path: &std::path::Path,
+    claude_json: Option<&std::path::Path>,
+) -> Result<Vec<(&'static str, ServerConfigs)>, String> {
+    let user_config = match claude_json {
+        Some(path) => read_json_file(path)?,
+        None => None,
+    };
+    let project_config = read_json_file(&project_path.join(".mcp.json"))?;
+    let local_config = user_config.as_ref().and_then(|config| {
+        config
+            .get("projects")?
+            .get(project_path.to_string_lossy().as_ref())
+    });
+
+    Ok(vec![
+        ("user", servers_of(user_config.as_ref()).collect()),
+        ("project", servers_of(project_config.as_ref()).collect()),
+        ("local", servers_of(local_config).collect()),
+    ])
+}
+
+/// Servers claude would see for a project, keyed by name.
+///
+/// Uses claude's precedence: local (per-project entries in ~/.claude.json)
+/// over project (.mcp.json) over user (top-level ~/.claude.json).
+pub fn configured_mcp_servers(
+    project_path: &std::path::Path,
+    claude_json: Option<&std::path::Path>,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    Ok(scoped_mcp_servers(project_path, claude_json)?
+        .into_iter()
+        .flat_map(|(_, servers)| servers)
+        .collect())
+}
+
+/// The config entry claude uses for `name`, with the scope it is stored in
+fn configured_server_entry(
+    project_path: &std::path::Path,
+    claude_json: Option<&std::path::Path>,
+    name: &str,
+) -> Result<Option<(&'static str, serde_json::Value)>, String> {
+    Ok(scoped_mcp_servers(project_path, claude_json)?
+        .into_iter()
+        .rev()
+        .find_map(|(scope, mut servers)| Some((scope, servers.remove(name)?))))
+}
+
+/// Resolve an agent's servers to definitions, failing on unknown names
+pub fn resolve_agent_mcp_servers(
+    servers: &[AgentMcpServer],
+    configured: &HashMap<String, serde_json::Value>,
+) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let mut resolved = serde_json::Map::new();
+    let mut missing = Vec::new();
+    for server in servers {
+        match server {
+            AgentMcpServer::Named(name) => match configured.get(name) {
+                Some(config) => {
+                    resolved.insert(name.clone(), config.clone());
+                }
+                None => missing.push(name.as_str()),
+            },
+            AgentMcpServer::Inline { name, config } => {
+                resolved.insert(name.clone(), serde_json::Value::Object(config.clone()));
+            }
+        }
+    }
+    if !missing.is_empty() {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("MCP servers not configured for this project: {}", missing.join(", ")),
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Check that a server can be reached (url) or started (command)
+pub async fn health_check_mcp_server(name: &str, config: &serde_json::Value) -> Result<(), String> {
+    if let Some(url) = config.get("url").and_then(|u| u.as_str()) {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(MCP_HEALTH_CHECK_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| e.to_string())?;
+        // Any HTTP response means the server is up; MCP endpoints often reject plain GETs
+        return client
+            .get(url)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("MCP server '{}' is unreachable at {}: {}", name, url, e));
+    }
+    match config.get("command").and_then(|c| c.as_str()) {
+        Some(command) if which::which(command).is_ok() => Ok(()),
+        Some(command) => Err(format!("MCP server '{}': command '{}' not found", name, command)),
+        None => Err(format!("MCP server '{}' has neither a command nor a url", name)),
+    }
+}
+
+/// MCP servers injected into one agent run, and how to undo it
+#[derive(Debug, Clone, Default)]
+pub struct McpInjection {
+    /// Names of the injected servers
+    pub servers: Vec<String>,
+    /// Temporary --mcp-config file to delete after the run
+    config_file: Option<PathBuf>,
+    /// .mcp.json the servers were merged into
+    merged_project_config: Option<PathBuf>,
+}
+
+/// A server merged into a project's .mcp.json by one or more running agents
+#[derive(Debug)]
+struct MergedServer {
+    /// Runs still using the merged definition
+    runs: usize,
+    /// Definition it replaced (None if the project had no server by that name)
+    original: Option<serde_json::Value>,
+}
+
+/// Servers merged into one .mcp.json
+#[derive(Debug, Default)]
+struct MergedProjectConfig {
+    /// Whether the file was created for the merge
+    created: bool,
+    servers: HashMap<String, MergedServer>,
+}
+
+/// Servers currently merged into project .mcp.json files, so that runs
+/// sharing a project undo only their own changes and only once the last of
+/// them finished
+static MERGED_SERVERS: Mutex<BTreeMap<PathBuf, MergedProjectConfig>> = Mutex::new(BTreeMap::new());
+
+fn parse_project_config(content: Option<&str>) -> Result<serde_json::Value, String> {
+    let mut config = match content {
+        Some(content) => serde_json::from_str::<serde_json::Value>(content)
+            .map_err(|e| format!("Failed to parse .mcp.json: {}", e))?,
+        None => serde_json::json!({}),
+    };
+    let object = config
+        .as_object_mut()
+        .ok_or_else(|| "Failed to parse .mcp.json: not an object".to_string())?;
+    if !object
+        .entry("mcpServers")
+        .or_insert_with(|| serde_json::json!({}))
+        .is_object()
+    {
+        return Err("Failed to parse .mcp.json: mcpServers is not an object".to_string());
+    }
+    Ok(config)
+}
+
+fn project_servers(config: &mut serde_json::Value) -> &mut serde_json::Map<String, serde_json::Value> {
+    config["mcpServers"]
+        .as_object_mut()
+        .expect("mcpServers checked by parse_project_config")
+}
+
+/// Merge servers into a project's .mcp.json under its lock
+async fn merge_project_servers(
+    path: &Path,
+    resolved: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), String> {
+    let names: Vec<String> = resolved.keys().cloned().collect();
+    let mut registered = false;
+    let result = update_locked(path, |current| {
+        let mut config = parse_project_config(current.as_deref())?;
+        let servers = project_servers(&mut config);
+        let mut merged = MERGED_SERVERS.lock().map_err(|e| e.to_string())?;
+        let project = merged.entry(path.to_path_buf()).or_insert_with(|| MergedProjectConfig {
+            created: current.is_none(),
+            servers: HashMap::new(),
+        });
+        for (name, server) in resolved {
+            let original = servers.get(&name).cloned();
+            project
+                .servers
+                .entry(name.clone())
+                .or_insert(MergedServer { runs: 0, original })
+                .runs += 1;
+            servers.insert(name, server);
+        }
+        registered = true;
+        serde_json::to_string_pretty(&config).map(Some).map_err(|e| e.to_string())
+    })
+    .await;
+    if result.is_err() && registered {
+        // Nothing was written, so only the bookkeeping needs undoing
+        release_merged_servers(path, &names);
+    }
+    result.map(|_| ())
+}
+
+/// Drop one run's use of merged servers, returning those no run uses any
+/// more with the definitions to put back, and whether the file was created
+/// for the merge
+fn release_merged_servers(
+    path: &Path,
+    names: &[String],
+) -> (Vec<(String, Option<serde_json::Value>)>, bool) {
+    let mut merged = MERGED_SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(project) = merged.get_mut(path) else {
+        return (Vec::new(), false);
+    };
+    let mut released = Vec::new();
+    for name in names {
+        if let Some(server) = project.servers.get_mut(name) {
+            server.runs -= 1;
+            if server.runs == 0 {
+                let original = project.servers.remove(name).and_then(|server| server.original);
+                released.push((name.clone(), original));
+            }
+        }
+    }
+    let created = project.created && project.servers.is_empty();
+    if project.servers.is_empty() {
+        merged.remove(path);
+    }
+    (released, created)
+}
+
+/// Take a run's servers back out of a project's .mcp.json, leaving
+/// everything else in the file as it is now
+async fn unmerge_project_servers(path: &Path, names: &[String]) -> Result<(), String> {
+    let _lock = FileLock::acquire(path, LOCK_WAIT).await?;
+    let (released, created) = release_merged_servers(path, names);
+    if released.is_empty() {
+        return Ok(());
+    }
+    let current = match fs::read_to_string(path) {
+        Ok(content) => content,
+        // Removed while the run was going; nothing left to undo
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(i18n::error(ErrorKind::FileRead, e)),
+    };
+    let mut config = parse_project_config(Some(&current))?;
+    let servers = project_servers(&mut config);
+    for (name, original) in released {
+        match original {
+            Some(original) => servers.insert(name, original),
+            None => servers.remove(&name),
+        };
+    }
+    let emptied = servers.is_empty() && config.as_object().is_some_and(|object| object.len() == 1);
+    if created && emptied {
+        return fs::remove_file(path).map_err(|e| write_error(path, e));
+    }
+    let content = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomically(path, content.as_bytes()).map_err(|e| write_error(path, e))
+}
+
+impl McpInjection {
+    /// Extra claude arguments for the injected servers
+    pub fn args(&self) -> Vec<String> {
+        match &self.config_file {
+            Some(path) => vec!["--mcp-config".to_string(), path.to_string_lossy().to_string()],
+            None => Vec::new(),
+        }
+    }
+
+    /// Remove the temporary config or take the servers back out of the
+    /// project's .mcp.json
+    pub async fn cleanup(&self) {
+        if let Some(path) = &self.config_file {
+            if let Err(e) = fs::remove_file(path) {
+                log::warn!("Failed to remove MCP config {}: {}", path.display(), e);
+            }
+        }
+        if let Some(path) = &self.merged_project_config {
+            if let Err(e) = unmerge_project_servers(path, &self.servers).await {
+                error!("Failed to remove MCP servers from {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Write the temporary --mcp-config file, readable only by the user as the
+/// server definitions may carry credentials
+fn write_private_file(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+/// Make resolved servers available to a run.
+///
+/// `config_dir` holds the temporary --mcp-config file; in merge mode the
+/// servers are written into `execution_path`/.mcp.json instead.
+pub async fn inject_mcp_servers(
+    run_id: i64,
+    resolved: serde_json::Map<String, serde_json::Value>,
+    mode: McpInjectionMode,
+    config_dir: &Path,
+    execution_path: &Path,
+) -> Result<McpInjection, String> {
+    let servers: Vec<String> = resolved.keys().cloned().collect();
+    if servers.is_empty() {
+        return Ok(McpInjection::default());
+    }
+
+    match mode {
+        McpInjectionMode::McpConfig => {
+            fs::create_dir_all(config_dir).map_err(|e| i18n::error(ErrorKind::FileWrite, e))?;
+            let path = config_dir.join(format!("run-{}.json", run_id));
+            let config = serde_json::json!({ "mcpServers": resolved });
+            let content = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+            write_private_file(&path, &content).map_err(|e| i18n::error(ErrorKind::FileWrite, e))?;
+            Ok(McpInjection {
+                servers,
+                config_file: Some(path),
+                merged_project_config: None,
+            })
+        }
+        McpInjectionMode::MergeProject => {
+            let path = execution_path.join(".mcp.json");
+            merge_project_servers(&path, resolved).await?;
+            Ok(McpInjection {
+                servers,
+                config_file: None,
+                merged_project_config: Some(path),
+            })
+        }
+    }
+}
+
+/// Load the agent MCP injection settings
+pub fn load_agent_mcp_settings(conn: &Connection) -> AgentMcpSettings {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![AGENT_MCP_SETTINGS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| serde_json::from_str(&value).ok())
+    .unwrap_or_default()
+}
+
+/// Get how agent MCP servers are injected into runs
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_agent_mcp_settings(db: State<'_, AgentDb>) -> Result<AgentMcpSettings, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(load_agent_mcp_settings(&conn))
+}
+
+/// Set how agent MCP servers are injected into runs
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_agent_mcp_settings(
+    db: State<'_, AgentDb>,
+    settings: AgentMcpSettings,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![AGENT_MCP_SETTINGS_KEY, value],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaced_servers_are_backed_up_from_their_config_entry() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let project = temp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join(".mcp.json"),
+            r#"{"mcpServers":{"db":{"command":"project-db"}}}"#,
+        )
+        .unwrap();
+        let claude_json = temp.path().join(".claude.json");
+        let github = serde_json::json!({"command": "gh-mcp", "env": {"GITHUB_TOKEN": "secret"}});
+        fs::write(
+            &claude_json,
+            serde_json::json!({
+                "mcpServers": {"github": github, "db": {"command": "user-db"}},
+                "projects": {project.to_string_lossy(): {"mcpServers": {}}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        // The entry keeps env, which `claude mcp get` does not report
+        let entry = configured_server_entry(&project, Some(&claude_json), "github").unwrap();
+        assert_eq!(entry, Some(("user", github)));
+        let entry = configured_server_entry(&project, Some(&claude_json), "db").unwrap();
+        assert_eq!(entry.unwrap().0, "project");
+        assert!(configured_server_entry(&project, Some(&claude_json), "nope")
+            .unwrap()
+            .is_none());
+
+        // The legacy import applies the valid servers and reports the rest
+        let candidate = |name: &str, error: Option<&str>| DesktopImportCandidate {
+            name: name.to_string(),
+            command: None,
+            args: Vec::new(),
+            env: HashMap::new(),
+            conflict: false,
+            error: error.map(String::from),
+        };
+        let (selected, invalid) = importable_candidates(vec![
+            candidate("fs", None),
+            candidate("broken", Some("Missing command field")),
+        ]);
+        assert_eq!(selected, vec!["fs"]);
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].name, "broken");
+        assert_eq!(invalid[0].error.as_deref(), Some("Missing command field"));
+    }
+
+    #[tokio::test]
+    async fn agent_servers_resolve_and_merge_mode_restores_project_config() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let project = temp.path().join("app");
+        fs::create_dir_all(&project).unwrap();
+        let project_config = r#"{"mcpServers":{"docs":{"command":"docs-mcp"},"db":{"command":"old-db"}}}"#;
+        fs::write(project.join(".mcp.json"), project_config).unwrap();
+        let claude_json = temp.path().join(".claude.json");
+        fs::write(
+            &claude_json,
+            serde_json::json!({
+                "mcpServers": {"github": {"command": "gh-mcp"}, "db": {"command": "user-db"}},
+                "projects": {project.to_string_lossy(): {"mcpServers": {"db": {"command": "local-db"}}}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let servers = parse_agent_mcp_servers(
+            r#"["github", "db", {"name": "search", "type": "http", "url": "http://localhost:9"}]"#,
+        )
+        .unwrap();
+        let configured = configured_mcp_servers(&project, Some(&claude_json)).unwrap();
+        let resolved = resolve_agent_mcp_servers(&servers, &configured).unwrap();
+        assert_eq!(resolved["db"]["command"], "local-db");
+        assert_eq!(resolved["search"]["url"], "http://localhost:9");
+
+        let missing = parse_agent_mcp_servers(r#"["github", "nope"]"#).unwrap();
+        let err = resolve_agent_mcp_servers(&missing, &configured).unwrap_err();
+        assert!(err.contains("nope"));
+
+        let injection = inject_mcp_servers(
+            7,
+            resolved.clone(),
+            McpInjectionMode::McpConfig,
+            &temp.path().join("configs"),
+            &project,
+        )
+        .await
+        .unwrap();
+        let config_file = temp.path().join("configs").join("run-7.json");
+        assert_eq!(injection.args()[1], config_file.to_string_lossy());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&config_file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        injection.cleanup().await;
+        assert!(!config_file.exists());
+
+        let injection = inject_mcp_servers(
+            8,
+            resolved.clone(),
+            McpInjectionMode::MergeProject,
+            temp.path(),
+            &project,
+        )
+        .await
+        .unwrap();
+        assert!(injection.args().is_empty());
+        let merged = read_json_file(&project.join(".mcp.json")).unwrap().unwrap();
+        assert_eq!(merged["mcpServers"]["docs"]["command"], "docs-mcp");
+        assert_eq!(merged["mcpServers"]["search"]["type"], "http");
+        injection.cleanup().await;
+        let restored = read_json_file(&project.join(".mcp.json")).unwrap().unwrap();
+        assert_eq!(restored, serde_json::from_str::<serde_json::Value>(project_config).unwrap());
+
+        // A second run on the project keeps its servers until it finishes,
+        // and edits made during the runs survive
+        let first = inject_mcp_servers(9, resolved.clone(), McpInjectionMode::MergeProject, temp.path(), &project)
+            .await
+            .unwrap();
+        let second = inject_mcp_servers(10, resolved, McpInjectionMode::MergeProject, temp.path(), &project)
+            .await
+            .unwrap();
+        let mut edited = read_json_file(&project.join(".mcp.json")).unwrap().unwrap();
+        edited["mcpServers"]["notes"] = serde_json::json!({"command": "notes-mcp"});
+        fs::write(project.join(".mcp.json"), edited.to_string()).unwrap();
+        first.cleanup().await;
+        let config = read_json_file(&project.join(".mcp.json")).unwrap().unwrap();
+        assert_eq!(config["mcpServers"]["search"]["type"], "http");
+        second.cleanup().await;
+        let config = read_json_file(&project.join(".mcp.json")).unwrap().unwrap();
+        assert_eq!(config["mcpServers"]["db"]["command"], "old-db");
+        assert_eq!(config["mcpServers"]["notes"]["command"], "notes-mcp");
+        assert!(config["mcpServers"].get("search").is_none());
+    }
+}
@@ -5,3 +5,32 @@ pub mod usage;
 pub mod storage;
 pub mod slash_commands;
 pub mod proxy;
+pub mod templates;
+pub mod i18n;
+pub mod worktrees;
+pub mod project_env;
+pub mod notices;
+pub mod schema;
+pub mod redaction;
+pub mod compaction;
+pub mod activity;
+pub mod saved_queries;
+pub mod session_meta;
+pub mod claude_config;
+pub mod session_export;
+pub mod prompt_analysis;
+pub mod execution_profiles;
+pub mod session_import;
+pub mod connectivity;
+pub mod usage_index;
+pub mod project_merge;
+pub mod workspace_backup;
+pub mod thinking;
+pub mod tool_stats;
+pub mod terminal;
+pub mod editor;
+pub mod models;
+pub mod performance;
+pub mod jobs;
+pub mod agent_watchers;
+pub mod run_metrics_cache;
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+/// What a model can do, for enabling features per selected model
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelCapabilities {
+    /// Full model ID
+    pub id: &'static str,
+    /// CLI alias that currently points at this model
+    pub alias: Option<&'static str>,
+    pub display_name: &'static str,
+    /// Largest context window the model accepts, in tokens
+    pub max_context_tokens: u64,
+    pub supports_thinking: bool,
+    pub supports_vision: bool,
+    /// Output tokens a response may use unless configured otherwise
+    pub default_output_limit: u32,
+}
+
+/// Known models, newest first within each family
+const MODELS: &[ModelCapabilities] = &[
+    ModelCapabilities {
+        id: "claude-opus-4-1-20250805",
+        alias: Some("opus"),
+        display_name: "Claude Opus 4.1",
+        max_context_tokens: 200_000,
+        supports_thinking: true,
+        supports_vision: true,
+        default_output_limit: 32_000,
+    },
+    ModelCapabilities {
+        id: "claude-opus-4-20250514",
+        alias: None,
+        display_name: "Claude Opus 4",
+        max_context_tokens: 200_000,
+        supports_thinking: true,
+        supports_vision: true,
+        default_output_limit: 32_000,
+    },
+    ModelCapabilities {
+        id: "claude-sonnet-4-20250514",
+        alias: Some("sonnet"),
+        display_name: "Claude Sonnet 4",
+        // With the 1M context beta; 200k without it
+        max_context_tokens: 1_000_000,
+        supports_thinking: true,
+        supports_vision: true,
+        default_output_limit: 64_000,
+    },
+    ModelCapabilities {
+        id: "claude-3-7-sonnet-20250219",
+        alias: None,
+        display_name: "Claude Sonnet 3.7",
+        max_context_tokens: 200_000,
+        supports_thinking: true,
+        supports_vision: true,
+        default_output_limit: 64_000,
+    },
+    ModelCapabilities {
+        id: "claude-3-5-haiku-20241022",
+        alias: Some("haiku"),
+        display_name: "Claude Haiku 3.5",
+        max_context_tokens: 200_000,
+        supports_thinking: false,
+        supports_vision: true,
+        default_output_limit: 8_192,
+    },
+];
+
+/// Aliases that stand for another alias's model as far as capabilities go:
+/// `opusplan` plans with opus, `default` is sonnet
+const ALIAS_STAND_INS: &[(&str, &str)] = &[("opusplan", "opus"), ("default", "sonnet")];
+
+/// Look up a model by full ID, alias, or an ID without its date suffix
+/// (`claude-sonnet-4` is the newest `claude-sonnet-4-*`)
+pub fn model_capabilities(model: &str) -> Option<&'static ModelCapabilities> {
+    let model = model.trim();
+    let model = ALIAS_STAND_INS
+        .iter()
+        .find(|(alias, _)| *alias == model)
+        .map(|(_, stand_in)| *stand_in)
+        .unwrap_or(model);
+    MODELS
+        .iter()
+        .find(|m| m.id == model || m.alias == Some(model))
+        .or_else(|| {
+            let prefix = format!("{}-", model);
+            MODELS.iter().find(|m| {
+                m.id.strip_prefix(&prefix)
+                    .is_some_and(|date| date.chars().all(|c| c.is_ascii_digit()))
+            })
+        })
+}
+
+/// All known models with their capabilities
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_available_models() -> Result<Vec<ModelCapabilities>, String> {
+    Ok(MODELS.to_vec())
+}
+
+/// Capabilities of a model or alias, `None` for models not in the registry
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_model_capabilities(model: String) -> Result<Option<ModelCapabilities>, String> {
+    Ok(model_capabilities(&model).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_and_undated_ids_resolve_to_registry_models() {
+        assert_eq!(model_capabilities("sonnet").unwrap().id, "claude-sonnet-4-20250514");
+        assert_eq!(model_capabilities("default").unwrap().id, "claude-sonnet-4-20250514");
+        assert_eq!(model_capabilities("opusplan").unwrap().id, "claude-opus-4-1-20250805");
+        assert_eq!(model_capabilities("claude-opus-4").unwrap().id, "claude-opus-4-20250514");
+        assert_eq!(model_capabilities("claude-opus-4-1").unwrap().id, "claude-opus-4-1-20250805");
+        assert!(!model_capabilities("haiku").unwrap().supports_thinking);
+        assert!(model_capabilities("claude-sonnet").is_none());
+        assert!(model_capabilities("gpt-4").is_none());
+    }
+}
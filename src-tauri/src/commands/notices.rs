@@ -0,0 +1,272 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+
+/// Kind of a one-line notice the claude CLI prints on stderr
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeKind {
+    Deprecation,
+    Update,
+    Auth,
+    Warning,
+}
+
+impl NoticeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Deprecation => "deprecation",
+            Self::Update => "update",
+            Self::Auth => "auth",
+            Self::Warning => "warning",
+        }
+    }
+
+    fn parse(kind: &str) -> Self {
+        match kind {
+            "deprecation" => Self::Deprecation,
+            "update" => Self::Update,
+            "auth" => Self::Auth,
+            _ => Self::Warning,
+        }
+    }
+}
+
+/// A notice collected from claude's stderr
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppNotice {
+    pub id: i64,
+    pub kind: NoticeKind,
+    pub message: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    /// How many times the same message was printed
+    pub count: i64,
+    /// Agent run that printed it last, if any
+    pub source_run_id: Option<i64>,
+    /// Claude session that printed it last, if any
+    pub source_session_id: Option<String>,
+    pub dismissed: bool,
+}
+
+/// Phrases of notices, checked against the lowercased line
+const NOTICE_PATTERNS: &[(&str, NoticeKind)] = &[
+    ("deprecated", NoticeKind::Deprecation),
+    ("deprecation", NoticeKind::Deprecation),
+    ("will be removed", NoticeKind::Deprecation),
+    ("update available", NoticeKind::Update),
+    ("new version", NoticeKind::Update),
+    ("newer version", NoticeKind::Update),
+    ("please upgrade", NoticeKind::Update),
+    ("claude update", NoticeKind::Update),
+    ("token expires", NoticeKind::Auth),
+    ("token will expire", NoticeKind::Auth),
+    ("session expires", NoticeKind::Auth),
+    ("re-authenticate", NoticeKind::Auth),
+    ("/login", NoticeKind::Auth),
+];
+
+/// Drop ANSI color codes so the same notice isn't stored twice
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' && chars.peek() == Some(&'[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Tell notices apart from real errors on a stderr line.
+///
+/// Returns the cleaned-up message and its kind for notices, `None` for
+/// anything that should keep going to the error events.
+pub fn classify_stderr_line(line: &str) -> Option<(NoticeKind, String)> {
+    let message = strip_ansi(line).trim().to_string();
+    let lower = message.to_lowercase();
+    if message.is_empty() || lower.starts_with("error") || lower.contains("error:") {
+        return None;
+    }
+
+    if let Some((_, kind)) = NOTICE_PATTERNS
+        .iter()
+        .find(|(pattern, _)| lower.contains(pattern))
+    {
+        return Some((*kind, message));
+    }
+    if lower.starts_with("warning:") || lower.starts_with("warn:") || lower.starts_with("note:") {
+        return Some((NoticeKind::Warning, message));
+    }
+    None
+}
+
+/// Store a notice, or bump the count and last_seen of a known one
+pub fn record_notice(
+    conn: &Connection,
+    kind: NoticeKind,
+    message: &str,
+    source_run_id: Option<i64>,
+    source_session_id: Option<&str>,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO app_notices (kind, message, source_run_id, source_session_id)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(message) DO UPDATE SET
+            last_seen = CURRENT_TIMESTAMP,
+            count = count + 1,
+            source_run_id = excluded.source_run_id,
+            source_session_id = excluded.source_session_id",
+        params![kind.as_str(), message, source_run_id, source_session_id],
+    )?;
+    conn.query_row(
+        "SELECT id FROM app_notices WHERE message = ?1",
+        params![message],
+        |row| row.get(0),
+    )
+}
+
+/// List notices, most recently seen first
+pub fn query_notices(conn: &Connection, include_dismissed: bool) -> rusqlite::Result<Vec<AppNotice>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, kind, message, first_seen, last_seen, count, source_run_id, source_session_id, dismissed
+         FROM app_notices WHERE ?1 OR dismissed = 0 ORDER BY last_seen DESC, id DESC",
+    )?;
+    let notices = stmt.query_map(params![include_dismissed], |row| {
+        Ok(AppNotice {
+            id: row.get(0)?,
+            kind: NoticeKind::parse(&row.get::<_, String>(1)?),
+            message: row.get(2)?,
+            first_seen: row.get(3)?,
+            last_seen: row.get(4)?,
+            count: row.get(5)?,
+            source_run_id: row.get(6)?,
+            source_session_id: row.get(7)?,
+            dismissed: row.get(8)?,
+        })
+    })?;
+    notices.collect()
+}
+
+/// Hide a notice; it stays dismissed if the CLI prints it again
+pub fn dismiss_notice(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    let rows = conn.execute("UPDATE app_notices SET dismissed = 1 WHERE id = ?1", params![id])?;
+    Ok(rows > 0)
+}
+
+/// Record a stderr line as a notice if it is one.
+///
+/// Returns false for real errors, which the caller keeps sending to its
+/// error events.
+pub fn capture_stderr_notice(
+    app: &AppHandle,
+    line: &str,
+    source_run_id: Option<i64>,
+    source_session_id: Option<&str>,
+) -> bool {
+    let Some((kind, message)) = classify_stderr_line(line) else {
+        return false;
+    };
+    log::warn!("Claude notice ({}): {}", kind.as_str(), message);
+
+    let db = app.state::<AgentDb>();
+    let recorded = match db.0.lock() {
+        Ok(conn) => record_notice(&conn, kind, &message, source_run_id, source_session_id)
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match recorded {
+        Ok(id) => {
+            let _ = app.emit("app-notice", id);
+        }
+        Err(e) => log::error!("Failed to record notice: {}", e),
+    }
+    true
+}
+
+/// List notices collected from claude's stderr
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_app_notices(
+    db: State<'_, AgentDb>,
+    include_dismissed: Option<bool>,
+) -> Result<Vec<AppNotice>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    query_notices(&conn, include_dismissed.unwrap_or(false))
+        .map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Dismiss a notice
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn dismiss_app_notice(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    if dismiss_notice(&conn, id).map_err(|e| i18n::error(ErrorKind::Database, e))? {
+        Ok(())
+    } else {
+        Err(i18n::error(ErrorKind::InvalidInput, format!("notice {} not found", id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_notices_and_leaves_errors_alone() {
+        let notice = |line| classify_stderr_line(line).map(|(kind, _)| kind);
+        assert_eq!(
+            notice("\u{1b}[33mUpdate available: 1.0.80 → 1.1.0. Run claude update\u{1b}[0m"),
+            Some(NoticeKind::Update)
+        );
+        assert_eq!(
+            notice("The --max-turns flag is deprecated and will be removed"),
+            Some(NoticeKind::Deprecation)
+        );
+        assert_eq!(notice("Your OAuth token expires in 2 days"), Some(NoticeKind::Auth));
+        assert_eq!(notice("Warning: MCP server slow to start"), Some(NoticeKind::Warning));
+
+        assert_eq!(notice("Error: Invalid API key · Please run /login"), None);
+        assert_eq!(notice("TypeError: Cannot read properties of undefined"), None);
+        assert_eq!(notice("    at Object.<anonymous> (cli.js:12:3)"), None);
+        assert_eq!(notice("   "), None);
+    }
+
+    #[test]
+    fn repeated_notices_are_counted_and_stay_dismissed() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::commands::agents::init_schema(&conn).unwrap();
+
+        let id = record_notice(&conn, NoticeKind::Update, "New version available", None, Some("s1"))
+            .unwrap();
+        let again = record_notice(&conn, NoticeKind::Update, "New version available", Some(7), None)
+            .unwrap();
+        assert_eq!(id, again);
+
+        let notices = query_notices(&conn, false).unwrap();
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].count, 2);
+        assert_eq!(notices[0].source_run_id, Some(7));
+        assert_eq!(notices[0].kind, NoticeKind::Update);
+
+        assert!(dismiss_notice(&conn, id).unwrap());
+        assert!(!dismiss_notice(&conn, id + 1).unwrap());
+        record_notice(&conn, NoticeKind::Update, "New version available", None, None).unwrap();
+        assert!(query_notices(&conn, false).unwrap().is_empty());
+        assert_eq!(query_notices(&conn, true).unwrap()[0].count, 3);
+    }
+}
@@ -0,0 +1,349 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::activity::ActivityRange;
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+
+/// Length of the periods a performance trend is split into
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PerformanceBucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl PerformanceBucket {
+    /// SQL for the first day of the bucket a run's `created_at` falls in;
+    /// weeks start on Monday
+    fn start_sql(self) -> &'static str {
+        match self {
+            Self::Day => "date(r.created_at)",
+            Self::Week => "date(r.created_at, 'weekday 0', '-6 days')",
+            Self::Month => "date(r.created_at, 'start of month')",
+        }
+    }
+}
+
+/// What the agents leaderboard is ordered by, best first
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardSort {
+    SuccessRate,
+    Runs,
+    MedianDuration,
+    MedianCost,
+    TotalCost,
+}
+
+/// Run counts and medians over a set of runs
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunPerformance {
+    pub total_runs: i64,
+    /// Runs per status: completed, failed, cancelled, running, ...
+    pub runs_by_status: BTreeMap<String, i64>,
+    /// Completed share of the completed and failed runs; `None` before any finished
+    pub success_rate: Option<f64>,
+    /// Runs with recorded metrics, which the medians are taken over
+    pub measured_runs: i64,
+    pub median_duration_ms: Option<i64>,
+    pub median_cost_usd: Option<f64>,
+    pub total_cost_usd: f64,
+}
+
+/// Performance of an agent's runs started in one bucket
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentPerformancePoint {
+    /// First day of the bucket, YYYY-MM-DD (UTC)
+    pub bucket_start: String,
+    #[serde(flatten)]
+    pub performance: RunPerformance,
+}
+
+/// An agent's performance over a range, for comparing agents
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentLeaderboardEntry {
+    pub agent_id: i64,
+    pub agent_name: String,
+    pub agent_icon: String,
+    #[serde(flatten)]
+    pub performance: RunPerformance,
+}
+
+/// Runs collected for one bucket or agent
+#[derive(Default)]
+struct RunAccumulator {
+    performance: RunPerformance,
+    durations: Vec<i64>,
+    costs: Vec<f64>,
+}
+
+impl RunAccumulator {
+    /// Add a run; `measured` is whether it has an entry in `run_metrics`
+    fn add(&mut self, status: String, measured: bool, duration_ms: Option<i64>, cost_usd: Option<f64>) {
+        let performance = &mut self.performance;
+        performance.total_runs += 1;
+        *performance.runs_by_status.entry(status).or_default() += 1;
+        if measured {
+            performance.measured_runs += 1;
+        }
+        self.durations.extend(duration_ms);
+        if let Some(cost) = cost_usd {
+            self.costs.push(cost);
+            performance.total_cost_usd += cost;
+        }
+    }
+
+    fn finish(mut self) -> RunPerformance {
+        let mut performance = self.performance;
+        let count = |status: &str| performance.runs_by_status.get(status).copied().unwrap_or(0);
+        let (completed, failed) = (count("completed"), count("failed"));
+        performance.success_rate =
+            (completed + failed > 0).then(|| completed as f64 / (completed + failed) as f64);
+
+        self.durations.sort_unstable();
+        performance.median_duration_ms = median(&self.durations, |a, b| (a + b) / 2);
+        self.costs.sort_by(f64::total_cmp);
+        performance.median_cost_usd = median(&self.costs, |a, b| (a + b) / 2.0);
+        performance
+    }
+}
+
+/// Median of sorted values, averaging the middle two of an even count
+fn median<T: Copy>(sorted: &[T], mean: impl Fn(T, T) -> T) -> Option<T> {
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(sorted[mid]),
+        _ => Some(mean(sorted[mid - 1], sorted[mid])),
+    }
+}
+
+/// Lower bound for `created_at`, in SQLite's `CURRENT_TIMESTAMP` format so it
+/// compares as text; the empty string matches every run
+fn created_since(range: ActivityRange, now: DateTime<Utc>) -> String {
+    range
+        .since(now)
+        .map(|since| since.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Per-bucket performance of an agent's runs created at or after `since`.
+///
+/// Runs without metrics count towards the run totals and success rate but
+/// not towards the medians. Served by `idx_agent_runs_agent_created`.
+pub fn agent_performance(
+    conn: &Connection,
+    agent_id: i64,
+    since: &str,
+    bucket: PerformanceBucket,
+) -> rusqlite::Result<Vec<AgentPerformancePoint>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, r.status, m.run_id IS NOT NULL, m.duration_ms, m.cost_usd
+         FROM agent_runs r LEFT JOIN run_metrics m ON m.run_id = r.id
+         WHERE r.agent_id = ?1 AND r.created_at >= ?2",
+        bucket.start_sql()
+    ))?;
+    let mut buckets: BTreeMap<String, RunAccumulator> = BTreeMap::new();
+    let mut rows = stmt.query(params![agent_id, since])?;
+    while let Some(row) = rows.next()? {
+        buckets
+            .entry(row.get(0)?)
+            .or_default()
+            .add(row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?);
+    }
+    Ok(buckets
+        .into_iter()
+        .map(|(bucket_start, runs)| AgentPerformancePoint {
+            bucket_start,
+            performance: runs.finish(),
+        })
+        .collect())
+}
+
+/// Performance of every agent with runs created at or after `since`, best
+/// first by `sort_by`; agents without a value to sort by go last.
+///
+/// Served by `idx_agent_runs_created`.
+pub fn agents_leaderboard(
+    conn: &Connection,
+    since: &str,
+    sort_by: LeaderboardSort,
+) -> rusqlite::Result<Vec<AgentLeaderboardEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.agent_id, a.name, a.icon, r.status, m.run_id IS NOT NULL, m.duration_ms, m.cost_usd
+         FROM agent_runs r
+         JOIN agents a ON a.id = r.agent_id
+         LEFT JOIN run_metrics m ON m.run_id = r.id
+         WHERE r.created_at >= ?1",
+    )?;
+    let mut agents: BTreeMap<i64, (String, String, RunAccumulator)> = BTreeMap::new();
+    let mut rows = stmt.query(params![since])?;
+    while let Some(row) = rows.next()? {
+        let (_, _, runs) = match agents.entry(row.get(0)?) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert((row.get(1)?, row.get(2)?, RunAccumulator::default()))
+            }
+        };
+        runs.add(row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?);
+    }
+
+    let mut entries: Vec<AgentLeaderboardEntry> = agents
+        .into_iter()
+        .map(|(agent_id, (agent_name, agent_icon, runs))| AgentLeaderboardEntry {
+            agent_id,
+            agent_name,
+            agent_icon,
+            performance: runs.finish(),
+        })
+        .collect();
+    // Higher keys rank first; `None` sorts below every value
+    let key = |entry: &AgentLeaderboardEntry| -> Option<f64> {
+        let p = &entry.performance;
+        match sort_by {
+            LeaderboardSort::SuccessRate => p.success_rate,
+            LeaderboardSort::Runs => Some(p.total_runs as f64),
+            LeaderboardSort::MedianDuration => p.median_duration_ms.map(|d| -(d as f64)),
+            LeaderboardSort::MedianCost => p.median_cost_usd.map(|c| -c),
+            LeaderboardSort::TotalCost => Some(p.total_cost_usd),
+        }
+    };
+    entries.sort_by(|a, b| {
+        match (key(a), key(b)) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+        .then_with(|| b.performance.total_runs.cmp(&a.performance.total_runs))
+        .then_with(|| a.agent_name.cmp(&b.agent_name))
+    });
+    Ok(entries)
+}
+
+/// Run counts, success rate and median duration and cost of an agent's
+/// runs, per day, week or month of the range (default: all runs, per week)
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_agent_performance(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    range: Option<ActivityRange>,
+    bucket: Option<PerformanceBucket>,
+) -> Result<Vec<AgentPerformancePoint>, String> {
+    let since = created_since(range.unwrap_or(ActivityRange::All), Utc::now());
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    agent_performance(&conn, agent_id, &since, bucket.unwrap_or(PerformanceBucket::Week))
+        .map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// All agents' performance over a range, best first (default: all runs, by success rate)
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_agents_leaderboard(
+    db: State<'_, AgentDb>,
+    range: Option<ActivityRange>,
+    sort_by: Option<LeaderboardSort>,
+) -> Result<Vec<AgentLeaderboardEntry>, String> {
+    let since = created_since(range.unwrap_or(ActivityRange::All), Utc::now());
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    agents_leaderboard(&conn, &since, sort_by.unwrap_or(LeaderboardSort::SuccessRate))
+        .map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::{init_schema, insert_agent, AgentInput};
+
+    fn insert_run(
+        conn: &Connection,
+        agent_id: i64,
+        status: &str,
+        created_at: &str,
+        metrics: Option<(Option<i64>, Option<f64>)>,
+    ) {
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, created_at)
+             VALUES (?1, 'A', 'bot', 't', 'sonnet', '/tmp', 's', ?2, ?3)",
+            params![agent_id, status, created_at],
+        )
+        .unwrap();
+        if let Some((duration_ms, cost_usd)) = metrics {
+            conn.execute(
+                "INSERT INTO run_metrics (run_id, source, duration_ms, cost_usd) VALUES (?1, 'jsonl', ?2, ?3)",
+                params![conn.last_insert_rowid(), duration_ms, cost_usd],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn trends_bucket_runs_and_skip_unknown_metrics_in_medians() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        let agent = |name: &str| {
+            let input = AgentInput {
+                name: name.to_string(),
+                icon: "bot".to_string(),
+                system_prompt: "You are helpful".to_string(),
+                ..Default::default()
+            };
+            insert_agent(&conn, input).unwrap().id.unwrap()
+        };
+        let (reviewer, writer) = (agent("Reviewer"), agent("Writer"));
+
+        // Monday and Wednesday of one week, then the next Monday
+        insert_run(&conn, reviewer, "completed", "2025-03-03 09:00:00", Some((Some(1000), Some(0.10))));
+        insert_run(&conn, reviewer, "completed", "2025-03-05 09:00:00", Some((Some(3000), Some(0.30))));
+        insert_run(&conn, reviewer, "failed", "2025-03-05 10:00:00", None);
+        insert_run(&conn, reviewer, "cancelled", "2025-03-05 11:00:00", Some((None, None)));
+        insert_run(&conn, reviewer, "completed", "2025-03-10 09:00:00", Some((Some(500), Some(0.05))));
+        insert_run(&conn, writer, "failed", "2025-03-04 09:00:00", Some((Some(9000), Some(1.0))));
+
+        let weeks = agent_performance(&conn, reviewer, "", PerformanceBucket::Week).unwrap();
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[0].bucket_start, "2025-03-03");
+        let week = &weeks[0].performance;
+        assert_eq!((week.total_runs, week.measured_runs), (4, 3));
+        assert_eq!(week.runs_by_status["cancelled"], 1);
+        assert_eq!(week.success_rate, Some(2.0 / 3.0));
+        assert_eq!(week.median_duration_ms, Some(2000));
+        assert_eq!(week.median_cost_usd, Some(0.2));
+        assert_eq!(weeks[1].bucket_start, "2025-03-10");
+
+        let days = agent_performance(&conn, reviewer, "2025-03-05 00:00:00", PerformanceBucket::Day).unwrap();
+        assert_eq!(days.iter().map(|d| d.bucket_start.as_str()).collect::<Vec<_>>(), ["2025-03-05", "2025-03-10"]);
+        let months = agent_performance(&conn, reviewer, "", PerformanceBucket::Month).unwrap();
+        assert_eq!((months[0].bucket_start.as_str(), months[0].performance.total_runs), ("2025-03-01", 5));
+
+        let by_success = agents_leaderboard(&conn, "", LeaderboardSort::SuccessRate).unwrap();
+        assert_eq!(by_success[0].agent_name, "Reviewer");
+        assert_eq!(by_success[1].performance.success_rate, Some(0.0));
+        let by_duration = agents_leaderboard(&conn, "", LeaderboardSort::MedianDuration).unwrap();
+        assert_eq!(by_duration[0].performance.median_duration_ms, Some(1000));
+        let by_cost = agents_leaderboard(&conn, "", LeaderboardSort::TotalCost).unwrap();
+        assert_eq!(by_cost[0].agent_name, "Writer");
+
+        for sql in [
+            "SELECT * FROM agent_runs r WHERE r.agent_id = 1 AND r.created_at >= ''",
+            "SELECT * FROM agent_runs r WHERE r.created_at >= ''",
+        ] {
+            let plan: String = conn
+                .query_row(&format!("EXPLAIN QUERY PLAN {}", sql), [], |row| row.get(3))
+                .unwrap();
+            assert!(plan.contains("USING INDEX idx_agent_runs_"), "{}", plan);
+        }
+    }
+}
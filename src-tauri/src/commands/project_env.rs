@@ -0,0 +1,286 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tauri::{AppHandle, Manager, State};
+use tokio::process::Command;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::claude::{create_system_command, project_path_key};
+use super::i18n::{self, ErrorKind};
+
+/// Where a variable of the child environment comes from
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvSource {
+    /// Inherited from the app through the variable whitelist
+    Inherited,
+    /// Configured for the project
+    Project,
+    /// Passed for this run only
+    Run,
+}
+
+/// A variable as the claude process will see it
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvEntry {
+    pub key: String,
+    /// The value, masked when the name looks like a secret
+    pub value: String,
+    pub source: EnvSource,
+    pub masked: bool,
+}
+
+/// Name fragments of variables whose values are never shown in full
+const SECRET_MARKERS: &[&str] = &[
+    "KEY", "TOKEN", "SECRET", "PASSWORD", "PASSWD", "AUTH", "CREDENTIAL", "DATABASE_URL",
+];
+
+/// Whether a variable name looks like it holds a secret
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// Keep the first characters of a secret so users can tell values apart
+fn mask_value(value: &str) -> String {
+    let visible: String = value.chars().take(4).collect();
+    if value.chars().count() <= 8 {
+        "********".to_string()
+    } else {
+        format!("{}********", visible)
+    }
+}
+
+/// Reject names that a process environment cannot hold
+fn validate_env_key(key: &str) -> Result<(), String> {
+    let valid = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("invalid environment variable name '{}'", key),
+        ))
+    }
+}
+
+/// Load the environment configured for a project
+pub fn load_project_env(
+    conn: &Connection,
+    project_path: &str,
+) -> rusqlite::Result<BTreeMap<String, String>> {
+    let mut stmt =
+        conn.prepare("SELECT key, value FROM project_env WHERE project_key = ?1 ORDER BY key")?;
+    let rows = stmt.query_map(params![project_path_key(project_path)], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    rows.collect()
+}
+
+/// Replace the environment configured for a project
+pub fn save_project_env(
+    conn: &mut Connection,
+    project_path: &str,
+    env: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    for key in env.keys() {
+        validate_env_key(key)?;
+    }
+
+    let project_key = project_path_key(project_path);
+    let tx = conn
+        .transaction()
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    tx.execute(
+        "DELETE FROM project_env WHERE project_key = ?1",
+        params![project_key],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    for (key, value) in env {
+        tx.execute(
+            "INSERT INTO project_env (project_key, key, value) VALUES (?1, ?2, ?3)",
+            params![project_key, key, value],
+        )
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    }
+    tx.commit().map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Merge the project environment with per-run overrides (which win)
+pub fn merged_env(
+    project_env: &BTreeMap<String, String>,
+    overrides: Option<&HashMap<String, String>>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut merged = project_env.clone();
+    if let Some(overrides) = overrides {
+        for (key, value) in overrides {
+            validate_env_key(key)?;
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(merged.into_iter().collect())
+}
+
+/// Environment to add to a claude process started for `project_path`.
+///
+/// Applied after the global whitelist, so project values replace inherited
+/// ones and per-run overrides replace both.
+pub fn resolve_env(
+    app: &AppHandle,
+    project_path: &str,
+    overrides: Option<&HashMap<String, String>>,
+) -> Result<Vec<(String, String)>, String> {
+    let project_env = {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        load_project_env(&conn, project_path).map_err(|e| i18n::error(ErrorKind::Database, e))?
+    };
+    if !project_env.is_empty() {
+        log::info!(
+            "Applying {} project environment variables for {}",
+            project_env.len(),
+            project_path
+        );
+    }
+    merged_env(&project_env, overrides)
+}
+
+/// Describe the variables explicitly set on a command, masking secrets
+pub fn describe_env(
+    cmd: &Command,
+    project_env: &BTreeMap<String, String>,
+    overrides: Option<&HashMap<String, String>>,
+) -> Vec<EnvEntry> {
+    let mut entries: Vec<EnvEntry> = cmd
+        .as_std()
+        .get_envs()
+        .filter_map(|(key, value)| {
+            let key = key.to_string_lossy().to_string();
+            let value = value?.to_string_lossy().to_string();
+            let source = if overrides.is_some_and(|o| o.contains_key(&key)) {
+                EnvSource::Run
+            } else if project_env.contains_key(&key) {
+                EnvSource::Project
+            } else {
+                EnvSource::Inherited
+            };
+            let masked = is_sensitive_key(&key);
+            let value = if masked { mask_value(&value) } else { value };
+            Some(EnvEntry {
+                key,
+                value,
+                source,
+                masked,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    entries
+}
+
+/// Get the environment variables configured for a project
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_project_env(
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<BTreeMap<String, String>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    load_project_env(&conn, &project_path).map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Replace the environment variables configured for a project
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_project_env(
+    db: State<'_, AgentDb>,
+    project_path: String,
+    env: BTreeMap<String, String>,
+) -> Result<(), String> {
+    log::info!(
+        "Saving {} environment variables for project {}",
+        env.len(),
+        project_path
+    );
+    let mut conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    save_project_env(&mut conn, &project_path, &env)
+}
+
+/// Show the environment a claude process for this project would get
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn preview_claude_env(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    project_path: String,
+    env_overrides: Option<HashMap<String, String>>,
+) -> Result<Vec<EnvEntry>, String> {
+    let project_env = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        load_project_env(&conn, &project_path).map_err(|e| i18n::error(ErrorKind::Database, e))?
+    };
+    let extra_env = merged_env(&project_env, env_overrides.as_ref())?;
+
+    // The binary path only affects PATH tweaks; fall back to a bare name
+    let claude_path =
+        crate::claude_binary::find_claude_binary(&app).unwrap_or_else(|_| "claude".to_string());
+    let mut cmd = create_system_command(&claude_path, Vec::new(), Path::new(&project_path));
+    cmd.envs(extra_env);
+
+    Ok(describe_env(&cmd, &project_env, env_overrides.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_overrides_win_and_secrets_are_masked() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::commands::agents::init_schema(&conn).unwrap();
+
+        let env = BTreeMap::from([
+            ("DATABASE_URL".to_string(), "postgres://u:pw@localhost/test".to_string()),
+            ("ANTHROPIC_MODEL".to_string(), "project-model".to_string()),
+        ]);
+        save_project_env(&mut conn, "/work/app/", &env).unwrap();
+        let project_env = load_project_env(&conn, "/work/app").unwrap();
+        assert_eq!(project_env, env);
+
+        let overrides = HashMap::from([("ANTHROPIC_MODEL".to_string(), "run-model".to_string())]);
+        let merged = merged_env(&project_env, Some(&overrides)).unwrap();
+        assert!(merged.contains(&("ANTHROPIC_MODEL".to_string(), "run-model".to_string())));
+
+        let mut cmd = Command::new("claude");
+        cmd.env("HOME", "/home/me").envs(merged);
+        let entries = describe_env(&cmd, &project_env, Some(&overrides));
+        let sources: Vec<_> = entries.iter().map(|e| (e.key.as_str(), e.source)).collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("ANTHROPIC_MODEL", EnvSource::Run),
+                ("DATABASE_URL", EnvSource::Project),
+                ("HOME", EnvSource::Inherited),
+            ]
+        );
+        assert_eq!(entries[1].value, "post********");
+        assert!(entries[1].masked);
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::commands::agents::init_schema(&conn).unwrap();
+        let env = BTreeMap::from([("BAD=NAME".to_string(), "x".to_string())]);
+        assert!(save_project_env(&mut conn, "/work/app", &env).is_err());
+        let overrides = HashMap::from([("1X".to_string(), String::new())]);
+        assert!(merged_env(&BTreeMap::new(), Some(&overrides)).is_err());
+    }
+}
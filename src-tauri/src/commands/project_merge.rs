@@ -0,0 +1,544 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::claude::{
+    canonical_project_key, canonical_project_path, encode_project_path,
+    get_project_path_from_sessions, project_path_key,
+};
+use super::i18n::{self, ErrorKind};
+use super::jobs::run_as_job;
+use super::session_meta::{
+    load_project_metadata, merge_into_db, sync_project, write_sidecar, SIDECAR_FILE_NAME,
+};
+use crate::checkpoint::state::CheckpointState;
+
+/// Directory holding a project's checkpoint timelines, one subdirectory per session
+const TIMELINES_DIR: &str = ".timelines";
+
+/// ~/.claude/projects directories whose sessions ran in the same real directory
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateProjectGroup {
+    /// The resolved path the directories share
+    pub project_path: String,
+    pub project_ids: Vec<String>,
+    /// The directory claude uses for the path today if it exists, else the one
+    /// with the most sessions
+    pub suggested_target: String,
+}
+
+/// What a moved item is
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergedItemKind {
+    Session,
+    Timeline,
+    /// Anything else in the project directory, e.g. subagent transcripts
+    Other,
+}
+
+/// One file or directory moved into the target project
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedItem {
+    pub source_project_id: String,
+    pub name: String,
+    pub kind: MergedItemKind,
+    /// The target already had an identical copy, so the source copy was deleted
+    pub duplicate: bool,
+}
+
+/// Outcome of `merge_projects`
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectMergeReport {
+    pub target_id: String,
+    pub items: Vec<MergedItem>,
+    /// Source directories removed after every move was verified
+    pub removed_dirs: Vec<String>,
+    /// Source directories left in place because something unexpected remained
+    pub kept_dirs: Vec<String>,
+    /// Session names, favorites and notes moved to the target
+    pub metadata_sessions: usize,
+    pub agent_runs_updated: usize,
+}
+
+struct PlannedMove {
+    source_project_id: String,
+    from: PathBuf,
+    to: PathBuf,
+    kind: MergedItemKind,
+    duplicate: bool,
+}
+
+fn count_sessions(project_dir: &Path) -> usize {
+    fs::read_dir(project_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+/// Group the project directories by the real path their sessions ran in
+pub fn group_duplicate_projects(projects_dir: &Path) -> Result<Vec<DuplicateProjectGroup>, String> {
+    let entries = fs::read_dir(projects_dir).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+    let mut groups: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        // Directories without a readable cwd can't be placed safely
+        let Ok(project_path) = get_project_path_from_sessions(&dir) else {
+            continue;
+        };
+        let canonical = canonical_project_path(&project_path);
+        groups
+            .entry(project_path_key(&canonical))
+            .or_insert_with(|| (canonical, Vec::new()))
+            .1
+            .push(entry.file_name().to_string_lossy().to_string());
+    }
+
+    Ok(groups
+        .into_values()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|(project_path, mut project_ids)| {
+            project_ids.sort();
+            let encoded = encode_project_path(Path::new(&project_path));
+            let suggested_target = project_ids
+                .iter()
+                .find(|id| **id == encoded)
+                .or_else(|| {
+                    project_ids
+                        .iter()
+                        .max_by_key(|id| count_sessions(&projects_dir.join(id)))
+                })
+                .cloned()
+                .unwrap_or_default();
+            DuplicateProjectGroup {
+                project_path,
+                project_ids,
+                suggested_target,
+            }
+        })
+        .collect())
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    a.is_file() && b.is_file() && matches!((fs::read(a), fs::read(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// A project directory name that stays inside the projects directory
+fn is_project_id(id: &str) -> bool {
+    !id.is_empty() && !id.contains(['/', '\\']) && !id.starts_with('.')
+}
+
+/// Items that moving `from` to `to` takes. A directory the target already
+/// has is merged entry by entry, so only files can clash.
+fn plan_item(
+    from: PathBuf,
+    to: PathBuf,
+    kind: MergedItemKind,
+    items: &mut Vec<(PathBuf, PathBuf, MergedItemKind)>,
+) -> Result<(), String> {
+    if from.is_dir() && to.is_dir() {
+        for entry in fs::read_dir(&from).map_err(|e| i18n::error(ErrorKind::FileRead, e))? {
+            let entry = entry.map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+            plan_item(entry.path(), to.join(entry.file_name()), kind, items)?;
+        }
+    } else {
+        items.push((from, to, kind));
+    }
+    Ok(())
+}
+
+/// Remove `dir` and the directories below it that the merge left empty
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    let _ = fs::remove_dir(dir);
+}
+
+/// Everything to move out of the source directories; fails without touching
+/// anything when an item exists in the target with different contents
+fn plan_merge(
+    projects_dir: &Path,
+    source_ids: &[String],
+    target_id: &str,
+) -> Result<Vec<PlannedMove>, String> {
+    if !is_project_id(target_id) {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("'{}' is not a project directory", target_id),
+        ));
+    }
+    let target_dir = projects_dir.join(target_id);
+    if !target_dir.is_dir() {
+        return Err(i18n::error(ErrorKind::ProjectNotFound, target_id));
+    }
+
+    let mut moves = Vec::new();
+    let mut conflicts = Vec::new();
+    for source_id in source_ids {
+        if source_id == target_id || !is_project_id(source_id) {
+            return Err(i18n::error(
+                ErrorKind::InvalidInput,
+                format!("'{}' can't be merged into '{}'", source_id, target_id),
+            ));
+        }
+        let source_dir = projects_dir.join(source_id);
+        if !source_dir.is_dir() {
+            return Err(i18n::error(ErrorKind::ProjectNotFound, source_id));
+        }
+
+        let mut items = Vec::new();
+        for entry in fs::read_dir(&source_dir).map_err(|e| i18n::error(ErrorKind::FileRead, e))? {
+            let entry = entry.map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+            let name = entry.file_name();
+            if name == SIDECAR_FILE_NAME {
+                // Merged through the DB
+                continue;
+            }
+            if name == TIMELINES_DIR && entry.path().is_dir() {
+                let timelines =
+                    fs::read_dir(entry.path()).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+                for timeline in timelines.flatten() {
+                    plan_item(
+                        timeline.path(),
+                        target_dir.join(TIMELINES_DIR).join(timeline.file_name()),
+                        MergedItemKind::Timeline,
+                        &mut items,
+                    )?;
+                }
+                continue;
+            }
+            let kind = if entry.path().extension().and_then(|s| s.to_str()) == Some("jsonl") {
+                MergedItemKind::Session
+            } else {
+                MergedItemKind::Other
+            };
+            plan_item(entry.path(), target_dir.join(&name), kind, &mut items)?;
+        }
+
+        for (from, to, kind) in items {
+            let duplicate = to.exists();
+            if duplicate && !same_contents(&from, &to) {
+                conflicts.push(from.display().to_string());
+                continue;
+            }
+            moves.push(PlannedMove {
+                source_project_id: source_id.clone(),
+                from,
+                to,
+                kind,
+                duplicate,
+            });
+        }
+    }
+
+    if !conflicts.is_empty() {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!(
+                "already in the target with different contents: {}",
+                conflicts.join(", ")
+            ),
+        ));
+    }
+    Ok(moves)
+}
+
+/// Move the contents of the source project directories into the target.
+///
+/// Every move is checked before anything is deleted; if a move fails, the
+/// ones already made are moved back. Source directories are only removed
+/// once they are empty.
+pub fn merge_project_dirs(
+    projects_dir: &Path,
+    source_ids: &[String],
+    target_id: &str,
+) -> Result<ProjectMergeReport, String> {
+    let moves = plan_merge(projects_dir, source_ids, target_id)?;
+
+    let mut done: Vec<&PlannedMove> = Vec::new();
+    for planned in moves.iter().filter(|m| !m.duplicate) {
+        let result = planned
+            .to
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::rename(&planned.from, &planned.to));
+        if let Err(e) = result {
+            for moved in done.iter().rev() {
+                if let Err(e) = fs::rename(&moved.to, &moved.from) {
+                    log::error!(
+                        "Failed to move {} back to {}: {}",
+                        moved.to.display(),
+                        moved.from.display(),
+                        e
+                    );
+                }
+            }
+            return Err(i18n::error(
+                ErrorKind::FileWrite,
+                format!("{}: {}", planned.from.display(), e),
+            ));
+        }
+        done.push(planned);
+    }
+
+    let unverified: Vec<String> = moves
+        .iter()
+        .filter(|m| !m.duplicate && (m.from.exists() || !m.to.exists()))
+        .map(|m| m.from.display().to_string())
+        .collect();
+    if !unverified.is_empty() {
+        return Err(i18n::error(
+            ErrorKind::FileWrite,
+            format!("moves could not be verified, sources kept: {}", unverified.join(", ")),
+        ));
+    }
+
+    let mut report = ProjectMergeReport {
+        target_id: target_id.to_string(),
+        ..Default::default()
+    };
+    for planned in &moves {
+        if planned.duplicate {
+            let removed = if planned.from.is_dir() {
+                fs::remove_dir_all(&planned.from)
+            } else {
+                fs::remove_file(&planned.from)
+            };
+            if let Err(e) = removed {
+                log::warn!("Failed to remove duplicate {}: {}", planned.from.display(), e);
+            }
+        }
+        report.items.push(MergedItem {
+            source_project_id: planned.source_project_id.clone(),
+            name: planned
+                .to
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            kind: planned.kind,
+            duplicate: planned.duplicate,
+        });
+    }
+
+    for source_id in source_ids {
+        let source_dir = projects_dir.join(source_id);
+        let _ = fs::remove_file(source_dir.join(SIDECAR_FILE_NAME));
+        // Directories merged entry by entry are left behind empty
+        if let Ok(entries) = fs::read_dir(&source_dir) {
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    remove_empty_dirs(&entry.path());
+                }
+            }
+        }
+        match fs::remove_dir(&source_dir) {
+            Ok(()) => report.removed_dirs.push(source_id.clone()),
+            Err(e) => {
+                log::warn!("Kept {} after merging it: {}", source_dir.display(), e);
+                report.kept_dirs.push(source_id.clone());
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn claude_projects_dir() -> Result<PathBuf, String> {
+    crate::claude_dir::projects_dir()
+}
+
+/// Find project directories that are the same repository under different names
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn find_duplicate_projects() -> Result<Vec<DuplicateProjectGroup>, String> {
+    group_duplicate_projects(&claude_projects_dir()?)
+}
+
+/// Merge project directories into `target_id`: session files, timelines,
+/// session metadata and the project path of agent runs.
+///
+/// Refused while a claude run is active in any of the projects. Runs as a
+/// job that can be cancelled until files start moving.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn merge_projects(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    source_ids: Vec<String>,
+    target_id: String,
+) -> Result<ProjectMergeReport, String> {
+    crate::claude_dir::check_dir_writable()?;
+    let projects_dir = claude_projects_dir()?;
+    let project_path_of =
+        |id: &String| get_project_path_from_sessions(&projects_dir.join(id)).ok();
+    let source_paths: Vec<String> = source_ids.iter().filter_map(project_path_of).collect();
+    let target_path = project_path_of(&target_id)
+        .or_else(|| source_paths.first().cloned())
+        .map(|path| canonical_project_path(&path));
+
+    let checkpoint_state = app.state::<CheckpointState>();
+    for path in source_paths.iter().chain(target_path.iter()) {
+        if checkpoint_state.has_active_run(&canonical_project_key(path)) {
+            return Err(i18n::error(
+                ErrorKind::InvalidInput,
+                format!("a run is active in {}", path),
+            ));
+        }
+    }
+
+    let params = serde_json::json!({ "source_ids": source_ids, "target_id": target_id });
+    run_as_job(&app, "merge_projects", params, |job| async move {
+        job.progress(0, 3, "syncing_metadata");
+        {
+            let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+            // Pick up metadata edited outside the app before the sidecars go away
+            for id in source_ids.iter().chain(std::iter::once(&target_id)) {
+                if let Err(e) = sync_project(&conn, &projects_dir, id) {
+                    log::warn!("Failed to sync session metadata for {}: {}", id, e);
+                }
+            }
+        }
+
+        // Nothing has moved yet; past this point the merge runs to the end
+        job.check_cancelled()?;
+        job.progress(1, 3, "moving_files");
+        let mut report = merge_project_dirs(&projects_dir, &source_ids, &target_id)?;
+
+        job.progress(2, 3, "updating_database");
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        let db_error = |e: rusqlite::Error| i18n::error(ErrorKind::Database, e);
+        for source_id in &source_ids {
+            let sessions = load_project_metadata(&conn, source_id).map_err(db_error)?;
+            merge_into_db(&conn, &target_id, &sessions).map_err(db_error)?;
+            conn.execute(
+                "DELETE FROM session_metadata WHERE project_id = ?1",
+                params![source_id],
+            )
+            .map_err(db_error)?;
+            report.metadata_sessions += sessions.len();
+        }
+        write_sidecar(&conn, &projects_dir, &target_id)?;
+
+        if let Some(target_path) = target_path {
+            let target_key = project_path_key(&target_path);
+            let source_keys: HashSet<String> = source_paths
+                .iter()
+                .flat_map(|path| [project_path_key(path), canonical_project_key(path)])
+                .filter(|key| *key != target_key)
+                .collect();
+            let runs: Vec<(i64, String)> = {
+                let mut stmt = conn
+                    .prepare("SELECT id, project_path FROM agent_runs")
+                    .map_err(db_error)?;
+                let rows = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .map_err(db_error)?;
+                rows.collect::<rusqlite::Result<_>>().map_err(db_error)?
+            };
+            for (id, path) in runs {
+                if source_keys.contains(&project_path_key(&path)) {
+                    conn.execute(
+                        "UPDATE agent_runs SET project_path = ?1 WHERE id = ?2",
+                        params![target_path, id],
+                    )
+                    .map_err(db_error)?;
+                    report.agent_runs_updated += 1;
+                }
+            }
+        }
+
+        log::info!(
+            "Merged {:?} into {}: {} items, {} agent runs updated",
+            source_ids,
+            target_id,
+            report.items.len(),
+            report.agent_runs_updated
+        );
+        Ok(report)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn session(cwd: &str) -> String {
+        format!("{{\"type\":\"user\",\"cwd\":\"{}\",\"message\":{{\"role\":\"user\",\"content\":\"hi\"}}}}\n", cwd)
+    }
+
+    #[test]
+    fn duplicates_are_grouped_and_merged_after_checking_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        let projects_dir = temp_dir.path();
+        let cwd = "/nonexistent/work/my_app";
+        let target = encode_project_path(Path::new(cwd));
+        let legacy = "-nonexistent-work-my_app";
+        fs::create_dir_all(projects_dir.join(&target).join(TIMELINES_DIR).join("s1")).unwrap();
+        fs::write(projects_dir.join(&target).join("s1.jsonl"), session(cwd)).unwrap();
+        fs::create_dir_all(projects_dir.join(legacy).join(TIMELINES_DIR).join("s2")).unwrap();
+        fs::write(projects_dir.join(legacy).join("s2.jsonl"), session(cwd)).unwrap();
+        // Same session in both directories
+        fs::write(projects_dir.join(legacy).join("s1.jsonl"), session(cwd)).unwrap();
+        fs::write(projects_dir.join(legacy).join(SIDECAR_FILE_NAME), "{}").unwrap();
+        // Both directories hold checkpoints of s1
+        let pool = Path::new(TIMELINES_DIR).join("s1").join("files").join("content_pool");
+        for id in [target.as_str(), legacy] {
+            fs::create_dir_all(projects_dir.join(id).join(&pool)).unwrap();
+            fs::write(projects_dir.join(id).join(&pool).join("abc"), "same").unwrap();
+        }
+        let legacy_checkpoint = Path::new(TIMELINES_DIR).join("s1").join("checkpoints").join("c2");
+        fs::create_dir_all(projects_dir.join(legacy).join(&legacy_checkpoint)).unwrap();
+        fs::create_dir_all(projects_dir.join("-other")).unwrap();
+        fs::write(projects_dir.join("-other").join("s3.jsonl"), session("/elsewhere")).unwrap();
+
+        let groups = group_duplicate_projects(projects_dir).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].project_ids.len(), 2);
+        assert_eq!(groups[0].suggested_target, target);
+
+        // A different file under the same name stops the merge before anything moves
+        fs::write(projects_dir.join("-other").join("s1.jsonl"), session("/elsewhere")).unwrap();
+        let err = merge_project_dirs(projects_dir, &["-other".to_string()], &target).unwrap_err();
+        assert!(err.starts_with("[invalid_input]"), "{}", err);
+        assert!(projects_dir.join("-other").join("s3.jsonl").exists());
+        assert!(!projects_dir.join(&target).join("s3.jsonl").exists());
+
+        for bad_target in ["..", "../x", ""] {
+            let err = merge_project_dirs(projects_dir, &[legacy.to_string()], bad_target).unwrap_err();
+            assert!(err.starts_with("[invalid_input]"), "{}", err);
+        }
+
+        let report = merge_project_dirs(projects_dir, &[legacy.to_string()], &target).unwrap();
+        assert_eq!(report.removed_dirs, vec![legacy.to_string()]);
+        assert_eq!(report.items.len(), 5);
+        assert_eq!(report.items.iter().filter(|item| item.duplicate).count(), 2);
+        assert!(projects_dir.join(&target).join("s2.jsonl").exists());
+        assert!(projects_dir.join(&target).join(TIMELINES_DIR).join("s2").is_dir());
+        assert!(projects_dir.join(&target).join(&legacy_checkpoint).is_dir());
+        assert!(projects_dir.join(&target).join(&pool).join("abc").exists());
+        assert!(!projects_dir.join(legacy).exists());
+    }
+}
@@ -0,0 +1,488 @@
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{query_agent, AgentDb};
+use super::claude::effective_claude_md_files;
+use super::compaction::CHARS_PER_TOKEN;
+use super::i18n::{self, ErrorKind};
+use super::usage::cost_of_usage;
+
+/// app_settings key holding the prompt soft limits (JSON object)
+pub const PROMPT_SOFT_LIMITS_KEY: &str = "prompt_soft_limits";
+
+/// app_settings key holding the risky-prompt keywords per locale (JSON object)
+pub const PROMPT_RISK_KEYWORDS_KEY: &str = "prompt_risk_keywords";
+
+/// Token counts above which a prompt component is flagged
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptSoftLimits {
+    pub system_prompt_tokens: u64,
+    pub task_tokens: u64,
+    /// All CLAUDE.md files together
+    pub claude_md_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl Default for PromptSoftLimits {
+    fn default() -> Self {
+        Self {
+            system_prompt_tokens: 8_000,
+            task_tokens: 4_000,
+            claude_md_tokens: 10_000,
+            total_tokens: 20_000,
+        }
+    }
+}
+
+/// Part of the first-turn input of a run
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptComponentKind {
+    SystemPrompt,
+    Task,
+    ClaudeMd,
+}
+
+/// Size estimate of one prompt component
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptComponent {
+    pub kind: PromptComponentKind,
+    /// File path for CLAUDE.md components
+    pub path: Option<String>,
+    pub chars: u64,
+    pub estimated_tokens: u64,
+}
+
+/// A component (or the total) above its soft limit
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptLimitWarning {
+    /// `system_prompt`, `task`, `claude_md` or `total`
+    pub component: String,
+    pub estimated_tokens: u64,
+    pub limit: u64,
+}
+
+/// Preflight size and cost estimate of an agent run
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptAnalysis {
+    pub agent_id: i64,
+    pub model: String,
+    pub components: Vec<PromptComponent>,
+    pub total_tokens: u64,
+    /// Input cost of the first turn from the pricing table, without caching
+    pub estimated_input_cost_usd: f64,
+    /// Tokens are characters divided by this, not a real tokenizer count
+    pub chars_per_token: u64,
+    pub soft_limits: PromptSoftLimits,
+    pub warnings: Vec<PromptLimitWarning>,
+}
+
+/// Load the prompt soft limits, falling back to the defaults
+pub fn load_prompt_soft_limits(conn: &Connection) -> PromptSoftLimits {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![PROMPT_SOFT_LIMITS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| serde_json::from_str(&value).ok())
+    .unwrap_or_default()
+}
+
+/// Approximate token count of a text
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(CHARS_PER_TOKEN)
+}
+
+fn component(kind: PromptComponentKind, path: Option<String>, text: &str) -> PromptComponent {
+    PromptComponent {
+        kind,
+        path,
+        chars: text.chars().count() as u64,
+        estimated_tokens: estimate_tokens(text),
+    }
+}
+
+/// Full model name for the aliases agents are stored with, for pricing
+fn pricing_model(model: &str) -> &str {
+    match model {
+        "opus" => "claude-opus-4",
+        "sonnet" => "claude-sonnet-4",
+        other => other,
+    }
+}
+
+/// Estimate the prompt components of a run and flag the ones above their limit
+pub fn analyze_prompt(
+    agent_id: i64,
+    model: &str,
+    system_prompt: &str,
+    task: &str,
+    claude_md: &[(String, String)],
+    soft_limits: PromptSoftLimits,
+) -> PromptAnalysis {
+    let mut components = vec![
+        component(PromptComponentKind::SystemPrompt, None, system_prompt),
+        component(PromptComponentKind::Task, None, task),
+    ];
+    components.extend(claude_md.iter().map(|(path, content)| {
+        component(PromptComponentKind::ClaudeMd, Some(path.clone()), content)
+    }));
+
+    let tokens_of = |kind: PromptComponentKind| -> u64 {
+        components
+            .iter()
+            .filter(|c| c.kind == kind)
+            .map(|c| c.estimated_tokens)
+            .sum()
+    };
+    let total_tokens: u64 = components.iter().map(|c| c.estimated_tokens).sum();
+    let mut warnings = Vec::new();
+    for (name, estimated_tokens, limit) in [
+        (
+            "system_prompt",
+            tokens_of(PromptComponentKind::SystemPrompt),
+            soft_limits.system_prompt_tokens,
+        ),
+        ("task", tokens_of(PromptComponentKind::Task), soft_limits.task_tokens),
+        ("claude_md", tokens_of(PromptComponentKind::ClaudeMd), soft_limits.claude_md_tokens),
+        ("total", total_tokens, soft_limits.total_tokens),
+    ] {
+        if estimated_tokens > limit {
+            warnings.push(PromptLimitWarning {
+                component: name.to_string(),
+                estimated_tokens,
+                limit,
+            });
+        }
+    }
+
+    let estimated_input_cost_usd = cost_of_usage(
+        pricing_model(model),
+        &serde_json::json!({ "input_tokens": total_tokens }),
+    );
+    PromptAnalysis {
+        agent_id,
+        model: model.to_string(),
+        components,
+        total_tokens,
+        estimated_input_cost_usd,
+        chars_per_token: CHARS_PER_TOKEN,
+        soft_limits,
+        warnings,
+    }
+}
+
+/// How destructive a prompt looks
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptRiskLevel {
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+/// A phrase that marks a risky intent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskKeyword {
+    pub keyword: String,
+    /// e.g. `delete`, `rewrite`, `migrate`, `force_push`
+    pub category: String,
+    pub level: PromptRiskLevel,
+}
+
+/// Risky-prompt keywords per locale; every list is checked, whatever the UI language
+pub type PromptRiskKeywords = BTreeMap<String, Vec<RiskKeyword>>;
+
+/// A keyword found in a prompt
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptRiskReason {
+    pub keyword: String,
+    pub category: String,
+    pub level: PromptRiskLevel,
+    pub locale: String,
+}
+
+/// Outcome of `assess_prompt_risk`
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptRisk {
+    /// The highest level among the reasons
+    pub level: PromptRiskLevel,
+    pub reasons: Vec<PromptRiskReason>,
+}
+
+/// Built-in keywords, used until the user saves their own lists
+pub fn default_prompt_risk_keywords() -> PromptRiskKeywords {
+    use PromptRiskLevel::{High, Low, Medium};
+    let list = |entries: &[(&str, &str, PromptRiskLevel)]| -> Vec<RiskKeyword> {
+        entries
+            .iter()
+            .map(|(keyword, category, level)| RiskKeyword {
+                keyword: keyword.to_string(),
+                category: category.to_string(),
+                level: *level,
+            })
+            .collect()
+    };
+    BTreeMap::from([
+        (
+            "en".to_string(),
+            list(&[
+                ("force push", "force_push", High),
+                ("push --force", "force_push", High),
+                ("push -f", "force_push", High),
+                ("reset --hard", "discard", High),
+                ("rm -rf", "delete", High),
+                ("drop table", "delete", High),
+                ("drop database", "delete", High),
+                ("delete all", "delete", High),
+                ("wipe", "delete", High),
+                ("delete", "delete", Medium),
+                ("remove", "delete", Medium),
+                ("rewrite", "rewrite", Medium),
+                ("migrate", "migrate", Medium),
+                ("migration", "migrate", Medium),
+                ("overwrite", "rewrite", Medium),
+                ("refactor", "rewrite", Low),
+                ("rename", "rewrite", Low),
+            ]),
+        ),
+        (
+            "zh-CN".to_string(),
+            list(&[
+                ("强制推送", "force_push", High),
+                ("删除所有", "delete", High),
+                ("全部删除", "delete", High),
+                ("清空", "delete", High),
+                ("删库", "delete", High),
+                ("删除", "delete", Medium),
+                ("移除", "delete", Medium),
+                ("重写", "rewrite", Medium),
+                ("覆盖", "rewrite", Medium),
+                ("迁移", "migrate", Medium),
+                ("重构", "rewrite", Low),
+                ("重命名", "rewrite", Low),
+            ]),
+        ),
+    ])
+}
+
+/// Load the risky-prompt keywords, falling back to the built-in lists
+pub fn load_prompt_risk_keywords(conn: &Connection) -> PromptRiskKeywords {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![PROMPT_RISK_KEYWORDS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| serde_json::from_str(&value).ok())
+    .unwrap_or_else(default_prompt_risk_keywords)
+}
+
+/// Whether `keyword` occurs in the lowercased `prompt`.
+///
+/// Keywords that start or end with a letter or digit only match whole words,
+/// so "remove" does not match "removed". CJK text has no word boundaries
+/// and is matched as a substring.
+fn contains_keyword(prompt: &str, keyword: &str) -> bool {
+    let keyword = keyword.trim().to_lowercase();
+    if keyword.is_empty() {
+        return false;
+    }
+    let is_word_char = |c: char| c.is_ascii_alphanumeric();
+    let start = if keyword.starts_with(is_word_char) { r"\b" } else { "" };
+    let end = if keyword.ends_with(is_word_char) { r"\b" } else { "" };
+    Regex::new(&format!("{}{}{}", start, regex::escape(&keyword), end))
+        .is_ok_and(|pattern| pattern.is_match(prompt))
+}
+
+/// Flag risky intents in a prompt from keyword lists. Purely local: no model
+/// or network is involved, so it can run before every prompt.
+pub fn assess_prompt_risk(prompt: &str, keywords: &PromptRiskKeywords) -> PromptRisk {
+    let prompt = prompt.to_lowercase();
+    let reasons: Vec<PromptRiskReason> = keywords
+        .iter()
+        .flat_map(|(locale, entries)| entries.iter().map(move |entry| (locale, entry)))
+        .filter(|(_, entry)| contains_keyword(&prompt, &entry.keyword))
+        .map(|(locale, entry)| PromptRiskReason {
+            keyword: entry.keyword.clone(),
+            category: entry.category.clone(),
+            level: entry.level,
+            locale: locale.clone(),
+        })
+        .collect();
+    PromptRisk {
+        level: reasons
+            .iter()
+            .map(|reason| reason.level)
+            .max()
+            .unwrap_or(PromptRiskLevel::None),
+        reasons,
+    }
+}
+
+/// Check a prompt for destructive intents before it is sent to claude
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn analyze_prompt_risk(db: State<'_, AgentDb>, prompt: String) -> Result<PromptRisk, String> {
+    let keywords = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        load_prompt_risk_keywords(&conn)
+    };
+    Ok(assess_prompt_risk(&prompt, &keywords))
+}
+
+/// Get the risky-prompt keywords per locale
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_prompt_risk_keywords(db: State<'_, AgentDb>) -> Result<PromptRiskKeywords, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(load_prompt_risk_keywords(&conn))
+}
+
+/// Save the risky-prompt keywords per locale
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_prompt_risk_keywords(
+    db: State<'_, AgentDb>,
+    keywords: PromptRiskKeywords,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let value = serde_json::to_string(&keywords).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![PROMPT_RISK_KEYWORDS_KEY, value],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+/// Estimate the size and first-turn input cost of running an agent on a task
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn analyze_agent_prompt(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    task: String,
+    project_path: String,
+) -> Result<PromptAnalysis, String> {
+    let (agent, soft_limits) = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        (query_agent(&conn, agent_id)?, load_prompt_soft_limits(&conn))
+    };
+
+    let claude_md: Vec<(String, String)> = effective_claude_md_files(Path::new(&project_path))
+        .into_iter()
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            Some((path.to_string_lossy().to_string(), content))
+        })
+        .collect();
+
+    Ok(analyze_prompt(
+        agent_id,
+        &agent.model,
+        &agent.system_prompt,
+        &task,
+        &claude_md,
+        soft_limits,
+    ))
+}
+
+/// Get the prompt soft limits
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_prompt_soft_limits(db: State<'_, AgentDb>) -> Result<PromptSoftLimits, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(load_prompt_soft_limits(&conn))
+}
+
+/// Save the prompt soft limits
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_prompt_soft_limits(
+    db: State<'_, AgentDb>,
+    limits: PromptSoftLimits,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let value = serde_json::to_string(&limits).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![PROMPT_SOFT_LIMITS_KEY, value],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_are_estimated_and_limits_flagged() {
+        let system_prompt = "x".repeat(400);
+        let claude_md = vec![
+            ("/home/u/.claude/CLAUDE.md".to_string(), "y".repeat(40)),
+            ("/work/app/CLAUDE.md".to_string(), "z".repeat(41)),
+        ];
+        let limits = PromptSoftLimits {
+            system_prompt_tokens: 50,
+            claude_md_tokens: 20,
+            ..Default::default()
+        };
+
+        let analysis = analyze_prompt(1, "opus", &system_prompt, "fix it", &claude_md, limits);
+        let tokens: Vec<u64> = analysis.components.iter().map(|c| c.estimated_tokens).collect();
+        assert_eq!(tokens, vec![100, 2, 10, 11]);
+        assert_eq!(analysis.total_tokens, 123);
+        let flagged: Vec<&str> = analysis.warnings.iter().map(|w| w.component.as_str()).collect();
+        assert_eq!(flagged, vec!["system_prompt", "claude_md"]);
+        // Opus input costs more than Sonnet input
+        let sonnet =
+            analyze_prompt(1, "sonnet", &system_prompt, "fix it", &claude_md, Default::default());
+        assert!(analysis.estimated_input_cost_usd > sonnet.estimated_input_cost_usd);
+        assert!(sonnet.warnings.is_empty());
+    }
+
+    #[test]
+    fn risky_prompts_are_flagged_in_english_and_chinese() {
+        let keywords = default_prompt_risk_keywords();
+
+        let risk = assess_prompt_risk("Rewrite the parser, then git push --force", &keywords);
+        assert_eq!(risk.level, PromptRiskLevel::High);
+        let categories: Vec<&str> = risk.reasons.iter().map(|r| r.category.as_str()).collect();
+        assert_eq!(categories, vec!["force_push", "rewrite"]);
+
+        // Whole words only: "removed" is not "remove"
+        let risk = assess_prompt_risk("Explain why the test was removed", &keywords);
+        assert_eq!(risk.level, PromptRiskLevel::None);
+
+        let risk = assess_prompt_risk("把旧的接口删除，并迁移数据库", &keywords);
+        assert_eq!(risk.level, PromptRiskLevel::Medium);
+        assert!(risk.reasons.iter().all(|r| r.locale == "zh-CN"));
+        assert_eq!(risk.reasons.len(), 2);
+        let risk = assess_prompt_risk("清空日志目录", &keywords);
+        assert_eq!(risk.level, PromptRiskLevel::High);
+
+        assert_eq!(assess_prompt_risk("add a unit test", &keywords).level, PromptRiskLevel::None);
+    }
+}
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use rusqlite::params;
+use rusqlite::{params, Connection};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
 
 use crate::commands::agents::AgentDb;
 
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProxySettings {
     pub http_proxy: Option<String>,
@@ -26,10 +29,15 @@ impl Default for ProxySettings {
 }
 
 /// Get proxy settings from the database
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn get_proxy_settings(db: State<'_, AgentDb>) -> Result<ProxySettings, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    
+    Ok(load_proxy_settings(&conn))
+}
+
+/// Proxy settings saved in the settings table
+pub fn load_proxy_settings(conn: &Connection) -> ProxySettings {
     let mut settings = ProxySettings::default();
     
     // Query each proxy setting
@@ -58,10 +66,11 @@ pub async fn get_proxy_settings(db: State<'_, AgentDb>) -> Result<ProxySettings,
         }
     }
     
-    Ok(settings)
+    settings
 }
 
 /// Save proxy settings to the database
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn save_proxy_settings(
     db: State<'_, AgentDb>,
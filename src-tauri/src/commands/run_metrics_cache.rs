@@ -0,0 +1,222 @@
+//! Metrics of session files, parsed once.
+//!
+//! claude only appends to a session's JSONL, so the totals of each file are
+//! kept along with how far it was parsed. A file whose size and mtime are
+//! unchanged is not parsed again, and one that grew is parsed from where the
+//! last call stopped, as long as what was parsed before is still there.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use super::agents::{AgentRunMetrics, JsonlParseReport, MetricsAccumulator};
+
+/// Session files whose parse state is kept; the least recently used go first
+const CACHED_FILES_LIMIT: usize = 256;
+
+/// Totals of the complete lines at the start of a file
+#[derive(Debug, Clone, Default)]
+struct ParsedPrefix {
+    /// Bytes parsed, always just after a `\n`
+    offset: usize,
+    /// Lines parsed, for the line numbers of malformed lines
+    lines: usize,
+    /// SHA-256 of the bytes parsed, to tell a rewritten file from a grown one
+    digest: [u8; 32],
+    totals: MetricsAccumulator,
+    report: JsonlParseReport,
+}
+
+impl ParsedPrefix {
+    /// Count one line that starts after the lines parsed so far
+    fn add_line(&mut self, line: &str) {
+        self.lines += 1;
+        let parsed = self
+            .report
+            .parse_line(self.lines, line, false)
+            .expect("lenient JSONL parsing never fails");
+        if let Some(value) = parsed {
+            self.totals.add(&value);
+        }
+    }
+}
+
+struct CachedFile {
+    size: usize,
+    modified: Option<SystemTime>,
+    prefix: ParsedPrefix,
+    metrics: AgentRunMetrics,
+    report: JsonlParseReport,
+    last_used: u64,
+}
+
+/// Parse state of recently listed session files, keyed by path
+#[derive(Clone, Default)]
+pub struct RunMetricsCache(Arc<Mutex<(u64, HashMap<PathBuf, CachedFile>)>>);
+
+/// Metrics of `content`, continuing from `prefix` when it still fits.
+///
+/// Returns the prefix extended through the last complete line; a trailing
+/// partial line counts towards the metrics but is parsed again next time.
+fn parse_from(
+    prefix: Option<ParsedPrefix>,
+    content: &str,
+) -> (ParsedPrefix, AgentRunMetrics, JsonlParseReport) {
+    let mut prefix = prefix
+        .filter(|prefix| {
+            prefix.offset <= content.len()
+                && content.is_char_boundary(prefix.offset)
+                && Sha256::digest(&content.as_bytes()[..prefix.offset])[..] == prefix.digest
+        })
+        .unwrap_or_default();
+    if prefix.offset == 0 && content.starts_with('\u{feff}') {
+        prefix.offset = '\u{feff}'.len_utf8();
+    }
+
+    let complete_end = content[prefix.offset..]
+        .rfind('\n')
+        .map_or(prefix.offset, |newline| prefix.offset + newline + 1);
+    for line in content[prefix.offset..complete_end].split_terminator('\n') {
+        prefix.add_line(line);
+    }
+    prefix.offset = complete_end;
+    prefix.digest = Sha256::digest(&content.as_bytes()[..complete_end]).into();
+
+    let mut with_tail = prefix.clone();
+    with_tail.add_line(&content[complete_end..]);
+    (prefix, with_tail.totals.metrics(), with_tail.report)
+}
+
+impl RunMetricsCache {
+    /// Metrics of the session file at `path`, whose current `content` was
+    /// read with mtime `modified`.
+    ///
+    /// In strict mode a malformed line fails the call, as it does for
+    /// `AgentRunMetrics::from_jsonl_with_report`.
+    pub fn metrics(
+        &self,
+        path: &Path,
+        content: &str,
+        modified: Option<SystemTime>,
+        strict: bool,
+    ) -> Result<(AgentRunMetrics, JsonlParseReport), String> {
+        let cached = {
+            let mut cache = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            let (clock, files) = &mut *cache;
+            *clock += 1;
+            match files.get_mut(path) {
+                Some(file) if file.size == content.len() && file.modified == modified => {
+                    file.last_used = *clock;
+                    return strict_check(file.metrics.clone(), file.report.clone(), strict);
+                }
+                // Parsed outside the lock; whatever the file became, it is replaced
+                _ => files.remove(path).map(|file| file.prefix),
+            }
+        };
+
+        let (prefix, metrics, report) = parse_from(cached, content);
+
+        let mut cache = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let (clock, files) = &mut *cache;
+        if files.len() >= CACHED_FILES_LIMIT {
+            if let Some(oldest) = files
+                .iter()
+                .min_by_key(|(_, file)| file.last_used)
+                .map(|(path, _)| path.clone())
+            {
+                files.remove(&oldest);
+            }
+        }
+        files.insert(
+            path.to_path_buf(),
+            CachedFile {
+                size: content.len(),
+                modified,
+                prefix,
+                metrics: metrics.clone(),
+                report: report.clone(),
+                last_used: *clock,
+            },
+        );
+        drop(cache);
+
+        strict_check(metrics, report, strict)
+    }
+}
+
+/// Fail with the first malformed line in strict mode
+fn strict_check(
+    metrics: AgentRunMetrics,
+    report: JsonlParseReport,
+    strict: bool,
+) -> Result<(AgentRunMetrics, JsonlParseReport), String> {
+    match report.samples.first() {
+        Some(malformed) if strict => Err(malformed.strict_error()),
+        _ => Ok((metrics, report)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn line(tokens: i64) -> String {
+        format!(r#"{{"type":"assistant","message":{{"usage":{{"input_tokens":{},"output_tokens":0}}}}}}"#, tokens)
+    }
+
+    #[test]
+    fn appended_lines_extend_the_cached_totals() {
+        let cache = RunMetricsCache::default();
+        let path = Path::new("/sessions/s1.jsonl");
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let t1 = t0 + Duration::from_secs(1);
+
+        let mut content = format!("\u{feff}{}\nnot json\n{}", line(1), &line(2)[..10]);
+        let (metrics, report) = cache.metrics(path, &content, Some(t0), false).unwrap();
+        assert_eq!((metrics.total_tokens, report.skipped_lines), (Some(1), 2));
+        assert!(cache.metrics(path, &content, Some(t0), true).unwrap_err().contains("line 2"));
+
+        // The partial line is completed and more is appended
+        content = format!("\u{feff}{}\nnot json\n{}\n{}\n", line(1), line(2), line(4));
+        let (metrics, report) = cache.metrics(path, &content, Some(t1), false).unwrap();
+        assert_eq!(metrics.total_tokens, Some(7));
+        assert_eq!(metrics.message_count, Some(3));
+        assert_eq!((report.total_lines, report.skipped_lines), (4, 1));
+
+        let (expected, expected_report) = AgentRunMetrics::from_jsonl_with_report(&content, false).unwrap();
+        assert_eq!(metrics.total_tokens, expected.total_tokens);
+        assert_eq!(report.samples[0].line_number, expected_report.samples[0].line_number);
+
+        // The next call starts after the last complete line; a file that
+        // shrank was rewritten and is parsed from the start
+        let offset = {
+            let cache = cache.0.lock().unwrap();
+            cache.1[path].prefix.offset
+        };
+        assert_eq!(offset, content.len());
+        let rewritten = format!("{}\n", line(100));
+        assert_eq!(cache.metrics(path, &content, Some(t1), false).unwrap().0.total_tokens, Some(7));
+        assert_eq!(cache.metrics(path, &rewritten, Some(t1), false).unwrap().0.total_tokens, Some(100));
+    }
+
+    #[test]
+    fn rewritten_files_are_parsed_again() {
+        let cache = RunMetricsCache::default();
+        let path = Path::new("/sessions/s2.jsonl");
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let t1 = t0 + Duration::from_secs(1);
+
+        let content = format!("{}\n", line(1));
+        assert_eq!(cache.metrics(path, &content, Some(t0), false).unwrap().0.total_tokens, Some(1));
+
+        // Longer, with a first line of the same length but different content
+        let rewritten = format!("{}\n{}\n", line(5), line(2));
+        let (metrics, report) = cache.metrics(path, &rewritten, Some(t1), false).unwrap();
+        assert_eq!(metrics.total_tokens, Some(7));
+        assert_eq!(report.total_lines, 2);
+    }
+}
@@ -0,0 +1,450 @@
+use rusqlite::{params, Batch, Connection, ErrorCode, OpenFlags};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+use super::storage::{json_to_sql_value, sql_value_to_json};
+
+/// Rows returned by a saved query before the result is truncated
+const MAX_QUERY_ROWS: usize = 1000;
+
+/// Time a saved query may run before it is interrupted
+const QUERY_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// Example queries that ship with the app, as (name, description, sql)
+const BUILTIN_QUERIES: &[(&str, &str, &str)] = &[
+    (
+        "top_agents_by_cost",
+        "Agents with the highest recorded cost (:limit, default 10)",
+        "SELECT r.agent_name, COUNT(*) AS runs, ROUND(SUM(m.cost_usd), 4) AS total_cost_usd
+         FROM agent_runs r JOIN run_metrics m ON m.run_id = r.id
+         GROUP BY r.agent_name ORDER BY total_cost_usd DESC LIMIT COALESCE(:limit, 10)",
+    ),
+    (
+        "failure_rate_by_model",
+        "Share of finished runs that failed, per model",
+        "SELECT model, COUNT(*) AS runs, SUM(status = 'failed') AS failed,
+                ROUND(100.0 * SUM(status = 'failed') / COUNT(*), 1) AS failure_rate_pct
+         FROM agent_runs WHERE status IN ('completed', 'failed')
+         GROUP BY model ORDER BY failure_rate_pct DESC",
+    ),
+    (
+        "runs_per_weekday",
+        "Number of agent runs started on each day of the week (UTC)",
+        "SELECT CASE strftime('%w', created_at)
+                    WHEN '0' THEN 'Sunday' WHEN '1' THEN 'Monday' WHEN '2' THEN 'Tuesday'
+                    WHEN '3' THEN 'Wednesday' WHEN '4' THEN 'Thursday' WHEN '5' THEN 'Friday'
+                    ELSE 'Saturday' END AS weekday,
+                COUNT(*) AS runs
+         FROM agent_runs GROUP BY strftime('%w', created_at) ORDER BY strftime('%w', created_at)",
+    ),
+];
+
+/// A named read-only query over the app database
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub name: String,
+    pub description: Option<String>,
+    pub sql: String,
+    /// Shipped with the app; cannot be changed or deleted
+    pub builtin: bool,
+    pub updated_at: Option<String>,
+}
+
+/// Rows returned by a saved query
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<JsonValue>>,
+    /// More rows matched than `MAX_QUERY_ROWS`
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// First SQL keyword, skipping whitespace and comments
+fn first_keyword(sql: &str) -> String {
+    let mut rest = sql.trim_start();
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map(|(_, after)| after).unwrap_or("").trim_start();
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map(|(_, after)| after).unwrap_or("").trim_start();
+        } else {
+            break;
+        }
+    }
+    rest.chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+/// Check that `sql` is exactly one statement and that it only reads
+pub fn validate_select(conn: &Connection, sql: &str) -> Result<(), String> {
+    let invalid = |detail: &str| i18n::error(ErrorKind::InvalidInput, detail);
+
+    if !matches!(first_keyword(sql).as_str(), "SELECT" | "WITH") {
+        return Err(invalid("saved queries must be a SELECT statement"));
+    }
+    let mut batch = Batch::new(conn, sql);
+    let statement = batch
+        .next()
+        .map_err(|e| invalid(&e.to_string()))?
+        .ok_or_else(|| invalid("empty query"))?;
+    // Catches WITH ... INSERT/UPDATE/DELETE
+    if !statement.readonly() {
+        return Err(invalid("saved queries must not modify the database"));
+    }
+    if batch.next().map_err(|e| invalid(&e.to_string()))?.is_some() {
+        return Err(invalid("saved queries must be a single statement"));
+    }
+    Ok(())
+}
+
+/// Run a validated SELECT on `conn` with a row and time limit.
+///
+/// The connection is switched to `query_only` first, so even a statement that
+/// slipped past validation cannot write.
+pub fn run_read_only_query(
+    conn: &Connection,
+    sql: &str,
+    params: &HashMap<String, JsonValue>,
+    max_rows: usize,
+    time_limit: Duration,
+) -> Result<SavedQueryResult, String> {
+    validate_select(conn, sql)?;
+    conn.execute_batch("PRAGMA query_only = ON")
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| i18n::error(ErrorKind::InvalidInput, e))?;
+    for (name, value) in params {
+        let key = if name.starts_with([':', '@', '$']) {
+            name.clone()
+        } else {
+            format!(":{}", name)
+        };
+        let index = stmt
+            .parameter_index(&key)
+            .map_err(|e| i18n::error(ErrorKind::InvalidInput, e))?
+            .ok_or_else(|| {
+                i18n::error(ErrorKind::InvalidInput, format!("unknown query parameter '{}'", name))
+            })?;
+        let value = json_to_sql_value(value).map_err(|e| i18n::error(ErrorKind::InvalidInput, e))?;
+        stmt.raw_bind_parameter(index, value)
+            .map_err(|e| i18n::error(ErrorKind::InvalidInput, e))?;
+    }
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+
+    // Interrupt the query from another thread once the time limit is up
+    let interrupt = conn.get_interrupt_handle();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let watchdog = std::thread::spawn(move || {
+        if done_rx.recv_timeout(time_limit) == Err(mpsc::RecvTimeoutError::Timeout) {
+            interrupt.interrupt();
+        }
+    });
+
+    let started = Instant::now();
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    let collected = (|| {
+        let mut query = stmt.raw_query();
+        while let Some(row) = query.next()? {
+            if rows.len() == max_rows {
+                truncated = true;
+                break;
+            }
+            let values = (0..columns.len())
+                .map(|i| row.get_ref(i).map(sql_value_to_json))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows.push(values);
+        }
+        Ok::<_, rusqlite::Error>(())
+    })();
+    drop(done_tx);
+    let _ = watchdog.join();
+
+    match collected {
+        Ok(()) => Ok(SavedQueryResult {
+            columns,
+            rows,
+            truncated,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::OperationInterrupted => {
+            Err(i18n::error(
+                ErrorKind::InvalidInput,
+                format!("query exceeded the {:?} time limit", time_limit),
+            ))
+        }
+        Err(e) => Err(i18n::error(ErrorKind::Database, e)),
+    }
+}
+
+fn builtin_query(name: &str) -> Option<SavedQuery> {
+    BUILTIN_QUERIES
+        .iter()
+        .find(|(builtin, _, _)| *builtin == name)
+        .map(|(name, description, sql)| SavedQuery {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            sql: sql.to_string(),
+            builtin: true,
+            updated_at: None,
+        })
+}
+
+/// List the built-in queries followed by the user's, by name
+pub fn query_saved_queries(conn: &Connection) -> rusqlite::Result<Vec<SavedQuery>> {
+    let mut queries: Vec<SavedQuery> = BUILTIN_QUERIES
+        .iter()
+        .filter_map(|(name, _, _)| builtin_query(name))
+        .collect();
+    let mut stmt = conn
+        .prepare("SELECT name, description, sql, updated_at FROM saved_queries ORDER BY name")?;
+    let saved = stmt.query_map([], |row| {
+        Ok(SavedQuery {
+            name: row.get(0)?,
+            description: row.get(1)?,
+            sql: row.get(2)?,
+            builtin: false,
+            updated_at: row.get(3)?,
+        })
+    })?;
+    for query in saved {
+        queries.push(query?);
+    }
+    Ok(queries)
+}
+
+/// Look up a built-in or saved query by name
+pub fn find_saved_query(conn: &Connection, name: &str) -> Result<SavedQuery, String> {
+    query_saved_queries(conn)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?
+        .into_iter()
+        .find(|query| query.name == name)
+        .ok_or_else(|| i18n::error(ErrorKind::InvalidInput, format!("no saved query named '{}'", name)))
+}
+
+/// Store a user query after checking it is a single read-only SELECT
+pub fn upsert_saved_query(
+    conn: &Connection,
+    name: &str,
+    sql: &str,
+    description: Option<&str>,
+) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(i18n::error(ErrorKind::InvalidInput, "query name is empty"));
+    }
+    if builtin_query(name).is_some() {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("'{}' is a built-in query", name),
+        ));
+    }
+    validate_select(conn, sql)?;
+    conn.execute(
+        "INSERT INTO saved_queries (name, description, sql) VALUES (?1, ?2, ?3)
+         ON CONFLICT(name) DO UPDATE SET
+            description = excluded.description, sql = excluded.sql, updated_at = CURRENT_TIMESTAMP",
+        params![name, description, sql],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+/// List built-in and user saved queries
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn list_saved_queries(db: State<'_, AgentDb>) -> Result<Vec<SavedQuery>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    query_saved_queries(&conn).map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Create or replace a user saved query
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn save_saved_query(
+    db: State<'_, AgentDb>,
+    name: String,
+    sql: String,
+    description: Option<String>,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    upsert_saved_query(&conn, &name, &sql, description.as_deref())
+}
+
+/// Delete a user saved query
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn delete_saved_query(db: State<'_, AgentDb>, name: String) -> Result<(), String> {
+    if builtin_query(&name).is_some() {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("'{}' is a built-in query", name),
+        ));
+    }
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let rows = conn
+        .execute("DELETE FROM saved_queries WHERE name = ?1", params![name])
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    if rows == 0 {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!("no saved query named '{}'", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Run a saved query on a read-only connection to the app database
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn run_saved_query(
+    app: AppHandle,
+    name: String,
+    params: Option<HashMap<String, JsonValue>>,
+) -> Result<SavedQueryResult, String> {
+    let query = {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        find_saved_query(&conn, &name)?
+    };
+    let db_path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| i18n::error(ErrorKind::PathNotFound, e))?
+        .join("agents.db");
+
+    tokio::task::spawn_blocking(move || {
+        let conn = Connection::open_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        run_read_only_query(
+            &conn,
+            &query.sql,
+            &params.unwrap_or_default(),
+            MAX_QUERY_ROWS,
+            QUERY_TIME_LIMIT,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::commands::agents::init_schema(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn only_single_select_statements_are_accepted() {
+        let conn = test_conn();
+        assert!(validate_select(&conn, "SELECT name FROM agents").is_ok());
+        assert!(validate_select(&conn, "-- note\n/* c */ with t AS (SELECT 1) SELECT * FROM t;").is_ok());
+
+        let rejected = [
+            "INSERT INTO agents (name, icon, system_prompt) VALUES ('x', 'y', 'z')",
+            "PRAGMA query_only = OFF",
+            "SELECT 1; DROP TABLE agents",
+            "SELECT 1; PRAGMA query_only = OFF",
+            "WITH t AS (SELECT 1) DELETE FROM agents",
+            "/* SELECT */ DELETE FROM agents",
+            "ATTACH DATABASE 'other.db' AS other",
+            "",
+        ];
+        for sql in rejected {
+            assert!(validate_select(&conn, sql).is_err(), "accepted: {}", sql);
+        }
+        assert!(upsert_saved_query(&conn, "wipe", "DELETE FROM agents", None).is_err());
+        assert!(upsert_saved_query(&conn, "runs_per_weekday", "SELECT 1", None).is_err());
+    }
+
+    #[test]
+    fn queries_run_read_only_with_params_and_row_limit() {
+        let conn = test_conn();
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt) VALUES ('A', 'bot', 'p')",
+            [],
+        )
+        .unwrap();
+        for status in ["completed", "failed", "completed"] {
+            conn.execute(
+                "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status)
+                 VALUES (1, 'A', 'bot', 't', 'sonnet', '/p', '', ?1)",
+                params![status],
+            )
+            .unwrap();
+        }
+        upsert_saved_query(
+            &conn,
+            "by_status",
+            "SELECT status, COUNT(*) AS runs FROM agent_runs WHERE status = :status GROUP BY status",
+            Some("Runs with a status"),
+        )
+        .unwrap();
+
+        let query = find_saved_query(&conn, "by_status").unwrap();
+        let params = HashMap::from([("status".to_string(), JsonValue::from("completed"))]);
+        let result =
+            run_read_only_query(&conn, &query.sql, &params, 10, QUERY_TIME_LIMIT).unwrap();
+        assert_eq!(result.columns, ["status", "runs"]);
+        assert_eq!(result.rows, [vec![JsonValue::from("completed"), JsonValue::from(2)]]);
+
+        let unknown = HashMap::from([("nope".to_string(), JsonValue::from(1))]);
+        assert!(run_read_only_query(&conn, &query.sql, &unknown, 10, QUERY_TIME_LIMIT).is_err());
+
+        let builtin = find_saved_query(&conn, "failure_rate_by_model").unwrap();
+        let result =
+            run_read_only_query(&conn, &builtin.sql, &HashMap::new(), 10, QUERY_TIME_LIMIT).unwrap();
+        assert_eq!(result.rows[0][2], JsonValue::from(1));
+
+        let result = run_read_only_query(
+            &conn,
+            "SELECT id FROM agent_runs",
+            &HashMap::new(),
+            2,
+            QUERY_TIME_LIMIT,
+        )
+        .unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert!(result.truncated);
+
+        // The connection stays query-only afterwards
+        assert!(conn.execute("DELETE FROM agent_runs", []).is_err());
+    }
+
+    #[test]
+    fn long_queries_are_interrupted() {
+        let conn = test_conn();
+        let endless = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT count(*) FROM n";
+        let err = run_read_only_query(
+            &conn,
+            endless,
+            &HashMap::new(),
+            10,
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert!(err.contains("time limit"), "{}", err);
+    }
+}
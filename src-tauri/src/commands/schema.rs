@@ -0,0 +1,10 @@
+use serde_json::Value;
+use crate::api_schema::api_command;
+use macro_rules_attribute::apply;
+
+/// Describe every registered command, its parameters and payload types
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn generate_api_schema() -> Result<Value, String> {
+    Ok(crate::api_schema::generate())
+}
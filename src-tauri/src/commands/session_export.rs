@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::fs;
+use std::path::Path;
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{AgentDb, JsonlParseReport};
+use super::i18n::{self, ErrorKind};
+use super::redaction::{stored_redactor, Redactor};
+use crate::checkpoint::storage::write_atomically;
+
+/// Content removed from a session before it is exported
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionExportFilters {
+    /// `thinking` and `redacted_thinking` blocks
+    pub exclude_thinking: bool,
+    /// `tool_use` blocks, i.e. the tool calls and their inputs
+    pub exclude_tool_inputs: bool,
+    /// `tool_result` blocks and the `toolUseResult` detail of user messages
+    pub exclude_tool_results: bool,
+    /// `image` and `document` blocks
+    pub exclude_attachments: bool,
+}
+
+/// Output format of a session export
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionExportFormat {
+    /// The filtered JSONL messages with their original structure, plus the manifest
+    Json,
+    /// A readable transcript
+    Markdown,
+}
+
+/// How many items each filter removed
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionExportRemoved {
+    pub thinking: usize,
+    pub tool_inputs: usize,
+    pub tool_results: usize,
+    pub attachments: usize,
+    /// Messages left without content by the filters and dropped
+    pub messages: usize,
+}
+
+/// Describes an export: what was filtered and how much was removed
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionExportManifest {
+    pub project_id: String,
+    pub session_id: String,
+    pub exported_at: String,
+    pub format: SessionExportFormat,
+    pub filters: SessionExportFilters,
+    pub removed: SessionExportRemoved,
+    pub message_count: usize,
+}
+
+/// Which filter, if any, removes a content block
+fn block_filter<'a>(
+    block: &JsonValue,
+    filters: &SessionExportFilters,
+    removed: &'a mut SessionExportRemoved,
+) -> Option<&'a mut usize> {
+    match block.get("type").and_then(|t| t.as_str())? {
+        "thinking" | "redacted_thinking" if filters.exclude_thinking => Some(&mut removed.thinking),
+        "tool_use" | "server_tool_use" if filters.exclude_tool_inputs => {
+            Some(&mut removed.tool_inputs)
+        }
+        "tool_result" if filters.exclude_tool_results => Some(&mut removed.tool_results),
+        "image" | "document" if filters.exclude_attachments => Some(&mut removed.attachments),
+        _ => None,
+    }
+}
+
+/// Remove filtered blocks from one message; returns false when nothing is left
+fn filter_message(
+    message: &mut JsonValue,
+    filters: &SessionExportFilters,
+    removed: &mut SessionExportRemoved,
+) -> bool {
+    if filters.exclude_tool_results {
+        if let Some(object) = message.as_object_mut() {
+            object.remove("toolUseResult");
+        }
+    }
+
+    let Some(content) = message
+        .pointer_mut("/message/content")
+        .and_then(|content| content.as_array_mut())
+    else {
+        return true;
+    };
+    if content.is_empty() {
+        return true;
+    }
+    content.retain(|block| match block_filter(block, filters, removed) {
+        Some(count) => {
+            *count += 1;
+            false
+        }
+        None => true,
+    });
+    !content.is_empty()
+}
+
+/// Apply the filters to every message, dropping the ones left empty
+pub fn filter_messages(
+    messages: Vec<JsonValue>,
+    filters: &SessionExportFilters,
+) -> (Vec<JsonValue>, SessionExportRemoved) {
+    let mut removed = SessionExportRemoved::default();
+    let mut kept = Vec::with_capacity(messages.len());
+    for mut message in messages {
+        if filter_message(&mut message, filters, &mut removed) {
+            kept.push(message);
+        } else {
+            removed.messages += 1;
+        }
+    }
+    (kept, removed)
+}
+
+/// Text of a `tool_result` block, whose content is a string or a list of blocks
+fn tool_result_text(block: &JsonValue) -> String {
+    match block.get("content") {
+        Some(JsonValue::String(text)) => text.clone(),
+        Some(JsonValue::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Render filtered messages as a Markdown transcript
+pub fn render_markdown(manifest: &SessionExportManifest, messages: &[JsonValue]) -> String {
+    let mut markdown = format!(
+        "# Session {}\n\n**Project:** {}\n**Exported:** {}\n\n---\n\n",
+        manifest.session_id, manifest.project_id, manifest.exported_at
+    );
+
+    for message in messages {
+        let role = match message.get("type").and_then(|t| t.as_str()) {
+            Some("user") => "User",
+            Some("assistant") => "Assistant",
+            _ => continue,
+        };
+        let blocks = match message.pointer("/message/content") {
+            Some(JsonValue::String(text)) => vec![serde_json::json!({"type": "text", "text": text})],
+            Some(JsonValue::Array(blocks)) => blocks.clone(),
+            _ => continue,
+        };
+
+        markdown.push_str(&format!("## {}\n\n", role));
+        for block in &blocks {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") => {
+                    let text = block.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                    markdown.push_str(&format!("{}\n\n", text));
+                }
+                Some("thinking") => {
+                    let thinking = block.get("thinking").and_then(|t| t.as_str()).unwrap_or("");
+                    for line in thinking.lines() {
+                        markdown.push_str(&format!("> {}\n", line));
+                    }
+                    markdown.push('\n');
+                }
+                Some("tool_use") => {
+                    let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("tool");
+                    let input = block
+                        .get("input")
+                        .map(|input| serde_json::to_string_pretty(input).unwrap_or_default())
+                        .unwrap_or_default();
+                    markdown.push_str(&format!("**Tool:** `{}`\n\n```json\n{}\n```\n\n", name, input));
+                }
+                Some("tool_result") => {
+                    markdown.push_str(&format!(
+                        "**Tool result:**\n\n```\n{}\n```\n\n",
+                        tool_result_text(block)
+                    ));
+                }
+                Some("image") | Some("document") => markdown.push_str("*[attachment]*\n\n"),
+                _ => {}
+            }
+        }
+    }
+    markdown
+}
+
+/// Export a session to `path` with the given content removed.
+///
+/// Filtering and rendering happen here so the removed content never reaches
+/// the frontend; the stored redaction patterns are applied to the output.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn export_session(
+    db: State<'_, AgentDb>,
+    project_id: String,
+    session_id: String,
+    format: SessionExportFormat,
+    filters: SessionExportFilters,
+    path: String,
+) -> Result<SessionExportManifest, String> {
+    let session_path = crate::claude_dir::claude_dir()?
+        .join("projects")
+        .join(&project_id)
+        .join(format!("{}.jsonl", session_id));
+    if !session_path.exists() {
+        return Err(i18n::error(ErrorKind::SessionNotFound, session_id));
+    }
+    let content = fs::read(&session_path).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+    // The rendered output is redacted as a whole below
+    let (messages, _) = JsonlParseReport::parse_bytes(&content, &Redactor::disabled(), false)?;
+
+    let (messages, removed) = filter_messages(messages, &filters);
+    let manifest = SessionExportManifest {
+        project_id,
+        session_id,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        format,
+        filters,
+        removed,
+        message_count: messages.len(),
+    };
+    let output = match format {
+        SessionExportFormat::Json => serde_json::to_string_pretty(&serde_json::json!({
+            "manifest": manifest,
+            "messages": messages,
+        }))
+        .map_err(|e| e.to_string())?,
+        SessionExportFormat::Markdown => render_markdown(&manifest, &messages),
+    };
+
+    let redactor = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        stored_redactor(&conn)
+    };
+    write_atomically(Path::new(&path), redactor.redact(&output).as_bytes())
+        .map_err(|e| i18n::error(ErrorKind::FileWrite, e))?;
+    log::info!(
+        "Exported session {} to {} ({} messages)",
+        manifest.session_id,
+        path,
+        manifest.message_count
+    );
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn filters_remove_blocks_and_keep_structure() {
+        let messages = vec![
+            json!({"type": "assistant", "uuid": "a1", "message": {"role": "assistant", "content": [
+                {"type": "thinking", "thinking": "secret plan"},
+                {"type": "text", "text": "Running ls"},
+                {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "ls"}},
+            ]}}),
+            json!({"type": "user", "uuid": "u1", "toolUseResult": {"stdout": "a.txt"}, "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "t1", "content": "a.txt"},
+            ]}}),
+            json!({"type": "user", "uuid": "u2", "message": {"role": "user", "content": "thanks"}}),
+        ];
+        let filters = SessionExportFilters {
+            exclude_thinking: true,
+            exclude_tool_results: true,
+            ..Default::default()
+        };
+
+        let (kept, removed) = filter_messages(messages, &filters);
+        assert_eq!(
+            removed,
+            SessionExportRemoved { thinking: 1, tool_results: 1, messages: 1, ..Default::default() }
+        );
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0]["uuid"], "a1");
+        let content = kept[0]["message"]["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[1]["input"]["command"], "ls");
+        assert_eq!(kept[1]["message"]["content"], "thanks");
+    }
+}
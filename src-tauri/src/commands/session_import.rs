@@ -0,0 +1,251 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{AgentDb, JsonlParseReport};
+use super::claude::{
+    encode_project_path, extract_first_user_message, find_project_dir, normalize_project_path,
+    Session,
+};
+use super::i18n::{self, ErrorKind};
+use crate::checkpoint::storage::write_atomically;
+
+/// app_settings key holding the largest file imported without `force`, in bytes
+pub const SESSION_IMPORT_MAX_BYTES_KEY: &str = "session_import_max_bytes";
+
+/// Default import size limit
+pub const DEFAULT_SESSION_IMPORT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Outcome of importing a session file
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionImport {
+    pub session: Session,
+    /// Lines that were not valid JSON and were left out
+    pub skipped_lines: usize,
+    /// The file's session ID, when it was replaced because it was missing,
+    /// malformed or already used in the target project
+    pub original_session_id: Option<String>,
+}
+
+/// A transcript ready to be written into a project directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedImport {
+    pub session_id: String,
+    pub content: String,
+    pub skipped_lines: usize,
+    pub original_session_id: Option<String>,
+}
+
+/// Whether a JSONL entry is a conversation message
+fn is_conversation_message(value: &JsonValue) -> bool {
+    matches!(
+        value.get("type").and_then(|t| t.as_str()),
+        Some("user") | Some("assistant")
+    ) && value.get("message").is_some()
+}
+
+/// Parse and rewrite a transcript for `project_path`.
+///
+/// The session ID is taken from the file unless it is not a UUID or
+/// `id_taken` reports it as used, in which case a new one is generated.
+/// Every entry's `sessionId` and `cwd` are set to the new session and
+/// project, which is how claude and `list_projects` map a session to its
+/// project.
+pub fn prepare_import(
+    content: &str,
+    project_path: &str,
+    id_taken: impl Fn(&str) -> bool,
+) -> Result<PreparedImport, String> {
+    let (mut values, report) = JsonlParseReport::parse(content, false)?;
+    if !values.iter().any(is_conversation_message) {
+        return Err(i18n::error(
+            ErrorKind::InvalidInput,
+            format!(
+                "no user or assistant messages in {} lines ({} unparseable)",
+                report.total_lines, report.skipped_lines
+            ),
+        ));
+    }
+
+    let file_session_id = values
+        .iter()
+        .find_map(|value| value.get("sessionId").and_then(|id| id.as_str()))
+        .map(str::to_string);
+    let (session_id, original_session_id) = match file_session_id {
+        Some(id) if uuid::Uuid::parse_str(&id).is_ok() && !id_taken(&id) => (id, None),
+        other => (uuid::Uuid::new_v4().to_string(), other),
+    };
+
+    let mut lines = Vec::with_capacity(values.len());
+    for value in values.iter_mut() {
+        if let Some(entry) = value.as_object_mut() {
+            if entry.contains_key("sessionId") {
+                entry.insert("sessionId".to_string(), JsonValue::from(session_id.as_str()));
+            }
+            if entry.contains_key("cwd") {
+                entry.insert("cwd".to_string(), JsonValue::from(project_path));
+            }
+        }
+        lines.push(serde_json::to_string(value).map_err(|e| e.to_string())?);
+    }
+
+    Ok(PreparedImport {
+        session_id,
+        content: lines.join("\n") + "\n",
+        skipped_lines: report.skipped_lines,
+        original_session_id,
+    })
+}
+
+/// Load the import size limit, falling back to the default
+fn load_session_import_max_bytes(conn: &Connection) -> u64 {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![SESSION_IMPORT_MAX_BYTES_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| value.parse().ok())
+    .unwrap_or(DEFAULT_SESSION_IMPORT_MAX_BYTES)
+}
+
+/// Import a session JSONL file into a project so it can be viewed and resumed.
+///
+/// Files above the configured size limit are rejected with `file_too_large`
+/// unless `force` is set.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn import_session_file(
+    db: State<'_, AgentDb>,
+    file_path: String,
+    target_project_path: String,
+    force: Option<bool>,
+) -> Result<SessionImport, String> {
+    crate::claude_dir::check_dir_writable()?;
+    let project_path = normalize_project_path(&target_project_path)?.path;
+    let source = PathBuf::from(&file_path);
+    let size = fs::metadata(&source)
+        .map_err(|e| i18n::error(ErrorKind::FileRead, e))?
+        .len();
+    let max_bytes = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        load_session_import_max_bytes(&conn)
+    };
+    if size > max_bytes && !force.unwrap_or(false) {
+        return Err(i18n::error(
+            ErrorKind::FileTooLarge,
+            format!("{} is {} bytes, the limit is {}", file_path, size, max_bytes),
+        ));
+    }
+    let content = fs::read_to_string(&source).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+
+    let projects_dir = crate::claude_dir::claude_dir()?
+        .join("projects");
+    let project_dir = find_project_dir(&projects_dir, Path::new(&project_path));
+    let prepared = prepare_import(&content, &project_path, |id| {
+        project_dir.join(format!("{}.jsonl", id)).exists()
+    })?;
+
+    fs::create_dir_all(&project_dir).map_err(|e| i18n::error(ErrorKind::FileWrite, e))?;
+    let session_path = project_dir.join(format!("{}.jsonl", prepared.session_id));
+    write_atomically(&session_path, prepared.content.as_bytes())
+        .map_err(|e| i18n::error(ErrorKind::FileWrite, e))?;
+    log::info!(
+        "Imported {} into {} as session {} ({} lines skipped)",
+        file_path,
+        project_dir.display(),
+        prepared.session_id,
+        prepared.skipped_lines
+    );
+
+    let (first_message, message_timestamp) = extract_first_user_message(&session_path);
+    let project_id = project_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| encode_project_path(Path::new(&project_path)));
+    Ok(SessionImport {
+        session: Session {
+            id: prepared.session_id,
+            project_id,
+            project_path,
+            todo_data: None,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            first_message,
+            message_timestamp,
+            title: None,
+        },
+        skipped_lines: prepared.skipped_lines,
+        original_session_id: prepared.original_session_id,
+    })
+}
+
+/// Get the size above which session imports need `force`, in bytes
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_session_import_max_bytes(db: State<'_, AgentDb>) -> Result<u64, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(load_session_import_max_bytes(&conn))
+}
+
+/// Set the size above which session imports need `force`, in bytes
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_session_import_max_bytes(
+    db: State<'_, AgentDb>,
+    max_bytes: u64,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![SESSION_IMPORT_MAX_BYTES_KEY, max_bytes.to_string()],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_ID: &str = "0b6c3a1e-5d2f-4f7a-9c1e-2a3b4c5d6e7f";
+
+    #[test]
+    fn import_rewrites_ids_and_reports_bad_lines() {
+        let content = format!(
+            "{{\"type\":\"summary\",\"summary\":\"Fix build\"}}\n\
+             {{\"type\":\"user\",\"sessionId\":\"{id}\",\"cwd\":\"/home/alice/app\",\"message\":{{\"role\":\"user\",\"content\":\"hi\"}}}}\n\
+             not json\n\
+             {{\"type\":\"assistant\",\"sessionId\":\"{id}\",\"cwd\":\"/home/alice/app\",\"message\":{{\"role\":\"assistant\",\"content\":[]}}}}\n",
+            id = SESSION_ID
+        );
+
+        let prepared = prepare_import(&content, "/work/app", |_| false).unwrap();
+        assert_eq!(prepared.session_id, SESSION_ID);
+        assert_eq!(prepared.original_session_id, None);
+        assert_eq!(prepared.skipped_lines, 1);
+        let (values, _) = JsonlParseReport::parse(&prepared.content, true).unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[1]["cwd"], "/work/app");
+        assert!(values[0].get("cwd").is_none());
+
+        // The ID is already used in the target project
+        let prepared = prepare_import(&content, "/work/app", |id| id == SESSION_ID).unwrap();
+        assert_ne!(prepared.session_id, SESSION_ID);
+        assert_eq!(prepared.original_session_id.as_deref(), Some(SESSION_ID));
+        assert!(!prepared.content.contains(SESSION_ID));
+
+        let err = prepare_import("{\"type\":\"summary\"}\n", "/work/app", |_| false).unwrap_err();
+        assert!(err.starts_with("[invalid_input]"), "{}", err);
+    }
+}
@@ -0,0 +1,628 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+use crate::checkpoint::storage::write_atomically;
+
+/// Sidecar file kept next to a project's sessions in ~/.claude/projects/{project}
+pub const SIDECAR_FILE_NAME: &str = ".opcode-metadata.json";
+
+const SIDECAR_VERSION: u32 = 1;
+
+/// User-supplied metadata for a session
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Title generated from the first exchange; `name` takes precedence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// RFC 3339; the newer side wins when the DB and the file disagree
+    pub updated_at: String,
+}
+
+/// Contents of a project's sidecar file, keyed by session ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SidecarFile {
+    pub version: u32,
+    #[serde(default)]
+    pub sessions: BTreeMap<String, SessionMetadata>,
+}
+
+/// Metadata for every project, as written by `export_session_metadata`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionMetadataExport {
+    pub version: u32,
+    #[serde(default)]
+    pub projects: BTreeMap<String, BTreeMap<String, SessionMetadata>>,
+}
+
+fn claude_projects_dir() -> Result<PathBuf, String> {
+    crate::claude_dir::projects_dir()
+}
+
+/// Whether `incoming` should replace `current` (ties go to `incoming`)
+fn is_newer_or_same(incoming: &str, current: &str) -> bool {
+    match (
+        DateTime::parse_from_rfc3339(incoming),
+        DateTime::parse_from_rfc3339(current),
+    ) {
+        (Ok(incoming), Ok(current)) => incoming >= current,
+        _ => incoming >= current,
+    }
+}
+
+/// Load the metadata of all sessions of a project
+pub fn load_project_metadata(
+    conn: &Connection,
+    project_id: &str,
+) -> rusqlite::Result<BTreeMap<String, SessionMetadata>> {
+    let mut stmt = conn.prepare(
+        "SELECT session_id, name, favorite, notes, updated_at, title FROM session_metadata WHERE project_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![project_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            SessionMetadata {
+                name: row.get(1)?,
+                favorite: row.get(2)?,
+                notes: row.get(3)?,
+                title: row.get(5)?,
+                updated_at: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn upsert_metadata(
+    conn: &Connection,
+    project_id: &str,
+    session_id: &str,
+    metadata: &SessionMetadata,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO session_metadata (project_id, session_id, name, favorite, notes, updated_at, title)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(project_id, session_id) DO UPDATE SET
+             name = excluded.name, favorite = excluded.favorite,
+             notes = excluded.notes, updated_at = excluded.updated_at,
+             title = excluded.title",
+        params![
+            project_id,
+            session_id,
+            metadata.name,
+            metadata.favorite,
+            metadata.notes,
+            metadata.updated_at,
+            metadata.title
+        ],
+    )?;
+    Ok(())
+}
+
+/// Merge sessions into the DB, keeping whichever side was updated last.
+/// Returns how many sessions changed.
+pub fn merge_into_db(
+    conn: &Connection,
+    project_id: &str,
+    sessions: &BTreeMap<String, SessionMetadata>,
+) -> rusqlite::Result<usize> {
+    let mut changed = 0;
+    for (session_id, incoming) in sessions {
+        let current: Option<String> = conn
+            .query_row(
+                "SELECT updated_at FROM session_metadata WHERE project_id = ?1 AND session_id = ?2",
+                params![project_id, session_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if current.is_none_or(|current| is_newer_or_same(&incoming.updated_at, &current)) {
+            upsert_metadata(conn, project_id, session_id, incoming)?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+fn read_sidecar(path: &Path) -> Result<Option<SidecarFile>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| i18n::error(ErrorKind::InvalidInput, format!("{}: {}", path.display(), e)))
+}
+
+/// Write a project's metadata from the DB to its sidecar file
+pub fn write_sidecar(conn: &Connection, projects_dir: &Path, project_id: &str) -> Result<(), String> {
+    let project_dir = projects_dir.join(project_id);
+    if !project_dir.is_dir() {
+        return Ok(());
+    }
+    let sidecar = SidecarFile {
+        version: SIDECAR_VERSION,
+        sessions: load_project_metadata(conn, project_id)
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?,
+    };
+    let json = serde_json::to_string_pretty(&sidecar).map_err(|e| e.to_string())?;
+    write_atomically(&project_dir.join(SIDECAR_FILE_NAME), json.as_bytes())
+        .map_err(|e| i18n::error(ErrorKind::FileWrite, e))
+}
+
+/// Bring the DB and a project's sidecar file back in sync
+pub fn sync_project(conn: &Connection, projects_dir: &Path, project_id: &str) -> Result<(), String> {
+    let path = projects_dir.join(project_id).join(SIDECAR_FILE_NAME);
+    let file = read_sidecar(&path)?.unwrap_or_default();
+    merge_into_db(conn, project_id, &file.sessions)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+
+    let merged = load_project_metadata(conn, project_id)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    if merged != file.sessions {
+        write_sidecar(conn, projects_dir, project_id)?;
+    }
+    Ok(())
+}
+
+/// Sync every project that has metadata in the DB or a sidecar file
+pub fn sync_all(conn: &Connection, projects_dir: &Path) -> Result<usize, String> {
+    let mut project_ids: BTreeSet<String> = {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT project_id FROM session_metadata")
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        rows.collect::<rusqlite::Result<_>>()
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?
+    };
+    if let Ok(entries) = fs::read_dir(projects_dir) {
+        for entry in entries.flatten() {
+            if entry.path().join(SIDECAR_FILE_NAME).is_file() {
+                project_ids.insert(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    for project_id in &project_ids {
+        if let Err(e) = sync_project(conn, projects_dir, project_id) {
+            log::warn!("Failed to sync session metadata for {}: {}", project_id, e);
+        }
+    }
+    Ok(project_ids.len())
+}
+
+/// Read the sidecar files back into the DB on startup
+pub fn sync_on_startup(conn: &Connection) {
+    let result = claude_projects_dir().and_then(|dir| sync_all(conn, &dir));
+    match result {
+        Ok(count) => log::info!("Synced session metadata for {} projects", count),
+        Err(e) => log::warn!("Failed to sync session metadata: {}", e),
+    }
+}
+
+/// Longest generated title, in characters
+const SESSION_TITLE_MAX_CHARS: usize = 50;
+
+/// Salient words a user's first sentence needs to make a title on its own
+const SESSION_TITLE_MIN_WORDS: usize = 3;
+
+/// Words that say nothing about what a session is about
+const TITLE_STOPWORDS: &[&str] = &[
+    "a", "an", "the", "this", "that", "these", "those", "it", "its", "is", "are", "be", "to",
+    "of", "in", "on", "at", "for", "with", "and", "or", "me", "my", "i", "you", "we", "can",
+    "could", "would", "please", "look", "check", "see", "here", "there", "file", "files", "code",
+    "do", "does", "what", "how", "why", "hi", "hello", "thanks", "ok", "okay",
+];
+
+/// Openers stripped from the start of a sentence, matched case-insensitively
+const TITLE_FILLERS: &[&str] = &[
+    "please ", "can you ", "could you ", "would you ", "i'll ", "i will ", "let me ",
+    "i'm going to ", "i need you to ", "sure, ", "sure! ", "okay, ", "ok, ", "great, ",
+    "请", "帮我", "麻烦", "我来", "让我", "好的，", "好的,", "你能", "能否",
+];
+
+/// Sentence ends; a '.' only counts when followed by whitespace, so `main.rs` survives
+const SENTENCE_ENDS: &[char] = &['?', '!', '。', '？', '！'];
+
+/// Clause breaks where an over-long CJK title can be cut
+const CJK_CLAUSE_BREAKS: &[char] = &['，', '、', '；', '：', ',', ';', ':'];
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}')
+}
+
+/// Drop fenced code blocks and inline code markers
+fn strip_code(text: &str) -> String {
+    let mut kept = Vec::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            kept.push(line.replace('`', ""));
+        }
+    }
+    kept.join("\n")
+}
+
+/// First sentence of the first non-empty line, without markdown markers
+fn first_sentence(text: &str) -> Option<String> {
+    let line = text
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['#', '>', '-', '*', ' ']).trim())
+        .find(|line| !line.is_empty())?;
+    let mut end = line.len();
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends = SENTENCE_ENDS.contains(&c)
+            || (c == '.' && chars.peek().is_none_or(|(_, next)| next.is_whitespace()));
+        if ends {
+            end = i;
+            break;
+        }
+    }
+    let mut sentence = line[..end].trim().to_string();
+    // Openers can be stacked, e.g. "Sure, let me"
+    while let Some(filler) = TITLE_FILLERS.iter().find(|filler| {
+        sentence
+            .get(..filler.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(filler))
+    }) {
+        sentence = sentence[filler.len()..].trim_start().to_string();
+    }
+    (!sentence.is_empty()).then_some(sentence)
+}
+
+/// Rough count of meaningful words; two CJK characters count as one word
+fn salient_words(sentence: &str) -> usize {
+    let cjk = sentence.chars().filter(|&c| is_cjk(c)).count();
+    let latin = sentence
+        .split(|c: char| !c.is_alphanumeric() || is_cjk(c))
+        .filter(|word| !word.is_empty())
+        .filter(|word| !TITLE_STOPWORDS.contains(&word.to_lowercase().as_str()))
+        .count();
+    latin + cjk / 2
+}
+
+/// Cut a title to `SESSION_TITLE_MAX_CHARS`, at a word or CJK clause
+/// boundary when there is one
+fn truncate_title(title: &str) -> String {
+    if title.chars().count() <= SESSION_TITLE_MAX_CHARS {
+        return title.to_string();
+    }
+    let head: String = title.chars().take(SESSION_TITLE_MAX_CHARS).collect();
+    let cut = head
+        .rfind(|c: char| c.is_whitespace() || CJK_CLAUSE_BREAKS.contains(&c))
+        .filter(|&i| i >= head.len() / 2)
+        .unwrap_or(head.len());
+    format!("{}…", head[..cut].trim_end())
+}
+
+/// Build a short title from the first user message and, when that says
+/// too little (e.g. "look at this file"), the first assistant reply
+pub fn build_session_title(user: &str, assistant: Option<&str>) -> Option<String> {
+    let user = first_sentence(&strip_code(user));
+    let assistant = assistant.and_then(|text| first_sentence(&strip_code(text)));
+    let user_words = user.as_deref().map(salient_words).unwrap_or(0);
+    let title = match (user, assistant) {
+        (Some(user), _) if user_words >= SESSION_TITLE_MIN_WORDS => user,
+        (_, Some(assistant)) if salient_words(&assistant) > user_words => assistant,
+        (Some(user), _) => user,
+        (None, assistant) => assistant?,
+    };
+    let mut chars = title.chars();
+    let first = chars.next()?;
+    Some(truncate_title(&format!("{}{}", first.to_uppercase(), chars.as_str())))
+}
+
+/// Text of a message's content, either a string or text blocks
+fn message_text(message: &serde_json::Value) -> Option<String> {
+    let content = message.get("content")?;
+    if let Some(text) = content.as_str() {
+        return Some(text.to_string());
+    }
+    let text: Vec<&str> = content
+        .as_array()?
+        .iter()
+        .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+        .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+        .collect();
+    (!text.is_empty()).then(|| text.join("\n"))
+}
+
+/// First real user message and the first assistant text after it
+fn first_exchange(jsonl_path: &Path) -> Result<(Option<String>, Option<String>), String> {
+    let content = fs::read_to_string(jsonl_path).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+    let mut user = None;
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if entry.get("isMeta").and_then(|m| m.as_bool()) == Some(true) {
+            continue;
+        }
+        let Some(text) = entry.get("message").and_then(message_text) else {
+            continue;
+        };
+        match entry.get("type").and_then(|t| t.as_str()) {
+            Some("user") if user.is_none() => {
+                let local_command = text.starts_with("<command-name>")
+                    || text.starts_with("<local-command-stdout>")
+                    || text.starts_with("Caveat: The messages below were generated");
+                if !local_command {
+                    user = Some(text);
+                }
+            }
+            Some("assistant") if user.is_some() && !text.trim().is_empty() => {
+                return Ok((user, Some(text)));
+            }
+            _ => {}
+        }
+    }
+    Ok((user, None))
+}
+
+/// Titles stored in a project's sidecar file, by session ID
+pub fn load_sidecar_titles(project_dir: &Path) -> BTreeMap<String, String> {
+    read_sidecar(&project_dir.join(SIDECAR_FILE_NAME))
+        .ok()
+        .flatten()
+        .map(|file| {
+            file.sessions
+                .into_iter()
+                .filter_map(|(session_id, metadata)| Some((session_id, metadata.title?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get the names, favorites and notes of a project's sessions
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_session_metadata(
+    db: State<'_, AgentDb>,
+    project_id: String,
+) -> Result<BTreeMap<String, SessionMetadata>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    load_project_metadata(&conn, &project_id).map_err(|e| i18n::error(ErrorKind::Database, e))
+}
+
+/// Set a session's name, favorite flag and notes, mirroring them to the sidecar file
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn set_session_metadata(
+    db: State<'_, AgentDb>,
+    project_id: String,
+    session_id: String,
+    name: Option<String>,
+    favorite: bool,
+    notes: Option<String>,
+) -> Result<SessionMetadata, String> {
+    crate::claude_dir::check_dir_writable()?;
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    // The generated title is kept; only generate_session_title changes it
+    let title = load_project_metadata(&conn, &project_id)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?
+        .remove(&session_id)
+        .and_then(|current| current.title);
+    let metadata = SessionMetadata {
+        name: name.filter(|n| !n.trim().is_empty()),
+        favorite,
+        notes: notes.filter(|n| !n.trim().is_empty()),
+        title,
+        updated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    };
+    upsert_metadata(&conn, &project_id, &session_id, &metadata)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    write_sidecar(&conn, &claude_projects_dir()?, &project_id)?;
+    Ok(metadata)
+}
+
+/// Generate a session title from its first exchange and store it with the
+/// session's metadata. A stored title is returned as is unless `regenerate`
+/// is set. Titles are built locally, without calling any model.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn generate_session_title(
+    db: State<'_, AgentDb>,
+    project_id: String,
+    session_id: String,
+    regenerate: Option<bool>,
+) -> Result<String, String> {
+    crate::claude_dir::check_dir_writable()?;
+    let projects_dir = claude_projects_dir()?;
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let current = load_project_metadata(&conn, &project_id)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?
+        .remove(&session_id);
+    if let Some(title) = current.as_ref().and_then(|m| m.title.clone()) {
+        if !regenerate.unwrap_or(false) {
+            return Ok(title);
+        }
+    }
+
+    let session_path = projects_dir
+        .join(&project_id)
+        .join(format!("{}.jsonl", session_id));
+    if !session_path.is_file() {
+        return Err(i18n::error(ErrorKind::SessionNotFound, &session_id));
+    }
+    let (user, assistant) = first_exchange(&session_path)?;
+    let title = user
+        .as_deref()
+        .and_then(|user| build_session_title(user, assistant.as_deref()))
+        .ok_or_else(|| i18n::error(ErrorKind::InvalidInput, "session has no user message to title"))?;
+
+    let metadata = SessionMetadata {
+        title: Some(title.clone()),
+        updated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        ..current.unwrap_or(SessionMetadata {
+            name: None,
+            favorite: false,
+            notes: None,
+            title: None,
+            updated_at: String::new(),
+        })
+    };
+    upsert_metadata(&conn, &project_id, &session_id, &metadata)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    write_sidecar(&conn, &projects_dir, &project_id)?;
+    Ok(title)
+}
+
+/// Write the metadata of every project to a single file, e.g. for dotfiles
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn export_session_metadata(db: State<'_, AgentDb>, path: String) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let mut export = SessionMetadataExport {
+        version: SIDECAR_VERSION,
+        projects: BTreeMap::new(),
+    };
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT project_id FROM session_metadata")
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let project_ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let mut count = 0;
+    for project_id in project_ids {
+        let sessions = load_project_metadata(&conn, &project_id)
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        count += sessions.len();
+        export.projects.insert(project_id, sessions);
+    }
+
+    let json = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+    write_atomically(Path::new(&path), json.as_bytes())
+        .map_err(|e| i18n::error(ErrorKind::FileWrite, e))?;
+    Ok(count)
+}
+
+/// Merge an exported metadata file (newer entries win) and update the sidecar files
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn import_session_metadata(db: State<'_, AgentDb>, path: String) -> Result<usize, String> {
+    crate::claude_dir::check_dir_writable()?;
+    let content = fs::read_to_string(&path).map_err(|e| i18n::error(ErrorKind::FileRead, e))?;
+    let export: SessionMetadataExport = serde_json::from_str(&content)
+        .map_err(|e| i18n::error(ErrorKind::InvalidInput, e))?;
+
+    let projects_dir = claude_projects_dir()?;
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let mut changed = 0;
+    for (project_id, sessions) in &export.projects {
+        changed += merge_into_db(&conn, project_id, sessions)
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        write_sidecar(&conn, &projects_dir, project_id)?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_come_from_the_first_exchange() {
+        // A vague request falls back to what the assistant set out to do
+        assert_eq!(
+            build_session_title(
+                "look at this file\n```rust\nfn main() {}\n```",
+                Some("I'll refactor the retry loop in `src/net.rs`. First...")
+            )
+            .as_deref(),
+            Some("Refactor the retry loop in src/net.rs")
+        );
+        assert_eq!(
+            build_session_title("Please add dark mode to the settings page! Thanks", None).as_deref(),
+            Some("Add dark mode to the settings page")
+        );
+        assert_eq!(
+            build_session_title("请帮我修复登录页面的表单校验问题。谢谢", Some("好的")).as_deref(),
+            Some("修复登录页面的表单校验问题")
+        );
+
+        let long = build_session_title(
+            "Investigate why the nightly export job sometimes writes duplicate rows into the warehouse",
+            None,
+        )
+        .unwrap();
+        assert_eq!(long, "Investigate why the nightly export job sometimes…");
+        let long_zh = build_session_title(
+            "把用户模块里所有同步数据库调用改成异步调用，同时补充单元测试，并且更新相关文档和配置说明，最后整理提交记录并通知团队成员进行代码评审",
+            None,
+        )
+        .unwrap();
+        assert!(long_zh.chars().count() <= SESSION_TITLE_MAX_CHARS + 1);
+        assert!(long_zh.ends_with("并且更新相关文档和配置说明…"));
+    }
+
+    fn metadata(name: &str, updated_at: &str) -> SessionMetadata {
+        SessionMetadata {
+            name: Some(name.to_string()),
+            favorite: true,
+            notes: None,
+            title: None,
+            updated_at: updated_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn newer_side_wins_when_syncing_with_sidecar() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::commands::agents::init_schema(&conn).unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        let projects_dir = temp.path();
+        fs::create_dir_all(projects_dir.join("-repo")).unwrap();
+
+        upsert_metadata(&conn, "-repo", "old-in-db", &metadata("db", "2025-01-01T00:00:00Z")).unwrap();
+        upsert_metadata(&conn, "-repo", "new-in-db", &metadata("db", "2025-03-01T00:00:00Z")).unwrap();
+
+        let file = SidecarFile {
+            version: SIDECAR_VERSION,
+            sessions: BTreeMap::from([
+                ("old-in-db".to_string(), metadata("file", "2025-02-01T00:00:00Z")),
+                ("new-in-db".to_string(), metadata("file", "2025-02-01T00:00:00Z")),
+                ("file-only".to_string(), metadata("file", "2025-02-01T00:00:00Z")),
+            ]),
+        };
+        let sidecar_path = projects_dir.join("-repo").join(SIDECAR_FILE_NAME);
+        fs::write(&sidecar_path, serde_json::to_string(&file).unwrap()).unwrap();
+
+        sync_all(&conn, projects_dir).unwrap();
+
+        let names = |sessions: &BTreeMap<String, SessionMetadata>| {
+            sessions
+                .iter()
+                .map(|(id, m)| (id.clone(), m.name.clone().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            ("file-only".to_string(), "file".to_string()),
+            ("new-in-db".to_string(), "db".to_string()),
+            ("old-in-db".to_string(), "file".to_string()),
+        ];
+        assert_eq!(names(&load_project_metadata(&conn, "-repo").unwrap()), expected);
+        let written = read_sidecar(&sidecar_path).unwrap().unwrap();
+        assert_eq!(names(&written.sessions), expected);
+    }
+}
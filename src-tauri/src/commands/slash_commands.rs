@@ -1,11 +1,13 @@
 use anyhow::{Context, Result};
-use dirs;
 use log::{debug, error, info};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
 
 /// Represents a custom slash command
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SlashCommand {
     /// Unique identifier for the command (derived from file path)
@@ -246,6 +248,7 @@ fn create_default_commands() -> Vec<SlashCommand> {
 }
 
 /// Discover all custom slash commands
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn slash_commands_list(
     project_path: Option<String>,
@@ -282,8 +285,8 @@ pub async fn slash_commands_list(
     }
     
     // Load user commands
-    if let Some(home_dir) = dirs::home_dir() {
-        let user_commands_dir = home_dir.join(".claude").join("commands");
+    if let Ok(claude_dir) = crate::claude_dir::claude_dir() {
+        let user_commands_dir = claude_dir.join("commands");
         if user_commands_dir.exists() {
             debug!("Scanning user commands at: {:?}", user_commands_dir);
             
@@ -311,6 +314,7 @@ pub async fn slash_commands_list(
 }
 
 /// Get a single slash command by ID
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn slash_command_get(command_id: String) -> Result<SlashCommand, String> {
     debug!("Getting slash command: {}", command_id);
@@ -332,6 +336,7 @@ pub async fn slash_command_get(command_id: String) -> Result<SlashCommand, Strin
 }
 
 /// Create or update a slash command
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn slash_command_save(
     scope: String,
@@ -361,10 +366,7 @@ pub async fn slash_command_save(
             return Err("Project path required for project scope".to_string());
         }
     } else {
-        dirs::home_dir()
-            .ok_or_else(|| "Could not find home directory".to_string())?
-            .join(".claude")
-            .join("commands")
+        crate::claude_dir::claude_dir()?.join("commands")
     };
     
     // Build file path
@@ -375,6 +377,7 @@ pub async fn slash_command_save(
         }
     }
     
+    crate::claude_dir::check_writable(&file_path)?;
     // Create directories if needed
     fs::create_dir_all(&file_path)
         .map_err(|e| format!("Failed to create directories: {}", e))?;
@@ -415,6 +418,7 @@ pub async fn slash_command_save(
 }
 
 /// Delete a slash command
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn slash_command_delete(command_id: String, project_path: Option<String>) -> Result<String, String> {
     info!("Deleting slash command: {}", command_id);
@@ -436,6 +440,7 @@ pub async fn slash_command_delete(command_id: String, project_path: Option<Strin
         .find(|cmd| cmd.id == command_id)
         .ok_or_else(|| format!("Command not found: {}", command_id))?;
     
+    crate::claude_dir::check_writable(Path::new(&command.file_path))?;
     // Delete the file
     fs::remove_file(&command.file_path)
         .map_err(|e| format!("Failed to delete command file: {}", e))?;
@@ -5,8 +5,11 @@ use serde_json::{Map, Value as JsonValue};
 use std::collections::HashMap;
 use tauri::{AppHandle, Manager, State};
 use super::agents::AgentDb;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
 
 /// Represents metadata about a database table
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TableInfo {
     pub name: String,
@@ -15,6 +18,7 @@ pub struct TableInfo {
 }
 
 /// Represents metadata about a table column
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ColumnInfo {
     pub cid: i32,
@@ -26,6 +30,7 @@ pub struct ColumnInfo {
 }
 
 /// Represents a page of table data
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TableData {
     pub table_name: String,
@@ -38,6 +43,7 @@ pub struct TableData {
 }
 
 /// SQL query result
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct QueryResult {
     pub columns: Vec<String>,
@@ -47,6 +53,7 @@ pub struct QueryResult {
 }
 
 /// List all tables in the database
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn storage_list_tables(db: State<'_, AgentDb>) -> Result<Vec<TableInfo>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
@@ -107,6 +114,7 @@ pub async fn storage_list_tables(db: State<'_, AgentDb>) -> Result<Vec<TableInfo
 }
 
 /// Read table data with pagination
+#[apply(api_command!)]
 #[tauri::command]
 #[allow(non_snake_case)]
 pub async fn storage_read_table(
@@ -226,6 +234,7 @@ pub async fn storage_read_table(
 }
 
 /// Update a row in a table
+#[apply(api_command!)]
 #[tauri::command]
 #[allow(non_snake_case)]
 pub async fn storage_update_row(
@@ -282,6 +291,7 @@ pub async fn storage_update_row(
 }
 
 /// Delete a row from a table
+#[apply(api_command!)]
 #[tauri::command]
 #[allow(non_snake_case)]
 pub async fn storage_delete_row(
@@ -323,6 +333,7 @@ pub async fn storage_delete_row(
 }
 
 /// Insert a new row into a table
+#[apply(api_command!)]
 #[tauri::command]
 #[allow(non_snake_case)]
 pub async fn storage_insert_row(
@@ -364,6 +375,7 @@ pub async fn storage_insert_row(
 }
 
 /// Execute a raw SQL query
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn storage_execute_sql(
     db: State<'_, AgentDb>,
@@ -389,20 +401,7 @@ pub async fn storage_execute_sql(
             .query_map([], |row| {
                 let mut row_values = Vec::new();
                 for i in 0..column_count {
-                    let value = match row.get_ref(i)? {
-                        ValueRef::Null => JsonValue::Null,
-                        ValueRef::Integer(n) => JsonValue::Number(serde_json::Number::from(n)),
-                        ValueRef::Real(f) => {
-                            if let Some(n) = serde_json::Number::from_f64(f) {
-                                JsonValue::Number(n)
-                            } else {
-                                JsonValue::String(f.to_string())
-                            }
-                        }
-                        ValueRef::Text(s) => JsonValue::String(String::from_utf8_lossy(s).to_string()),
-                        ValueRef::Blob(b) => JsonValue::String(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, b)),
-                    };
-                    row_values.push(value);
+                    row_values.push(sql_value_to_json(row.get_ref(i)?));
                 }
                 Ok(row_values)
             })
@@ -430,6 +429,7 @@ pub async fn storage_execute_sql(
 }
 
 /// Reset the entire database (with confirmation)
+#[apply(api_command!)]
 #[tauri::command]
 pub async fn storage_reset_database(app: AppHandle) -> Result<(), String> {
     {
@@ -493,8 +493,25 @@ fn is_valid_table_name(conn: &Connection, table_name: &str) -> Result<bool, Stri
     Ok(count > 0)
 }
 
+/// Convert a SQLite value to JSON (blobs become base64 strings)
+pub fn sql_value_to_json(value: ValueRef) -> JsonValue {
+    match value {
+        ValueRef::Null => JsonValue::Null,
+        ValueRef::Integer(n) => JsonValue::Number(serde_json::Number::from(n)),
+        ValueRef::Real(f) => {
+            if let Some(n) = serde_json::Number::from_f64(f) {
+                JsonValue::Number(n)
+            } else {
+                JsonValue::String(f.to_string())
+            }
+        }
+        ValueRef::Text(s) => JsonValue::String(String::from_utf8_lossy(s).to_string()),
+        ValueRef::Blob(b) => JsonValue::String(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, b)),
+    }
+}
+
 /// Helper function to convert JSON value to SQL value
-fn json_to_sql_value(value: &JsonValue) -> Result<Box<dyn rusqlite::ToSql>, String> {
+pub fn json_to_sql_value(value: &JsonValue) -> Result<Box<dyn rusqlite::ToSql>, String> {
     match value {
         JsonValue::Null => Ok(Box::new(rusqlite::types::Null)),
         JsonValue::Bool(b) => Ok(Box::new(*b)),
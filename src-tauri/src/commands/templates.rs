@@ -0,0 +1,622 @@
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+/// Name of the manifest file inside a template directory
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Directory inside a template that mirrors the project layout
+const FILES_DIR: &str = "files";
+
+/// Project files that can be captured in a template
+const CLAUDE_MD: &str = "CLAUDE.md";
+const CLAUDE_SETTINGS: &str = ".claude/settings.json";
+const MCP_CONFIG: &str = ".mcp.json";
+
+/// Per-user settings claude keeps out of version control
+const CLAUDE_LOCAL_SETTINGS: &str = ".claude/settings.local.json";
+
+/// Marker files that hint at a project's language or toolchain
+const TOOLCHAIN_MARKERS: &[(&str, &str)] = &[
+    ("Cargo.toml", "rust"),
+    ("package.json", "node"),
+    ("pyproject.toml", "python"),
+];
+
+/// Which project files to capture when saving a template
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateIncludes {
+    #[serde(default)]
+    pub claude_md: bool,
+    #[serde(default)]
+    pub settings: bool,
+    #[serde(default)]
+    pub mcp_config: bool,
+}
+
+impl TemplateIncludes {
+    fn relative_paths(&self) -> Vec<&'static str> {
+        let mut paths = Vec::new();
+        if self.claude_md {
+            paths.push(CLAUDE_MD);
+        }
+        if self.settings {
+            paths.push(CLAUDE_SETTINGS);
+        }
+        if self.mcp_config {
+            paths.push(MCP_CONFIG);
+        }
+        paths
+    }
+}
+
+/// A saved project template
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTemplate {
+    pub id: String,
+    pub name: String,
+    /// Project the template was captured from
+    pub source_project_path: String,
+    /// Files in the template, relative to the project root
+    pub files: Vec<String>,
+    pub created_at: String,
+}
+
+/// Result of creating a project from a template
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateApplyResult {
+    pub target_path: String,
+    /// Files written, relative to the target directory
+    pub written_files: Vec<String>,
+    /// Existing files that were replaced because force was set
+    pub overwritten_files: Vec<String>,
+}
+
+/// What claude setup a directory already has
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectDirectoryInfo {
+    pub path: String,
+    pub has_claude_dir: bool,
+    pub has_settings: bool,
+    pub has_local_settings: bool,
+    pub has_claude_md: bool,
+    pub has_mcp_json: bool,
+    pub is_git_repo: bool,
+    /// Sessions recorded for this path under ~/.claude/projects
+    pub session_count: usize,
+    /// Toolchains detected from marker files, e.g. "rust" for Cargo.toml
+    pub toolchains: Vec<String>,
+}
+
+/// Starter files to create when initializing a project
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectInitOptions {
+    #[serde(default)]
+    pub claude_md: bool,
+    #[serde(default)]
+    pub settings: bool,
+    #[serde(default)]
+    pub local_settings: bool,
+    #[serde(default)]
+    pub mcp_json: bool,
+}
+
+/// Result of initializing a project's claude setup
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectInitResult {
+    /// Files created, relative to the project root
+    pub created_files: Vec<String>,
+    /// Selected files that already existed and were left alone
+    pub skipped_files: Vec<String>,
+}
+
+/// Inspect a directory's claude setup without modifying anything
+pub fn inspect_directory(path: &Path, projects_dir: Option<&Path>) -> ProjectDirectoryInfo {
+    let session_count = projects_dir
+        .map(|dir| super::claude::find_project_dir(dir, path))
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().extension().and_then(|ext| ext.to_str()) == Some("jsonl"))
+                .count()
+        })
+        .unwrap_or(0);
+
+    ProjectDirectoryInfo {
+        path: path.to_string_lossy().to_string(),
+        has_claude_dir: path.join(".claude").is_dir(),
+        has_settings: path.join(CLAUDE_SETTINGS).is_file(),
+        has_local_settings: path.join(CLAUDE_LOCAL_SETTINGS).is_file(),
+        has_claude_md: path.join(CLAUDE_MD).is_file(),
+        has_mcp_json: path.join(MCP_CONFIG).is_file(),
+        // Also true inside a subdirectory of a repository
+        is_git_repo: path.ancestors().any(|dir| dir.join(".git").exists()),
+        session_count,
+        toolchains: TOOLCHAIN_MARKERS
+            .iter()
+            .filter(|(marker, _)| path.join(marker).is_file())
+            .map(|(_, toolchain)| toolchain.to_string())
+            .collect(),
+    }
+}
+
+/// Starter CLAUDE.md listing the usual commands of the detected toolchains
+fn starter_claude_md(target: &Path, toolchains: &[String]) -> String {
+    let mut content = substitute_variables("# {{PROJECT_NAME}}\n", target, false);
+    let commands: Vec<&str> = toolchains
+        .iter()
+        .flat_map(|toolchain| match toolchain.as_str() {
+            "rust" => &["cargo build", "cargo test"][..],
+            "node" => &["npm run build", "npm test"][..],
+            "python" => &["pip install -e .", "pytest"][..],
+            _ => &[][..],
+        })
+        .copied()
+        .collect();
+    if !commands.is_empty() {
+        content.push_str("\n## Commands\n\n");
+        for command in commands {
+            content.push_str(&format!("- `{}`\n", command));
+        }
+    }
+    content
+}
+
+/// Create the selected starter files, never replacing existing ones
+pub fn initialize_directory(target: &Path, options: &ProjectInitOptions) -> Result<ProjectInitResult> {
+    if !target.is_dir() {
+        bail!("Not a directory: {}", target.display());
+    }
+
+    let toolchains = inspect_directory(target, None).toolchains;
+    let mut files: Vec<(&str, String)> = Vec::new();
+    if options.claude_md {
+        files.push((CLAUDE_MD, starter_claude_md(target, &toolchains)));
+    }
+    if options.settings {
+        let settings = serde_json::json!({ "permissions": { "allow": [], "deny": [] } });
+        files.push((CLAUDE_SETTINGS, serde_json::to_string_pretty(&settings)?));
+    }
+    if options.local_settings {
+        files.push((CLAUDE_LOCAL_SETTINGS, "{}".to_string()));
+    }
+    if options.mcp_json {
+        let mcp_config = serde_json::json!({ "mcpServers": {} });
+        files.push((MCP_CONFIG, serde_json::to_string_pretty(&mcp_config)?));
+    }
+
+    let mut created_files = Vec::new();
+    let mut skipped_files = Vec::new();
+    for (rel, content) in files {
+        let dest = target.join(rel);
+        if dest.exists() {
+            skipped_files.push(rel.to_string());
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&dest, content).with_context(|| format!("Failed to write {}", dest.display()))?;
+        created_files.push(rel.to_string());
+    }
+
+    Ok(ProjectInitResult {
+        created_files,
+        skipped_files,
+    })
+}
+
+/// Get the directory holding all project templates
+fn get_templates_dir(app: &AppHandle) -> Result<PathBuf> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow::anyhow!("Failed to get app data dir: {}", e))?;
+    Ok(app_dir.join("templates"))
+}
+
+/// Replace template variables such as {{PROJECT_NAME}} in file contents
+///
+/// With `json` set the values are escaped for use inside JSON strings, so a
+/// path with backslashes or quotes keeps the file parseable.
+pub fn substitute_variables(content: &str, target: &Path, json: bool) -> String {
+    let project_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let project_path = target.to_string_lossy().to_string();
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    let value = |raw: &str| {
+        if json {
+            let quoted = serde_json::Value::String(raw.to_string()).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        } else {
+            raw.to_string()
+        }
+    };
+    content
+        .replace("{{PROJECT_NAME}}", &value(&project_name))
+        .replace("{{PROJECT_PATH}}", &value(&project_path))
+        .replace("{{DATE}}", &value(&date))
+}
+
+/// Check that a template file path stays inside the directory it is joined to
+fn is_contained_path(rel: &str) -> bool {
+    let path = Path::new(rel);
+    path.components().next().is_some()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// Load a template manifest from its directory
+fn load_manifest(template_dir: &Path) -> Result<ProjectTemplate> {
+    let content = fs::read_to_string(template_dir.join(MANIFEST_FILE))
+        .with_context(|| format!("Failed to read template manifest in {:?}", template_dir))?;
+    let template: ProjectTemplate =
+        serde_json::from_str(&content).context("Failed to parse template manifest")?;
+    // The manifest is a plain file on disk; never follow it out of the project
+    if let Some(rel) = template.files.iter().find(|rel| !is_contained_path(rel)) {
+        bail!("Template file path escapes the project: {}", rel);
+    }
+    Ok(template)
+}
+
+/// List all templates in the templates directory
+pub fn list_templates(templates_dir: &Path) -> Result<Vec<ProjectTemplate>> {
+    let mut templates = Vec::new();
+    if !templates_dir.exists() {
+        return Ok(templates);
+    }
+
+    for entry in fs::read_dir(templates_dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        match load_manifest(&path) {
+            Ok(template) => templates.push(template),
+            Err(e) => warn!("Skipping invalid template {:?}: {}", path, e),
+        }
+    }
+
+    templates.sort_by_key(|t| t.name.to_lowercase());
+    Ok(templates)
+}
+
+/// Capture the selected files of a project into a new template
+pub fn save_template(
+    templates_dir: &Path,
+    name: &str,
+    source: &Path,
+    include: &TemplateIncludes,
+) -> Result<ProjectTemplate> {
+    if name.trim().is_empty() {
+        bail!("Template name cannot be empty");
+    }
+    if !source.is_dir() {
+        bail!("Source project not found: {}", source.display());
+    }
+
+    let files: Vec<&str> = include
+        .relative_paths()
+        .into_iter()
+        .filter(|rel| source.join(rel).is_file())
+        .collect();
+    if files.is_empty() {
+        bail!("None of the selected files exist in {}", source.display());
+    }
+
+    let template = ProjectTemplate {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.trim().to_string(),
+        source_project_path: source.to_string_lossy().to_string(),
+        files: files.iter().map(|f| f.to_string()).collect(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let template_dir = templates_dir.join(&template.id);
+    for rel in &files {
+        let dest = template_dir.join(FILES_DIR).join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source.join(rel), &dest)
+            .with_context(|| format!("Failed to copy {} into template", rel))?;
+    }
+
+    fs::write(
+        template_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&template)?,
+    )
+    .context("Failed to write template manifest")?;
+
+    Ok(template)
+}
+
+/// Copy a template into the target directory, substituting variables.
+///
+/// Existing files are left untouched and reported as an error unless `force` is set.
+pub fn apply_template(
+    template_dir: &Path,
+    target: &Path,
+    force: bool,
+) -> Result<TemplateApplyResult> {
+    let template = load_manifest(template_dir)?;
+
+    let existing: Vec<&String> = template
+        .files
+        .iter()
+        .filter(|rel| target.join(rel).exists())
+        .collect();
+    if !existing.is_empty() && !force {
+        bail!(
+            "Refusing to overwrite existing files: {}",
+            existing
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    fs::create_dir_all(target)
+        .with_context(|| format!("Failed to create {}", target.display()))?;
+
+    let mut written_files = Vec::new();
+    for rel in &template.files {
+        let source = template_dir.join(FILES_DIR).join(rel);
+        let content = fs::read_to_string(&source)
+            .with_context(|| format!("Failed to read template file {}", rel))?;
+
+        let dest = target.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = Path::new(rel).extension().is_some_and(|ext| ext == "json");
+        fs::write(&dest, substitute_variables(&content, target, json))
+            .with_context(|| format!("Failed to write {}", dest.display()))?;
+        written_files.push(rel.clone());
+    }
+
+    Ok(TemplateApplyResult {
+        target_path: target.to_string_lossy().to_string(),
+        written_files,
+        overwritten_files: existing.into_iter().cloned().collect(),
+    })
+}
+
+/// List saved project templates
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn list_project_templates(app: AppHandle) -> Result<Vec<ProjectTemplate>, String> {
+    let templates_dir = get_templates_dir(&app).map_err(|e| e.to_string())?;
+    list_templates(&templates_dir).map_err(|e| format!("Failed to list templates: {}", e))
+}
+
+/// Save CLAUDE.md, settings and/or MCP config of a project as a template
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn save_project_template(
+    app: AppHandle,
+    name: String,
+    source_project_path: String,
+    include: TemplateIncludes,
+) -> Result<ProjectTemplate, String> {
+    info!("Saving project template '{}' from {}", name, source_project_path);
+    let templates_dir = get_templates_dir(&app).map_err(|e| e.to_string())?;
+    save_template(&templates_dir, &name, Path::new(&source_project_path), &include)
+        .map_err(|e| format!("Failed to save template: {}", e))
+}
+
+/// Initialize a project directory from a saved template
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn create_project_from_template(
+    app: AppHandle,
+    target_path: String,
+    template_id: String,
+    force: Option<bool>,
+) -> Result<TemplateApplyResult, String> {
+    info!("Creating project at {} from template {}", target_path, template_id);
+
+    // Template ids are generated UUIDs; reject anything that could escape the directory
+    if uuid::Uuid::parse_str(&template_id).is_err() {
+        return Err(format!("Invalid template ID: {}", template_id));
+    }
+
+    let template_dir = get_templates_dir(&app)
+        .map_err(|e| e.to_string())?
+        .join(&template_id);
+    if !template_dir.is_dir() {
+        return Err(format!("Template not found: {}", template_id));
+    }
+
+    apply_template(&template_dir, Path::new(&target_path), force.unwrap_or(false))
+        .map_err(|e| format!("Failed to create project from template: {}", e))
+}
+
+/// Report whether a directory already has a claude setup (read-only)
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn inspect_project_directory(path: String) -> Result<ProjectDirectoryInfo, String> {
+    let path = super::claude::normalize_project_path(&path)?.path;
+    let projects_dir = crate::claude_dir::projects_dir().ok();
+    Ok(inspect_directory(Path::new(&path), projects_dir.as_deref()))
+}
+
+/// Create starter claude files (CLAUDE.md, settings, MCP config) in a project
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn initialize_project_claude_dir(
+    path: String,
+    options: ProjectInitOptions,
+) -> Result<ProjectInitResult, String> {
+    let path = super::claude::normalize_project_path(&path)?.path;
+    info!("Initializing claude setup in {}", path);
+    let result = initialize_directory(Path::new(&path), &options)
+        .map_err(|e| format!("Failed to initialize project: {}", e))?;
+    if !result.skipped_files.is_empty() {
+        warn!("Kept existing files in {}: {:?}", path, result.skipped_files);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn source_project(root: &Path) -> PathBuf {
+        let source = root.join("source");
+        fs::create_dir_all(source.join(".claude")).unwrap();
+        fs::write(source.join("CLAUDE.md"), "# {{PROJECT_NAME}}\n").unwrap();
+        fs::write(source.join(".claude/settings.json"), "{}").unwrap();
+        source
+    }
+
+    #[test]
+    fn template_round_trip_substitutes_project_name() {
+        let temp = TempDir::new().unwrap();
+        let templates_dir = temp.path().join("templates");
+        let source = source_project(temp.path());
+        let include = TemplateIncludes {
+            claude_md: true,
+            settings: true,
+            mcp_config: true,
+        };
+
+        let template = save_template(&templates_dir, "Rust", &source, &include).unwrap();
+        assert_eq!(template.files, vec![CLAUDE_MD, CLAUDE_SETTINGS]);
+        assert_eq!(list_templates(&templates_dir).unwrap().len(), 1);
+
+        let target = temp.path().join("my-app");
+        let result = apply_template(&templates_dir.join(&template.id), &target, false).unwrap();
+        assert_eq!(result.written_files.len(), 2);
+        assert!(result.overwritten_files.is_empty());
+        assert_eq!(fs::read_to_string(target.join("CLAUDE.md")).unwrap(), "# my-app\n");
+    }
+
+    #[test]
+    fn apply_refuses_to_overwrite_without_force() {
+        let temp = TempDir::new().unwrap();
+        let templates_dir = temp.path().join("templates");
+        let source = source_project(temp.path());
+        let include = TemplateIncludes {
+            claude_md: true,
+            settings: false,
+            mcp_config: false,
+        };
+        let template = save_template(&templates_dir, "Docs", &source, &include).unwrap();
+        let template_dir = templates_dir.join(&template.id);
+
+        let target = temp.path().join("existing");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("CLAUDE.md"), "keep me").unwrap();
+
+        assert!(apply_template(&template_dir, &target, false).is_err());
+        assert_eq!(fs::read_to_string(target.join("CLAUDE.md")).unwrap(), "keep me");
+
+        let result = apply_template(&template_dir, &target, true).unwrap();
+        assert_eq!(result.overwritten_files, vec![CLAUDE_MD.to_string()]);
+        assert_eq!(fs::read_to_string(target.join("CLAUDE.md")).unwrap(), "# existing\n");
+    }
+
+    #[test]
+    fn apply_rejects_escaping_paths_and_escapes_json_values() {
+        let temp = TempDir::new().unwrap();
+        let template_dir = temp.path().join("templates").join("t");
+        fs::create_dir_all(template_dir.join(FILES_DIR).join(".claude")).unwrap();
+        fs::write(
+            template_dir.join(FILES_DIR).join(CLAUDE_SETTINGS),
+            r#"{"env": {"PROJECT": "{{PROJECT_PATH}}"}}"#,
+        )
+        .unwrap();
+        let write_manifest = |files: &[&str]| {
+            let template = ProjectTemplate {
+                id: "t".to_string(),
+                name: "T".to_string(),
+                source_project_path: String::new(),
+                files: files.iter().map(|f| f.to_string()).collect(),
+                created_at: String::new(),
+            };
+            fs::write(
+                template_dir.join(MANIFEST_FILE),
+                serde_json::to_string(&template).unwrap(),
+            )
+            .unwrap();
+        };
+
+        let target = temp.path().join("quote\"app");
+        for rel in ["../outside.md", "/etc/passwd", "./CLAUDE.md", ""] {
+            write_manifest(&[rel]);
+            assert!(apply_template(&template_dir, &target, true).is_err(), "{}", rel);
+        }
+        assert!(!temp.path().join("outside.md").exists());
+        assert!(list_templates(&temp.path().join("templates")).unwrap().is_empty());
+
+        write_manifest(&[CLAUDE_SETTINGS]);
+        apply_template(&template_dir, &target, false).unwrap();
+        let settings: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(target.join(CLAUDE_SETTINGS)).unwrap())
+                .unwrap();
+        assert_eq!(settings["env"]["PROJECT"], target.to_string_lossy().as_ref());
+    }
+
+    #[test]
+    fn inspect_and_initialize_project_directory() {
+        let temp = TempDir::new().unwrap();
+        let project = temp.path().join("tool");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(project.join("CLAUDE.md"), "keep me").unwrap();
+
+        let projects_dir = temp.path().join("projects");
+        let sessions = projects_dir.join(crate::commands::claude::encode_project_path(&project));
+        fs::create_dir_all(&sessions).unwrap();
+        fs::write(sessions.join("a.jsonl"), "").unwrap();
+        fs::write(sessions.join("notes.txt"), "").unwrap();
+
+        let info = inspect_directory(&project, Some(&projects_dir));
+        assert!(info.has_claude_md && !info.has_claude_dir && !info.has_mcp_json);
+        assert_eq!(info.session_count, 1);
+        assert_eq!(info.toolchains, vec!["rust"]);
+        // Inspection never creates anything
+        assert!(!project.join(".claude").exists());
+
+        let options = ProjectInitOptions {
+            claude_md: true,
+            settings: true,
+            mcp_json: true,
+            ..Default::default()
+        };
+        let result = initialize_directory(&project, &options).unwrap();
+        assert_eq!(result.created_files, vec![CLAUDE_SETTINGS, MCP_CONFIG]);
+        assert_eq!(result.skipped_files, vec![CLAUDE_MD]);
+        assert_eq!(fs::read_to_string(project.join("CLAUDE.md")).unwrap(), "keep me");
+
+        let info = inspect_directory(&project, None);
+        assert!(info.has_claude_dir && info.has_settings && info.has_mcp_json);
+        assert!(!info.has_local_settings);
+
+        let fresh = temp.path().join("fresh");
+        fs::create_dir_all(&fresh).unwrap();
+        fs::write(fresh.join("package.json"), "{}").unwrap();
+        initialize_directory(&fresh, &options).unwrap();
+        let claude_md = fs::read_to_string(fresh.join("CLAUDE.md")).unwrap();
+        assert!(claude_md.starts_with("# fresh\n") && claude_md.contains("`npm test`"));
+    }
+}
@@ -0,0 +1,205 @@
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tauri::State;
+use crate::api_schema::api_command;
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::claude::normalize_project_path;
+use super::i18n::{self, ErrorKind};
+
+/// Setting with the terminal to open; unset tries the platform defaults
+pub const TERMINAL_APP_KEY: &str = "terminal_app";
+
+/// Terminals tried in order when none is configured
+fn default_terminals(os: &str) -> &'static [&'static str] {
+    match os {
+        "macos" => &["Terminal", "iTerm"],
+        "windows" => &["wt", "cmd"],
+        _ => &["x-terminal-emulator", "gnome-terminal", "konsole", "xterm"],
+    }
+}
+
+/// A program invocation that opens a terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TerminalLaunch {
+    program: String,
+    args: Vec<String>,
+}
+
+/// Quote a string for a POSIX shell
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Quote a string for an AppleScript string literal
+fn applescript_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// How to open `terminal` in `dir` on `os`, running `command` first if given.
+///
+/// Unknown terminal names are run as a program in `dir`, with the command
+/// passed the way xterm-style terminals take it.
+fn terminal_launch(os: &str, terminal: &str, dir: &str, command: Option<&str>) -> TerminalLaunch {
+    let launch = |program: &str, args: Vec<String>| TerminalLaunch {
+        program: program.to_string(),
+        args,
+    };
+    // POSIX terminals keep an interactive shell open after the command
+    let keep_shell = |command: &str| format!("{}; exec \"${{SHELL:-sh}}\"", command);
+
+    match (os, terminal) {
+        ("macos", app) => match command {
+            None => launch("open", vec!["-a".into(), app.into(), dir.into()]),
+            Some(command) => {
+                let line = format!("cd {} && {}", sh_quote(dir), command);
+                let script = if app.eq_ignore_ascii_case("iTerm") {
+                    format!(
+                        "tell application \"iTerm\"\n activate\n create window with default profile\n tell current session of current window to write text {}\nend tell",
+                        applescript_quote(&line)
+                    )
+                } else {
+                    format!(
+                        "tell application {}\n activate\n do script {}\nend tell",
+                        applescript_quote(app),
+                        applescript_quote(&line)
+                    )
+                };
+                launch("osascript", vec!["-e".into(), script])
+            }
+        },
+        ("windows", "wt") => {
+            let mut args = vec!["-d".to_string(), dir.to_string()];
+            if let Some(command) = command {
+                args.extend(["cmd".into(), "/K".into(), command.into()]);
+            }
+            launch("wt", args)
+        }
+        ("windows", "cmd") => {
+            // `start` inherits the working directory; a quoted /D path would
+            // be taken for the window title
+            let mut args = vec!["/C".into(), "start".into(), "cmd".into()];
+            if let Some(command) = command {
+                args.extend(["/K".into(), command.into()]);
+            }
+            launch("cmd", args)
+        }
+        (_, "gnome-terminal") => {
+            let mut args = vec![format!("--working-directory={}", dir)];
+            if let Some(command) = command {
+                args.extend(["--".into(), "sh".into(), "-c".into(), keep_shell(command)]);
+            }
+            launch("gnome-terminal", args)
+        }
+        (_, program) => {
+            let mut args = Vec::new();
+            if let Some(command) = command {
+                args.extend(["-e".into(), "sh".into(), "-c".into(), keep_shell(command)]);
+            }
+            launch(program, args)
+        }
+    }
+}
+
+/// Terminal configured in the settings, if any
+fn load_terminal_app(conn: &Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![TERMINAL_APP_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .map(|value| value.trim().to_string())
+    .filter(|value| !value.is_empty())
+}
+
+/// Open a terminal in a directory, optionally running a command in it first
+/// (e.g. `claude --resume <id>`).
+///
+/// Uses the `terminal_app` setting when set, otherwise the first of the
+/// platform's usual terminals that starts. Returns the terminal used.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn open_terminal_at(
+    db: State<'_, AgentDb>,
+    path: String,
+    command: Option<String>,
+) -> Result<String, String> {
+    let dir = normalize_project_path(&path)?.path;
+    let command = command.filter(|c| !c.trim().is_empty());
+    let configured = {
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        load_terminal_app(&conn)
+    };
+
+    let os = std::env::consts::OS;
+    let candidates: Vec<String> = match configured {
+        Some(terminal) => vec![terminal],
+        None => default_terminals(os).iter().map(|t| t.to_string()).collect(),
+    };
+
+    let mut last_error = None;
+    for terminal in &candidates {
+        let launch = terminal_launch(os, terminal, &dir, command.as_deref());
+        let spawned = Command::new(&launch.program)
+            .args(&launch.args)
+            .current_dir(Path::new(&dir))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(_) => {
+                log::info!("Opened {} in {}", terminal, dir);
+                return Ok(terminal.clone());
+            }
+            // Not installed; try the next one
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                last_error = Some(format!("{}: {}", terminal, e))
+            }
+            Err(e) => return Err(i18n::error(ErrorKind::ProcessSpawn, format!("{}: {}", terminal, e))),
+        }
+    }
+    Err(i18n::error(
+        ErrorKind::ProcessSpawn,
+        last_error.unwrap_or_else(|| "no terminal found".to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminals_open_in_the_directory_and_run_the_command() {
+        let command = Some("claude --resume abc");
+
+        assert_eq!(
+            terminal_launch("macos", "Terminal", "/p", None).args,
+            ["-a", "Terminal", "/p"]
+        );
+        let mac = terminal_launch("macos", "Terminal", "/it's", command);
+        assert_eq!(mac.program, "osascript");
+        assert!(mac.args[1].contains(r#"do script "cd '/it'\\''s' && claude --resume abc""#));
+        assert!(terminal_launch("macos", "iTerm", "/p", command).args[1].contains("write text"));
+
+        assert_eq!(
+            terminal_launch("linux", "gnome-terminal", "/p", command).args,
+            ["--working-directory=/p", "--", "sh", "-c", "claude --resume abc; exec \"${SHELL:-sh}\""]
+        );
+        assert!(terminal_launch("linux", "xterm", "/p", None).args.is_empty());
+        assert_eq!(terminal_launch("linux", "alacritty", "/p", command).args[0], "-e");
+
+        assert_eq!(
+            terminal_launch("windows", "wt", r"C:\p", command).args,
+            ["-d", r"C:\p", "cmd", "/K", "claude --resume abc"]
+        );
+        assert_eq!(
+            terminal_launch("windows", "cmd", r"C:\p", command).args,
+            ["/C", "start", "cmd", "/K", "claude --resume abc"]
+        );
+    }
+}
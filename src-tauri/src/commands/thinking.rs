@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::claude_binary::compare_versions;
+
+/// Environment variable claude reads the thinking budget from
+pub const MAX_THINKING_TOKENS_ENV: &str = "MAX_THINKING_TOKENS";
+
+/// First claude version that honours `MAX_THINKING_TOKENS`; older versions
+/// only react to the thinking keywords in the prompt
+pub const MAX_THINKING_TOKENS_MIN_VERSION: &str = "1.0.0";
+
+/// How much extended thinking a run may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThinkingLevel {
+    None,
+    Think,
+    ThinkHard,
+    Ultrathink,
+    /// An explicit budget in tokens
+    MaxTokens(u32),
+}
+
+/// Keyword levels with the prompt word that triggers them and their budget
+const KEYWORD_LEVELS: [(ThinkingLevel, &str, u32); 3] = [
+    (ThinkingLevel::Think, "think", 4_000),
+    (ThinkingLevel::ThinkHard, "think hard", 10_000),
+    (ThinkingLevel::Ultrathink, "ultrathink", 31_999),
+];
+
+impl ThinkingLevel {
+    /// Thinking budget in tokens, `None` when thinking is off
+    pub fn budget_tokens(self) -> Option<u32> {
+        match self {
+            Self::None | Self::MaxTokens(0) => None,
+            Self::MaxTokens(tokens) => Some(tokens),
+            level => KEYWORD_LEVELS
+                .iter()
+                .find(|(keyword_level, _, _)| *keyword_level == level)
+                .map(|(_, _, budget)| *budget),
+        }
+    }
+
+    /// Prompt keyword of the level, or of the smallest keyword level whose
+    /// budget covers an explicit token count
+    fn keyword(self) -> Option<&'static str> {
+        let budget = self.budget_tokens()?;
+        let (_, keyword, _) = KEYWORD_LEVELS
+            .iter()
+            .find(|(_, _, keyword_budget)| *keyword_budget >= budget)
+            .unwrap_or(&KEYWORD_LEVELS[2]);
+        Some(keyword)
+    }
+
+    /// Apply the level to a prompt for the claude version at hand.
+    ///
+    /// Versions that read `MAX_THINKING_TOKENS` get the budget through the
+    /// environment and an unchanged prompt. Older or unknown versions get
+    /// the matching keyword in front of the prompt instead.
+    pub fn apply(self, prompt: &str, cli_version: Option<&str>) -> (String, Vec<(String, String)>) {
+        let Some(budget) = self.budget_tokens() else {
+            return (prompt.to_string(), Vec::new());
+        };
+        let supports_env = cli_version.is_some_and(|version| {
+            compare_versions(version, MAX_THINKING_TOKENS_MIN_VERSION) != Ordering::Less
+        });
+        if supports_env {
+            let env = vec![(MAX_THINKING_TOKENS_ENV.to_string(), budget.to_string())];
+            return (prompt.to_string(), env);
+        }
+        match self.keyword() {
+            Some(keyword) => (format!("{}\n\n{}", keyword, prompt), Vec::new()),
+            None => (prompt.to_string(), Vec::new()),
+        }
+    }
+}
+
+impl fmt::Display for ThinkingLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Think => f.write_str("think"),
+            Self::ThinkHard => f.write_str("think_hard"),
+            Self::Ultrathink => f.write_str("ultrathink"),
+            Self::MaxTokens(tokens) => write!(f, "{}", tokens),
+        }
+    }
+}
+
+impl FromStr for ThinkingLevel {
+    type Err = String;
+
+    /// Parse `none`, `think`, `think_hard`, `ultrathink` or a token count
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let folded = s.trim().to_ascii_lowercase().replace([' ', '-'], "_");
+        match folded.as_str() {
+            "" | "none" => Ok(Self::None),
+            "think" => Ok(Self::Think),
+            "think_hard" => Ok(Self::ThinkHard),
+            "ultrathink" => Ok(Self::Ultrathink),
+            tokens => tokens.parse().map(Self::MaxTokens).map_err(|_| {
+                format!(
+                    "unknown thinking level '{}', expected none, think, think_hard, ultrathink or a token count",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_use_env_on_new_cli_and_keywords_on_old() {
+        assert_eq!("Think Hard".parse(), Ok(ThinkingLevel::ThinkHard));
+        assert_eq!("16000".parse(), Ok(ThinkingLevel::MaxTokens(16_000)));
+        assert!("deep".parse::<ThinkingLevel>().is_err());
+        assert_eq!(ThinkingLevel::MaxTokens(16_000).to_string(), "16000");
+
+        let (prompt, env) = ThinkingLevel::ThinkHard.apply("fix it", Some("1.0.41"));
+        assert_eq!(prompt, "fix it");
+        assert_eq!(env, vec![(MAX_THINKING_TOKENS_ENV.to_string(), "10000".to_string())]);
+
+        let (prompt, env) = ThinkingLevel::MaxTokens(16_000).apply("fix it", Some("0.2.9"));
+        assert_eq!(prompt, "ultrathink\n\nfix it");
+        assert!(env.is_empty());
+        let (prompt, _) = ThinkingLevel::Think.apply("fix it", None);
+        assert_eq!(prompt, "think\n\nfix it");
+
+        assert_eq!(ThinkingLevel::None.apply("fix it", Some("1.0.41")), ("fix it".to_string(), Vec::new()));
+    }
+}
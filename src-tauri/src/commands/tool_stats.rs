@@ -0,0 +1,277 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap};
+use tauri::State;
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+
+/// Prefix of the tool names claude gives MCP tools: `mcp__<server>__<tool>`
+const MCP_TOOL_PREFIX: &str = "mcp__";
+
+/// One MCP tool call of a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpToolCall {
+    pub server: String,
+    pub tool: String,
+    /// Time between the call and its result, when both were timestamped
+    pub latency_ms: Option<i64>,
+    pub is_error: bool,
+}
+
+/// Calls, errors and latency of one MCP tool
+#[apply(api_type!)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpToolStats {
+    pub server: String,
+    pub tool: String,
+    pub calls: i64,
+    pub errors: i64,
+    pub error_rate: f64,
+    /// Calls the latencies below were measured over
+    pub timed_calls: i64,
+    pub p50_ms: Option<i64>,
+    pub p95_ms: Option<i64>,
+}
+
+/// Split an MCP tool name into its server and tool
+pub fn parse_mcp_tool_name(name: &str) -> Option<(&str, &str)> {
+    let (server, tool) = name.strip_prefix(MCP_TOOL_PREFIX)?.split_once("__")?;
+    (!server.is_empty() && !tool.is_empty()).then_some((server, tool))
+}
+
+fn message_timestamp(message: &JsonValue) -> Option<DateTime<Utc>> {
+    message
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Pair the MCP tool_use blocks of a run's JSONL with their tool_result.
+///
+/// Latency is taken from the message timestamps; stream output without
+/// them still yields the calls and their errors. Calls without a result,
+/// e.g. of a killed run, count as calls without latency.
+pub fn trace_mcp_calls(content: &str) -> Vec<McpToolCall> {
+    let mut calls = Vec::new();
+    // tool_use id -> index into `calls` and the time of the call
+    let mut pending: HashMap<String, (usize, Option<DateTime<Utc>>)> = HashMap::new();
+
+    for line in content.lines() {
+        let Ok(message) = serde_json::from_str::<JsonValue>(line) else {
+            continue;
+        };
+        let blocks = message
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array());
+        for block in blocks.into_iter().flatten() {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("tool_use") => {
+                    let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                    let Some((server, tool)) = parse_mcp_tool_name(name) else {
+                        continue;
+                    };
+                    let id = block.get("id").and_then(|i| i.as_str()).unwrap_or("");
+                    pending.insert(id.to_string(), (calls.len(), message_timestamp(&message)));
+                    calls.push(McpToolCall {
+                        server: server.to_string(),
+                        tool: tool.to_string(),
+                        latency_ms: None,
+                        is_error: false,
+                    });
+                }
+                Some("tool_result") => {
+                    let id = block.get("tool_use_id").and_then(|i| i.as_str()).unwrap_or("");
+                    let Some((index, called_at)) = pending.remove(id) else {
+                        continue;
+                    };
+                    let call = &mut calls[index];
+                    call.is_error = block
+                        .get("is_error")
+                        .and_then(|e| e.as_bool())
+                        .unwrap_or(false);
+                    call.latency_ms = called_at
+                        .zip(message_timestamp(&message))
+                        .map(|(start, end)| (end - start).num_milliseconds().max(0));
+                }
+                _ => {}
+            }
+        }
+    }
+    calls
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[i64], pct: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// Aggregate calls per server and tool, sorted by server and tool
+pub fn aggregate_mcp_calls(calls: &[McpToolCall]) -> Vec<McpToolStats> {
+    let mut grouped: BTreeMap<(&str, &str), Vec<&McpToolCall>> = BTreeMap::new();
+    for call in calls {
+        grouped
+            .entry((call.server.as_str(), call.tool.as_str()))
+            .or_default()
+            .push(call);
+    }
+    grouped
+        .into_iter()
+        .map(|((server, tool), calls)| {
+            let errors = calls.iter().filter(|c| c.is_error).count() as i64;
+            let mut latencies: Vec<i64> = calls.iter().filter_map(|c| c.latency_ms).collect();
+            latencies.sort_unstable();
+            McpToolStats {
+                server: server.to_string(),
+                tool: tool.to_string(),
+                calls: calls.len() as i64,
+                errors,
+                error_rate: errors as f64 / calls.len() as f64,
+                timed_calls: latencies.len() as i64,
+                p50_ms: percentile(&latencies, 50.0),
+                p95_ms: percentile(&latencies, 95.0),
+            }
+        })
+        .collect()
+}
+
+/// Replace the stored MCP calls of a run
+pub fn store_mcp_calls(
+    conn: &mut Connection,
+    run_id: i64,
+    calls: &[McpToolCall],
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM run_mcp_calls WHERE run_id = ?1", params![run_id])?;
+    for call in calls {
+        tx.execute(
+            "INSERT INTO run_mcp_calls (run_id, server, tool, latency_ms, is_error)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![run_id, call.server, call.tool, call.latency_ms, call.is_error],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Load MCP calls matching a filter on `agent_runs` (aliased `r`)
+fn load_mcp_calls(
+    conn: &Connection,
+    filter: &str,
+    id: i64,
+) -> rusqlite::Result<Vec<McpToolCall>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT c.server, c.tool, c.latency_ms, c.is_error
+         FROM run_mcp_calls c JOIN agent_runs r ON r.id = c.run_id
+         WHERE {} = ?1 ORDER BY c.id",
+        filter
+    ))?;
+    let calls = stmt
+        .query_map(params![id], |row| {
+            Ok(McpToolCall {
+                server: row.get(0)?,
+                tool: row.get(1)?,
+                latency_ms: row.get(2)?,
+                is_error: row.get(3)?,
+            })
+        })?
+        .collect();
+    calls
+}
+
+/// MCP tool calls of a run, per server and tool
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_run_mcp_stats(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+) -> Result<Vec<McpToolStats>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let calls = load_mcp_calls(&conn, "r.id", run_id)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(aggregate_mcp_calls(&calls))
+}
+
+/// MCP tool calls of all runs of an agent, per server and tool
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_agent_tool_stats(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+) -> Result<Vec<McpToolStats>, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let calls = load_mcp_calls(&conn, "r.agent_id", agent_id)
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(aggregate_mcp_calls(&calls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::{init_schema, insert_agent, AgentInput};
+
+    #[test]
+    fn mcp_calls_are_paired_timed_and_rolled_up_per_agent() {
+        let content = [
+            r#"{"type":"assistant","timestamp":"2025-01-01T00:00:00.000Z","message":{"content":[{"type":"tool_use","id":"a","name":"mcp__github__search","input":{}},{"type":"tool_use","id":"b","name":"Read","input":{}}]}}"#,
+            r#"{"type":"user","timestamp":"2025-01-01T00:00:00.250Z","message":{"content":[{"type":"tool_result","tool_use_id":"a","content":"ok"}]}}"#,
+            r#"{"type":"assistant","timestamp":"2025-01-01T00:00:01.000Z","message":{"content":[{"type":"tool_use","id":"c","name":"mcp__github__search","input":{}}]}}"#,
+            r#"{"type":"user","timestamp":"2025-01-01T00:00:02.000Z","message":{"content":[{"type":"tool_result","tool_use_id":"c","is_error":true,"content":"boom"}]}}"#,
+            // Stream output without timestamps still counts
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"d","name":"mcp__db__query","input":{}}]}}"#,
+            r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"d","content":"rows"}]}}"#,
+        ]
+        .join("\n");
+
+        let calls = trace_mcp_calls(&content);
+        assert_eq!(calls.len(), 3);
+        let stats = aggregate_mcp_calls(&calls);
+        assert_eq!(
+            stats[0],
+            McpToolStats {
+                server: "db".to_string(),
+                tool: "query".to_string(),
+                calls: 1,
+                errors: 0,
+                error_rate: 0.0,
+                timed_calls: 0,
+                p50_ms: None,
+                p95_ms: None,
+            }
+        );
+        assert_eq!((stats[1].calls, stats[1].errors, stats[1].error_rate), (2, 1, 0.5));
+        assert_eq!((stats[1].p50_ms, stats[1].p95_ms), (Some(250), Some(1000)));
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        let input = AgentInput {
+            name: "A".to_string(),
+            icon: "bot".to_string(),
+            system_prompt: "You are helpful".to_string(),
+            ..Default::default()
+        };
+        let agent_id = insert_agent(&conn, input).unwrap().id.unwrap();
+        for _ in 0..2 {
+            conn.execute(
+                "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, 'A', 'i', 't', 'sonnet', '/tmp', 's')",
+                params![agent_id],
+            )
+            .unwrap();
+            let run_id = conn.last_insert_rowid();
+            store_mcp_calls(&mut conn, run_id, &calls).unwrap();
+        }
+        let run_stats = aggregate_mcp_calls(&load_mcp_calls(&conn, "r.id", 1).unwrap());
+        assert_eq!(run_stats, stats);
+        let agent_stats = aggregate_mcp_calls(&load_mcp_calls(&conn, "r.agent_id", agent_id).unwrap());
+        assert_eq!(agent_stats[1].calls, 4);
+    }
+}
@@ -3,9 +3,16 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use tauri::command;
+use std::path::{Path, PathBuf};
+use tauri::{command, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
 
+use super::agents::AgentDb;
+use super::claude::{canonical_project_key, canonical_project_path, project_path_key};
+use super::usage_index::load_usage_index;
+
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UsageEntry {
     timestamp: String,
@@ -19,6 +26,7 @@ pub struct UsageEntry {
     project_path: String,
 }
 
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UsageStats {
     total_cost: f64,
@@ -33,6 +41,7 @@ pub struct UsageStats {
     by_project: Vec<ProjectUsage>,
 }
 
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelUsage {
     model: String,
@@ -45,6 +54,7 @@ pub struct ModelUsage {
     session_count: u64,
 }
 
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DailyUsage {
     date: String,
@@ -53,6 +63,7 @@ pub struct DailyUsage {
     models_used: Vec<String>,
 }
 
+#[apply(api_type!)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectUsage {
     project_path: String,
@@ -143,11 +154,20 @@ fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
     cost
 }
 
+/// Cost in USD of a raw `usage` object from a session message
+pub fn cost_of_usage(model: &str, usage: &serde_json::Value) -> f64 {
+    serde_json::from_value::<UsageData>(usage.clone())
+        .map(|usage| calculate_cost(model, &usage))
+        .unwrap_or(0.0)
+}
+
+/// Usage entries of a session file, each with its `message id:request id`
+/// deduplication key when it has one
 fn parse_jsonl_file(
     path: &PathBuf,
     encoded_project_name: &str,
     processed_hashes: &mut HashSet<String>,
-) -> Vec<UsageEntry> {
+) -> Vec<(Option<String>, UsageEntry)> {
     let mut entries = Vec::new();
     let mut actual_project_path: Option<String> = None;
 
@@ -177,12 +197,14 @@ fn parse_jsonl_file(
                 if let Ok(entry) = serde_json::from_value::<JsonlEntry>(json_value) {
                     if let Some(message) = &entry.message {
                         // Deduplication based on message ID and request ID
+                        let mut dedup_key = None;
                         if let (Some(msg_id), Some(req_id)) = (&message.id, &entry.request_id) {
                             let unique_hash = format!("{}:{}", msg_id, req_id);
                             if processed_hashes.contains(&unique_hash) {
                                 continue; // Skip duplicate entry
                             }
-                            processed_hashes.insert(unique_hash);
+                            processed_hashes.insert(unique_hash.clone());
+                            dedup_key = Some(unique_hash);
                         }
 
                         if let Some(usage) = &message.usage {
@@ -208,7 +230,7 @@ fn parse_jsonl_file(
                                 .clone()
                                 .unwrap_or_else(|| encoded_project_name.to_string());
 
-                            entries.push(UsageEntry {
+                            entries.push((dedup_key, UsageEntry {
                                 timestamp: entry.timestamp,
                                 model: message
                                     .model
@@ -223,7 +245,7 @@ fn parse_jsonl_file(
                                 cost,
                                 session_id: entry.session_id.unwrap_or_else(|| session_id.clone()),
                                 project_path,
-                            });
+                            }));
                         }
                     }
                 }
@@ -234,6 +256,36 @@ fn parse_jsonl_file(
     entries
 }
 
+/// Cost in USD of each session stored in one ~/.claude/projects directory
+pub fn session_costs(project_dir: &Path) -> HashMap<String, f64> {
+    let mut costs = HashMap::new();
+    let mut processed_hashes = HashSet::new();
+    let encoded_project_name = project_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let Ok(entries) = fs::read_dir(project_dir) else {
+        return costs;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        // The file name is the session, even if resumed messages carry an older sessionId
+        let Some(session_id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let cost: f64 = parse_jsonl_file(&path, &encoded_project_name, &mut processed_hashes)
+            .iter()
+            .map(|(_, usage)| usage.cost)
+            .sum();
+        *costs.entry(session_id).or_insert(0.0) += cost;
+    }
+    costs
+}
+
 fn get_earliest_timestamp(path: &PathBuf) -> Option<String> {
     if let Ok(content) = fs::read_to_string(path) {
         let mut earliest_timestamp: Option<String> = None;
@@ -255,37 +307,107 @@ fn get_earliest_timestamp(path: &PathBuf) -> Option<String> {
     None
 }
 
-fn get_all_usage_entries(claude_path: &PathBuf) -> Vec<UsageEntry> {
-    let mut all_entries = Vec::new();
-    let mut processed_hashes = HashSet::new();
-    let projects_dir = claude_path.join("projects");
-
-    let mut files_to_process: Vec<(PathBuf, String)> = Vec::new();
-
-    if let Ok(projects) = fs::read_dir(&projects_dir) {
-        for project in projects.flatten() {
-            if project.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-                let project_name = project.file_name().to_string_lossy().to_string();
-                let project_path = project.path();
-
-                walkdir::WalkDir::new(&project_path)
-                    .into_iter()
-                    .filter_map(Result::ok)
-                    .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
-                    .for_each(|entry| {
-                        files_to_process.push((entry.path().to_path_buf(), project_name.clone()));
-                    });
-            }
+/// A session file and the stat that tells whether its index row is current
+#[derive(Debug, Clone)]
+pub struct UsageFile {
+    pub path: PathBuf,
+    /// Name of the ~/.claude/projects directory the file is in
+    pub project_name: String,
+    /// Modification time in milliseconds since the epoch
+    pub mtime: i64,
+    pub size: u64,
+}
+
+/// Usage of one session file, deduplicated within the file only
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileUsage {
+    pub earliest_timestamp: Option<String>,
+    pub entries: Vec<(Option<String>, UsageEntry)>,
+}
+
+/// Every session file under `projects_dir`
+pub fn list_usage_files(projects_dir: &Path) -> Vec<UsageFile> {
+    let mut files = Vec::new();
+    let Ok(projects) = fs::read_dir(projects_dir) else {
+        return files;
+    };
+    for project in projects.flatten() {
+        if !project.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        let project_name = project.file_name().to_string_lossy().to_string();
+        for entry in walkdir::WalkDir::new(project.path())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
+        {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|age| age.as_millis() as i64)
+                .unwrap_or(0);
+            files.push(UsageFile {
+                path: entry.path().to_path_buf(),
+                project_name: project_name.clone(),
+                mtime,
+                size: metadata.len(),
+            });
         }
     }
+    files
+}
+
+/// Parse one session file
+pub fn read_file_usage(file: &UsageFile) -> FileUsage {
+    FileUsage {
+        earliest_timestamp: get_earliest_timestamp(&file.path),
+        entries: parse_jsonl_file(&file.path, &file.project_name, &mut HashSet::new()),
+    }
+}
+
+/// All usage entries, deduplicated and sorted by time.
+///
+/// Files whose usage index row matches their size and mtime are read from
+/// the index; the rest are parsed.
+pub fn get_all_usage_entries(claude_path: &Path, db: Option<&AgentDb>) -> Vec<UsageEntry> {
+    let files = list_usage_files(&claude_path.join("projects"));
+    let mut index = db.map(load_usage_index).unwrap_or_default();
+    let mut file_usages: Vec<FileUsage> = files
+        .iter()
+        .map(|file| match index.remove(&file.path) {
+            Some((mtime, size, usage)) if mtime == file.mtime && size == file.size => usage,
+            _ => read_file_usage(file),
+        })
+        .collect();
 
     // Sort files by their earliest timestamp to ensure chronological processing
     // and deterministic deduplication.
-    files_to_process.sort_by_cached_key(|(path, _)| get_earliest_timestamp(path));
+    file_usages.sort_by(|a, b| a.earliest_timestamp.cmp(&b.earliest_timestamp));
+
+    let mut all_entries = Vec::new();
+    let mut processed_hashes = HashSet::new();
+    for usage in file_usages {
+        for (dedup_key, entry) in usage.entries {
+            if let Some(key) = dedup_key {
+                if !processed_hashes.insert(key) {
+                    continue;
+                }
+            }
+            all_entries.push(entry);
+        }
+    }
 
-    for (path, project_name) in files_to_process {
-        let entries = parse_jsonl_file(&path, &project_name, &mut processed_hashes);
-        all_entries.extend(entries);
+    // Sessions started through a symlink belong to the same project as its target
+    let mut canonical_paths: HashMap<String, String> = HashMap::new();
+    for entry in all_entries.iter_mut() {
+        entry.project_path = canonical_paths
+            .entry(entry.project_path.clone())
+            .or_insert_with(|| canonical_project_path(&entry.project_path))
+            .clone();
     }
 
     // Sort by timestamp
@@ -294,13 +416,12 @@ fn get_all_usage_entries(claude_path: &PathBuf) -> Vec<UsageEntry> {
     all_entries
 }
 
+#[apply(api_command!)]
 #[command]
-pub fn get_usage_stats(days: Option<u32>) -> Result<UsageStats, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
+pub fn get_usage_stats(db: State<'_, AgentDb>, days: Option<u32>) -> Result<UsageStats, String> {
+    let claude_path = crate::claude_dir::claude_dir()?;
 
-    let all_entries = get_all_usage_entries(&claude_path);
+    let all_entries = get_all_usage_entries(&claude_path, Some(&db));
 
     if all_entries.is_empty() {
         return Ok(UsageStats {
@@ -454,13 +575,16 @@ pub fn get_usage_stats(days: Option<u32>) -> Result<UsageStats, String> {
     })
 }
 
+#[apply(api_command!)]
 #[command]
-pub fn get_usage_by_date_range(start_date: String, end_date: String) -> Result<UsageStats, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
+pub fn get_usage_by_date_range(
+    db: State<'_, AgentDb>,
+    start_date: String,
+    end_date: String,
+) -> Result<UsageStats, String> {
+    let claude_path = crate::claude_dir::claude_dir()?;
 
-    let all_entries = get_all_usage_entries(&claude_path);
+    let all_entries = get_all_usage_entries(&claude_path, Some(&db));
 
     // Parse dates
     let start = NaiveDate::parse_from_str(&start_date, "%Y-%m-%d").or_else(|_| {
@@ -624,41 +748,112 @@ pub fn get_usage_by_date_range(start_date: String, end_date: String) -> Result<U
     })
 }
 
-#[command]
-pub fn get_usage_details(
-    project_path: Option<String>,
-    date: Option<String>,
-) -> Result<Vec<UsageEntry>, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    let mut all_entries = get_all_usage_entries(&claude_path);
+/// Rows returned by `get_usage_details` without pagination, and the largest
+/// page it serves, to keep IPC payloads bounded
+const USAGE_DETAILS_MAX_ROWS: usize = 5_000;
+
+/// Which usage entries `get_usage_details` returns
+#[derive(Debug, Clone, Default)]
+pub struct UsageDetailsQuery {
+    pub project_path: Option<String>,
+    /// Date prefix of the timestamp, e.g. "2025-01" or "2025-01-31"
+    pub date: Option<String>,
+    pub model: Option<String>,
+    pub min_cost: Option<f64>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
 
-    // Filter by project if specified
-    if let Some(project) = project_path {
-        all_entries.retain(|e| e.project_path == project);
-    }
+/// One page of usage entries with the totals of everything that matched
+#[apply(api_type!)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageDetailsPage {
+    entries: Vec<UsageEntry>,
+    /// Entries that matched the filters, across all pages
+    total_count: usize,
+    total_cost: f64,
+    /// More entries matched than fit in an unpaginated response
+    truncated: bool,
+}
 
-    // Filter by date if specified
-    if let Some(date) = date {
-        all_entries.retain(|e| e.timestamp.starts_with(&date));
+/// Filter `entries`, order them by time (ties by session and model) and
+/// cut out the requested page
+fn usage_details_page(mut entries: Vec<UsageEntry>, query: &UsageDetailsQuery) -> UsageDetailsPage {
+    let project_key = query.project_path.as_deref().map(canonical_project_key);
+    entries.retain(|e| {
+        project_key
+            .as_ref()
+            .is_none_or(|key| project_path_key(&e.project_path) == *key)
+            && query.date.as_ref().is_none_or(|date| e.timestamp.starts_with(date))
+            && query.model.as_ref().is_none_or(|model| e.model == *model)
+            && query.min_cost.is_none_or(|min_cost| e.cost >= min_cost)
+    });
+    entries.sort_by(|a, b| {
+        a.timestamp
+            .cmp(&b.timestamp)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+
+    let total_count = entries.len();
+    let total_cost = entries.iter().map(|e| e.cost).sum();
+    let paginated = query.limit.is_some() || query.offset.is_some();
+    let limit = query.limit.unwrap_or(USAGE_DETAILS_MAX_ROWS).min(USAGE_DETAILS_MAX_ROWS);
+    let entries: Vec<UsageEntry> = entries
+        .into_iter()
+        .skip(query.offset.unwrap_or(0))
+        .take(limit)
+        .collect();
+    UsageDetailsPage {
+        truncated: !paginated && entries.len() < total_count,
+        entries,
+        total_count,
+        total_cost,
     }
+}
 
-    Ok(all_entries)
+/// Usage entries matching the filters, oldest first.
+///
+/// Pass `limit`/`offset` to page through them; without either, at most
+/// `USAGE_DETAILS_MAX_ROWS` entries are returned and `truncated` says
+/// whether some were left out. The totals always cover every match.
+#[apply(api_command!)]
+#[command]
+pub fn get_usage_details(
+    db: State<'_, AgentDb>,
+    project_path: Option<String>,
+    date: Option<String>,
+    model: Option<String>,
+    min_cost: Option<f64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<UsageDetailsPage, String> {
+    let claude_path = crate::claude_dir::claude_dir()?;
+
+    // Unchanged session files are read from the usage index
+    let all_entries = get_all_usage_entries(&claude_path, Some(&db));
+    let query = UsageDetailsQuery {
+        project_path,
+        date,
+        model,
+        min_cost,
+        limit,
+        offset,
+    };
+    Ok(usage_details_page(all_entries, &query))
 }
 
+#[apply(api_command!)]
 #[command]
 pub fn get_session_stats(
+    db: State<'_, AgentDb>,
     since: Option<String>,
     until: Option<String>,
     order: Option<String>,
 ) -> Result<Vec<ProjectUsage>, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
+    let claude_path = crate::claude_dir::claude_dir()?;
 
-    let all_entries = get_all_usage_entries(&claude_path);
+    let all_entries = get_all_usage_entries(&claude_path, Some(&db));
 
     let since_date = since.and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok());
     let until_date = until.and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok());
@@ -718,3 +913,65 @@ pub fn get_session_stats(
 
     Ok(by_session)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, session_id: &str, model: &str, cost: f64) -> UsageEntry {
+        UsageEntry {
+            timestamp: timestamp.to_string(),
+            model: model.to_string(),
+            input_tokens: 0,
+            output_tokens: 0,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            cost,
+            session_id: session_id.to_string(),
+            project_path: "/nonexistent/app".to_string(),
+        }
+    }
+
+    #[test]
+    fn usage_details_are_filtered_sorted_and_paged_with_totals() {
+        let entries = || {
+            vec![
+                entry("2025-01-02T10:00:00Z", "b", "sonnet", 0.5),
+                entry("2025-01-01T10:00:00Z", "a", "opus", 2.0),
+                entry("2025-01-02T10:00:00Z", "a", "sonnet", 1.0),
+                entry("2025-02-01T10:00:00Z", "c", "sonnet", 0.01),
+            ]
+        };
+
+        let query = UsageDetailsQuery {
+            date: Some("2025-01".to_string()),
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let page = usage_details_page(entries(), &query);
+        assert_eq!(page.total_count, 3);
+        assert!((page.total_cost - 3.5).abs() < 1e-9);
+        let sessions: Vec<_> = page.entries.iter().map(|e| e.session_id.as_str()).collect();
+        assert_eq!(sessions, ["a", "b"]);
+        assert!(!page.truncated);
+
+        let query = UsageDetailsQuery {
+            project_path: Some("/nonexistent/app".to_string()),
+            model: Some("sonnet".to_string()),
+            min_cost: Some(0.1),
+            ..Default::default()
+        };
+        let page = usage_details_page(entries(), &query);
+        assert_eq!(page.total_count, 2);
+        assert_eq!(page.entries.len(), 2);
+
+        let many = (0..USAGE_DETAILS_MAX_ROWS + 1)
+            .map(|i| entry("2025-01-01T10:00:00Z", &format!("s{:05}", i), "sonnet", 0.0))
+            .collect();
+        let page = usage_details_page(many, &UsageDetailsQuery::default());
+        assert_eq!(page.entries.len(), USAGE_DETAILS_MAX_ROWS);
+        assert_eq!(page.total_count, USAGE_DETAILS_MAX_ROWS + 1);
+        assert!(page.truncated);
+    }
+}
@@ -0,0 +1,358 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::AgentDb;
+use super::i18n::{self, ErrorKind};
+use super::usage::{list_usage_files, read_file_usage, FileUsage, UsageFile};
+
+/// Event carrying `UsageIndexProgress` while the index is built
+pub const USAGE_INDEX_PROGRESS_EVENT: &str = "usage-index-progress";
+
+/// app_settings key holding the progress of the last build (JSON)
+pub const USAGE_INDEX_PROGRESS_KEY: &str = "usage_index_progress";
+
+/// Files parsed between two commits
+pub const USAGE_INDEX_BATCH_SIZE: usize = 50;
+
+/// Whether a build is running, and the flag that asks it to stop
+#[derive(Debug, Default)]
+pub struct UsageIndexState {
+    running: AtomicBool,
+    cancel: AtomicBool,
+}
+
+/// How far a usage index build got
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageIndexProgress {
+    /// Files indexed, including ones already current when the build started
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_processed: u64,
+    pub bytes_total: u64,
+    /// Last file committed, in path order
+    pub last_path: Option<String>,
+    pub last_mtime: Option<i64>,
+    pub finished: bool,
+    pub cancelled: bool,
+}
+
+/// Progress of the last build and whether one is running now
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageIndexStatus {
+    pub running: bool,
+    pub progress: Option<UsageIndexProgress>,
+}
+
+/// Indexed usage by file path, with the mtime and size it was read at
+pub fn load_usage_index(db: &AgentDb) -> HashMap<PathBuf, (i64, u64, FileUsage)> {
+    let Ok(conn) = db.0.lock() else {
+        return HashMap::new();
+    };
+    let rows = conn
+        .prepare("SELECT path, mtime, size, usage FROM usage_index_files")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+        });
+    match rows {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|(path, mtime, size, usage)| {
+                let usage = serde_json::from_str(&usage).ok()?;
+                Some((PathBuf::from(path), (mtime, size as u64, usage)))
+            })
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to read the usage index: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Progress saved by the last build
+fn load_progress(conn: &Connection) -> Option<UsageIndexProgress> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![USAGE_INDEX_PROGRESS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|value| serde_json::from_str(&value).ok())
+}
+
+fn save_progress(conn: &Connection, progress: &UsageIndexProgress) -> Result<(), String> {
+    let value = serde_json::to_string(progress).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![USAGE_INDEX_PROGRESS_KEY, value],
+    )
+    .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(())
+}
+
+/// Index the session files under `projects_dir`, committing after every
+/// `batch_size` files.
+///
+/// Files whose row matches their mtime and size are skipped, so a build that
+/// was interrupted picks up after the last committed batch. `cancel` is
+/// checked between batches.
+pub fn index_usage_files(
+    db: &AgentDb,
+    projects_dir: &Path,
+    batch_size: usize,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&UsageIndexProgress),
+) -> Result<UsageIndexProgress, String> {
+    let mut files = list_usage_files(projects_dir);
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let indexed: HashMap<PathBuf, (i64, u64)> = load_usage_index(db)
+        .into_iter()
+        .map(|(path, (mtime, size, _))| (path, (mtime, size)))
+        .collect();
+    let is_current =
+        |file: &UsageFile| indexed.get(&file.path) == Some(&(file.mtime, file.size));
+
+    let mut progress = UsageIndexProgress {
+        files_total: files.len(),
+        bytes_total: files.iter().map(|f| f.size).sum(),
+        ..Default::default()
+    };
+    for file in files.iter().filter(|file| is_current(file)) {
+        progress.files_done += 1;
+        progress.bytes_processed += file.size;
+    }
+    on_progress(&progress);
+
+    let pending: Vec<_> = files.iter().filter(|file| !is_current(file)).collect();
+    for batch in pending.chunks(batch_size.max(1)) {
+        if cancel.load(Ordering::SeqCst) {
+            progress.cancelled = true;
+            let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+            save_progress(&conn, &progress)?;
+            on_progress(&progress);
+            log::info!(
+                "Usage index build cancelled after {} of {} files",
+                progress.files_done,
+                progress.files_total
+            );
+            return Ok(progress);
+        }
+
+        // Parse without the lock; only the commit holds it
+        let parsed: Vec<_> = batch.iter().map(|file| (*file, read_file_usage(file))).collect();
+        let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        for (file, usage) in &parsed {
+            let usage = serde_json::to_string(usage).map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO usage_index_files (path, mtime, size, usage) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(path) DO UPDATE SET mtime = excluded.mtime, size = excluded.size,
+                     usage = excluded.usage, indexed_at = CURRENT_TIMESTAMP",
+                params![file.path.to_string_lossy(), file.mtime, file.size as i64, usage],
+            )
+            .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+            progress.files_done += 1;
+            progress.bytes_processed += file.size;
+            progress.last_path = Some(file.path.to_string_lossy().to_string());
+            progress.last_mtime = Some(file.mtime);
+        }
+        save_progress(&tx, &progress)?;
+        tx.commit().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+        drop(conn);
+        on_progress(&progress);
+    }
+
+    // Forget files that were deleted since they were indexed
+    let scanned: HashSet<&PathBuf> = files.iter().map(|file| &file.path).collect();
+    let deleted: Vec<_> = indexed.keys().filter(|path| !scanned.contains(path)).collect();
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    for path in deleted {
+        tx.execute(
+            "DELETE FROM usage_index_files WHERE path = ?1",
+            params![path.to_string_lossy()],
+        )
+        .map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    }
+    progress.finished = true;
+    save_progress(&tx, &progress)?;
+    tx.commit().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    drop(conn);
+    on_progress(&progress);
+    Ok(progress)
+}
+
+/// Start a build in the background unless one is running; returns whether it started
+pub fn start_usage_index_build(app: &AppHandle) -> bool {
+    let state = app.state::<UsageIndexState>();
+    if state.running.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    state.cancel.store(false, Ordering::SeqCst);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<UsageIndexState>();
+        let db = app.state::<AgentDb>();
+        let result = crate::claude_dir::projects_dir().and_then(|projects_dir| {
+            index_usage_files(
+                &db,
+                &projects_dir,
+                USAGE_INDEX_BATCH_SIZE,
+                &state.cancel,
+                |progress| {
+                    if let Err(e) = app.emit(USAGE_INDEX_PROGRESS_EVENT, progress) {
+                        log::warn!("Failed to emit {}: {}", USAGE_INDEX_PROGRESS_EVENT, e);
+                    }
+                },
+            )
+        });
+        if let Err(e) = result {
+            log::error!("Usage index build failed: {}", e);
+        }
+        state.running.store(false, Ordering::SeqCst);
+    });
+    true
+}
+
+/// Resume a build the previous instance was closed in the middle of
+pub fn resume_usage_index_build(app: &AppHandle) {
+    let interrupted = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(_) => return,
+        };
+        load_progress(&conn).is_some_and(|progress| !progress.finished && !progress.cancelled)
+    };
+    if interrupted {
+        log::info!("Resuming the interrupted usage index build");
+        start_usage_index_build(app);
+    }
+}
+
+/// Build or refresh the usage index in the background.
+///
+/// Progress is reported with `usage-index-progress` events. Returns false
+/// when a build is already running.
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn build_usage_index(app: AppHandle) -> Result<bool, String> {
+    Ok(start_usage_index_build(&app))
+}
+
+/// Ask the running build to stop after its current batch
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn cancel_usage_index_build(
+    state: State<'_, UsageIndexState>,
+) -> Result<bool, String> {
+    let running = state.running.load(Ordering::SeqCst);
+    if running {
+        state.cancel.store(true, Ordering::SeqCst);
+    }
+    Ok(running)
+}
+
+/// Progress of the last usage index build
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_usage_index_status(
+    db: State<'_, AgentDb>,
+    state: State<'_, UsageIndexState>,
+) -> Result<UsageIndexStatus, String> {
+    let conn = db.0.lock().map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    Ok(UsageIndexStatus {
+        running: state.running.load(Ordering::SeqCst),
+        progress: load_progress(&conn),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::init_schema;
+    use crate::commands::usage::get_all_usage_entries;
+    use std::fs;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    fn usage_line(message_id: &str, tokens: u64) -> String {
+        format!(
+            "{{\"timestamp\":\"2025-01-0{}T10:00:00Z\",\"sessionId\":\"s\",\"requestId\":\"r{}\",\"cwd\":\"/work/app\",\"message\":{{\"id\":\"{}\",\"model\":\"claude-sonnet-4\",\"usage\":{{\"input_tokens\":{},\"output_tokens\":1}}}}}}\n",
+            tokens, message_id, message_id, tokens
+        )
+    }
+
+    #[test]
+    fn cancelled_build_resumes_after_the_last_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("projects").join("-work-app");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(project_dir.join("a.jsonl"), usage_line("m1", 1)).unwrap();
+        fs::write(project_dir.join("b.jsonl"), usage_line("m2", 2)).unwrap();
+        // A resumed session repeats an earlier message
+        fs::write(
+            project_dir.join("c.jsonl"),
+            usage_line("m1", 1) + &usage_line("m3", 3),
+        )
+        .unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        let db = AgentDb(Mutex::new(conn));
+        let projects_dir = temp_dir.path().join("projects");
+
+        let cancel = AtomicBool::new(false);
+        let progress = index_usage_files(&db, &projects_dir, 1, &cancel, |progress| {
+            if progress.files_done == 1 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+        })
+        .unwrap();
+        assert!(progress.cancelled);
+        assert_eq!(progress.files_done, 1);
+        assert!(progress.last_path.unwrap().ends_with("a.jsonl"));
+        assert_eq!(load_usage_index(&db).len(), 1);
+
+        let mut reports = Vec::new();
+        let cancel = AtomicBool::new(false);
+        let progress = index_usage_files(&db, &projects_dir, 1, &cancel, |progress| {
+            reports.push(progress.files_done)
+        })
+        .unwrap();
+        assert!(progress.finished);
+        assert_eq!(reports, vec![1, 2, 3, 3]);
+        assert_eq!(progress.bytes_processed, progress.bytes_total);
+        let saved = load_progress(&db.0.lock().unwrap()).unwrap();
+        assert_eq!(saved, progress);
+
+        // Rows keep duplicates across files; reading deduplicates them like a fresh parse
+        let index = load_usage_index(&db);
+        let indexed: usize = index.values().map(|(_, _, usage)| usage.entries.len()).sum();
+        assert_eq!(indexed, 4);
+        assert_eq!(get_all_usage_entries(temp_dir.path(), Some(&db)).len(), 3);
+        assert_eq!(get_all_usage_entries(temp_dir.path(), None).len(), 3);
+    }
+}
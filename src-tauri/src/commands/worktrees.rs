@@ -0,0 +1,664 @@
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDateTime, Utc};
+use log::{info, warn};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Emitter, Manager, State};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use super::agents::{query_agent_run, AgentDb, STATUS_PAUSED, STATUS_RATE_LIMITED};
+use crate::checkpoint::storage::CheckpointStorage;
+
+/// Directory under app_data_dir holding one worktree per run
+const WORKTREES_DIR: &str = "worktrees";
+
+/// Directories never copied into (or compared across) plain-copy worktrees
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// Default retention window of the worktree janitor
+const DEFAULT_RETENTION_HOURS: u64 = 72;
+
+/// How an isolated worktree was created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorktreeKind {
+    /// Linked `git worktree` of the project repository
+    Git,
+    /// Plain recursive copy of a directory that isn't a git repository
+    Copy,
+}
+
+/// Kind of change between the original project and a worktree
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorktreeChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A file that differs between the original project and a worktree
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeChange {
+    /// Path relative to the project root, with forward slashes
+    pub path: String,
+    pub status: WorktreeChangeStatus,
+}
+
+/// Payload of the `agent-worktree-changes:{run_id}` event and the diff command
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeDiff {
+    pub run_id: i64,
+    pub project_path: String,
+    pub worktree_path: String,
+    pub changes: Vec<WorktreeChange>,
+}
+
+/// Result of copying worktree changes back into the project
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeApplyResult {
+    /// Paths written to (or deleted from) the project
+    pub applied: Vec<String>,
+    /// Requested paths that have no change in the worktree
+    pub skipped: Vec<String>,
+}
+
+/// Result of a janitor pass over the worktrees directory
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeCleanupResult {
+    /// Runs whose worktree was removed
+    pub removed_runs: Vec<i64>,
+    /// Leftover directories that no run referenced
+    pub removed_orphans: usize,
+}
+
+/// Get the directory holding all run worktrees
+pub fn get_worktrees_dir(app: &AppHandle) -> Result<PathBuf> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow::anyhow!("Failed to get app data dir: {}", e))?;
+    Ok(app_dir.join(WORKTREES_DIR))
+}
+
+/// Run git in `dir` and return its stdout
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Whether `path` is the top level of a git work tree
+fn is_git_repo(path: &Path) -> bool {
+    git(path, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .and_then(|out| String::from_utf8(out).ok())
+        .and_then(|top| Path::new(top.trim()).canonicalize().ok())
+        .is_some_and(|top| path.canonicalize().is_ok_and(|p| p == top))
+}
+
+/// Git worktrees have a `.git` file pointing back at the main repository
+pub fn worktree_kind(worktree: &Path) -> WorktreeKind {
+    if worktree.join(".git").is_file() {
+        WorktreeKind::Git
+    } else {
+        WorktreeKind::Copy
+    }
+}
+
+/// Split NUL-separated `git ls-files -z` output into relative paths
+fn parse_nul_list(output: &[u8]) -> Vec<String> {
+    output
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).into_owned())
+        .collect()
+}
+
+/// Relative paths of all files under `root`, skipping `SKIPPED_DIRS`
+fn walk_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            if SKIPPED_DIRS.iter().any(|skip| name == *skip) {
+                continue;
+            }
+            walk_files(root, &path, files)?;
+        } else if file_type.is_file() || file_type.is_symlink() {
+            if let Ok(rel) = path.strip_prefix(root) {
+                files.push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Files of a project as seen by the worktree kind: tracked and untracked
+/// non-ignored files for git, every file outside `SKIPPED_DIRS` otherwise
+fn list_files(root: &Path, kind: WorktreeKind) -> Result<Vec<String>> {
+    match kind {
+        WorktreeKind::Git => {
+            let output = git(root, &["ls-files", "-co", "--exclude-standard", "-z"])?;
+            Ok(parse_nul_list(&output)
+                .into_iter()
+                .filter(|rel| is_file_or_symlink(&root.join(rel)))
+                .collect())
+        }
+        WorktreeKind::Copy => {
+            let mut files = Vec::new();
+            walk_files(root, root, &mut files)?;
+            Ok(files)
+        }
+    }
+}
+
+/// Whether `path` is a regular file or a symlink, without following it
+fn is_file_or_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_file() || m.is_symlink())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Copy a single file, creating its parent directories.
+///
+/// A symlink is recreated with the same target rather than copied through,
+/// and one at `to` is replaced rather than written through.
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::symlink_metadata(to).is_ok_and(|m| m.is_symlink()) {
+        fs::remove_file(to).with_context(|| format!("Failed to replace {}", to.display()))?;
+    }
+    if fs::symlink_metadata(from)?.is_symlink() {
+        let target = fs::read_link(from)?;
+        if to.exists() {
+            fs::remove_file(to).with_context(|| format!("Failed to replace {}", to.display()))?;
+        }
+        create_symlink(&target, to).with_context(|| format!("Failed to link {}", to.display()))?;
+        return Ok(());
+    }
+    fs::copy(from, to).with_context(|| format!("Failed to copy {}", from.display()))?;
+    Ok(())
+}
+
+/// Create an isolated copy of `original` at `dest`.
+///
+/// Git repositories get a detached `git worktree` of HEAD with the uncommitted
+/// changes of the checkout copied over, so the agent starts from exactly what
+/// the user sees. Anything else is copied file by file.
+pub fn create_worktree(original: &Path, dest: &Path) -> Result<WorktreeKind> {
+    if dest.exists() {
+        bail!("Worktree already exists: {}", dest.display());
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).context("Failed to create worktrees directory")?;
+    }
+
+    if is_git_repo(original) {
+        let dest_str = dest.to_string_lossy();
+        match git(original, &["worktree", "add", "--detach", &dest_str, "HEAD"]) {
+            Ok(_) => {
+                let dirty = git(original, &["ls-files", "-mo", "--exclude-standard", "-z"])?;
+                for rel in parse_nul_list(&dirty) {
+                    let source = original.join(&rel);
+                    if is_file_or_symlink(&source) {
+                        copy_file(&source, &dest.join(&rel))?;
+                    }
+                }
+                let deleted = git(original, &["ls-files", "-d", "-z"])?;
+                for rel in parse_nul_list(&deleted) {
+                    let _ = fs::remove_file(dest.join(&rel));
+                }
+                return Ok(WorktreeKind::Git);
+            }
+            // e.g. a repository without any commit yet
+            Err(e) => warn!("git worktree failed, falling back to a copy: {}", e),
+        }
+    }
+
+    for rel in list_files(original, WorktreeKind::Copy)? {
+        copy_file(&original.join(&rel), &dest.join(&rel))?;
+    }
+    Ok(WorktreeKind::Copy)
+}
+
+/// Whether two files have different content; symlinks are compared by target
+fn files_differ(a: &Path, b: &Path) -> Result<bool> {
+    let (meta_a, meta_b) = (fs::symlink_metadata(a)?, fs::symlink_metadata(b)?);
+    if meta_a.is_symlink() || meta_b.is_symlink() {
+        return Ok(meta_a.is_symlink() != meta_b.is_symlink()
+            || fs::read_link(a)? != fs::read_link(b)?);
+    }
+    if meta_a.len() != meta_b.len() {
+        return Ok(true);
+    }
+    Ok(CheckpointStorage::hash_file(a)? != CheckpointStorage::hash_file(b)?)
+}
+
+/// Compare a worktree against the original project, sorted by path
+pub fn diff_worktree(original: &Path, worktree: &Path) -> Result<Vec<WorktreeChange>> {
+    let kind = worktree_kind(worktree);
+    let original_files: HashSet<String> = list_files(original, kind)?.into_iter().collect();
+    let worktree_files: HashSet<String> = list_files(worktree, kind)?.into_iter().collect();
+
+    let mut changes = BTreeMap::new();
+    for rel in &worktree_files {
+        if !original_files.contains(rel) {
+            changes.insert(rel.clone(), WorktreeChangeStatus::Added);
+        } else if files_differ(&original.join(rel), &worktree.join(rel))? {
+            changes.insert(rel.clone(), WorktreeChangeStatus::Modified);
+        }
+    }
+    for rel in original_files.difference(&worktree_files) {
+        changes.insert(rel.clone(), WorktreeChangeStatus::Deleted);
+    }
+
+    Ok(changes
+        .into_iter()
+        .map(|(path, status)| WorktreeChange { path, status })
+        .collect())
+}
+
+/// Copy the selected changes of a worktree back into the original project.
+///
+/// Only paths that show up in the diff are touched, so a request can never
+/// write outside the project.
+pub fn apply_changes(
+    original: &Path,
+    worktree: &Path,
+    paths: &[String],
+) -> Result<WorktreeApplyResult> {
+    let changes: BTreeMap<String, WorktreeChangeStatus> = diff_worktree(original, worktree)?
+        .into_iter()
+        .map(|c| (c.path, c.status))
+        .collect();
+
+    let mut applied = Vec::new();
+    let mut skipped = Vec::new();
+    for path in paths {
+        match changes.get(path) {
+            Some(WorktreeChangeStatus::Deleted) => {
+                fs::remove_file(original.join(path))
+                    .with_context(|| format!("Failed to delete {}", path))?;
+                applied.push(path.clone());
+            }
+            Some(_) => {
+                copy_file(&worktree.join(path), &original.join(path))?;
+                applied.push(path.clone());
+            }
+            None => skipped.push(path.clone()),
+        }
+    }
+
+    Ok(WorktreeApplyResult { applied, skipped })
+}
+
+/// Delete a worktree and unregister it from the repository it came from
+pub fn remove_worktree(original: &Path, worktree: &Path) -> Result<()> {
+    let kind = worktree_kind(worktree);
+    if worktree.exists() {
+        fs::remove_dir_all(worktree)
+            .with_context(|| format!("Failed to remove {}", worktree.display()))?;
+    }
+    if kind == WorktreeKind::Git && original.exists() {
+        if let Err(e) = git(original, &["worktree", "prune"]) {
+            warn!("Failed to prune worktrees of {}: {}", original.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Worktrees the runs in the database refer to
+#[derive(Default)]
+struct WorktreeRefs {
+    /// Run id, project and worktree of finished runs older than the cutoff
+    expired: Vec<(i64, String, PathBuf)>,
+    /// Every worktree path a run refers to
+    referenced: HashSet<PathBuf>,
+}
+
+fn query_worktree_refs(conn: &Connection, cutoff: NaiveDateTime) -> Result<WorktreeRefs> {
+    let mut stmt = conn.prepare(
+        "SELECT id, project_path, worktree_path, status, COALESCE(completed_at, created_at)
+         FROM agent_runs WHERE worktree_path IS NOT NULL",
+    )?;
+    let runs = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut refs = WorktreeRefs::default();
+    for (run_id, project_path, worktree_path, status, finished_at) in runs {
+        let worktree = PathBuf::from(&worktree_path);
+        refs.referenced.insert(worktree.clone());
+
+        // Runs still waiting to start or for a retry keep their worktree
+        let active = matches!(
+            status.as_str(),
+            "pending" | "running" | STATUS_PAUSED | STATUS_RATE_LIMITED
+        );
+        let expired = NaiveDateTime::parse_from_str(&finished_at, "%Y-%m-%d %H:%M:%S")
+            .is_ok_and(|at| at < cutoff);
+        if !active && expired {
+            refs.expired.push((run_id, project_path, worktree));
+        }
+    }
+    Ok(refs)
+}
+
+/// Remove worktrees of finished runs older than `retention`, plus directories
+/// in the worktrees folder that no run refers to.
+///
+/// The database is only locked to look the runs up, not while deleting.
+pub fn cleanup_expired(
+    db: &AgentDb,
+    worktrees_dir: &Path,
+    retention: chrono::Duration,
+) -> Result<WorktreeCleanupResult> {
+    let cutoff = (Utc::now() - retention).naive_utc();
+    let WorktreeRefs { expired, referenced } = {
+        let conn = db.0.lock().unwrap_or_else(|e| e.into_inner());
+        query_worktree_refs(&conn, cutoff)?
+    };
+
+    let mut removed_runs = Vec::new();
+    for (run_id, project_path, worktree) in expired {
+        if !worktree.exists() {
+            continue;
+        }
+        match remove_worktree(Path::new(&project_path), &worktree) {
+            Ok(()) => removed_runs.push(run_id),
+            Err(e) => warn!("Failed to remove worktree of run {}: {}", run_id, e),
+        }
+    }
+
+    let mut removed_orphans = 0;
+    if worktrees_dir.exists() {
+        for entry in fs::read_dir(worktrees_dir)?.flatten() {
+            let path = entry.path();
+            if !path.is_dir() || referenced.contains(&path) {
+                continue;
+            }
+            let old_enough = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .map(|modified| chrono::DateTime::<Utc>::from(modified).naive_utc() < cutoff)
+                .unwrap_or(false);
+            if old_enough && fs::remove_dir_all(&path).is_ok() {
+                removed_orphans += 1;
+            }
+        }
+    }
+
+    Ok(WorktreeCleanupResult {
+        removed_runs,
+        removed_orphans,
+    })
+}
+
+/// Load the project and worktree paths of a run that used an isolated worktree
+fn run_worktree(conn: &Connection, run_id: i64) -> Result<(String, String), String> {
+    let run = query_agent_run(conn, run_id)?;
+    let worktree_path = run
+        .worktree_path
+        .ok_or_else(|| format!("Run {} did not use an isolated worktree", run_id))?;
+    if !Path::new(&worktree_path).exists() {
+        return Err(format!("Worktree of run {} has been cleaned up", run_id));
+    }
+    Ok((run.project_path, worktree_path))
+}
+
+/// Emit `agent-worktree-changes:{run_id}` once a worktree run has finished
+pub fn report_worktree_changes(app: &AppHandle, conn: &Connection, run_id: i64) {
+    let Ok((project_path, worktree_path)) = run_worktree(conn, run_id) else {
+        return;
+    };
+    match diff_worktree(Path::new(&project_path), Path::new(&worktree_path)) {
+        Ok(changes) => {
+            info!("Run {} changed {} files in its worktree", run_id, changes.len());
+            let diff = WorktreeDiff {
+                run_id,
+                project_path,
+                worktree_path,
+                changes,
+            };
+            let _ = app.emit(&format!("agent-worktree-changes:{}", run_id), &diff);
+        }
+        Err(e) => warn!("Failed to diff worktree of run {}: {}", run_id, e),
+    }
+}
+
+/// List the changes an isolated run made compared to the original project
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_worktree_changes(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+) -> Result<WorktreeDiff, String> {
+    let (project_path, worktree_path) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        run_worktree(&conn, run_id)?
+    };
+
+    let changes = diff_worktree(Path::new(&project_path), Path::new(&worktree_path))
+        .map_err(|e| format!("Failed to diff worktree: {}", e))?;
+    Ok(WorktreeDiff {
+        run_id,
+        project_path,
+        worktree_path,
+        changes,
+    })
+}
+
+/// Copy selected changes of an isolated run back into the original project
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn apply_worktree_changes(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+    paths: Vec<String>,
+) -> Result<WorktreeApplyResult, String> {
+    let (project_path, worktree_path) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        run_worktree(&conn, run_id)?
+    };
+
+    info!(
+        "Applying {} worktree changes of run {} to {}",
+        paths.len(),
+        run_id,
+        project_path
+    );
+    apply_changes(Path::new(&project_path), Path::new(&worktree_path), &paths)
+        .map_err(|e| format!("Failed to apply worktree changes: {}", e))
+}
+
+/// Remove worktrees of runs that finished more than `retention_hours` ago (default 72)
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn cleanup_agent_worktrees(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    retention_hours: Option<u64>,
+) -> Result<WorktreeCleanupResult, String> {
+    let worktrees_dir = get_worktrees_dir(&app).map_err(|e| e.to_string())?;
+    let retention =
+        chrono::Duration::hours(retention_hours.unwrap_or(DEFAULT_RETENTION_HOURS) as i64);
+
+    let result = cleanup_expired(&db, &worktrees_dir, retention)
+        .map_err(|e| format!("Failed to clean up worktrees: {}", e))?;
+    info!(
+        "Removed {} run worktrees and {} orphaned directories",
+        result.removed_runs.len(),
+        result.removed_orphans
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::params;
+    use tempfile::TempDir;
+
+    #[test]
+    fn copy_worktree_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let original = temp.path().join("project");
+        fs::create_dir_all(original.join("src")).unwrap();
+        fs::create_dir_all(original.join("node_modules/dep")).unwrap();
+        fs::write(original.join("src/lib.rs"), "fn a() {}").unwrap();
+        fs::write(original.join("README.md"), "readme").unwrap();
+        fs::write(original.join("node_modules/dep/index.js"), "x").unwrap();
+
+        let worktree = temp.path().join("worktrees/1");
+        assert_eq!(create_worktree(&original, &worktree).unwrap(), WorktreeKind::Copy);
+        assert!(!worktree.join("node_modules").exists());
+        assert!(diff_worktree(&original, &worktree).unwrap().is_empty());
+
+        fs::write(worktree.join("src/lib.rs"), "fn b() {}").unwrap();
+        fs::write(worktree.join("src/new.rs"), "new").unwrap();
+        fs::remove_file(worktree.join("README.md")).unwrap();
+
+        let changes = diff_worktree(&original, &worktree).unwrap();
+        let summary: Vec<_> = changes.iter().map(|c| (c.path.as_str(), c.status)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("README.md", WorktreeChangeStatus::Deleted),
+                ("src/lib.rs", WorktreeChangeStatus::Modified),
+                ("src/new.rs", WorktreeChangeStatus::Added),
+            ]
+        );
+
+        let result = apply_changes(
+            &original,
+            &worktree,
+            &["src/lib.rs".to_string(), "../etc/passwd".to_string()],
+        )
+        .unwrap();
+        assert_eq!(result.applied, vec!["src/lib.rs"]);
+        assert_eq!(result.skipped, vec!["../etc/passwd"]);
+        assert_eq!(fs::read_to_string(original.join("src/lib.rs")).unwrap(), "fn b() {}");
+        assert!(original.join("README.md").exists());
+
+        remove_worktree(&original, &worktree).unwrap();
+        assert!(!worktree.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_applied_as_links() {
+        let temp = TempDir::new().unwrap();
+        let original = temp.path().join("project");
+        let outside = temp.path().join("outside.txt");
+        fs::create_dir_all(&original).unwrap();
+        fs::write(&outside, "outside").unwrap();
+        fs::write(original.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(&outside, original.join("config")).unwrap();
+
+        let worktree = temp.path().join("worktrees/1");
+        create_worktree(&original, &worktree).unwrap();
+        assert!(fs::symlink_metadata(worktree.join("config")).unwrap().is_symlink());
+        assert!(diff_worktree(&original, &worktree).unwrap().is_empty());
+
+        // The run replaced the link with a file and added a link of its own
+        fs::remove_file(worktree.join("config")).unwrap();
+        fs::write(worktree.join("config"), "mine").unwrap();
+        std::os::unix::fs::symlink("a.txt", worktree.join("alias")).unwrap();
+
+        let paths = vec!["alias".to_string(), "config".to_string()];
+        assert_eq!(apply_changes(&original, &worktree, &paths).unwrap().applied, paths);
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "outside");
+        assert_eq!(fs::read_to_string(original.join("config")).unwrap(), "mine");
+        assert_eq!(fs::read_link(original.join("alias")).unwrap(), Path::new("a.txt"));
+    }
+
+    #[test]
+    fn janitor_only_removes_expired_finished_runs() {
+        let temp = TempDir::new().unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::commands::agents::init_schema(&conn).unwrap();
+        let worktrees_dir = temp.path().join("worktrees");
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt) VALUES ('a', 'i', 'p')",
+            [],
+        )
+        .unwrap();
+
+        let insert_run = |status: &str, completed_at: &str| {
+            conn.execute(
+                "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, completed_at) VALUES (1, 'a', 'i', 't', 'sonnet', ?1, '', ?2, ?3)",
+                params![temp.path().to_string_lossy(), status, completed_at],
+            )
+            .unwrap();
+            let run_id = conn.last_insert_rowid();
+            let dir = worktrees_dir.join(run_id.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            conn.execute(
+                "UPDATE agent_runs SET worktree_path = ?1 WHERE id = ?2",
+                params![dir.to_string_lossy(), run_id],
+            )
+            .unwrap();
+            (run_id, dir)
+        };
+
+        let (old_id, old_dir) = insert_run("completed", "2000-01-01 00:00:00");
+        let (_, running_dir) = insert_run("running", "2000-01-01 00:00:00");
+        let (_, pending_dir) = insert_run("pending", "2000-01-01 00:00:00");
+        let (_, rate_limited_dir) = insert_run(STATUS_RATE_LIMITED, "2000-01-01 00:00:00");
+        let (_, recent_dir) = insert_run(
+            "completed",
+            &Utc::now().naive_utc().format("%Y-%m-%d %H:%M:%S").to_string(),
+        );
+
+        let db = AgentDb(std::sync::Mutex::new(conn));
+        let result = cleanup_expired(&db, &worktrees_dir, chrono::Duration::hours(1)).unwrap();
+        assert_eq!(result.removed_runs, vec![old_id]);
+        assert!(!old_dir.exists());
+        assert!(running_dir.exists());
+        assert!(pending_dir.exists());
+        assert!(rate_limited_dir.exists());
+        assert!(recent_dir.exists());
+    }
+}
@@ -0,0 +1,309 @@
+//! Advisory locks for files shared with other app windows and the claude CLI.
+//!
+//! A writer creates `<file>.lock` exclusively, writes the new content to a
+//! temporary file and renames it over the original, then removes the lock.
+//! Locks older than `STALE_LOCK_AGE` were left by a crashed writer and are
+//! taken over, by moving them aside first so that two writers can't both
+//! remove one and then each other's fresh lock.
+//!
+//! Writes that fail because the location is read-only (a managed
+//! ~/.claude, for example) report `claude_dir_readonly` with the path.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind as IoErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::checkpoint::storage::write_atomically;
+use crate::commands::i18n::{self, ErrorKind};
+
+/// How long a writer waits for a busy file before giving up
+pub const LOCK_WAIT: Duration = Duration::from_secs(2);
+
+/// Locks older than this are considered abandoned
+pub const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held lock on a file; the lock file is removed on drop
+#[derive(Debug)]
+pub struct FileLock {
+    lock_path: PathBuf,
+}
+
+impl FileLock {
+    /// Path of the lock file guarding `path`
+    pub fn lock_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        path.with_file_name(name)
+    }
+
+    /// Lock `path`, waiting up to `wait` for another writer to finish.
+    ///
+    /// Returns a `file_busy` error when the lock is still held after `wait`.
+    pub async fn acquire(path: &Path, wait: Duration) -> Result<Self, String> {
+        let lock_path = Self::lock_path(path);
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+                Ok(mut file) => {
+                    let _ = writeln!(file, "{}", std::process::id());
+                    return Ok(Self { lock_path });
+                }
+                Err(e) if e.kind() == IoErrorKind::AlreadyExists => {
+                    if Self::is_stale(&lock_path) {
+                        Self::remove_stale(&lock_path);
+                        continue;
+                    }
+                    if tokio::time::Instant::now() >= deadline {
+                        return Err(i18n::error(ErrorKind::FileBusy, path.display()));
+                    }
+                    tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+                }
+                Err(e) => return Err(write_error(&lock_path, e)),
+            }
+        }
+    }
+
+    /// Remove the lock at `lock_path` if it is still stale.
+    ///
+    /// The lock is renamed to a unique name first, which only one of several
+    /// writers seeing the same stale lock manages. Whatever was moved is
+    /// checked again, and put back if it was a fresh lock that replaced the
+    /// stale one in the meantime.
+    fn remove_stale(lock_path: &Path) {
+        let mut name = lock_path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", uuid::Uuid::new_v4()));
+        let aside = lock_path.with_file_name(name);
+        if fs::rename(lock_path, &aside).is_err() {
+            // Released, or taken over by another writer
+            return;
+        }
+        if Self::is_stale(&aside) {
+            log::warn!("Removed stale lock {}", lock_path.display());
+        } else if let Err(e) = fs::hard_link(&aside, lock_path) {
+            // Only fails when yet another lock was created meanwhile
+            log::warn!("Failed to restore lock {}: {}", lock_path.display(), e);
+        }
+        let _ = fs::remove_file(&aside);
+    }
+
+    fn is_stale(lock_path: &Path) -> bool {
+        fs::metadata(lock_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > STALE_LOCK_AGE)
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.lock_path);
+    }
+}
+
+/// Whether an io error means the location cannot be written at all
+pub fn is_readonly_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        IoErrorKind::PermissionDenied | IoErrorKind::ReadOnlyFilesystem
+    )
+}
+
+/// Error for a failed write to `path`: `claude_dir_readonly` when the
+/// location is read-only, `file_busy` when another process (e.g. a sync
+/// client) holds the file, `file_write` otherwise
+pub fn write_error(path: &Path, e: impl Into<anyhow::Error>) -> String {
+    let e = e.into();
+    let io_error = e.chain().find_map(|cause| cause.downcast_ref::<io::Error>());
+    if io_error.is_some_and(is_readonly_error) {
+        return i18n::error(ErrorKind::ClaudeDirReadonly, path.display());
+    }
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    #[cfg(windows)]
+    if matches!(io_error.and_then(io::Error::raw_os_error), Some(32) | Some(33)) {
+        return i18n::error(ErrorKind::FileBusy, path.display());
+    }
+    i18n::error(ErrorKind::FileWrite, format!("{}: {:#}", path.display(), e))
+}
+
+/// Check that `path` can be created or replaced before writing it.
+///
+/// The nearest existing directory at or above `path` must accept a new
+/// file, and an existing file must not be marked read-only. Paths in a
+/// claude directory opened read-only fail with `read_only_mode`.
+pub fn ensure_writable(path: &Path) -> Result<(), String> {
+    crate::claude_dir::check_writable(path)?;
+    if fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().readonly()) {
+        return Err(i18n::error(ErrorKind::ClaudeDirReadonly, path.display()));
+    }
+    let Some(dir) = path.ancestors().find(|ancestor| ancestor.is_dir()) else {
+        return Ok(());
+    };
+    tempfile::Builder::new()
+        .prefix(".write-check")
+        .tempfile_in(dir)
+        .map(drop)
+        .map_err(|e| write_error(dir, e))
+}
+
+/// Directories created by `create_dirs`, removed again on drop unless kept
+#[must_use]
+#[derive(Debug)]
+pub struct CreatedDirs {
+    top: Option<PathBuf>,
+}
+
+impl CreatedDirs {
+    /// Keep the directories once everything written into them succeeded
+    pub fn keep(mut self) {
+        self.top = None;
+    }
+}
+
+impl Drop for CreatedDirs {
+    fn drop(&mut self) {
+        if let Some(top) = self.top.take() {
+            let _ = fs::remove_dir_all(top);
+        }
+    }
+}
+
+/// Create `dir` and its missing parents.
+///
+/// Nothing is left behind when creation fails part way, and the returned
+/// guard removes what was created unless `keep` is called.
+pub fn create_dirs(dir: &Path) -> Result<CreatedDirs, String> {
+    let created = CreatedDirs {
+        top: dir
+            .ancestors()
+            .take_while(|ancestor| !ancestor.exists())
+            .last()
+            .map(Path::to_path_buf),
+    };
+    fs::create_dir_all(dir).map_err(|e| write_error(dir, e))?;
+    Ok(created)
+}
+
+/// Replace the contents of a shared file under its lock
+pub async fn write_locked(path: &Path, contents: &[u8]) -> Result<(), String> {
+    ensure_writable(path)?;
+    let _lock = FileLock::acquire(path, LOCK_WAIT).await?;
+    write_atomically(path, contents).map_err(|e| write_error(path, e))
+}
+
+/// Read, modify and write back a shared file under its lock.
+///
+/// `update` gets the current contents (`None` if the file does not exist)
+/// and returns the new contents, or `None` to leave the file alone.
+pub async fn update_locked<F>(path: &Path, update: F) -> Result<bool, String>
+where
+    F: FnOnce(Option<String>) -> Result<Option<String>, String>,
+{
+    ensure_writable(path)?;
+    let _lock = FileLock::acquire(path, LOCK_WAIT).await?;
+    let current = match fs::read_to_string(path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == IoErrorKind::NotFound => None,
+        Err(e) => return Err(i18n::error(ErrorKind::FileRead, e)),
+    };
+    match update(current)? {
+        Some(contents) => {
+            write_atomically(path, contents.as_bytes()).map_err(|e| write_error(path, e))?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn busy_file_returns_file_busy_and_stale_locks_are_taken_over() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("settings.json");
+
+        let held = FileLock::acquire(&path, LOCK_WAIT).await.unwrap();
+        let err = FileLock::acquire(&path, Duration::from_millis(120)).await.unwrap_err();
+        assert!(err.starts_with("[file_busy]"), "{}", err);
+        drop(held);
+        assert!(!FileLock::lock_path(&path).exists());
+
+        write_locked(&path, b"{}").await.unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        // A lock left behind by a crashed writer
+        let lock_path = FileLock::lock_path(&path);
+        let lock_file = fs::File::create(&lock_path).unwrap();
+        lock_file
+            .set_modified(SystemTime::now() - STALE_LOCK_AGE * 2)
+            .unwrap();
+        let changed = update_locked(&path, |current| {
+            assert_eq!(current.as_deref(), Some("{}"));
+            Ok(Some("{\"a\":1}".to_string()))
+        })
+        .await
+        .unwrap();
+        assert!(changed);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}");
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn only_stale_locks_are_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = FileLock::lock_path(&temp_dir.path().join("settings.json"));
+
+        // Another writer replaced the stale lock after this one saw it
+        fs::write(&lock_path, "1234\n").unwrap();
+        FileLock::remove_stale(&lock_path);
+        assert_eq!(fs::read_to_string(&lock_path).unwrap(), "1234\n");
+
+        fs::File::options()
+            .write(true)
+            .open(&lock_path)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_LOCK_AGE * 2)
+            .unwrap();
+        FileLock::remove_stale(&lock_path);
+        assert!(!lock_path.exists());
+        // Nothing is left behind under the temporary name
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        // Released before the takeover got to it
+        FileLock::remove_stale(&lock_path);
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn failed_writes_report_readonly_and_leave_no_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let blocker = temp_dir.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+
+        // A file where a directory should go; the blocker itself stays
+        assert!(create_dirs(&blocker.join("d")).is_err());
+        assert!(blocker.is_file());
+
+        // Directories are removed again unless kept
+        let created = create_dirs(&temp_dir.path().join("x/y")).unwrap();
+        drop(created);
+        assert!(!temp_dir.path().join("x").exists());
+        create_dirs(&temp_dir.path().join("x/y")).unwrap().keep();
+        assert!(temp_dir.path().join("x/y").is_dir());
+
+        let mut permissions = fs::metadata(&blocker).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&blocker, permissions).unwrap();
+        let err = ensure_writable(&blocker).unwrap_err();
+        assert!(err.starts_with("[claude_dir_readonly]"), "{}", err);
+
+        let denied = io::Error::from(IoErrorKind::PermissionDenied);
+        assert!(write_error(&blocker, denied).starts_with("[claude_dir_readonly]"));
+    }
+}
@@ -1,10 +1,14 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 // Declare modules
+pub mod api_schema;
 pub mod checkpoint;
 pub mod claude_binary;
+pub mod claude_dir;
 pub mod commands;
+pub mod file_lock;
 pub mod process;
+pub mod startup;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -0,0 +1,230 @@
+//! Whether the process recorded for a run is still that run's claude.
+//!
+//! A pid stored in the database can outlive its process: once claude exits
+//! the OS may hand the pid to an unrelated program. Liveness is therefore
+//! taken from the registry's child handle when there is one, and otherwise
+//! only trusted when the process behind the pid looks like the one started.
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::registry::ProcessRegistry;
+
+/// How far the start time of a process may be from the recorded one
+const START_TIME_TOLERANCE_SECS: i64 = 60;
+
+/// Program names a run's process can have
+const RUN_PROCESS_NAMES: &[&str] = &["claude", "node"];
+
+/// What the OS reports about the process with a given pid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbedProcess {
+    /// Executable name or path
+    pub name: String,
+    /// Unknown on platforms that don't report it
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of looking up a pid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeResult {
+    /// No process has the pid
+    Gone,
+    /// The process that currently has the pid
+    Alive(ProbedProcess),
+    /// The lookup itself failed (e.g. `ps` couldn't be run), so nothing is known
+    Unknown,
+}
+
+/// Looks up processes by pid
+pub trait ProcessProbe {
+    /// What currently has `pid`
+    fn probe(&self, pid: u32) -> ProbeResult;
+}
+
+/// Where child handles of running agents are kept
+pub trait RunRegistry {
+    /// Whether the child of `run_id` is still running; None when the
+    /// registry holds no child for the run
+    fn child_running(&self, run_id: i64) -> Option<bool>;
+}
+
+impl RunRegistry for ProcessRegistry {
+    fn child_running(&self, run_id: i64) -> Option<bool> {
+        ProcessRegistry::child_running(self, run_id).ok().flatten()
+    }
+}
+
+/// A run the database shows as running or paused
+#[derive(Debug, Clone)]
+pub struct ActiveRun {
+    pub run_id: i64,
+    pub pid: u32,
+    /// RFC 3339 time the process was spawned, as stored with the run
+    pub process_started_at: Option<String>,
+}
+
+/// Whether `process` is plausibly the one spawned for `run`
+fn is_run_process(run: &ActiveRun, process: &ProbedProcess) -> bool {
+    let name = process.name.to_lowercase();
+    if !RUN_PROCESS_NAMES.iter().any(|known| name.contains(known)) {
+        return false;
+    }
+    let recorded = run
+        .process_started_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok());
+    match (recorded, process.started_at) {
+        (Some(recorded), Some(started_at)) => {
+            (started_at - recorded.with_timezone(&Utc)).num_seconds().abs() <= START_TIME_TOLERANCE_SECS
+        }
+        // Without both times the name is all there is to go by
+        _ => true,
+    }
+}
+
+/// Whether the process of `run` has exited; a run whose pid can't be
+/// looked up is assumed to still be running
+pub fn run_exited(run: &ActiveRun, registry: &impl RunRegistry, probe: &impl ProcessProbe) -> bool {
+    if let Some(running) = registry.child_running(run.run_id) {
+        return !running;
+    }
+    match probe.probe(run.pid) {
+        ProbeResult::Alive(process) => !is_run_process(run, &process),
+        ProbeResult::Gone => true,
+        ProbeResult::Unknown => false,
+    }
+}
+
+/// Probes processes with `ps` on Unix and `tasklist` on Windows
+pub struct SystemProbe;
+
+impl ProcessProbe for SystemProbe {
+    fn probe(&self, pid: u32) -> ProbeResult {
+        let probed = if cfg!(target_os = "windows") {
+            let output = match std::process::Command::new("tasklist")
+                .args(["/FI", &format!("PID eq {}", pid)])
+                .args(["/FO", "CSV", "/NH"])
+                .output()
+            {
+                Ok(output) if output.status.success() => output,
+                _ => return ProbeResult::Unknown,
+            };
+            parse_tasklist(&String::from_utf8_lossy(&output.stdout))
+        } else {
+            let output = match std::process::Command::new("ps")
+                .args(["-o", "etime=", "-o", "comm=", "-p", &pid.to_string()])
+                .output()
+            {
+                Ok(output) => output,
+                Err(_) => return ProbeResult::Unknown,
+            };
+            // ps exits with 1 when no process has the pid
+            if !output.status.success() {
+                return match output.status.code() {
+                    Some(1) => ProbeResult::Gone,
+                    _ => ProbeResult::Unknown,
+                };
+            }
+            parse_ps(&String::from_utf8_lossy(&output.stdout), Utc::now())
+        };
+        probed.map_or(ProbeResult::Gone, ProbeResult::Alive)
+    }
+}
+
+/// Parse `ps -o etime= -o comm=` output, e.g. "   01:02:03 node"
+fn parse_ps(output: &str, now: DateTime<Utc>) -> Option<ProbedProcess> {
+    let (etime, name) = output.trim().split_once(char::is_whitespace)?;
+    Some(ProbedProcess {
+        name: name.trim().to_string(),
+        started_at: parse_elapsed_secs(etime).map(|secs| now - Duration::seconds(secs)),
+    })
+}
+
+/// Seconds in a `[[dd-]hh:]mm:ss` elapsed time
+fn parse_elapsed_secs(etime: &str) -> Option<i64> {
+    let (days, clock) = match etime.split_once('-') {
+        Some((days, clock)) => (days.parse::<i64>().ok()?, clock),
+        None => (0, etime),
+    };
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(days * 86_400 + secs)
+}
+
+/// Parse `tasklist /FO CSV /NH` output; "INFO: No tasks..." means no process
+fn parse_tasklist(output: &str) -> Option<ProbedProcess> {
+    let line = output.lines().find(|line| line.starts_with('"'))?;
+    let name = line.split(',').next()?.trim_matches('"');
+    Some(ProbedProcess {
+        name: name.to_string(),
+        started_at: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct FakeProbe(HashMap<u32, ProbedProcess>);
+
+    impl ProcessProbe for FakeProbe {
+        fn probe(&self, pid: u32) -> ProbeResult {
+            match pid {
+                // ps could not be run
+                15 => ProbeResult::Unknown,
+                _ => self.0.get(&pid).cloned().map_or(ProbeResult::Gone, ProbeResult::Alive),
+            }
+        }
+    }
+
+    struct FakeRegistry(HashMap<i64, bool>);
+
+    impl RunRegistry for FakeRegistry {
+        fn child_running(&self, run_id: i64) -> Option<bool> {
+            self.0.get(&run_id).copied()
+        }
+    }
+
+    #[test]
+    fn exited_runs_are_told_apart_from_reused_pids() {
+        let started = "2025-03-01T10:00:00+00:00";
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let run = |run_id, pid| ActiveRun {
+            run_id,
+            pid,
+            process_started_at: Some(started.to_string()),
+        };
+        let probe = FakeProbe(HashMap::from([
+            (10, ProbedProcess { name: "node".into(), started_at: Some(at("2025-03-01T10:00:02+00:00")) }),
+            // The pid now belongs to another program, or to a later claude
+            (11, ProbedProcess { name: "postgres".into(), started_at: Some(at(started)) }),
+            (12, ProbedProcess { name: "/usr/local/bin/claude".into(), started_at: Some(at("2025-03-02T08:00:00+00:00")) }),
+            (13, ProbedProcess { name: "claude.exe".into(), started_at: None }),
+        ]));
+        let registry = FakeRegistry(HashMap::from([(1, true), (2, false)]));
+
+        // The registry's child handle wins over whatever owns the pid
+        assert!(!run_exited(&run(1, 99), &registry, &probe));
+        assert!(run_exited(&run(2, 10), &registry, &probe));
+
+        assert!(!run_exited(&run(3, 10), &registry, &probe));
+        assert!(run_exited(&run(3, 11), &registry, &probe));
+        assert!(run_exited(&run(3, 12), &registry, &probe));
+        assert!(!run_exited(&run(3, 13), &registry, &probe));
+        assert!(run_exited(&run(3, 14), &registry, &probe));
+        // A failed lookup leaves the run alone
+        assert!(!run_exited(&run(3, 15), &registry, &probe));
+
+        let now = at("2025-03-01T12:00:00+00:00");
+        let probed = parse_ps("  1-01:00:05 /usr/bin/node\n", now).unwrap();
+        assert_eq!(probed.name, "/usr/bin/node");
+        assert_eq!(probed.started_at, Some(at("2025-02-28T11:00:00+00:00") - Duration::seconds(5)));
+        assert_eq!(parse_elapsed_secs("02:03"), Some(123));
+        assert_eq!(parse_ps("", now), None);
+        assert_eq!(parse_tasklist("\"claude.exe\",\"4242\",\"Console\",\"1\",\"10,000 K\"\r\n").unwrap().name, "claude.exe");
+        assert_eq!(parse_tasklist("INFO: No tasks are running which match the specified criteria.\r\n"), None);
+    }
+}
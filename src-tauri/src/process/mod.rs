@@ -1,3 +1,6 @@
+pub mod liveness;
+pub mod output;
 pub mod registry;
 
+pub use output::*;
 pub use registry::*;
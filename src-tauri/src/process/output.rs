@@ -0,0 +1,367 @@
+use log::warn;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Bytes of output a process keeps in memory before its retention applies
+pub const OUTPUT_MEMORY_CAP_BYTES: usize = 8 * 1024 * 1024;
+
+/// Directory under the app data dir that spilled run output goes to
+const RUN_OUTPUT_DIR: &str = "run_output";
+
+/// Lines between entries of the spill file's line index
+const SPILL_INDEX_STRIDE: u64 = 1024;
+
+/// What happens to a run's output once it outgrows the in-memory cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputRetention {
+    /// Drop the oldest lines and keep the tail
+    #[default]
+    Truncate,
+    /// Move the oldest lines to the run's output file and keep the tail, so
+    /// the complete output is retained
+    SpillToFile,
+    /// Keep what fits and drop everything after it
+    Discard,
+}
+
+impl fmt::Display for OutputRetention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Truncate => "truncate",
+            Self::SpillToFile => "spill_to_file",
+            Self::Discard => "discard",
+        })
+    }
+}
+
+impl FromStr for OutputRetention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "spill_to_file" => Ok(Self::SpillToFile),
+            "discard" => Ok(Self::Discard),
+            other => Err(format!(
+                "unknown output retention '{}', expected truncate, spill_to_file or discard",
+                other
+            )),
+        }
+    }
+}
+
+/// File a run's output spills to; `stream` is "jsonl" for stdout and
+/// "stderr" for stderr
+pub fn run_output_path(app_data_dir: &Path, run_id: i64, stream: &str) -> PathBuf {
+    app_data_dir
+        .join(RUN_OUTPUT_DIR)
+        .join(format!("{}.{}", run_id, stream))
+}
+
+/// Output of a process, held in memory up to a cap.
+///
+/// Beyond the cap the retention decides what is lost. In spill mode the
+/// oldest lines are appended to the spill file instead, and `contents`
+/// stitches the file and the in-memory tail back together.
+pub struct RetainedOutput {
+    retention: OutputRetention,
+    cap: usize,
+    spill_path: Option<PathBuf>,
+    tail: String,
+    total_bytes: u64,
+    /// Lines pushed, including those dropped since
+    total_lines: u64,
+    /// Number of the first line `contents` still holds; lines before it were
+    /// dropped by truncation
+    first_line: u64,
+    spilled: bool,
+    /// Lines and bytes written to the spill file
+    spilled_lines: u64,
+    spilled_bytes: u64,
+    /// Byte offset in the spill file of every `SPILL_INDEX_STRIDE`th line,
+    /// so a cursor is served without reading the file from the start
+    spill_index: Vec<u64>,
+    /// Set once discard mode starts dropping lines
+    full: bool,
+}
+
+impl Default for RetainedOutput {
+    fn default() -> Self {
+        Self::new(OutputRetention::Truncate, OUTPUT_MEMORY_CAP_BYTES, None)
+    }
+}
+
+impl RetainedOutput {
+    /// Spill mode without a spill path behaves like truncate
+    pub fn new(retention: OutputRetention, cap: usize, spill_path: Option<PathBuf>) -> Self {
+        Self {
+            retention,
+            cap,
+            spill_path,
+            tail: String::new(),
+            total_bytes: 0,
+            total_lines: 0,
+            first_line: 0,
+            spilled: false,
+            spilled_lines: 0,
+            spilled_bytes: 0,
+            spill_index: Vec::new(),
+            full: false,
+        }
+    }
+
+    /// Append a line, applying the retention once the cap is exceeded
+    pub fn push_line(&mut self, line: &str) {
+        self.total_bytes += line.len() as u64 + 1;
+        self.total_lines += 1;
+        if self.retention == OutputRetention::Discard
+            && (self.full || self.tail.len() + line.len() + 1 > self.cap)
+        {
+            self.full = true;
+            return;
+        }
+        self.tail.push_str(line);
+        self.tail.push('\n');
+        if self.tail.len() > self.cap {
+            self.shed();
+        }
+    }
+
+    /// Drop (or spill) the oldest lines down to three quarters of the cap,
+    /// so this runs once per few megabytes rather than once per line
+    fn shed(&mut self) {
+        let excess = self.tail.len() - self.cap / 4 * 3;
+        // Cut after a newline; a newline byte is always a char boundary
+        let cut = self.tail.as_bytes()[excess..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|i| excess + i + 1)
+            .unwrap_or(self.tail.len());
+        let spilled = self.retention == OutputRetention::SpillToFile
+            && match self.spill(cut) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Failed to spill output, dropping it instead: {}", e);
+                    false
+                }
+            };
+        if !spilled {
+            self.first_line += self.tail.as_bytes()[..cut].iter().filter(|&&b| b == b'\n').count() as u64;
+        }
+        self.tail.replace_range(..cut, "");
+    }
+
+    /// Write the first `len` bytes of the tail to the spill file, replacing
+    /// whatever a previous run with the same id left there
+    fn spill(&mut self, len: usize) -> io::Result<()> {
+        let Some(path) = &self.spill_path else {
+            return Err(io::Error::other("no spill file"));
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.spilled)
+            .truncate(!self.spilled)
+            .open(path)?;
+        let chunk = &self.tail.as_bytes()[..len];
+        file.write_all(chunk)?;
+        if !self.spilled {
+            self.spilled_lines = 0;
+            self.spilled_bytes = 0;
+            self.spill_index = vec![0];
+        }
+        // Chunks end after a newline, so every line in them is complete
+        for (i, _) in chunk.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+            self.spilled_lines += 1;
+            if self.spilled_lines.is_multiple_of(SPILL_INDEX_STRIDE) {
+                self.spill_index.push(self.spilled_bytes + i as u64 + 1);
+            }
+        }
+        self.spilled_bytes += len as u64;
+        self.spilled = true;
+        Ok(())
+    }
+
+    /// Spilled lines from line `line` of the spill file on, read from the
+    /// nearest indexed offset
+    fn spilled_lines_from(&self, line: u64) -> io::Result<String> {
+        let Some(path) = &self.spill_path else {
+            return Err(io::Error::other("no spill file"));
+        };
+        let offset = self.spill_index[(line / SPILL_INDEX_STRIDE) as usize];
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut lines = String::new();
+        file.take(self.spilled_bytes - offset).read_to_string(&mut lines)?;
+        let skip = (line % SPILL_INDEX_STRIDE) as usize;
+        let start = match skip {
+            0 => 0,
+            _ => lines
+                .match_indices('\n')
+                .nth(skip - 1)
+                .map_or(lines.len(), |(i, _)| i + 1),
+        };
+        lines.replace_range(..start, "");
+        Ok(lines)
+    }
+
+    /// Move the tail to the spill file once the output has spilled, so the
+    /// file alone holds the complete output after the process is gone
+    pub fn finish(&mut self) {
+        if !self.spilled || self.tail.is_empty() {
+            return;
+        }
+        match self.spill(self.tail.len()) {
+            Ok(()) => self.tail.clear(),
+            Err(e) => warn!("Failed to spill the rest of the output: {}", e),
+        }
+    }
+
+    /// The retained output: the spilled lines, if any, followed by the tail
+    pub fn contents(&self) -> String {
+        let mut contents = match (&self.spill_path, self.spilled) {
+            (Some(path), true) => fs::read_to_string(path).unwrap_or_else(|e| {
+                warn!("Failed to read spilled output {}: {}", path.display(), e);
+                String::new()
+            }),
+            _ => String::new(),
+        };
+        contents.push_str(&self.tail);
+        contents
+    }
+
+    /// Retained lines after line number `cursor` (None for all of them).
+    ///
+    /// Returns the lines, the cursor to continue from and whether lines were
+    /// lost: the cursor fell before the retained window, or was past the end
+    /// and so belongs to other output. Lines are numbered from the first one
+    /// pushed, so cursors stay valid as old lines are dropped.
+    pub fn lines_since(&self, cursor: Option<u64>) -> (String, u64, bool) {
+        let (start, reset) = match cursor {
+            Some(cursor) if cursor < self.first_line || cursor > self.total_lines => (self.first_line, true),
+            Some(cursor) => (cursor, false),
+            None => (self.first_line, false),
+        };
+        let skip = start - self.first_line;
+        // Live readers are usually within the tail and never touch the file
+        let mut lines = String::new();
+        if self.spilled && skip < self.spilled_lines {
+            match self.spilled_lines_from(skip) {
+                Ok(spilled) => lines = spilled,
+                Err(e) => warn!("Failed to read spilled output: {}", e),
+            }
+        }
+        for line in self.tail.lines().skip(skip.saturating_sub(self.spilled_lines) as usize) {
+            lines.push_str(line);
+            lines.push('\n');
+        }
+        (lines, self.total_lines, reset)
+    }
+
+    /// Bytes of output seen, including what was dropped or spilled
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Whether any output went to the spill file
+    pub fn spilled(&self) -> bool {
+        self.spilled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn push_lines(output: &mut RetainedOutput, count: usize) {
+        for i in 0..count {
+            output.push_line(&format!("line {:03}", i));
+        }
+    }
+
+    #[test]
+    fn retention_decides_what_is_kept_beyond_the_cap() {
+        let temp_dir = TempDir::new().unwrap();
+        let spill_path = run_output_path(temp_dir.path(), 7, "jsonl");
+        let expected: String = (0..100).map(|i| format!("line {:03}\n", i)).collect();
+
+        let mut spill = RetainedOutput::new(OutputRetention::SpillToFile, 200, Some(spill_path.clone()));
+        push_lines(&mut spill, 100);
+        assert!(spill.spilled());
+        assert!(spill.tail.len() <= 200);
+        assert_eq!(spill.total_bytes(), expected.len() as u64);
+        assert_eq!(spill.contents(), expected);
+        spill.finish();
+        assert_eq!(fs::read_to_string(&spill_path).unwrap(), expected);
+        assert_eq!(spill.contents(), expected);
+
+        let mut truncate = RetainedOutput::new(OutputRetention::Truncate, 200, None);
+        push_lines(&mut truncate, 100);
+        let contents = truncate.contents();
+        assert!(!truncate.spilled() && contents.len() <= 200);
+        assert!(expected.ends_with(&contents) && contents.starts_with("line"));
+
+        let mut discard = RetainedOutput::new(OutputRetention::Discard, 200, None);
+        push_lines(&mut discard, 100);
+        assert!(expected.starts_with(&discard.contents()));
+        assert_eq!(discard.contents().len(), 22 * 9);
+        assert_eq!(discard.total_bytes(), expected.len() as u64);
+
+        // A later run with the same id starts a fresh file
+        let mut rerun = RetainedOutput::new(OutputRetention::SpillToFile, 20, Some(spill_path.clone()));
+        push_lines(&mut rerun, 3);
+        rerun.finish();
+        assert_eq!(fs::read_to_string(&spill_path).unwrap(), "line 000\nline 001\nline 002\n");
+
+        assert_eq!("Spill_To_File".parse::<OutputRetention>(), Ok(OutputRetention::SpillToFile));
+        assert!("keep".parse::<OutputRetention>().is_err());
+    }
+
+    #[test]
+    fn line_cursors_stay_absolute_across_truncation() {
+        let mut output = RetainedOutput::new(OutputRetention::Truncate, 200, None);
+        push_lines(&mut output, 5);
+        let (lines, cursor, reset) = output.lines_since(None);
+        assert_eq!((lines.lines().count(), cursor, reset), (5, 5, false));
+
+        push_lines(&mut output, 95);
+        assert!(output.first_line > 5);
+        // Lines 5.. were partly dropped while the reader was away
+        let (lines, cursor, reset) = output.lines_since(Some(5));
+        assert!(reset);
+        assert_eq!(cursor, 100);
+        assert_eq!(lines.lines().count() as u64, 100 - output.first_line);
+
+        output.push_line("line 100");
+        let (lines, cursor, reset) = output.lines_since(Some(100));
+        assert_eq!((lines.as_str(), cursor, reset), ("line 100\n", 101, false));
+        // A cursor from other output starts over
+        assert!(output.lines_since(Some(500)).2);
+
+        // Spilled lines are found through the index, the tail without the file
+        let temp_dir = TempDir::new().unwrap();
+        let spill_path = run_output_path(temp_dir.path(), 8, "jsonl");
+        let mut spill = RetainedOutput::new(OutputRetention::SpillToFile, 2000, Some(spill_path.clone()));
+        push_lines(&mut spill, 3000);
+        assert!(spill.spilled_lines > 2 * SPILL_INDEX_STRIDE);
+        let all = spill.contents();
+        for cursor in [0, 1, 1023, 1024, 1025, 2500, spill.spilled_lines, 2999, 3000] {
+            let expected: String = all.lines().skip(cursor as usize).map(|l| format!("{}\n", l)).collect();
+            assert_eq!(spill.lines_since(Some(cursor)), (expected, 3000, false), "{}", cursor);
+        }
+        fs::remove_file(&spill_path).unwrap();
+        assert_eq!(spill.lines_since(Some(2999)).0, "line 2999\n");
+
+        // Discarded lines are counted but never returned
+        let mut discard = RetainedOutput::new(OutputRetention::Discard, 20, None);
+        push_lines(&mut discard, 5);
+        assert_eq!(discard.lines_since(Some(2)), ("".to_string(), 5, false));
+    }
+}
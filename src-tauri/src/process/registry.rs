@@ -2,9 +2,15 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tokio::process::Child;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin};
+use crate::api_schema::api_type;
+use macro_rules_attribute::apply;
+
+use super::output::RetainedOutput;
 
 /// Type of process being tracked
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProcessType {
     AgentRun {
@@ -17,6 +23,7 @@ pub enum ProcessType {
 }
 
 /// Information about a running agent process
+#[apply(api_type!)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessInfo {
     pub run_id: i64,
@@ -26,6 +33,24 @@ pub struct ProcessInfo {
     pub project_path: String,
     pub task: String,
     pub model: String,
+    /// Seconds since the last output line, set once the run is considered stalled
+    #[serde(default)]
+    pub stalled_for_secs: Option<u64>,
+    /// Whether the process is stopped by `pause_process`
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Counts of the tracked processes, as shown in the status bar
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionStatusSummary {
+    pub running_agent_runs: usize,
+    pub paused_agent_runs: usize,
+    pub running_sessions: usize,
+    pub paused_sessions: usize,
+    /// Running processes last found stalled by `check_stalled`
+    pub stalled: usize,
 }
 
 /// Information about a running process with handle
@@ -33,12 +58,42 @@ pub struct ProcessInfo {
 pub struct ProcessHandle {
     pub info: ProcessInfo,
     pub child: Arc<Mutex<Option<Child>>>,
-    pub live_output: Arc<Mutex<String>>,
+    /// Output of the process; the registry is its only owner
+    pub live_output: RetainedOutput,
+    /// Set once the reader is done and has collected the output
+    pub output_finished: bool,
+    /// Piped stdin for interactive runs (None when stdin is not piped)
+    pub stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+    /// When the process last produced output (or was registered)
+    pub last_output_at: DateTime<Utc>,
+}
+
+/// Output of a process once its reader is done
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FinishedOutput {
+    pub contents: String,
+    /// Bytes of output seen, including what was dropped or spilled
+    pub total_bytes: u64,
+    pub spilled: bool,
+}
+
+impl FinishedOutput {
+    fn collect(output: &mut RetainedOutput) -> Self {
+        output.finish();
+        Self {
+            contents: output.contents(),
+            total_bytes: output.total_bytes(),
+            spilled: output.spilled(),
+        }
+    }
 }
 
 /// Registry for tracking active agent processes
 pub struct ProcessRegistry {
     processes: Arc<Mutex<HashMap<i64, ProcessHandle>>>, // run_id -> ProcessHandle
+    /// Output of agent runs unregistered (e.g. killed) before their reader
+    /// finished, kept until the reader collects it
+    detached_output: Arc<Mutex<HashMap<i64, RetainedOutput>>>,
     next_id: Arc<Mutex<i64>>, // Auto-incrementing ID for non-agent processes
 }
 
@@ -46,6 +101,7 @@ impl ProcessRegistry {
     pub fn new() -> Self {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
+            detached_output: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1000000)), // Start at high number to avoid conflicts
         }
     }
@@ -78,6 +134,8 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            stalled_for_secs: None,
+            paused: false,
         };
 
         self.register_process_internal(run_id, process_info, child)
@@ -102,6 +160,8 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            stalled_for_secs: None,
+            paused: false,
         };
 
         // For sidecar processes, we register without the child handle since it's managed differently
@@ -110,7 +170,10 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(None)), // No tokio::process::Child handle for sidecar
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: RetainedOutput::default(),
+            output_finished: false,
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
+            last_output_at: Utc::now(),
         };
 
         processes.insert(run_id, process_handle);
@@ -136,6 +199,8 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            stalled_for_secs: None,
+            paused: false,
         };
 
         // Register without child - Claude sessions use ClaudeProcessState for process management
@@ -144,7 +209,10 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(None)), // No child handle for Claude sessions
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: RetainedOutput::default(),
+            output_finished: false,
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
+            last_output_at: Utc::now(),
         };
 
         processes.insert(run_id, process_handle);
@@ -163,7 +231,10 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(Some(child))),
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: RetainedOutput::default(),
+            output_finished: false,
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
+            last_output_at: Utc::now(),
         };
 
         processes.insert(run_id, process_handle);
@@ -199,10 +270,19 @@ impl ProcessRegistry {
     }
 
     /// Unregister a process (called when it completes)
-    #[allow(dead_code)]
+    ///
+    /// The output of an agent run whose reader is still going moves aside
+    /// until `finish_live_output` collects it.
     pub fn unregister_process(&self, run_id: i64) -> Result<(), String> {
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
-        processes.remove(&run_id);
+        if let Some(handle) = processes.remove(&run_id) {
+            if matches!(handle.info.process_type, ProcessType::AgentRun { .. }) && !handle.output_finished {
+                self.detached_output
+                    .lock()
+                    .map_err(|e| e.to_string())?
+                    .insert(run_id, handle.live_output);
+            }
+        }
         Ok(())
     }
 
@@ -230,6 +310,32 @@ impl ProcessRegistry {
             .collect())
     }
 
+    /// Count running, paused and stalled processes by type
+    pub fn status_summary(&self) -> Result<ExecutionStatusSummary, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let mut summary = ExecutionStatusSummary::default();
+        for handle in processes.values() {
+            let info = &handle.info;
+            let (running, paused) = match info.process_type {
+                ProcessType::AgentRun { .. } => {
+                    (&mut summary.running_agent_runs, &mut summary.paused_agent_runs)
+                }
+                ProcessType::ClaudeSession { .. } => {
+                    (&mut summary.running_sessions, &mut summary.paused_sessions)
+                }
+            };
+            if info.paused {
+                *paused += 1;
+            } else {
+                *running += 1;
+            }
+            if !info.paused && info.stalled_for_secs.is_some() {
+                summary.stalled += 1;
+            }
+        }
+        Ok(summary)
+    }
+
     /// Get a specific running process
     #[allow(dead_code)]
     pub fn get_process(&self, run_id: i64) -> Result<Option<ProcessInfo>, String> {
@@ -237,21 +343,113 @@ impl ProcessRegistry {
         Ok(processes.get(&run_id).map(|handle| handle.info.clone()))
     }
 
+    /// Attach the piped stdin of an interactive process
+    pub async fn attach_stdin(&self, run_id: i64, stdin: ChildStdin) -> Result<(), String> {
+        let stdin_arc = self.stdin_handle(run_id)?
+            .ok_or_else(|| format!("Process {} not found in registry", run_id))?;
+        *stdin_arc.lock().await = Some(stdin);
+        Ok(())
+    }
+
+    /// Write a line to the stdin of an interactive process
+    pub async fn write_stdin(&self, run_id: i64, line: &str) -> Result<(), String> {
+        let stdin_arc = self.stdin_handle(run_id)?
+            .ok_or_else(|| format!("Process {} not found in registry", run_id))?;
+        let mut stdin_guard = stdin_arc.lock().await;
+        let stdin = stdin_guard
+            .as_mut()
+            .ok_or_else(|| format!("Process {} is not accepting input", run_id))?;
+
+        stdin
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to stdin: {}", e))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to flush stdin: {}", e))
+    }
+
+    /// Close the stdin of an interactive process (no-op if it isn't piped)
+    pub async fn close_stdin(&self, run_id: i64) -> Result<(), String> {
+        if let Some(stdin_arc) = self.stdin_handle(run_id)? {
+            if let Some(mut stdin) = stdin_arc.lock().await.take() {
+                let _ = stdin.shutdown().await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Clone the stdin handle out of the registry so it can be used across awaits
+    fn stdin_handle(
+        &self,
+        run_id: i64,
+    ) -> Result<Option<Arc<tokio::sync::Mutex<Option<ChildStdin>>>>, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        Ok(processes.get(&run_id).map(|handle| handle.stdin.clone()))
+    }
+
+    /// Stop (`paused = true`) or continue a process with SIGSTOP/SIGCONT.
+    ///
+    /// Returns false if the process isn't registered. Not supported on Windows.
+    pub fn set_paused(&self, run_id: i64, paused: bool) -> Result<bool, String> {
+        use crate::commands::i18n::{self, ErrorKind};
+
+        if cfg!(target_os = "windows") {
+            return Err(i18n::error(
+                ErrorKind::Unsupported,
+                "pausing a process needs SIGSTOP/SIGCONT",
+            ));
+        }
+
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let Some(handle) = processes.get_mut(&run_id) else {
+            return Ok(false);
+        };
+        let signal = if paused { "-STOP" } else { "-CONT" };
+        let output = std::process::Command::new("kill")
+            .args([signal, &handle.info.pid.to_string()])
+            .output()
+            .map_err(|e| i18n::error(ErrorKind::ProcessSpawn, e))?;
+        if !output.status.success() {
+            return Err(i18n::error(
+                ErrorKind::ProcessNotFound,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            ));
+        }
+
+        handle.info.paused = paused;
+        handle.info.stalled_for_secs = None;
+        // Time spent paused isn't silence; restart the stall clock
+        handle.last_output_at = Utc::now();
+        Ok(true)
+    }
+
     /// Kill a running process with proper cleanup
     pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
         use log::{error, info, warn};
 
+        // Close stdin first so an interactive process isn't left waiting on input
+        self.close_stdin(run_id).await?;
+
         // First check if the process exists and get its PID
-        let (pid, child_arc) = {
+        let (pid, child_arc, paused) = {
             let processes = self.processes.lock().map_err(|e| e.to_string())?;
             if let Some(handle) = processes.get(&run_id) {
-                (handle.info.pid, handle.child.clone())
+                (handle.info.pid, handle.child.clone(), handle.info.paused)
             } else {
                 warn!("Process {} not found in registry", run_id);
                 return Ok(false); // Process not found
             }
         };
 
+        // A stopped process can't handle the signal until it is continued
+        if paused {
+            if let Err(e) = self.set_paused(run_id, false) {
+                warn!("Failed to continue paused process {} before killing it: {}", run_id, e);
+            }
+        }
+
         info!(
             "Attempting graceful shutdown of process {} (PID: {})",
             run_id, pid
@@ -363,6 +561,11 @@ impl ProcessRegistry {
                 .args(["/F", "/PID", &pid.to_string()])
                 .output()
         } else {
+            // Continue the process in case it was paused, so it can handle SIGTERM
+            let _ = std::process::Command::new("kill")
+                .args(["-CONT", &pid.to_string()])
+                .output();
+
             // First try SIGTERM
             let term_result = std::process::Command::new("kill")
                 .args(["-TERM", &pid.to_string()])
@@ -429,55 +632,151 @@ impl ProcessRegistry {
     /// Check if a process is still running by trying to get its status
     #[allow(dead_code)]
     pub async fn is_process_running(&self, run_id: i64) -> Result<bool, String> {
+        Ok(self.child_running(run_id)?.unwrap_or(false))
+    }
+
+    /// Whether the child of a process has exited, from its handle; None when
+    /// the process isn't registered or its child was already reaped
+    pub fn child_running(&self, run_id: i64) -> Result<Option<bool>, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let Some(handle) = processes.get(&run_id) else {
+            return Ok(None);
+        };
+        let child_arc = handle.child.clone();
+        drop(processes);
 
-        if let Some(handle) = processes.get(&run_id) {
-            let child_arc = handle.child.clone();
-            drop(processes); // Release the lock before async operation
+        let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
+        let Some(child) = child_guard.as_mut() else {
+            return Ok(None);
+        };
+        match child.try_wait() {
+            Ok(None) => Ok(Some(true)),
+            // Exited, or its status can't be read; assume not running
+            Ok(Some(_)) | Err(_) => {
+                *child_guard = None;
+                Ok(Some(false))
+            }
+        }
+    }
 
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(ref mut child) = child_guard.as_mut() {
-                match child.try_wait() {
-                    Ok(Some(_)) => {
-                        // Process has exited
-                        *child_guard = None;
-                        Ok(false)
-                    }
-                    Ok(None) => {
-                        // Process is still running
-                        Ok(true)
-                    }
-                    Err(_) => {
-                        // Error checking status, assume not running
+    /// Wait a few seconds for the child of a process to exit; Some(true) when
+    /// it exited successfully, None when its status can't be observed (not
+    /// registered, already reaped, or still running)
+    pub async fn wait_for_exit(&self, run_id: i64) -> Option<bool> {
+        let child_arc = {
+            let processes = self.processes.lock().ok()?;
+            processes.get(&run_id)?.child.clone()
+        };
+        for _ in 0..50 {
+            {
+                let mut child_guard = child_arc.lock().ok()?;
+                match child_guard.as_mut()?.try_wait() {
+                    Ok(Some(status)) => {
                         *child_guard = None;
-                        Ok(false)
+                        return Some(status.success());
                     }
+                    Ok(None) => {}
+                    Err(_) => return None,
                 }
-            } else {
-                Ok(false) // No child handle
             }
-        } else {
-            Ok(false) // Process not found in registry
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
+        None
     }
 
-    /// Append to live output for a process
+    /// Append a line to the live output of a process and record the activity
     pub fn append_live_output(&self, run_id: i64, output: &str) -> Result<(), String> {
-        let processes = self.processes.lock().map_err(|e| e.to_string())?;
-        if let Some(handle) = processes.get(&run_id) {
-            let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            live_output.push_str(output);
-            live_output.push('\n');
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.live_output.push_line(output);
+            handle.last_output_at = Utc::now();
+            handle.info.stalled_for_secs = None;
+        } else if let Some(live_output) = self
+            .detached_output
+            .lock()
+            .map_err(|e| e.to_string())?
+            .get_mut(&run_id)
+        {
+            // Lines still buffered in the pipe of a killed run
+            live_output.push_line(output);
+        }
+        Ok(())
+    }
+
+    /// Replace the output buffer of a process that has not produced output yet,
+    /// e.g. to apply its agent's retention
+    pub fn set_live_output(&self, run_id: i64, live_output: RetainedOutput) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.live_output = live_output;
+        }
+        Ok(())
+    }
+
+    /// Finish the output of a process whose reader is done and return it.
+    ///
+    /// A registered process keeps serving its output through
+    /// `get_live_output`; the output of an unregistered one is released.
+    pub fn finish_live_output(&self, run_id: i64) -> Result<Option<FinishedOutput>, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.output_finished = true;
+            return Ok(Some(FinishedOutput::collect(&mut handle.live_output)));
         }
+        drop(processes);
+        let detached = self
+            .detached_output
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(&run_id);
+        Ok(detached.map(|mut live_output| FinishedOutput::collect(&mut live_output)))
+    }
+
+    /// Drop the output an unregistered run left behind, e.g. once the run is deleted
+    pub fn discard_detached_output(&self, run_id: i64) -> Result<(), String> {
+        self.detached_output
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(&run_id);
         Ok(())
     }
 
+    /// Check whether a process has gone without output for at least `threshold_secs`
+    ///
+    /// Records the idle time on the process info while stalled and returns it;
+    /// returns None if the process produced output recently, is paused or isn't registered.
+    pub fn check_stalled(&self, run_id: i64, threshold_secs: u64) -> Result<Option<u64>, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let Some(handle) = processes.get_mut(&run_id) else {
+            return Ok(None);
+        };
+        if handle.info.paused {
+            return Ok(None);
+        }
+
+        let idle_secs = (Utc::now() - handle.last_output_at).num_seconds().max(0) as u64;
+        handle.info.stalled_for_secs = if idle_secs >= threshold_secs {
+            Some(idle_secs)
+        } else {
+            None
+        };
+        Ok(handle.info.stalled_for_secs)
+    }
+
+    /// Live output of a process after line `cursor`, as `RetainedOutput::lines_since`
+    pub fn live_output_since(&self, run_id: i64, cursor: Option<u64>) -> Result<(String, u64, bool), String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        match processes.get(&run_id) {
+            Some(handle) => Ok(handle.live_output.lines_since(cursor)),
+            None => Ok((String::new(), 0, cursor.is_some_and(|cursor| cursor > 0))),
+        }
+    }
+
     /// Get live output for a process
     pub fn get_live_output(&self, run_id: i64) -> Result<String, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
-            let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            Ok(live_output.clone())
+            Ok(handle.live_output.contents())
         } else {
             Ok(String::new())
         }
@@ -528,3 +827,58 @@ impl Default for ProcessRegistryState {
         Self(Arc::new(ProcessRegistry::new()))
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn paused_process_is_not_stalled_and_can_be_killed() {
+        let child = tokio::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id().unwrap();
+        let registry = ProcessRegistry::new();
+        registry
+            .register_process(1, 1, "a".into(), pid, "/tmp".into(), "t".into(), "m".into(), child)
+            .unwrap();
+
+        assert!(registry.set_paused(1, true).unwrap());
+        assert!(registry.get_process(1).unwrap().unwrap().paused);
+        assert_eq!(registry.check_stalled(1, 0).unwrap(), None);
+        assert!(!registry.set_paused(2, true).unwrap());
+        let session = registry
+            .register_claude_session("s".into(), 2, "/tmp".into(), "t".into(), "m".into())
+            .unwrap();
+        assert_eq!(registry.check_stalled(session, 0).unwrap(), Some(0));
+        assert_eq!(
+            registry.status_summary().unwrap(),
+            ExecutionStatusSummary {
+                paused_agent_runs: 1,
+                running_sessions: 1,
+                stalled: 1,
+                ..Default::default()
+            }
+        );
+
+        // Killing continues the stopped process first, so it exits promptly
+        assert!(registry.kill_process(1).await.unwrap());
+        assert!(registry.get_process(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn out_of_range_live_cursors_restart_with_a_reset() {
+        let registry = ProcessRegistry::new();
+        // A cursor kept from a run this registry never saw, e.g. before a restart
+        assert_eq!(registry.live_output_since(1, Some(3)).unwrap(), (String::new(), 0, true));
+        assert!(!registry.live_output_since(1, None).unwrap().2);
+
+        let session = registry
+            .register_claude_session("s".into(), 2, "/tmp".into(), "t".into(), "m".into())
+            .unwrap();
+        registry.append_live_output(session, "a").unwrap();
+        assert_eq!(
+            registry.live_output_since(session, Some(7)).unwrap(),
+            ("a\n".to_string(), 1, true)
+        );
+        assert_eq!(registry.live_output_since(session, Some(1)).unwrap(), (String::new(), 1, false));
+    }
+}
@@ -0,0 +1,255 @@
+//! Work done after the window is up.
+//!
+//! Opening and migrating agents.db and resolving the claude directory can
+//! take seconds when the home directory is on a network share, so the setup
+//! hook only registers state that is cheap to create and leaves the rest to
+//! `initialize`. Each phase it finishes is announced with a
+//! `startup-progress` event and the end with `startup-complete`; until then
+//! commands fail with `not_ready`, which the frontend retries.
+
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use crate::api_schema::{api_command, api_type};
+use macro_rules_attribute::apply;
+
+use crate::checkpoint::state::CheckpointState;
+use crate::claude_dir;
+use crate::commands::agents::{init_database, AgentDb};
+use crate::commands::i18n::{self, ErrorKind};
+use crate::commands::jobs::JobState;
+
+/// Emitted with a `StartupProgress` when a phase finished
+pub const STARTUP_PROGRESS_EVENT: &str = "startup-progress";
+
+/// Emitted with the final `StartupStatus`, whether startup succeeded or not
+pub const STARTUP_COMPLETE_EVENT: &str = "startup-complete";
+
+/// Commands that answer while the app is starting
+const AVAILABLE_WHILE_STARTING: &[&str] = &["get_startup_status", "get_backend_locale"];
+
+/// Startup phases, in the order they run
+#[apply(api_type!)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupPhase {
+    /// agents.db opened and migrated, saved settings applied
+    Database,
+    /// The claude directory resolved for checkpoints and session metadata
+    ClaudeDir,
+    /// Runs, jobs and watchers left by the previous instance reconciled
+    Registry,
+}
+
+/// A finished phase
+#[apply(api_type!)]
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupProgress {
+    pub phase: StartupPhase,
+    /// Milliseconds the phase took
+    pub duration_ms: u64,
+}
+
+#[apply(api_type!)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StartupStatus {
+    /// Whether commands can be called
+    pub ready: bool,
+    /// Phases finished so far
+    pub completed: Vec<StartupProgress>,
+    /// Why startup failed; the app stays unusable until restarted
+    pub error: Option<String>,
+}
+
+/// Progress of `initialize`, managed before the window opens
+#[derive(Clone, Default)]
+pub struct StartupState(Arc<Mutex<StartupStatus>>);
+
+impl StartupState {
+    pub fn status(&self) -> StartupStatus {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Fail with `not_ready` when `command` can't run yet
+    pub fn check_ready(&self, command: &str) -> Result<(), String> {
+        if AVAILABLE_WHILE_STARTING.contains(&command) {
+            return Ok(());
+        }
+        let status = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if status.ready {
+            return Ok(());
+        }
+        match &status.error {
+            Some(e) => Err(i18n::error(ErrorKind::NotReady, format!("{}: startup failed: {}", command, e))),
+            None => Err(i18n::error(ErrorKind::NotReady, command)),
+        }
+    }
+
+    fn complete_phase(&self, phase: StartupPhase, started: Instant) -> StartupProgress {
+        let progress = StartupProgress {
+            phase,
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        let mut status = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        status.completed.push(progress.clone());
+        progress
+    }
+
+    fn finish(&self, error: Option<String>) -> StartupStatus {
+        let mut status = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        status.ready = error.is_none();
+        status.error = error;
+        status.clone()
+    }
+}
+
+/// Wrap the command handler so that commands fail with `not_ready` until
+/// startup finished
+pub fn gate<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke: Invoke<R>| {
+        let checked = invoke
+            .message
+            .webview_ref()
+            .try_state::<StartupState>()
+            .map(|state| state.check_ready(invoke.message.command()));
+        if let Some(Err(e)) = checked {
+            invoke.resolver.reject(e);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+/// Progress of startup, for a window that loaded after the events were sent
+#[apply(api_command!)]
+#[tauri::command]
+pub async fn get_startup_status(state: tauri::State<'_, StartupState>) -> Result<StartupStatus, String> {
+    Ok(state.status())
+}
+
+/// Run the startup phases and mark the app ready
+pub async fn initialize(app: AppHandle) {
+    let state = app.state::<StartupState>().inner().clone();
+    let started = Instant::now();
+    let result = run_phases(&app, &state).await;
+    if let Err(e) = &result {
+        log::error!("Startup failed: {}", e);
+    }
+    let status = state.finish(result.err());
+    log::info!("Startup finished in {} ms", started.elapsed().as_millis());
+    if let Err(e) = app.emit(STARTUP_COMPLETE_EVENT, &status) {
+        log::warn!("Failed to emit {}: {}", STARTUP_COMPLETE_EVENT, e);
+    }
+}
+
+async fn run_phases(app: &AppHandle, state: &StartupState) -> Result<(), String> {
+    let started = Instant::now();
+    let db_app = app.clone();
+    let conn = tauri::async_runtime::spawn_blocking(move || open_database(&db_app))
+        .await
+        .map_err(|e| e.to_string())??;
+    app.manage(AgentDb(Mutex::new(conn)));
+    app.manage(JobState::default());
+    report(app, state.complete_phase(StartupPhase::Database, started));
+
+    let started = Instant::now();
+    let resolved = tauri::async_runtime::spawn_blocking(|| {
+        claude_dir::claude_dir()
+            .ok()
+            .and_then(|claude_path| claude_path.canonicalize().ok())
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if let Some(resolved) = resolved {
+        app.state::<CheckpointState>().set_claude_dir(resolved).await;
+    }
+    report(app, state.complete_phase(StartupPhase::ClaudeDir, started));
+
+    let started = Instant::now();
+    let registry_app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || reconcile(&registry_app))
+        .await
+        .map_err(|e| e.to_string())?;
+    report(app, state.complete_phase(StartupPhase::Registry, started));
+    Ok(())
+}
+
+fn report(app: &AppHandle, progress: StartupProgress) {
+    log::info!("Startup phase {:?} took {} ms", progress.phase, progress.duration_ms);
+    if let Err(e) = app.emit(STARTUP_PROGRESS_EVENT, &progress) {
+        log::warn!("Failed to emit {}: {}", STARTUP_PROGRESS_EVENT, e);
+    }
+}
+
+/// Open agents.db and apply the proxy, locale and claude directory saved in it
+fn open_database(app: &AppHandle) -> Result<Connection, String> {
+    let conn = init_database(app).map_err(|e| i18n::error(ErrorKind::Database, e))?;
+    let proxy_settings = crate::commands::proxy::load_proxy_settings(&conn);
+    log::info!("Loaded proxy settings: enabled={}", proxy_settings.enabled);
+    crate::commands::proxy::apply_proxy_settings(&proxy_settings);
+
+    // Restore the locale used for backend messages
+    i18n::load_saved_locale(&conn);
+    // And the claude directory picked with switch_claude_dir
+    claude_dir::apply(claude_dir::load(&conn));
+    Ok(conn)
+}
+
+/// Bring the database in line with what outlived the previous instance and
+/// restart its background work
+fn reconcile(app: &AppHandle) {
+    {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().unwrap_or_else(|e| e.into_inner());
+        // Pick up session names and favorites edited outside the app
+        crate::commands::session_meta::sync_on_startup(&conn);
+        // Jobs left running by a previous instance stopped with it
+        match crate::commands::jobs::interrupt_unfinished_jobs(&conn) {
+            Ok(0) => {}
+            Ok(count) => log::info!("Marked {} unfinished jobs as interrupted", count),
+            Err(e) => log::warn!("Failed to mark unfinished jobs as interrupted: {}", e),
+        }
+    }
+
+    // Pick up a usage index build the last session was closed during
+    crate::commands::usage_index::resume_usage_index_build(app);
+
+    // Runs left behind by a previous instance lost their process with it
+    tauri::async_runtime::spawn(crate::commands::agents::recover_interrupted_runs(app.clone()));
+
+    // Push run counts to the status bar as they change
+    tauri::async_runtime::spawn(crate::commands::agents::watch_execution_status(app.clone()));
+
+    // Run agents on file changes in the projects their watchers cover
+    crate::commands::agent_watchers::start_enabled_watchers(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_wait_for_startup_to_finish() {
+        let state = StartupState::default();
+        assert!(state.check_ready("get_startup_status").is_ok());
+        assert!(state.check_ready("list_agents").unwrap_err().starts_with("[not_ready]"));
+
+        state.complete_phase(StartupPhase::Database, Instant::now());
+        assert!(state.check_ready("list_agents").is_err());
+        let status = state.finish(None);
+        assert!(status.ready);
+        assert_eq!(status.completed[0].phase, StartupPhase::Database);
+        assert!(state.check_ready("list_agents").is_ok());
+
+        let failed = StartupState::default();
+        failed.finish(Some("disk full".to_string()));
+        let error = failed.check_ready("list_agents").unwrap_err();
+        assert!(error.starts_with("[not_ready]") && error.contains("disk full"));
+        assert!(!failed.status().ready);
+    }
+}
@@ -0,0 +1,326 @@
+//! Runs agent processes against `tests/fixtures/fake-claude`, a script that
+//! plays back stream-json scenarios the way the claude CLI prints them.
+//!
+//! The binary is resolved through the `claude_binary_path` setting of a
+//! temporary `agents.db`, and spawned with a temporary HOME and project
+//! directory, so nothing here touches a real claude installation.
+#![cfg(unix)]
+
+use opcode_lib::claude_binary::{get_claude_version, is_executable, stored_claude_binary_path};
+use opcode_lib::commands::agents::{
+    complete_agent_run, create_agent_system_command, detect_rate_limit, init_schema,
+    insert_agent, query_agent_run, AgentInput, AgentRunMetrics, RunSummary,
+};
+use opcode_lib::commands::claude::{session_id_from_message, ClaudeOutputFormat};
+use opcode_lib::process::{
+    ExecutionStatusSummary, OutputRetention, ProcessRegistry, RetainedOutput,
+    OUTPUT_MEMORY_CAP_BYTES,
+};
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+const FAKE_CLAUDE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fake-claude");
+
+const INIT: &str = r#"out {"type":"system","subtype":"init","session_id":"fake-session","model":"claude-sonnet-4-20250514","tools":[]}"#;
+
+/// App data dir with a database pointing `claude_binary_path` at the fake,
+/// plus a HOME and a project directory
+struct Sandbox {
+    dir: TempDir,
+    conn: Connection,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        for sub in ["app-data", "home", "project"] {
+            std::fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        let conn = Connection::open(dir.path().join("app-data").join("agents.db")).unwrap();
+        init_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES ('claude_binary_path', ?1)",
+            params![FAKE_CLAUDE],
+        )
+        .unwrap();
+        Self { dir, conn }
+    }
+
+    fn path(&self, sub: &str) -> PathBuf {
+        self.dir.path().join(sub)
+    }
+
+    /// A pending run of a new agent
+    fn insert_run(&self) -> i64 {
+        let input = AgentInput {
+            name: "Fake".to_string(),
+            icon: "bot".to_string(),
+            system_prompt: "You are helpful".to_string(),
+            ..Default::default()
+        };
+        let agent_id = insert_agent(&self.conn, input).unwrap().id.unwrap();
+        self.conn
+            .execute(
+                "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status)
+                 VALUES (?1, 'Fake', 'bot', 'say hi', 'sonnet', ?2, '', 'pending')",
+                params![agent_id, self.path("project").to_string_lossy()],
+            )
+            .unwrap();
+        self.conn.last_insert_rowid()
+    }
+
+    /// Spawn the configured claude binary on a scenario
+    fn spawn(&self, scenario: &[&str]) -> tokio::process::Child {
+        let claude_path = stored_claude_binary_path(&self.conn).expect("binary path is stored");
+        let scenario_path = self.path("scenario");
+        std::fs::write(&scenario_path, scenario.join("\n")).unwrap();
+
+        let mut args = vec!["-p".to_string(), "say hi".to_string()];
+        args.extend(ClaudeOutputFormat::StreamJson.args());
+        let mut cmd = create_agent_system_command(&claude_path, args, &self.path("project"));
+        cmd.env("HOME", self.path("home"))
+            .env("FAKE_CLAUDE_SCENARIO", &scenario_path)
+            .env("FAKE_CLAUDE_ARGS", self.path("args"))
+            .kill_on_drop(true);
+        cmd.spawn().unwrap()
+    }
+}
+
+/// Read a process to the end the way a run does: stdout line by line,
+/// picking up the session ID, and stderr alongside it
+async fn collect(mut child: tokio::process::Child) -> (String, String, String, ExitStatus) {
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr_task = tokio::spawn(async move {
+        let mut text = String::new();
+        stderr.read_to_string(&mut text).await.unwrap();
+        text
+    });
+
+    let mut output = String::new();
+    let mut session_id = String::new();
+    while let Some(line) = stdout.next_line().await.unwrap() {
+        if session_id.is_empty() {
+            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
+                session_id = session_id_from_message(&msg).unwrap_or_default().to_string();
+            }
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    let status = child.wait().await.unwrap();
+    (output, stderr_task.await.unwrap(), session_id, status)
+}
+
+fn read_args(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn streamed_run_completes_with_session_and_metrics() {
+    let sandbox = Sandbox::new();
+    let claude_path = stored_claude_binary_path(&sandbox.conn).unwrap();
+    assert!(is_executable(Path::new(&claude_path)));
+    assert_eq!(get_claude_version(&claude_path).unwrap().as_deref(), Some("1.0.99"));
+
+    let run_id = sandbox.insert_run();
+    let child = sandbox.spawn(&[
+        INIT,
+        r#"out {"type":"assistant","message":{"content":[{"type":"text","text":"Hi"}],"usage":{"input_tokens":10,"output_tokens":2}}}"#,
+        "sleep 0.1",
+        r#"out {"type":"assistant","message":{"content":[{"type":"text","text":"there"}],"usage":{"input_tokens":12,"output_tokens":3}}}"#,
+        r#"out {"type":"result","subtype":"success","is_error":false,"result":"Hi there","duration_ms":150,"total_cost_usd":0.0123,"session_id":"fake-session"}"#,
+    ]);
+    sandbox
+        .conn
+        .execute(
+            "UPDATE agent_runs SET status = 'running', pid = ?1 WHERE id = ?2",
+            params![child.id(), run_id],
+        )
+        .unwrap();
+    assert_eq!(query_agent_run(&sandbox.conn, run_id).unwrap().status, "running");
+
+    let (output, stderr, session_id, status) = collect(child).await;
+    assert!(status.success(), "fake claude failed: {}", stderr);
+    assert_eq!(session_id, "fake-session");
+
+    let args = read_args(&sandbox.path("args"));
+    assert_eq!(args[..2], ["-p", "say hi"]);
+    assert!(args.contains(&"stream-json".to_string()) && args.contains(&"--verbose".to_string()));
+
+    assert_eq!(
+        complete_agent_run(&sandbox.conn, run_id, &session_id, &output).unwrap(),
+        "fake-session"
+    );
+    let run = query_agent_run(&sandbox.conn, run_id).unwrap();
+    assert_eq!((run.status.as_str(), run.session_id.as_str()), ("completed", "fake-session"));
+    assert!(run.completed_at.is_some());
+
+    let metrics = AgentRunMetrics::from_jsonl(&output);
+    assert_eq!(metrics.total_tokens, Some(27));
+    assert_eq!(metrics.cost_usd, Some(0.0123));
+    assert_eq!(metrics.duration_ms, Some(150));
+
+    let summary = RunSummary::from_jsonl(run_id, 150, &output, "live_output");
+    assert_eq!(summary.status, "completed");
+    assert_eq!(summary.last_assistant_snippet.as_deref(), Some("there"));
+    assert!(detect_rate_limit(&output, &stderr, !status.success()).is_none());
+}
+
+#[tokio::test]
+async fn session_id_is_recovered_when_not_picked_up_while_streaming() {
+    let sandbox = Sandbox::new();
+    let run_id = sandbox.insert_run();
+    let (output, _, _, _) = collect(sandbox.spawn(&["out not json yet", INIT])).await;
+
+    // The stream was read without extracting the ID, e.g. after a reader error
+    assert_eq!(complete_agent_run(&sandbox.conn, run_id, "", &output).unwrap(), "fake-session");
+}
+
+#[tokio::test]
+async fn rate_limited_run_fails_with_retry_hint() {
+    let sandbox = Sandbox::new();
+    let (output, stderr, _, status) = collect(sandbox.spawn(&[
+        INIT,
+        "err API Error: 429 rate_limit_error, retry-after: 30",
+        r#"out {"type":"result","subtype":"error_during_execution","is_error":true,"result":"Request failed","session_id":"fake-session"}"#,
+        "exit 1",
+    ]))
+    .await;
+
+    assert_eq!(status.code(), Some(1));
+    let hit = detect_rate_limit(&output, &stderr, !status.success()).expect("rate limit is detected");
+    assert!(hit.message.contains("rate_limit_error"));
+    assert_eq!(hit.retry_after_secs, Some(30));
+    assert_eq!(RunSummary::from_jsonl(1, 0, &output, "live_output").status, "failed");
+}
+
+#[tokio::test]
+async fn registered_run_is_cancelled_through_the_registry() {
+    let sandbox = Sandbox::new();
+    let run_id = sandbox.insert_run();
+    let child = sandbox.spawn(&[INIT, "sleep 30", r#"out {"type":"result","is_error":false}"#]);
+    let pid = child.id().unwrap();
+
+    let registry = ProcessRegistry::new();
+    registry
+        .register_process(
+            run_id,
+            1,
+            "Fake".to_string(),
+            pid,
+            sandbox.path("project").to_string_lossy().into_owned(),
+            "say hi".to_string(),
+            "sonnet".to_string(),
+            child,
+        )
+        .unwrap();
+    assert_eq!(registry.status_summary().unwrap().running_agent_runs, 1);
+
+    let killed = tokio::time::timeout(Duration::from_secs(10), registry.kill_process(run_id))
+        .await
+        .expect("kill finishes well before the scenario would");
+    assert!(killed.unwrap());
+    assert!(!registry.is_process_running(run_id).await.unwrap());
+    assert_eq!(registry.status_summary().unwrap(), ExecutionStatusSummary::default());
+}
+
+/// Register a spawned run the way `spawn_agent_system` does
+fn register(registry: &ProcessRegistry, sandbox: &Sandbox, run_id: i64, child: tokio::process::Child) {
+    let pid = child.id().unwrap();
+    registry
+        .register_process(
+            run_id,
+            1,
+            "Fake".to_string(),
+            pid,
+            sandbox.path("project").to_string_lossy().into_owned(),
+            "say hi".to_string(),
+            "sonnet".to_string(),
+            child,
+        )
+        .unwrap();
+    let spill_path = sandbox.path("app-data").join("run_output").join(format!("{}.jsonl", run_id));
+    registry
+        .set_live_output(
+            run_id,
+            RetainedOutput::new(OutputRetention::SpillToFile, OUTPUT_MEMORY_CAP_BYTES, Some(spill_path)),
+        )
+        .unwrap();
+}
+
+#[tokio::test]
+async fn live_output_is_served_byte_for_byte_from_the_registry() {
+    let sandbox = Sandbox::new();
+    let run_id = sandbox.insert_run();
+    let long_text = "x".repeat(64 * 1024);
+    let assistant = format!(
+        r#"out {{"type":"assistant","message":{{"content":[{{"type":"text","text":"{}"}}]}}}}"#,
+        long_text
+    );
+    let scenario = [
+        INIT,
+        r#"out {"type":"assistant","message":{"content":[{"type":"text","text":"héllo ✓ \"quoted\""}]}}"#,
+        "out   indented, not json  ",
+        &assistant,
+        "sleep 0.1",
+        r#"out {"type":"result","subtype":"success","is_error":false,"result":"done","session_id":"fake-session"}"#,
+    ];
+    let expected: String = scenario
+        .iter()
+        .filter_map(|step| step.strip_prefix("out "))
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    let mut child = sandbox.spawn(&scenario);
+    let stdout = child.stdout.take().unwrap();
+    let registry = ProcessRegistry::new();
+    register(&registry, &sandbox, run_id, child);
+
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await.unwrap() {
+        registry.append_live_output(run_id, &line).unwrap();
+    }
+    assert_eq!(registry.get_live_output(run_id).unwrap(), expected);
+
+    let finished = registry.finish_live_output(run_id).unwrap().unwrap();
+    assert_eq!(finished.contents, expected);
+    assert_eq!(finished.total_bytes, expected.len() as u64);
+    assert!(!finished.spilled);
+    // A finished run that is still registered keeps serving the same bytes
+    assert_eq!(registry.get_live_output(run_id).unwrap(), expected);
+}
+
+#[tokio::test]
+async fn output_of_a_killed_run_stays_with_its_reader() {
+    let sandbox = Sandbox::new();
+    let run_id = sandbox.insert_run();
+    let mut child = sandbox.spawn(&[INIT, "out second", "sleep 30", "out never"]);
+    let stdout = child.stdout.take().unwrap();
+    let registry = ProcessRegistry::new();
+    register(&registry, &sandbox, run_id, child);
+
+    let mut lines = BufReader::new(stdout).lines();
+    for _ in 0..2 {
+        let line = lines.next_line().await.unwrap().unwrap();
+        registry.append_live_output(run_id, &line).unwrap();
+    }
+    assert!(registry.kill_process(run_id).await.unwrap());
+    // Unregistered runs have no live output, as before
+    assert_eq!(registry.get_live_output(run_id).unwrap(), "");
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        registry.append_live_output(run_id, &line).unwrap();
+    }
+    let finished = registry.finish_live_output(run_id).unwrap().unwrap();
+    assert_eq!(finished.contents, format!("{}\nsecond\n", &INIT[4..]));
+    assert!(registry.finish_live_output(run_id).unwrap().is_none());
+}
@@ -1,7 +1,8 @@
 import { useState, useEffect } from "react";
 import { motion } from "framer-motion";
 import { Bot, FolderCode } from "lucide-react";
-import { api, type Project, type Session, type ClaudeMdFile } from "@/lib/api";
+import { api, type Project, type Session, type ClaudeMdFile, type StartupProgress, type StartupStatus } from "@/lib/api";
+import { listen } from "@tauri-apps/api/event";
 import { OutputCacheProvider } from "@/lib/outputCache";
 import { TabProvider } from "@/contexts/TabContext";
 import { ThemeProvider } from "@/contexts/ThemeContext";
@@ -18,6 +19,7 @@ import { UsageDashboard } from "@/components/UsageDashboard";
 import { MCPManager } from "@/components/MCPManager";
 import { NFOCredits } from "@/components/NFOCredits";
 import { ClaudeBinaryDialog } from "@/components/ClaudeBinaryDialog";
+import { ClaudeDirHealthBanner } from "@/components/ClaudeDirHealthBanner";
 import { Toast, ToastContainer } from "@/components/ui/toast";
 import { ProjectSettings } from '@/components/ProjectSettings';
 import { TabManager } from "@/components/TabManager";
@@ -383,6 +385,9 @@ function AppContent() {
         onSettingsClick={() => createSettingsTab()}
         onInfoClick={() => setShowNFO(true)}
       />
+
+      {/* Warning while ~/.claude cannot be written */}
+      <ClaudeDirHealthBanner />
       
       {/* Topbar - Commented out since navigation moved to titlebar */}
       {/* <Topbar
@@ -523,12 +528,50 @@ function App() {
     };
   }, []);
 
+  // The backend opens its database after the window is up; commands fail
+  // with not_ready until then, so the app is mounted once it's done
+  const [startup, setStartup] = useState<StartupStatus | null>(null);
+
+  useEffect(() => {
+    const unlisteners: Array<() => void> = [];
+    let disposed = false;
+    (async () => {
+      unlisteners.push(
+        await listen<StartupProgress>("startup-progress", (event) => {
+          setStartup((current) => ({
+            ready: false,
+            error: null,
+            ...current,
+            completed: [...(current?.completed ?? []), event.payload],
+          }));
+        }),
+        await listen<StartupStatus>("startup-complete", (event) => setStartup(event.payload)),
+      );
+      if (disposed) {
+        unlisteners.forEach((unlisten) => unlisten());
+        return;
+      }
+      // Events sent before the listeners were registered are missed
+      const status = await api.getStartupStatus();
+      setStartup((current) => (current?.ready || current?.error ? current : status));
+    })().catch((err) => {
+      console.error("Failed to follow startup:", err);
+      setStartup({ ready: true, completed: [] });
+    });
+    return () => {
+      disposed = true;
+      unlisteners.forEach((unlisten) => unlisten());
+    };
+  }, []);
+
+  const ready = startup?.ready ?? false;
+
   return (
     <ThemeProvider>
       <OutputCacheProvider>
         <TabProvider>
-          <AppContent />
-          <StartupIntro visible={showIntro} />
+          {ready && <AppContent />}
+          <StartupIntro visible={showIntro || !ready} startup={startup} />
         </TabProvider>
       </OutputCacheProvider>
     </ThemeProvider>
@@ -11,7 +11,8 @@ import {
   ChevronDown,
   Maximize2,
   X,
-  Settings2
+  Settings2,
+  FileText
 } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
@@ -24,7 +25,7 @@ import {
   DialogTitle,
 } from "@/components/ui/dialog";
 import { Tabs, TabsList, TabsTrigger, TabsContent } from "@/components/ui/tabs";
-import { api, type Agent } from "@/lib/api";
+import { api, type Agent, type RunChangeSummary, type RunSummary } from "@/lib/api";
 import { cn } from "@/lib/utils";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { StreamMessage } from "./StreamMessage";
@@ -75,6 +76,21 @@ export interface ClaudeStreamMessage {
   [key: string]: any;
 }
 
+/**
+ * Describe a run's file changes, e.g. "3 files modified, 120 lines added"
+ */
+function formatFileChanges(changes: RunChangeSummary): string {
+  const plural = (count: number, word: string) => `${count} ${word}${count === 1 ? "" : "s"}`;
+  const parts: string[] = [];
+  if (changes.files_modified) parts.push(`${plural(changes.files_modified, "file")} modified`);
+  if (changes.files_added) parts.push(`${plural(changes.files_added, "file")} added`);
+  if (changes.files_deleted) parts.push(`${plural(changes.files_deleted, "file")} deleted`);
+  if (parts.length === 0) return "No files changed";
+  if (changes.line_delta > 0) parts.push(`${plural(changes.line_delta, "line")} added`);
+  if (changes.line_delta < 0) parts.push(`${plural(-changes.line_delta, "line")} removed`);
+  return parts.join(", ");
+}
+
 /**
  * AgentExecution component for running CC agents
  * 
@@ -98,6 +114,7 @@ export const AgentExecution: React.FC<AgentExecutionProps> = ({
   const [messages, setMessages] = useState<ClaudeStreamMessage[]>([]);
   const [rawJsonlOutput, setRawJsonlOutput] = useState<string[]>([]);
   const [error, setError] = useState<string | null>(null);
+  const [fileChanges, setFileChanges] = useState<RunChangeSummary | null>(null);
   const [copyPopoverOpen, setCopyPopoverOpen] = useState(false);
   
   // Analytics tracking
@@ -295,6 +312,7 @@ export const AgentExecution: React.FC<AgentExecutionProps> = ({
       setMessages([]);
       setRawJsonlOutput([]);
       setRunId(null);
+      setFileChanges(null);
       
       // Clear any existing listeners
       unlistenRefs.current.forEach(unlisten => unlisten());
@@ -342,11 +360,12 @@ export const AgentExecution: React.FC<AgentExecutionProps> = ({
         });
       });
 
-      const completeUnlisten = await listen<boolean>(`agent-complete:${executionRunId}`, (event) => {
+      const completeUnlisten = await listen<RunSummary>(`agent-complete:${executionRunId}`, (event) => {
         setIsRunning(false);
         const duration = executionStartTime ? Date.now() - executionStartTime : undefined;
         setExecutionStartTime(null);
-        if (!event.payload) {
+        setFileChanges(event.payload.file_changes ?? null);
+        if (event.payload.status !== 'completed') {
           setError("Agent execution failed");
           // Update tab status to error
           if (tabId) {
@@ -617,6 +636,16 @@ export const AgentExecution: React.FC<AgentExecutionProps> = ({
               </motion.div>
             )}
 
+            {/* Files the finished run changed */}
+            {fileChanges && (
+              <div className="p-3 rounded-md bg-muted/50 border border-border flex items-center gap-2">
+                <FileText className="h-3.5 w-3.5 text-muted-foreground flex-shrink-0" />
+                <span className="text-caption text-muted-foreground">
+                  {formatFileChanges(fileChanges)}
+                </span>
+              </div>
+            )}
+
             {/* Model Selection */}
             <div className="space-y-3">
               <Label className="text-caption text-muted-foreground">Model Selection</Label>
@@ -18,7 +18,7 @@ import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card';
 import { Badge } from '@/components/ui/badge';
 import { Toast, ToastContainer } from '@/components/ui/toast';
 import { Popover } from '@/components/ui/popover';
-import { api, type AgentRunWithMetrics } from '@/lib/api';
+import { api, type AgentRunWithMetrics, type McpToolStats, type RunSummary } from '@/lib/api';
 import { useOutputCache } from '@/lib/outputCache';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { StreamMessage } from './StreamMessage';
@@ -28,6 +28,17 @@ import { AGENT_ICONS } from './CCAgents';
 import type { ClaudeStreamMessage } from './AgentExecution';
 import { useTabState } from '@/hooks/useTabState';
 
+// Runs with more output than this get a warning before the output renders
+const LARGE_OUTPUT_BYTES = 8 * 1024 * 1024;
+
+// Format a byte count to human readable
+const formatFileSize = (bytes: number): string => {
+  const k = 1024;
+  const sizes = ['B', 'KB', 'MB', 'GB'];
+  const i = Math.min(Math.floor(Math.log(Math.max(bytes, 1)) / Math.log(k)), sizes.length - 1);
+  return `${parseFloat((bytes / Math.pow(k, i)).toFixed(1))} ${sizes[i]}`;
+};
+
 interface AgentRunOutputViewerProps {
   /**
    * The agent run ID to display
@@ -59,6 +70,7 @@ export function AgentRunOutputViewer({
 }: AgentRunOutputViewerProps) {
   const { updateTabTitle, updateTabStatus } = useTabState();
   const [run, setRun] = useState<AgentRunWithMetrics | null>(null);
+  const [mcpStats, setMcpStats] = useState<McpToolStats[]>([]);
   const [messages, setMessages] = useState<ClaudeStreamMessage[]>([]);
   const [rawJsonlOutput, setRawJsonlOutput] = useState<string[]>([]);
   const [loading, setLoading] = useState(true);
@@ -106,6 +118,7 @@ export function AgentRunOutputViewer({
         setLoading(true);
         const agentRun = await api.getAgentRun(parseInt(agentRunId));
         setRun(agentRun);
+        api.getRunMcpStats(agentRun.id!).then(setMcpStats).catch(() => setMcpStats([]));
         updateTabTitle(tabId, `Agent: ${agentRun.agent_name || 'Unknown'}`);
         updateTabStatus(tabId, agentRun.status === 'running' ? 'running' : agentRun.status === 'failed' ? 'error' : 'complete');
       } catch (error) {
@@ -303,7 +316,7 @@ export function AgentRunOutputViewer({
         setToast({ message: event.payload, type: 'error' });
       });
 
-      const completeUnlisten = await listen<boolean>(`agent-complete:${run!.id}`, () => {
+      const completeUnlisten = await listen<RunSummary>(`agent-complete:${run!.id}`, () => {
         setToast({ message: 'Agent execution completed', type: 'success' });
         // Don't set status here as the parent component should handle it
       });
@@ -588,6 +601,24 @@ export function AgentRunOutputViewer({
                       </div>
                     )}
                   </div>
+                  {mcpStats.length > 0 && (
+                    <div className="flex flex-wrap items-center gap-2 text-xs text-muted-foreground mt-2">
+                      {mcpStats.map((stat) => (
+                        <Badge key={`${stat.server}/${stat.tool}`} variant="secondary" className="text-xs font-normal">
+                          {stat.server}/{stat.tool} · {stat.calls} call{stat.calls === 1 ? '' : 's'}
+                          {stat.errors > 0 && ` · ${Math.round(stat.error_rate * 100)}% errors`}
+                          {stat.p50_ms !== undefined && stat.p50_ms !== null && ` · p50 ${stat.p50_ms}ms`}
+                          {stat.p95_ms !== undefined && stat.p95_ms !== null && ` · p95 ${stat.p95_ms}ms`}
+                        </Badge>
+                      ))}
+                    </div>
+                  )}
+                  {(run.output_spilled || (run.output_bytes ?? 0) > LARGE_OUTPUT_BYTES) && (
+                    <p className="text-xs text-yellow-600 mt-2">
+                      Large output ({formatFileSize(run.output_bytes ?? 0)})
+                      {run.output_spilled && ', kept in full in the run output file'}; rendering may be slow
+                    </p>
+                  )}
                 </div>
               </div>
               <div className="flex items-center gap-1">
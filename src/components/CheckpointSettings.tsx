@@ -14,7 +14,7 @@ import { Switch } from "@/components/ui/switch";
 import { SelectComponent, type SelectOption } from "@/components/ui/select";
 import { Input } from "@/components/ui/input";
 import { Card } from "@/components/ui/card";
-import { api, type CheckpointStrategy } from "@/lib/api";
+import { api, type CheckpointRetention, type CheckpointStrategy } from "@/lib/api";
 import { cn } from "@/lib/utils";
 
 interface CheckpointSettingsProps {
@@ -44,7 +44,9 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
   const [autoCheckpointEnabled, setAutoCheckpointEnabled] = useState(true);
   const [checkpointStrategy, setCheckpointStrategy] = useState<CheckpointStrategy>("smart");
   const [totalCheckpoints, setTotalCheckpoints] = useState(0);
+  const [autoCheckpointPending, setAutoCheckpointPending] = useState(false);
   const [keepCount, setKeepCount] = useState(10);
+  const [retention, setRetention] = useState<CheckpointRetention>({});
   const [isLoading, setIsLoading] = useState(false);
   const [isSaving, setIsSaving] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -70,6 +72,8 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
       setAutoCheckpointEnabled(settings.auto_checkpoint_enabled);
       setCheckpointStrategy(settings.checkpoint_strategy);
       setTotalCheckpoints(settings.total_checkpoints);
+      setAutoCheckpointPending(settings.auto_checkpoint_pending);
+      setRetention(settings.retention ?? {});
     } catch (err) {
       console.error("Failed to load checkpoint settings:", err);
       setError("Failed to load checkpoint settings");
@@ -89,7 +93,8 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
         projectId,
         projectPath,
         autoCheckpointEnabled,
-        checkpointStrategy
+        checkpointStrategy,
+        retention
       );
       
       setSuccessMessage("Settings saved successfully");
@@ -112,7 +117,8 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
         sessionId,
         projectId,
         projectPath,
-        keepCount
+        keepCount,
+        retention
       );
       
       setSuccessMessage(`Removed ${removed} old checkpoints`);
@@ -222,6 +228,34 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
           </p>
         </div>
 
+        {/* Retention per kind */}
+        <div className="space-y-2">
+          <Label className="text-label">Retention</Label>
+          <div className="grid grid-cols-2 gap-2">
+            <Input
+              type="number"
+              min="1"
+              placeholder="Keep all manual"
+              value={retention.keepManual ?? ""}
+              onChange={(e) => setRetention({ ...retention, keepManual: parseInt(e.target.value) || undefined })}
+              disabled={isLoading}
+              className="h-9"
+            />
+            <Input
+              type="number"
+              min="1"
+              placeholder="Keep all automatic"
+              value={retention.keepAuto ?? ""}
+              onChange={(e) => setRetention({ ...retention, keepAuto: parseInt(e.target.value) || undefined })}
+              disabled={isLoading}
+              className="h-9"
+            />
+          </div>
+          <p className="text-caption text-muted-foreground">
+            Applied after each automatic checkpoint. The current checkpoint and branch points are always kept.
+          </p>
+        </div>
+
         {/* Save button */}
         <motion.div
           whileTap={{ scale: 0.97 }}
@@ -259,6 +293,11 @@ export const CheckpointSettings: React.FC<CheckpointSettingsProps> = ({
             <p className="text-caption text-muted-foreground">
               Total checkpoints: <span className="font-medium text-foreground">{totalCheckpoints}</span>
             </p>
+            {autoCheckpointPending && (
+              <p className="text-caption text-muted-foreground">
+                Checkpoint scheduled after the current run
+              </p>
+            )}
           </div>
         </div>
 
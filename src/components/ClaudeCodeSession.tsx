@@ -13,7 +13,7 @@ import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { Popover } from "@/components/ui/popover";
-import { api, type Session } from "@/lib/api";
+import { api, type CheckpointKind, type CheckpointStrategy, type Session } from "@/lib/api";
 import { cn } from "@/lib/utils";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { StreamMessage } from "./StreamMessage";
@@ -31,6 +31,17 @@ import { useVirtualizer } from "@tanstack/react-virtual";
 import { useTrackEvent, useComponentMetrics, useWorkflowTracking } from "@/hooks";
 import { SessionPersistenceService } from "@/services/sessionPersistence";
 
+/** Kind recorded on checkpoints created by each auto-checkpoint strategy */
+const AUTO_CHECKPOINT_KINDS: Record<CheckpointStrategy, CheckpointKind> = {
+  manual: "manual",
+  per_prompt: "auto_prompt",
+  per_tool_use: "auto_tool",
+  smart: "auto_smart",
+};
+
+/** A checkpoint younger than this covers a risky prompt without asking */
+const RECENT_CHECKPOINT_SECS = 10 * 60;
+
 interface ClaudeCodeSessionProps {
   /**
    * Optional session to resume (when clicking from SessionList)
@@ -266,6 +277,20 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
     }
   }, [session]); // Remove hasLoadedSession dependency to ensure it runs on mount
 
+  // Reload the conversation when the session file changes outside the app
+  useEffect(() => {
+    if (!session) return;
+    let unlisten: UnlistenFn | undefined;
+    listen(`session-externally-modified:${session.id}`, () => {
+      if (isMountedRef.current && !isLoading) {
+        loadSessionHistory();
+      }
+    }).then((fn) => {
+      unlisten = fn;
+    });
+    return () => unlisten?.();
+  }, [session, isLoading]);
+
   // Report streaming state changes
   useEffect(() => {
     onStreamingChange?.(isLoading, claudeSessionId);
@@ -430,6 +455,38 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
 
   // Project path selection handled by parent tab controls
 
+  /**
+   * Before a prompt that looks destructive, offer a safety checkpoint when
+   * there is no recent one. Returns false when the user cancels the prompt.
+   */
+  const confirmRiskyPrompt = async (prompt: string, session: Session): Promise<boolean> => {
+    try {
+      const risk = await api.analyzePromptRisk(prompt);
+      if (risk.level !== "high") return true;
+      const settings = await api.getCheckpointSettings(session.id, session.project_id, projectPath);
+      const age = settings.seconds_since_last_checkpoint;
+      if (age != null && age < RECENT_CHECKPOINT_SECS) return true;
+
+      const reasons = risk.reasons.map((reason) => reason.keyword).join(", ");
+      if (window.confirm(`This prompt looks destructive (${reasons}) and there is no recent checkpoint. Create one before sending?`)) {
+        await api.createCheckpoint(
+          session.id,
+          session.project_id,
+          projectPath,
+          undefined,
+          `Before: ${prompt.slice(0, 60)}`,
+          "auto_safety"
+        );
+        return true;
+      }
+      return window.confirm("Send the prompt without a checkpoint?");
+    } catch (err) {
+      // The check is advisory; never block a prompt because it failed
+      console.error("Failed to check prompt risk:", err);
+      return true;
+    }
+  };
+
   const handleSendPrompt = async (prompt: string, model: "sonnet" | "opus") => {
     console.log('[ClaudeCodeSession] handleSendPrompt called with:', { prompt, model, projectPath, claudeSessionId, effectiveSession });
     
@@ -449,6 +506,10 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
       return;
     }
 
+    if (effectiveSession && !(await confirmRiskyPrompt(prompt, effectiveSession))) {
+      return;
+    }
+
     try {
       setIsLoading(true);
       setError(null);
@@ -701,12 +762,22 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
               );
 
               if (settings.auto_checkpoint_enabled) {
-                await api.checkAutoCheckpoint(
+                const shouldCheckpoint = await api.checkAutoCheckpoint(
                   effectiveSession.id,
                   effectiveSession.project_id,
                   projectPath,
                   prompt
                 );
+                if (shouldCheckpoint) {
+                  await api.createCheckpoint(
+                    effectiveSession.id,
+                    effectiveSession.project_id,
+                    projectPath,
+                    undefined,
+                    undefined,
+                    AUTO_CHECKPOINT_KINDS[settings.checkpoint_strategy]
+                  );
+                }
                 // Reload timeline to show new checkpoint
                 setTimelineVersion((v) => v + 1);
               }
@@ -814,7 +885,12 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
       }
     } catch (err) {
       console.error("Failed to send prompt:", err);
-      setError("Failed to send prompt");
+      const message = String(err);
+      setError(
+        message.startsWith("[session_in_other_project]")
+          ? message.replace(/^\[session_in_other_project\]\s*/, "")
+          : "Failed to send prompt"
+      );
       setIsLoading(false);
       hasActiveSessionRef.current = false;
     }
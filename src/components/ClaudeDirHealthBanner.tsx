@@ -0,0 +1,54 @@
+import React, { useCallback, useEffect, useState } from "react";
+import { AlertTriangle, RefreshCw } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { api, type ClaudeDirHealth } from "@/lib/api";
+import { useTranslation } from "react-i18next";
+
+/** How often ~/.claude is checked again, in milliseconds */
+const HEALTH_POLL_INTERVAL_MS = 60_000;
+
+/**
+ * Persistent warning shown while ~/.claude cannot be written, e.g. on
+ * managed machines or while a sync client holds it
+ */
+export const ClaudeDirHealthBanner: React.FC = () => {
+  const { t } = useTranslation();
+  const [health, setHealth] = useState<ClaudeDirHealth | null>(null);
+
+  const check = useCallback(async () => {
+    try {
+      setHealth(await api.checkClaudeDirHealth());
+    } catch {
+      // The check itself failing is not a reason to warn
+    }
+  }, []);
+
+  useEffect(() => {
+    check();
+    const timer = window.setInterval(check, HEALTH_POLL_INTERVAL_MS);
+    return () => window.clearInterval(timer);
+  }, [check]);
+
+  // A missing ~/.claude is created by claude itself on first run
+  if (!health || !health.exists || health.writable) {
+    return null;
+  }
+
+  return (
+    <div className="flex items-center gap-3 border-b border-yellow-500/30 bg-yellow-500/10 px-4 py-2 text-sm">
+      <AlertTriangle className="h-4 w-4 flex-shrink-0 text-yellow-600" />
+      <div className="flex-1 min-w-0">
+        <div>{t("components.claude_dir_health.readonly")}</div>
+        <div className="truncate text-xs text-muted-foreground">
+          {t("components.claude_dir_health.details", {
+            paths: health.problems.map((problem) => problem.path).join(", "),
+          })}
+        </div>
+      </div>
+      <Button variant="ghost" size="sm" onClick={check}>
+        <RefreshCw className="mr-1 h-3 w-3" />
+        {t("components.claude_dir_health.recheck")}
+      </Button>
+    </div>
+  );
+};
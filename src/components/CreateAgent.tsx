@@ -6,7 +6,7 @@ import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { Card } from "@/components/ui/card";
 import { Toast, ToastContainer } from "@/components/ui/toast";
-import { api, type Agent } from "@/lib/api";
+import { api, type Agent, type ThinkingLevel } from "@/lib/api";
 import { cn } from "@/lib/utils";
 import MDEditor from "@uiw/react-md-editor";
 import { type AgentIconName } from "./CCAgents";
@@ -51,6 +51,7 @@ export const CreateAgent: React.FC<CreateAgentProps> = ({
   const [systemPrompt, setSystemPrompt] = useState(agent?.system_prompt || "");
   const [defaultTask, setDefaultTask] = useState(agent?.default_task || "");
   const [model, setModel] = useState(agent?.model || "sonnet");
+  const [thinkingLevel, setThinkingLevel] = useState<string>(agent?.thinking_level || "");
   const [saving, setSaving] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [toast, setToast] = useState<{ message: string; type: "success" | "error" } | null>(null);
@@ -80,7 +81,12 @@ export const CreateAgent: React.FC<CreateAgentProps> = ({
           selectedIcon, 
           systemPrompt, 
           defaultTask || undefined, 
-          model
+          model,
+          undefined,
+          undefined,
+          undefined,
+          undefined,
+          (thinkingLevel.trim() || "none") as ThinkingLevel
         );
       } else {
         await api.createAgent(
@@ -88,7 +94,12 @@ export const CreateAgent: React.FC<CreateAgentProps> = ({
           selectedIcon, 
           systemPrompt, 
           defaultTask || undefined, 
-          model
+          model,
+          undefined,
+          undefined,
+          undefined,
+          undefined,
+          (thinkingLevel.trim() || undefined) as ThinkingLevel | undefined
         );
       }
       
@@ -110,7 +121,8 @@ export const CreateAgent: React.FC<CreateAgentProps> = ({
          selectedIcon !== (agent?.icon || "bot") || 
          systemPrompt !== (agent?.system_prompt || "") ||
          defaultTask !== (agent?.default_task || "") ||
-         model !== (agent?.model || "sonnet")) && 
+         model !== (agent?.model || "sonnet") ||
+         thinkingLevel !== (agent?.thinking_level || "")) && 
         !confirm(t('components.create_agent.unsaved_changes'))) {
       return;
     }
@@ -309,6 +321,20 @@ export const CreateAgent: React.FC<CreateAgentProps> = ({
                   {t('components.create_agent.default_task_help')}
                 </p>
               </div>
+              <div className="space-y-2 mt-4">
+                <Label htmlFor="thinking-level" className="text-caption text-muted-foreground">{t('components.create_agent.thinking_level')}</Label>
+                <Input
+                  id="thinking-level"
+                  type="text"
+                  placeholder="none"
+                  value={thinkingLevel}
+                  onChange={(e) => setThinkingLevel(e.target.value)}
+                  className="h-9"
+                />
+                <p className="text-caption text-muted-foreground">
+                  {t('components.create_agent.thinking_level_help')}
+                </p>
+              </div>
             </Card>
 
             {/* System Prompt */}
@@ -9,13 +9,15 @@ import {
   Check,
   Globe,
   FileJson,
+  Plus,
 } from "lucide-react";
 import { Dialog, DialogContent, DialogHeader, DialogTitle } from "@/components/ui/dialog";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Card, CardContent, CardFooter } from "@/components/ui/card";
 import { Badge } from "@/components/ui/badge";
-import { api, type GitHubAgentFile, type AgentExport, type Agent } from "@/lib/api";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
+import { api, type GitHubAgentFile, type AgentExport, type Agent, type AgentSource } from "@/lib/api";
 import { type AgentIconName } from "./CCAgents";
 import { ICON_MAP } from "./IconPicker";
 import { open } from "@tauri-apps/plugin-shell";
@@ -45,14 +47,57 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
   const [selectedAgent, setSelectedAgent] = useState<AgentPreview | null>(null);
   const [importing, setImporting] = useState(false);
   const [existingAgents, setExistingAgents] = useState<Agent[]>([]);
+  const [sources, setSources] = useState<AgentSource[]>([]);
+  const [sourceIndex, setSourceIndex] = useState(0);
+  const [newSource, setNewSource] = useState("");
+  const [rateLimited, setRateLimited] = useState(false);
+  const [token, setToken] = useState("");
+  const source: AgentSource | undefined = sources[sourceIndex];
 
   useEffect(() => {
     if (isOpen) {
-      fetchAgents();
+      fetchAgents(source);
       fetchExistingAgents();
     }
+  }, [isOpen, sourceIndex, sources.length]);
+
+  useEffect(() => {
+    if (isOpen) {
+      api.listAgentSources().then(setSources).catch((err) => {
+        console.error("Failed to load agent sources:", err);
+      });
+    }
   }, [isOpen]);
 
+  const sourceLabel = (s: AgentSource) => (s.path ? `${s.repo}/${s.path}` : s.repo);
+
+  const handleSaveToken = async () => {
+    try {
+      await api.setGitHubToken(token.trim() || null);
+      setToken("");
+      fetchAgents(source);
+    } catch (err) {
+      console.error("Failed to save GitHub token:", err);
+    }
+  };
+
+  const handleAddSource = async () => {
+    // "owner/name" or "owner/name/some/dir"
+    const [owner, name, ...path] = newSource.trim().replace(/^https:\/\/github\.com\//, "").split("/");
+    if (!owner || !name) return;
+    const repo = `${owner}/${name}`;
+    const dir = path.filter(Boolean).join("/");
+    try {
+      const updated = await api.addAgentSource(repo, dir);
+      setSources(updated);
+      setSourceIndex(Math.max(0, updated.findIndex((s) => s.repo === repo && s.path === dir)));
+      setNewSource("");
+    } catch (err) {
+      console.error("Failed to add agent source:", err);
+      alert(`Failed to add repository: ${err instanceof Error ? err.message : String(err)}`);
+    }
+  };
+
   const fetchExistingAgents = async () => {
     try {
       const agents = await api.listAgents();
@@ -62,15 +107,22 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
     }
   };
 
-  const fetchAgents = async () => {
+  const fetchAgents = async (from?: AgentSource) => {
     try {
       setLoading(true);
       setError(null);
-      const agentFiles = await api.fetchGitHubAgents();
+      setRateLimited(false);
+      const agentFiles = await api.fetchGitHubAgents(from);
       setAgents(agentFiles);
     } catch (err) {
       console.error("Failed to fetch GitHub agents:", err);
-      setError("Failed to fetch agents from GitHub. Please check your internet connection.");
+      const message = String(err);
+      if (message.startsWith("[rate_limited]")) {
+        setRateLimited(true);
+        setError(message.replace(/^\[rate_limited\]\s*/, ""));
+      } else {
+        setError("Failed to fetch agents from GitHub. Please check your internet connection.");
+      }
     } finally {
       setLoading(false);
     }
@@ -115,7 +167,7 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
 
     try {
       setImporting(true);
-      await api.importAgentFromGitHub(selectedAgent.file.download_url);
+      await api.importAgentFromGitHub(selectedAgent.file.download_url, source);
       
       // Refresh existing agents list
       await fetchExistingAgents();
@@ -138,7 +190,7 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
   );
 
   const getAgentDisplayName = (fileName: string) => {
-    return fileName.replace(".opcode.json", "").replace(/-/g, " ")
+    return fileName.replace(/\.(opcode|claudia)\.json$/, "").replace(/-/g, " ")
       .split(" ")
       .map(word => word.charAt(0).toUpperCase() + word.slice(1))
       .join(" ");
@@ -152,7 +204,8 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
   const handleGitHubLinkClick = async (e: React.MouseEvent) => {
     e.preventDefault();
     try {
-      await open("https://github.com/getAsterisk/opcode/tree/main/cc_agents");
+      const from = source ?? { repo: "getAsterisk/opcode", path: "cc_agents" };
+      await open(`https://github.com/${from.repo}/tree/HEAD/${from.path}`);
     } catch (error) {
       console.error('Failed to open GitHub link:', error);
     }
@@ -177,13 +230,40 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
                 onClick={handleGitHubLinkClick}
                 className="text-primary hover:underline inline-flex items-center gap-1"
               >
-                github.com/getAsterisk/opcode/cc_agents
+                github.com/{source ? sourceLabel(source) : "getAsterisk/opcode/cc_agents"}
                 <Globe className="h-3 w-3" />
               </button>
             </p>
             <p className="text-sm text-muted-foreground mt-1">
               You can contribute your custom agents to the repository!
             </p>
+            <div className="flex items-center gap-2 mt-3">
+              {sources.length > 1 && (
+                <Select value={String(sourceIndex)} onValueChange={(value) => setSourceIndex(Number(value))}>
+                  <SelectTrigger className="w-[260px]">
+                    <SelectValue />
+                  </SelectTrigger>
+                  <SelectContent>
+                    {sources.map((s, index) => (
+                      <SelectItem key={sourceLabel(s)} value={String(index)}>
+                        {sourceLabel(s)}
+                      </SelectItem>
+                    ))}
+                  </SelectContent>
+                </Select>
+              )}
+              <Input
+                placeholder="owner/repo/path"
+                value={newSource}
+                onChange={(e) => setNewSource(e.target.value)}
+                onKeyDown={(e) => e.key === "Enter" && handleAddSource()}
+                className="flex-1"
+              />
+              <Button onClick={handleAddSource} variant="outline" size="sm" disabled={!newSource.includes("/")}>
+                <Plus className="h-4 w-4 mr-1" />
+                Add Repository
+              </Button>
+            </div>
           </div>
 
           {/* Search Bar */}
@@ -209,7 +289,21 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
               <div className="flex flex-col items-center justify-center h-64 text-center">
                 <AlertCircle className="h-12 w-12 text-destructive mb-4" />
                 <p className="text-sm text-muted-foreground mb-4">{error}</p>
-                <Button onClick={fetchAgents} variant="outline" size="sm">
+                {rateLimited && (
+                  <div className="flex items-center gap-2 mb-4 w-full max-w-md">
+                    <Input
+                      type="password"
+                      placeholder="GitHub token"
+                      value={token}
+                      onChange={(e) => setToken(e.target.value)}
+                      className="flex-1"
+                    />
+                    <Button onClick={handleSaveToken} size="sm" disabled={!token.trim()}>
+                      Save Token
+                    </Button>
+                  </div>
+                )}
+                <Button onClick={() => fetchAgents(source)} variant="outline" size="sm">
                   Try Again
                 </Button>
               </div>
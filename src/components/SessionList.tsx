@@ -1,4 +1,5 @@
 import React, { useState } from "react";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { motion, AnimatePresence } from "framer-motion";
 import { Clock, MessageSquare } from "lucide-react";
 import { Card } from "@/components/ui/card";
@@ -70,6 +71,23 @@ export const SessionList: React.FC<SessionListProps> = ({
   React.useEffect(() => {
     setCurrentPage(1);
   }, [sessions.length]);
+
+  // First messages are read in the background and arrive one session at a time
+  const [firstMessages, setFirstMessages] = useState<Record<string, string | null>>({});
+  React.useEffect(() => {
+    const pending = sessions.filter((session) => !session.first_message);
+    const unlisteners: Promise<UnlistenFn>[] = pending.map((session) =>
+      listen<{ sessionId: string; firstMessage: string | null }>(
+        `session-summary-ready:${session.id}`,
+        (event) => {
+          setFirstMessages((prev) => ({ ...prev, [event.payload.sessionId]: event.payload.firstMessage }));
+        }
+      )
+    );
+    return () => {
+      unlisteners.forEach((unlisten) => unlisten.then((fn) => fn()));
+    };
+  }, [sessions]);
   
   return (
     <TooltipProvider>
@@ -146,10 +164,15 @@ export const SessionList: React.FC<SessionListProps> = ({
                       )}
                     </div>
                     
+                    {/* Generated title */}
+                    {session.title && (
+                      <p className="text-caption font-medium truncate mb-1">{session.title}</p>
+                    )}
+
                     {/* First message preview */}
-                    {session.first_message ? (
+                    {session.first_message || firstMessages[session.id] ? (
                       <p className="text-caption text-muted-foreground line-clamp-2 mb-2">
-                        {truncateText(getFirstLine(session.first_message), 120)}
+                        {truncateText(getFirstLine(session.first_message || firstMessages[session.id] || ""), 120)}
                       </p>
                     ) : (
                       <p className="text-caption text-muted-foreground/60 italic mb-2">
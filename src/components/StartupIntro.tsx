@@ -1,13 +1,28 @@
 import { AnimatePresence, motion } from "framer-motion";
 import opcodeLogo from "../../src-tauri/icons/icon.png";
 import type { CSSProperties } from "react";
+import { useTranslation } from "react-i18next";
+import type { StartupPhase, StartupStatus } from "@/lib/api";
+
+const STARTUP_PHASES: StartupPhase[] = ["database", "claude_dir", "registry"];
 
 /**
  * StartupIntro - a lightweight startup overlay shown on app launch.
  * - Non-interactive; auto-fades after parent hides it via the `visible` prop.
  * - Uses existing shimmer/rotating-symbol styles from shimmer.css.
+ * - While the backend is starting, shows the phase it is in from `startup`.
  */
-export function StartupIntro({ visible }: { visible: boolean }) {
+export function StartupIntro({ visible, startup }: { visible: boolean; startup?: StartupStatus | null }) {
+  const { t } = useTranslation();
+  const pendingPhase = STARTUP_PHASES.find(
+    (phase) => !startup?.completed.some((done) => done.phase === phase)
+  );
+  const startupMessage = startup?.error
+    ? t("components.startup.failed", { error: startup.error })
+    : startup && !startup.ready && pendingPhase
+      ? t(`components.startup.${pendingPhase}`)
+      : null;
+
   // Simple entrance animations only
   return (
     <AnimatePresence>
@@ -18,7 +33,7 @@ export function StartupIntro({ visible }: { visible: boolean }) {
           exit={{ opacity: 0 }}
           transition={{ duration: 0.35 }}
           className="fixed inset-0 z-[60] flex items-center justify-center bg-background"
-          aria-hidden="true"
+          aria-hidden={!startupMessage}
         >
           {/* Ambient radial glow */}
           <motion.div
@@ -84,7 +99,11 @@ export function StartupIntro({ visible }: { visible: boolean }) {
               </motion.div>
             </div>
 
-
+            {startupMessage && (
+              <p className={startup?.error ? "mt-4 text-sm text-destructive" : "mt-4 text-sm text-muted-foreground"}>
+                {startupMessage}
+              </p>
+            )}
           </motion.div>
         </motion.div>
       )}
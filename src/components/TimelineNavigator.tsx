@@ -558,10 +558,14 @@ export const TimelineNavigator: React.FC<TimelineNavigatorProps> = ({
                     {diff.modifiedFiles.map((file) => (
                       <div key={file.path} className="flex items-center justify-between text-xs">
                         <span className="font-mono">{file.path}</span>
-                        <div className="flex items-center gap-2 text-xs">
-                          <span className="text-green-600">+{file.additions}</span>
-                          <span className="text-red-600">-{file.deletions}</span>
-                        </div>
+                        {file.tooLarge ? (
+                          <span className="text-muted-foreground">too large to diff</span>
+                        ) : (
+                          <div className="flex items-center gap-2 text-xs">
+                            <span className="text-green-600">+{file.additions}</span>
+                            <span className="text-red-600">-{file.deletions}</span>
+                          </div>
+                        )}
                       </div>
                     ))}
                   </div>
@@ -15,6 +15,41 @@ export interface ProcessInfo {
   project_path: string;
   task: string;
   model: string;
+  stalled_for_secs?: number;
+  paused?: boolean; // Stopped with SIGSTOP
+}
+
+/**
+ * Counts of running processes for the status bar, also pushed with the
+ * `execution-status-changed` event whenever they change
+ */
+export interface ExecutionStatusSummary {
+  running_agent_runs: number;
+  paused_agent_runs: number;
+  running_sessions: number;
+  paused_sessions: number;
+  stalled: number;
+}
+
+export type StartupPhase = "database" | "claude_dir" | "registry";
+
+/**
+ * A finished startup phase, pushed with the `startup-progress` event
+ */
+export interface StartupProgress {
+  phase: StartupPhase;
+  duration_ms: number;
+}
+
+/**
+ * Progress of the backend's startup, also pushed with the `startup-complete`
+ * event when it ends. Until `ready` commands fail with "[not_ready] ..." and
+ * can be retried.
+ */
+export interface StartupStatus {
+  ready: boolean;
+  completed: StartupProgress[];
+  error?: string | null; // Startup failed; restarting the app is the way out
 }
 
 /**
@@ -25,12 +60,55 @@ export interface Project {
   id: string;
   /** The original project path (decoded from the directory name) */
   path: string;
-  /** List of session IDs (JSONL file names without extension) */
+  /** List of session IDs (JSONL file names without extension); empty when listed with "counts" */
   sessions: string[];
+  /** Number of sessions in the project */
+  session_count: number;
   /** Unix timestamp when the project directory was created */
   created_at: number;
   /** Unix timestamp of the most recent session (if any) */
   most_recent_session?: number;
+  /** Number of agent runs that targeted this project (omitted when run info is skipped) */
+  agent_run_count?: number;
+  /** Creation time of the latest agent run in this project */
+  last_agent_run_at?: string;
+}
+
+/**
+ * How much listProjects returns: "counts" skips the session ID lists
+ */
+export type ProjectListDetail = "counts" | "full";
+
+/**
+ * Project directories whose sessions ran in the same real directory
+ */
+export interface DuplicateProjectGroup {
+  /** The resolved path the directories share */
+  project_path: string;
+  project_ids: string[];
+  /** Claude's current directory for the path if it exists, else the one with the most sessions */
+  suggested_target: string;
+}
+
+/**
+ * One file or directory moved by mergeProjects
+ */
+export interface MergedItem {
+  source_project_id: string;
+  name: string;
+  kind: "session" | "timeline" | "other";
+  /** The target already had an identical copy */
+  duplicate: boolean;
+}
+
+export interface ProjectMergeReport {
+  target_id: string;
+  items: MergedItem[];
+  removed_dirs: string[];
+  /** Source directories left in place because something unexpected remained */
+  kept_dirs: string[];
+  metadata_sessions: number;
+  agent_runs_updated: number;
 }
 
 /**
@@ -51,6 +129,139 @@ export interface Session {
   first_message?: string;
   /** Timestamp of the first user message (if available) */
   message_timestamp?: string;
+  /** Title generated from the first exchange, if any */
+  title?: string;
+}
+
+/**
+ * User-supplied name, favorite flag and notes for a session, mirrored to
+ * ~/.claude/projects/{project}/.opcode-metadata.json
+ */
+export interface SessionMetadata {
+  name?: string;
+  favorite: boolean;
+  notes?: string;
+  /** Title generated from the first exchange; name takes precedence */
+  title?: string;
+  /** RFC 3339; the newer of the DB and the sidecar file wins */
+  updated_at: string;
+}
+
+/**
+ * Content removed from a session export
+ */
+export interface SessionExportFilters {
+  exclude_thinking?: boolean;
+  exclude_tool_inputs?: boolean; // tool_use blocks
+  exclude_tool_results?: boolean; // tool_result blocks and toolUseResult details
+  exclude_attachments?: boolean; // image and document blocks
+}
+
+export type SessionExportFormat = "json" | "markdown";
+
+/**
+ * Written into JSON exports and returned by exportSession
+ */
+export interface SessionExportManifest {
+  project_id: string;
+  session_id: string;
+  exported_at: string;
+  format: SessionExportFormat;
+  filters: Required<SessionExportFilters>;
+  removed: {
+    thinking: number;
+    tool_inputs: number;
+    tool_results: number;
+    attachments: number;
+    messages: number; // Messages left empty by the filters
+  };
+  message_count: number;
+}
+
+/**
+ * Result of importing a session JSONL file into a project
+ */
+export interface SessionImport {
+  session: Session;
+  skipped_lines: number; // Unparseable lines left out
+  original_session_id?: string; // Set when the file's session ID was replaced
+}
+
+export interface ConnectivityCheck {
+  kind: "dns" | "https" | "mcp" | "claude_version" | "claude_ping";
+  target: string; // Host, command or MCP server name
+  status: "pass" | "fail" | "skipped";
+  detail?: string; // Output on success, the error on failure, credentials masked
+  duration_ms: number;
+}
+
+export interface ConnectivityReport {
+  checks: ConnectivityCheck[];
+  proxy_variables: { name: string; value: string }[]; // In effect for the app process
+  proxy_settings_enabled: boolean;
+  checked_at: string;
+}
+
+/**
+ * Token counts above which analyzeAgentPrompt flags a prompt component
+ */
+export interface PromptSoftLimits {
+  system_prompt_tokens: number;
+  task_tokens: number;
+  claude_md_tokens: number; // All CLAUDE.md files together
+  total_tokens: number;
+}
+
+export type PromptRiskLevel = "none" | "low" | "medium" | "high";
+
+/**
+ * A phrase that marks a risky intent in a prompt
+ */
+export interface RiskKeyword {
+  keyword: string;
+  category: string; // e.g. "delete", "rewrite", "migrate", "force_push"
+  level: PromptRiskLevel;
+}
+
+/**
+ * Risky-prompt keywords per locale ("en", "zh-CN", ...); all lists are checked
+ */
+export type PromptRiskKeywords = Record<string, RiskKeyword[]>;
+
+export interface PromptRiskReason {
+  keyword: string;
+  category: string;
+  level: PromptRiskLevel;
+  locale: string;
+}
+
+/**
+ * Result of analyzePromptRisk: the highest level and the keywords that matched
+ */
+export interface PromptRisk {
+  level: PromptRiskLevel;
+  reasons: PromptRiskReason[];
+}
+
+export interface PromptComponent {
+  kind: "system_prompt" | "task" | "claude_md";
+  path?: string; // File path for CLAUDE.md components
+  chars: number;
+  estimated_tokens: number;
+}
+
+/**
+ * Preflight size and cost estimate of an agent run
+ */
+export interface PromptAnalysis {
+  agent_id: number;
+  model: string;
+  components: PromptComponent[];
+  total_tokens: number;
+  estimated_input_cost_usd: number; // First turn, without caching
+  chars_per_token: number; // Tokens are approximated as chars / chars_per_token
+  soft_limits: PromptSoftLimits;
+  warnings: { component: string; estimated_tokens: number; limit: number }[];
 }
 
 /**
@@ -60,6 +271,14 @@ export interface ClaudeSettings {
   [key: string]: any;
 }
 
+/**
+ * A key of the claude CLI config (`claude config`) and its value
+ */
+export interface ClaudeConfigEntry {
+  key: string;
+  value: any;
+}
+
 /**
  * Represents the Claude Code version status
  */
@@ -72,6 +291,37 @@ export interface ClaudeVersionStatus {
   output: string;
 }
 
+/**
+ * A location in ~/.claude that cannot be written
+ */
+export interface ClaudeDirProblem {
+  path: string;
+  /** Coded error, e.g. "[claude_dir_readonly] ..." */
+  error: string;
+}
+
+/**
+ * Whether the app can write to ~/.claude
+ */
+export interface ClaudeDirHealth {
+  claude_dir: string;
+  exists: boolean;
+  /** Whether every checked location accepts writes */
+  writable: boolean;
+  problems: ClaudeDirProblem[];
+  checked_at: string;
+}
+
+/**
+ * Which claude directory the app uses. Switching emits "claude-dir-changed".
+ */
+export interface ClaudeDirSettings {
+  /** Directory used instead of ~/.claude */
+  path?: string | null;
+  /** Writes into the directory fail with "[read_only_mode] ..." */
+  read_only: boolean;
+}
+
 /**
  * Represents a CLAUDE.md file found in the project
  */
@@ -111,6 +361,23 @@ export interface ClaudeInstallation {
   installation_type: "System" | "Custom";
 }
 
+/**
+ * Payload of the `claude-binary-changed` event, emitted when the configured
+ * binary disappeared and another installation was picked automatically.
+ * When none is found, commands fail with the `claude_not_installed` error kind.
+ */
+export interface ClaudeBinaryChanged {
+  previous_path: string;
+  new_path: string;
+  version?: string;
+  source: string;
+}
+
+/**
+ * Output format passed to `claude --output-format`
+ */
+export type ClaudeOutputFormat = 'stream-json' | 'json' | 'text';
+
 // Agent API types
 export interface Agent {
   id?: number;
@@ -122,6 +389,111 @@ export interface Agent {
   hooks?: string; // JSON string of HooksConfiguration
   created_at: string;
   updated_at: string;
+  auto_resume?: boolean; // Resume runs cut off by an app restart
+  mcp_servers?: string; // JSON array of MCP server names or inline definitions
+  retry_on_rate_limit?: boolean; // Requeue runs that hit a rate limit, with backoff
+  thinking_level?: ThinkingLevel; // Extended thinking for the agent's runs
+  output_retention?: OutputRetention; // What happens to output beyond the in-memory cap
+  auto_continue_on_truncation?: boolean; // Continue runs cut off by the output token limit
+  startup_timeout_seconds?: number; // Seconds to wait for first output; unset uses the app default, 0 never times out
+  additional_dirs?: string[]; // Directories outside the project passed to runs with --add-dir
+}
+
+/**
+ * What happens to a run's output beyond the in-memory cap: drop the oldest
+ * lines, move them to the run output file, or drop everything past the cap
+ */
+export type OutputRetention = "truncate" | "spill_to_file" | "discard";
+
+/**
+ * Extended thinking: a keyword level or a max-thinking-tokens count as a string
+ */
+export type ThinkingLevel = "none" | "think" | "think_hard" | "ultrathink" | `${number}`;
+
+/**
+ * What a model can do, for enabling features per selected model
+ */
+export interface ModelCapabilities {
+  id: string; // Full model ID
+  alias?: string; // CLI alias that currently points at this model
+  display_name: string;
+  max_context_tokens: number;
+  supports_thinking: boolean;
+  supports_vision: boolean;
+  default_output_limit: number; // Output tokens a response may use by default
+}
+
+export type PerformanceBucket = "day" | "week" | "month";
+
+export type LeaderboardSort =
+  | "success_rate"
+  | "runs"
+  | "median_duration"
+  | "median_cost"
+  | "total_cost";
+
+export interface RunPerformance {
+  total_runs: number;
+  runs_by_status: Record<string, number>; // completed, failed, cancelled, running, ...
+  success_rate?: number; // Completed share of the completed and failed runs
+  measured_runs: number; // Runs with recorded metrics, which the medians are taken over
+  median_duration_ms?: number;
+  median_cost_usd?: number;
+  total_cost_usd: number;
+}
+
+export interface AgentPerformancePoint extends RunPerformance {
+  bucket_start: string; // First day of the bucket, YYYY-MM-DD (UTC)
+}
+
+export interface AgentLeaderboardEntry extends RunPerformance {
+  agent_id: number;
+  agent_name: string;
+  agent_icon: string;
+}
+
+/**
+ * "interrupted": the job was still running when the app was closed
+ */
+export type JobStatus = "running" | "completed" | "failed" | "cancelled" | "interrupted";
+
+/**
+ * A long-running operation and its outcome, kept across window reloads.
+ * Updates are emitted as "job-updated" events.
+ */
+export interface Job {
+  id: number;
+  /** Command that runs the job, e.g. "export_workspace" */
+  kind: string;
+  params: any;
+  status: JobStatus;
+  /** Share of the work done, from 0 to 1 */
+  progress: number;
+  progress_message?: string | null;
+  /** What the command returned */
+  result?: any;
+  error?: string | null;
+  started_at: string;
+  finished_at?: string | null;
+}
+
+/**
+ * Runs an agent in a project when files matching its patterns change
+ */
+export interface AgentWatcher {
+  id: number;
+  agent_id: number;
+  project_path: string;
+  /** Globs relative to the project, e.g. "src/**\/*.ts" */
+  patterns: string[];
+  debounce_ms: number;
+  min_interval_secs: number;
+  /** `{{changed_files}}` receives the changes; unset uses the agent's default task */
+  task_template?: string | null;
+  enabled: boolean;
+  last_triggered_at?: string | null;
+  last_run_id?: number | null;
+  created_at: string;
 }
 
 export interface AgentExport {
@@ -134,9 +506,21 @@ export interface AgentExport {
     default_task?: string;
     model: string;
     hooks?: string;
+    // Since version 2; version 1 files import with the default permissions
+    enable_file_read?: boolean;
+    enable_file_write?: boolean;
+    enable_network?: boolean;
   };
 }
 
+/**
+ * A GitHub repository directory agents are imported from
+ */
+export interface AgentSource {
+  repo: string; // owner/name
+  path: string; // Directory in the repository, "" for the root
+}
+
 export interface GitHubAgentFile {
   name: string;
   path: string;
@@ -154,124 +538,550 @@ export interface AgentRun {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'running', 'paused', 'completed', 'failed', 'cancelled', 'rate_limited'
   pid?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  stalled_for_secs?: number; // Seconds without output, set while a running run is stalled
+  worktree_path?: string; // Isolated worktree the run executed in
+  failure_reason?: string; // e.g. 'app_restart' when the app quit mid-run, 'no_result' when claude exited without a final result, 'startup_timeout' when it printed nothing in time
+  resumed_from_run_id?: number; // The interrupted run this one resumed
+  mcp_servers?: string[]; // MCP servers injected into the run
+  retried_from_run_id?: number; // The rate-limited run this one retried automatically
+  final_result?: AgentRunFinalResult; // The final result message claude printed
+  execution_profile_id?: number; // Execution profile the run was started with
+  project_path_input?: string; // Project path as entered; project_path is the canonical form
+  thinking_level?: ThinkingLevel; // Thinking level the run was started with
+  file_changes?: RunChangeSummary; // What the run did to the files it wrote
+  output_bytes?: number; // Bytes of stdout and stderr, set when the run finishes
+  output_spilled?: boolean; // Output beyond the in-memory cap went to the run output file
+  output_truncated?: boolean; // Claude's last response stopped at the output token limit
+  continued_from_run_id?: number; // The truncated run this one continued
+  startup_timeout_seconds?: number; // No-output timeout the run was started with, 0 for none
+  additional_dirs?: string[]; // Directories the run was given with --add-dir
+  manual_retry_of_run_id?: number; // The stopped run the user started over with retryAgentRun
 }
 
-export interface AgentRunMetrics {
-  duration_ms?: number;
-  total_tokens?: number;
-  cost_usd?: number;
-  message_count?: number;
+/**
+ * A named bundle of run settings; explicit run parameters override it
+ */
+export interface ExecutionProfile {
+  id?: number;
+  name: string;
+  model?: string;
+  permission_mode?: "default" | "acceptEdits" | "plan" | "bypassPermissions";
+  sandbox_profile_id?: number; // Rejected: this build can't enforce a sandbox
+  env: Record<string, string>;
+  extra_args: string[];
+  created_at: string;
+  updated_at: string;
 }
 
-export interface AgentRunWithMetrics {
-  id?: number;
-  agent_id: number;
-  agent_name: string;
-  agent_icon: string;
-  task: string;
-  model: string;
-  project_path: string;
-  session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
-  pid?: number;
+export type ExecutionProfileInput = Omit<ExecutionProfile, "id" | "created_at" | "updated_at">;
+
+/**
+ * The final `result` message of a claude run, as printed by the CLI
+ */
+export interface AgentRunFinalResult {
+  type: "result";
+  subtype?: string;
+  is_error?: boolean;
+  num_turns?: number;
   duration_ms?: number;
-  total_tokens?: number;
-  process_started_at?: string;
-  created_at: string;
-  completed_at?: string;
-  metrics?: AgentRunMetrics;
-  output?: string; // Real-time JSONL content
+  total_cost_usd?: number;
+  result?: string;
+  usage?: { input_tokens?: number; output_tokens?: number; [key: string]: unknown };
+  [key: string]: unknown;
 }
 
-// Usage Dashboard types
-export interface UsageEntry {
-  project: string;
-  timestamp: string;
-  model: string;
-  input_tokens: number;
-  output_tokens: number;
-  cache_write_tokens: number;
-  cache_read_tokens: number;
-  cost: number;
+/**
+ * A run stopped by an API rate limit or overload
+ * (payload of the `agent-rate-limited` events)
+ */
+export interface RateLimitedEvent {
+  run_id: number;
+  /** 1 for the original run, 2 for its first retry, ... */
+  attempt: number;
+  message: string;
+  /** Wait the API suggested, in seconds */
+  retry_after_secs?: number;
+  /** Set when the run will be retried automatically */
+  next_retry_in_secs?: number;
 }
 
-export interface ModelUsage {
-  model: string;
-  total_cost: number;
-  total_tokens: number;
-  input_tokens: number;
-  output_tokens: number;
-  cache_creation_tokens: number;
-  cache_read_tokens: number;
-  session_count: number;
+/**
+ * An agent's default task and the {{name}} variables it uses
+ */
+export interface TaskTemplate {
+  template?: string;
+  variables: {
+    name: string;
+    /** Filled in automatically ({{date}}, and {{branch}} in git repositories) */
+    auto: boolean;
+  }[];
 }
 
-export interface DailyUsage {
-  date: string;
-  total_cost: number;
-  total_tokens: number;
-  models_used: string[];
+/**
+ * A run started on launch to pick up one interrupted by an app restart
+ * (payload of the `agent-runs-auto-resumed` event), or to continue one cut
+ * off by the output token limit (payload of `agent-run-continued`)
+ */
+export interface AutoResumedRun {
+  original_run_id: number;
+  run_id: number;
+  agent_name: string;
 }
 
-export interface ProjectUsage {
-  project_path: string;
-  project_name: string;
-  total_cost: number;
-  total_tokens: number;
-  session_count: number;
-  last_used: string;
+/**
+ * A file that differs between a project and the isolated worktree of a run
+ */
+export interface WorktreeChange {
+  path: string;
+  status: 'added' | 'modified' | 'deleted';
 }
 
-export interface UsageStats {
-  total_cost: number;
-  total_tokens: number;
-  total_input_tokens: number;
-  total_output_tokens: number;
-  total_cache_creation_tokens: number;
-  total_cache_read_tokens: number;
-  total_sessions: number;
-  by_model: ModelUsage[];
-  by_date: DailyUsage[];
-  by_project: ProjectUsage[];
+/**
+ * Changes of an isolated run; also the payload of `agent-worktree-changes:{runId}`
+ */
+export interface WorktreeDiff {
+  run_id: number;
+  project_path: string;
+  worktree_path: string;
+  changes: WorktreeChange[];
 }
 
 /**
- * Represents a checkpoint in the session timeline
+ * Result of copying worktree changes back into the project
  */
-export interface Checkpoint {
-  id: string;
-  sessionId: string;
-  projectId: string;
-  messageIndex: number;
-  timestamp: string;
-  description?: string;
-  parentCheckpointId?: string;
-  metadata: CheckpointMetadata;
+export interface WorktreeApplyResult {
+  applied: string[];
+  skipped: string[];
 }
 
 /**
- * Metadata associated with a checkpoint
+ * Result of removing expired run worktrees
  */
-export interface CheckpointMetadata {
-  totalTokens: number;
-  modelUsed: string;
-  userPrompt: string;
-  fileChanges: number;
-  snapshotSize: number;
+export interface WorktreeCleanupResult {
+  removed_runs: number[];
+  removed_orphans: number;
 }
 
 /**
- * Represents a file snapshot at a checkpoint
+ * A notice (update, deprecation, auth) the claude CLI printed on stderr.
+ * A new one is announced with the `app-notice` event.
+ */
+export interface AppNotice {
+  id: number;
+  kind: "deprecation" | "update" | "auth" | "warning";
+  message: string;
+  first_seen: string;
+  last_seen: string;
+  /** How many times the CLI printed the same message */
+  count: number;
+  source_run_id?: number;
+  source_session_id?: string;
+  dismissed: boolean;
+}
+
+/**
+ * A parameter of a command or a field of a payload type, as sent over IPC
+ */
+export interface ApiSchemaField {
+  /** Name on the wire (camelCase for command parameters, serde-renamed for fields) */
+  name: string;
+  rustName: string;
+  /** Rust type as written in the source */
+  type: string;
+  optional: boolean;
+  flatten?: boolean;
+}
+
+/**
+ * Approximate context consumption of a session. Always an estimate: it is
+ * derived from the transcript, not from the CLI's own count.
+ */
+export interface ContextEstimate {
+  session_id: string;
+  estimated_tokens: number;
+  context_window: number;
+  percent_used: number;
+  /** Messages after the last compaction boundary */
+  messages_since_compaction: number;
+  compaction_count: number;
+  /** 'usage' when based on reported token usage, 'characters' for a length-based guess */
+  method: "usage" | "characters";
+  is_estimate: true;
+}
+
+/**
+ * Outcome of compacting a session with /compact
+ */
+export interface CompactionResult {
+  /** Session holding the compacted conversation (claude may fork a new one) */
+  session_id: string;
+  before: ContextEstimate;
+  after?: ContextEstimate;
+  /** Estimated tokens freed */
+  tokens_saved?: number;
+}
+
+/**
+ * Patterns masked in claude output before it reaches the UI or the database
+ */
+export interface RedactionSettings {
+  /** Names of the built-in token formats */
+  builtin: string[];
+  /** User regexes, applied after the built-in ones */
+  custom: string[];
+}
+
+/**
+ * Machine-readable description of every registered backend command
+ */
+export interface ApiSchema {
+  version: number;
+  commands: {
+    name: string;
+    module: string;
+    description: string;
+    params: ApiSchemaField[];
+    returns: string;
+    error: string | null;
+  }[];
+  types: Record<string, {
+    kind: "struct" | "enum";
+    module: string;
+    renameAll: string | null;
+    fields?: ApiSchemaField[];
+    variants?: { name: string; rustName: string; payload: string | null }[];
+    tag?: string;
+    untagged?: boolean;
+  }>;
+}
+
+/**
+ * A variable of the environment a claude process would be started with
+ */
+export interface EnvEntry {
+  key: string;
+  /** Masked when the name looks like a secret */
+  value: string;
+  source: "inherited" | "project" | "run";
+  masked: boolean;
+}
+
+/**
+ * Result of a single pre-flight check for an agent run
+ */
+export interface ValidationCheck {
+  check: string;
+  ok: boolean;
+  detail: string;
+  hard: boolean;
+}
+
+export interface AgentRunMetrics {
+  duration_ms?: number;
+  total_tokens?: number;
+  cost_usd?: number;
+  message_count?: number;
+}
+
+/**
+ * Output appended to a run since a cursor.
+ * The cursor is opaque: pass it back unchanged on the next poll.
+ * When `reset` is true, discard previously received output and use `content` as the full output.
+ */
+export interface SessionOutputDelta {
+  content: string;
+  cursor: string;
+  source: 'file' | 'live_buffer';
+  reset: boolean;
+}
+
+/**
+ * A JSONL line that failed to parse
+ */
+export interface MalformedLine {
+  line_number: number;
+  snippet: string;
+  error: string;
+}
+
+/**
+ * Summary of the lines skipped while parsing a session JSONL file.
+ * Also emitted on `session-parse-warnings:{sessionId}` when loading history.
+ */
+export interface JsonlParseReport {
+  total_lines: number;
+  skipped_lines: number;
+  samples: MalformedLine[];
+}
+
+/**
+ * Calls, errors and latency of one MCP tool, per run or rolled up per agent
+ */
+export interface McpToolStats {
+  server: string;
+  tool: string;
+  calls: number;
+  errors: number;
+  error_rate: number;
+  /** Calls the latencies were measured over; 0 when the output had no timestamps */
+  timed_calls: number;
+  p50_ms?: number;
+  p95_ms?: number;
+}
+
+/**
+ * What a run did to the files it wrote, tracked without a checkpoint
+ */
+export interface RunChangeSummary {
+  files_modified: number;
+  files_added: number;
+  files_deleted: number;
+  /** Lines after the run minus lines before */
+  line_delta: number;
+}
+
+/**
+ * Summary of a finished run, the payload of `agent-complete:{runId}` and `run-summary`
+ */
+export interface RunSummary {
+  run_id: number;
+  status: 'completed' | 'failed' | 'rate_limited';
+  duration_ms: number;
+  total_tokens?: number;
+  cost_usd?: number;
+  /** Distinct files the run wrote or edited */
+  artifact_count: number;
+  last_assistant_snippet?: string;
+  metrics_source?: 'jsonl' | 'live_output';
+  file_changes?: RunChangeSummary;
+  /** Claude's last response stopped at the output token limit */
+  output_truncated: boolean;
+}
+
+export interface AgentRunWithMetrics {
+  id?: number;
+  agent_id: number;
+  agent_name: string;
+  agent_icon: string;
+  task: string;
+  model: string;
+  project_path: string;
+  session_id: string;
+  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  pid?: number;
+  duration_ms?: number;
+  total_tokens?: number;
+  process_started_at?: string;
+  created_at: string;
+  completed_at?: string;
+  metrics?: AgentRunMetrics;
+  output?: string; // Real-time JSONL content
+  parse_report?: JsonlParseReport; // Lines skipped while computing metrics
+  metrics_source?: 'jsonl' | 'live_output'; // Session file, or captured output when no JSONL was written
+  output_bytes?: number; // Bytes of stdout and stderr, set when the run finishes
+  output_spilled?: boolean; // Output beyond the in-memory cap went to the run output file
+  output_truncated?: boolean; // Claude's last response stopped at the output token limit
+  continued_from_run_id?: number; // The truncated run this one continued
+  startup_timeout_seconds?: number; // No-output timeout the run was started with, 0 for none
+  additional_dirs?: string[]; // Directories the run was given with --add-dir
+  manual_retry_of_run_id?: number; // The stopped run the user started over with retryAgentRun
+}
+
+// Usage Dashboard types
+export interface UsageEntry {
+  timestamp: string;
+  model: string;
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_tokens: number;
+  cache_read_tokens: number;
+  cost: number;
+  session_id: string;
+  project_path: string;
+}
+
+/**
+ * Filters and page of getUsageDetails
+ */
+export interface UsageDetailsOptions {
+  projectPath?: string;
+  date?: string; // Timestamp prefix, e.g. "2025-01" or "2025-01-31"
+  model?: string;
+  minCost?: number;
+  limit?: number;
+  offset?: number;
+}
+
+/**
+ * One page of usage entries, oldest first, with totals over every match
+ */
+export interface UsageDetailsPage {
+  entries: UsageEntry[];
+  total_count: number;
+  total_cost: number;
+  truncated: boolean; // Set when an unpaginated request hit the row cap
+}
+
+/**
+ * Progress of a usage index build, also sent as usage-index-progress events
+ */
+export interface UsageIndexProgress {
+  files_done: number; // Includes files already indexed when the build started
+  files_total: number;
+  bytes_processed: number;
+  bytes_total: number;
+  last_path?: string; // Last file committed
+  last_mtime?: number;
+  finished: boolean;
+  cancelled: boolean;
+}
+
+export interface UsageIndexStatus {
+  running: boolean;
+  progress?: UsageIndexProgress; // Last build, if any
+}
+
+export interface ModelUsage {
+  model: string;
+  total_cost: number;
+  total_tokens: number;
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_tokens: number;
+  cache_read_tokens: number;
+  session_count: number;
+}
+
+export interface DailyUsage {
+  date: string;
+  total_cost: number;
+  total_tokens: number;
+  models_used: string[];
+}
+
+/**
+ * How far back a project activity feed goes
+ */
+export type ActivityRange = "day" | "week" | "month" | "all";
+
+/**
+ * An agent run, interactive session or checkpoint in a project's activity feed
+ */
+export interface ActivityEntry {
+  kind: "agent_run" | "session" | "checkpoint";
+  timestamp: string;
+  /** Agent task, first prompt of the session, or checkpoint description */
+  title: string;
+  session_id?: string;
+  /** Status of an agent run */
+  status?: string;
+  cost_usd?: number;
+  run_id?: number;
+  agent_name?: string;
+  checkpoint_id?: string;
+  parent_checkpoint_id?: string;
+}
+
+/**
+ * A named read-only query over the app database
+ */
+export interface SavedQuery {
+  name: string;
+  description?: string;
+  sql: string;
+  /** Shipped with the app; cannot be changed or deleted */
+  builtin: boolean;
+  updated_at?: string;
+}
+
+export interface SavedQueryResult {
+  columns: string[];
+  rows: any[][];
+  /** More rows matched than the row limit */
+  truncated: boolean;
+  elapsed_ms: number;
+}
+
+export interface ProjectUsage {
+  project_path: string;
+  project_name: string;
+  total_cost: number;
+  total_tokens: number;
+  session_count: number;
+  last_used: string;
+}
+
+export interface UsageStats {
+  total_cost: number;
+  total_tokens: number;
+  total_input_tokens: number;
+  total_output_tokens: number;
+  total_cache_creation_tokens: number;
+  total_cache_read_tokens: number;
+  total_sessions: number;
+  by_model: ModelUsage[];
+  by_date: DailyUsage[];
+  by_project: ProjectUsage[];
+}
+
+/**
+ * Represents a checkpoint in the session timeline
+ */
+export interface Checkpoint {
+  id: string;
+  sessionId: string;
+  projectId: string;
+  messageIndex: number;
+  timestamp: string;
+  description?: string;
+  parentCheckpointId?: string;
+  kind: CheckpointKind;
+  metadata: CheckpointMetadata;
+}
+
+/**
+ * Why a checkpoint was created; everything but `manual` is automatic
+ */
+export type CheckpointKind = 'manual' | 'auto_prompt' | 'auto_tool' | 'auto_smart' | 'auto_safety';
+
+/**
+ * How many checkpoints of each kind a session keeps. Unset limits keep
+ * everything; the current checkpoint and branch points are never pruned.
+ */
+export interface CheckpointRetention {
+  keepManual?: number;
+  keepAuto?: number;
+  maxAutoBytes?: number;
+}
+
+/**
+ * Metadata associated with a checkpoint
+ */
+export interface CheckpointMetadata {
+  totalTokens: number;
+  modelUsed: string;
+  userPrompt: string;
+  fileChanges: number;
+  snapshotSize: number;
+  totalCostUsd?: number; // Cost of all messages up to this checkpoint; absent in older checkpoints
+}
+
+/**
+ * Represents a file snapshot at a checkpoint
  */
 export interface FileSnapshot {
   checkpointId: string;
   filePath: string;
+  /** Root `filePath` is relative to: 0 for the project, 1.. for the session's additional directories */
+  rootIndex: number;
   content: string;
+  /** File the content is streamed from when it isn't held in memory */
+  sourcePath?: string;
   hash: string;
   isDeleted: boolean;
   permissions?: number;
@@ -285,6 +1095,8 @@ export interface TimelineNode {
   checkpoint: Checkpoint;
   children: TimelineNode[];
   fileSnapshotIds: string[];
+  tokensDelta?: number; // Tokens used since the parent checkpoint
+  costDelta?: number; // Cost in USD since the parent checkpoint, when known
 }
 
 /**
@@ -313,6 +1125,49 @@ export interface CheckpointResult {
   warnings: string[];
 }
 
+/**
+ * Result of repairing a session timeline
+ */
+export interface TimelineRepairReport {
+  sessionId: string;
+  source: 'primary' | 'backup' | 'rebuilt';
+  recoveredCheckpoints: number;
+  warnings: string[];
+}
+
+/**
+ * Payload of the `timeline-updated:{sessionId}` event, emitted whenever a
+ * checkpoint is created, restored or cleaned up, or the settings change.
+ * Re-fetch the timeline only when this arrives instead of polling.
+ */
+export interface TimelineUpdate {
+  sessionId: string;
+  totalCheckpoints: number;
+  currentCheckpointId?: string;
+}
+
+/**
+ * Payload of the `session-externally-modified:{sessionId}` event, emitted when
+ * the session file changed outside the app (e.g. claude ran in a terminal)
+ * and the checkpoint manager reloaded it
+ */
+export interface SessionExternallyModified {
+  sessionId: string;
+  projectId: string;
+}
+
+/**
+ * Payload of the `checkpoint-progress:{sessionId}` event, sent while a
+ * checkpoint is created or restored
+ */
+export interface CheckpointProgress {
+  sessionId: string;
+  phase: "scanning" | "snapshotting" | "saving" | "restoring" | "done";
+  filesDone: number;
+  filesTotal: number;
+  bytesDone: number;
+}
+
 /**
  * Diff between two checkpoints
  */
@@ -332,7 +1187,40 @@ export interface FileDiff {
   path: string;
   additions: number;
   deletions: number;
-  diffContent?: string;
+  diffContent?: string; // Only returned by getFileDiff
+  tooLarge?: boolean; // Skipped: above the size limit for line diffs
+}
+
+/**
+ * A checkpoint that touched a file, from findCheckpointsTouchingFile
+ */
+export interface CheckpointFileMatch {
+  checkpointId: string;
+  timestamp: string;
+  filePath: string; // Relative to the project root, with / separators; "@<n>/" for additional directories
+  change: 'added' | 'modified' | 'deleted';
+}
+
+/**
+ * Deduplication statistics of the checkpoint content pool of a session
+ */
+export interface ContentPoolStats {
+  blobCount: number;
+  totalBytes: number; // Compressed size on disk
+  referencedBlobs: number;
+  unreferencedBlobs: number; // Removed by gcContentPool
+  unreferencedBytes: number;
+  logicalBytes: number; // Uncompressed size of all snapshots
+  dedupRatio: number; // logicalBytes / totalBytes
+}
+
+/**
+ * Checkpoint data of a session whose JSONL no longer exists
+ */
+export interface OrphanedCheckpointData {
+  projectId: string;
+  sessionId: string;
+  sizeBytes: number; // Size of the session's .timelines directory
 }
 
 /**
@@ -359,6 +1247,16 @@ export interface MCPServer {
   status: ServerStatus;
 }
 
+/**
+ * How agent runs receive their MCP servers
+ */
+export interface AgentMcpSettings {
+  /** A temporary --mcp-config file, or merged into the project's .mcp.json */
+  mode: 'mcp_config' | 'merge_project';
+  /** Check each server is reachable before a run starts */
+  health_check: boolean;
+}
+
 /**
  * Server status information
  */
@@ -432,6 +1330,7 @@ export interface AddServerResult {
 export interface ImportResult {
   imported_count: number;
   failed_count: number;
+  skipped_count: number;
   servers: ImportServerResult[];
 }
 
@@ -442,12 +1341,172 @@ export interface ImportServerResult {
   name: string;
   success: boolean;
   error?: string;
+  /** New name when the server was renamed to avoid a conflict */
+  imported_as?: string;
+}
+
+/**
+ * How to handle Claude Desktop servers whose name is already configured
+ */
+export type ImportConflictStrategy = "skip" | "overwrite" | "rename";
+
+/**
+ * Sections written by exportWorkspace; omitted flags keep their default
+ * (everything but session files)
+ */
+export interface WorkspaceSections {
+  agents?: boolean;
+  mcp_servers?: boolean;
+  app_settings?: boolean;
+  execution_profiles?: boolean;
+  session_metadata?: boolean;
+  sessions?: boolean;
+}
+
+/**
+ * Outcome of exporting or importing one workspace section
+ */
+export interface SectionReport {
+  section: string;
+  imported: number;
+  skipped: number;
+  /** A failed section changes nothing; the others are still applied */
+  error?: string;
+}
+
+/**
+ * A server found in the Claude Desktop config
+ */
+export interface DesktopImportCandidate {
+  name: string;
+  command?: string;
+  args: string[];
+  env: Record<string, string>;
+  /** Whether a server with this name is already configured */
+  conflict: boolean;
+  /** Why the server cannot be imported, if it can't */
+  error?: string;
+}
+
+/**
+ * Servers that an import from Claude Desktop would bring in
+ */
+export interface DesktopImportPreview {
+  config_path: string;
+  servers: DesktopImportCandidate[];
+}
+
+/**
+ * A project path after normalization by the backend
+ */
+export interface NormalizedProjectPath {
+  path: string;
+  inside_home: boolean;
+}
+
+/**
+ * Which project files to capture in a template
+ */
+export interface TemplateIncludes {
+  claude_md: boolean;
+  settings: boolean;
+  mcp_config: boolean;
+}
+
+/**
+ * A saved project template (CLAUDE.md, .claude/settings.json, .mcp.json)
+ */
+export interface ProjectTemplate {
+  id: string;
+  name: string;
+  source_project_path: string;
+  files: string[];
+  created_at: string;
+}
+
+/**
+ * Result of initializing a project from a template
+ */
+export interface TemplateApplyResult {
+  target_path: string;
+  written_files: string[];
+  overwritten_files: string[];
+}
+
+/**
+ * Existing claude setup of a project directory
+ */
+export interface ProjectDirectoryInfo {
+  path: string;
+  has_claude_dir: boolean;
+  has_settings: boolean;
+  has_local_settings: boolean;
+  has_claude_md: boolean;
+  has_mcp_json: boolean;
+  is_git_repo: boolean;
+  session_count: number;
+  /** Detected from marker files: "rust", "node", "python" */
+  toolchains: string[];
+}
+
+/**
+ * Starter files to create when initializing a project
+ */
+export interface ProjectInitOptions {
+  claude_md?: boolean;
+  settings?: boolean;
+  local_settings?: boolean;
+  mcp_json?: boolean;
+}
+
+/**
+ * Result of initializing a project's claude setup
+ */
+export interface ProjectInitResult {
+  created_files: string[];
+  skipped_files: string[];
+}
+
+/**
+ * Locale used for backend messages
+ */
+export type BackendLocale = 'en' | 'zh-CN';
+
+/**
+ * A backend error split into its stable kind and localized message
+ */
+export interface BackendError {
+  kind?: string;
+  message: string;
+}
+
+/**
+ * Splits a backend error string of the form `[kind] message` into its parts.
+ * Errors without a kind are returned with only the message.
+ */
+export function parseBackendError(error: unknown): BackendError {
+  const text = error instanceof Error ? error.message : String(error);
+  const match = /^\[([a-z_]+)\] ([\s\S]*)$/.exec(text);
+  return match ? { kind: match[1], message: match[2] } : { message: text };
 }
 
 /**
  * API client for interacting with the Rust backend
  */
 export const api = {
+  /**
+   * Gets how far the backend's startup got; answers while it is starting
+   * @returns Promise resolving to the startup status
+   */
+  async getStartupStatus(): Promise<StartupStatus> {
+    try {
+      return await invoke<StartupStatus>("get_startup_status");
+    } catch (error) {
+      console.error("Failed to get startup status:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets the user's home directory path
    * @returns Promise resolving to the home directory path
@@ -463,17 +1522,44 @@ export const api = {
 
   /**
    * Lists all projects in the ~/.claude/projects directory
+   * @param includeRunInfo - Include agent run counts per project (default true)
+   * @param detail - "counts" to skip session ID lists (default "full")
    * @returns Promise resolving to an array of projects
    */
-  async listProjects(): Promise<Project[]> {
+  async listProjects(includeRunInfo?: boolean, detail?: ProjectListDetail): Promise<Project[]> {
     try {
-      return await invoke<Project[]>("list_projects");
+      return await invoke<Project[]>("list_projects", { includeRunInfo, detail });
     } catch (error) {
       console.error("Failed to list projects:", error);
       throw error;
     }
   },
 
+  /**
+   * Finds project directories that are the same repository under different names
+   */
+  async findDuplicateProjects(): Promise<DuplicateProjectGroup[]> {
+    try {
+      return await invoke<DuplicateProjectGroup[]>("find_duplicate_projects");
+    } catch (error) {
+      console.error("Failed to find duplicate projects:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Moves the sessions, timelines and metadata of the source projects into the
+   * target and removes the emptied source directories
+   */
+  async mergeProjects(sourceIds: string[], targetId: string): Promise<ProjectMergeReport> {
+    try {
+      return await invoke<ProjectMergeReport>("merge_projects", { sourceIds, targetId });
+    } catch (error) {
+      console.error("Failed to merge projects:", error);
+      throw error;
+    }
+  },
+
   /**
    * Creates a new project for the given directory path
    * @param path - The directory path to create a project for
@@ -488,8 +1574,26 @@ export const api = {
     }
   },
 
+  /**
+   * Normalizes a pasted or dropped project path (quotes, file://, ~, env vars)
+   * and checks that it is an existing directory
+   * @param input - The raw path entered by the user
+   * @returns Promise resolving to the normalized path and whether it is inside the home directory
+   */
+  async resolveProjectPath(input: string): Promise<NormalizedProjectPath> {
+    try {
+      return await invoke<NormalizedProjectPath>('resolve_project_path', { input });
+    } catch (error) {
+      console.error("Failed to resolve project path:", error);
+      throw error;
+    }
+  },
+
   /**
    * Retrieves sessions for a specific project
+   *
+   * Uncached first messages are `undefined` and follow as
+   * `session-summary-ready:{sessionId}` events.
    * @param projectId - The ID of the project to retrieve sessions for
    * @returns Promise resolving to an array of sessions
    */
@@ -502,13 +1606,338 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the names, favorites and notes of a project's sessions
+   * @param projectId - The project ID
+   * @returns Promise resolving to metadata keyed by session ID
+   */
+  async getSessionMetadata(projectId: string): Promise<Record<string, SessionMetadata>> {
+    try {
+      return await invoke<Record<string, SessionMetadata>>('get_session_metadata', { projectId });
+    } catch (error) {
+      console.error("Failed to get session metadata:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets a session's name, favorite flag and notes
+   * @param projectId - The project ID
+   * @param sessionId - The session ID
+   * @param favorite - Whether the session is pinned
+   * @param name - Optional display name (cleared if empty)
+   * @param notes - Optional notes (cleared if empty)
+   * @returns Promise resolving to the saved metadata
+   */
+  async setSessionMetadata(
+    projectId: string,
+    sessionId: string,
+    favorite: boolean,
+    name?: string,
+    notes?: string
+  ): Promise<SessionMetadata> {
+    try {
+      return await invoke<SessionMetadata>('set_session_metadata', {
+        projectId,
+        sessionId,
+        name,
+        favorite,
+        notes
+      });
+    } catch (error) {
+      console.error("Failed to set session metadata:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Generates a short session title from its first exchange, locally
+   * @param projectId - The project ID
+   * @param sessionId - The session ID
+   * @param regenerate - Replace a title that was generated before
+   * @returns Promise resolving to the stored title
+   */
+  async generateSessionTitle(
+    projectId: string,
+    sessionId: string,
+    regenerate?: boolean
+  ): Promise<string> {
+    try {
+      return await invoke<string>('generate_session_title', {
+        projectId,
+        sessionId,
+        regenerate
+      });
+    } catch (error) {
+      console.error("Failed to generate session title:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Writes the session metadata of every project to one file
+   * @param path - Destination file
+   * @returns Promise resolving to the number of sessions exported
+   */
+  async exportSessionMetadata(path: string): Promise<number> {
+    try {
+      return await invoke<number>('export_session_metadata', { path });
+    } catch (error) {
+      console.error("Failed to export session metadata:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Merges an exported session metadata file; newer entries win
+   * @param path - File written by exportSessionMetadata
+   * @returns Promise resolving to the number of sessions updated
+   */
+  async importSessionMetadata(path: string): Promise<number> {
+    try {
+      return await invoke<number>('import_session_metadata', { path });
+    } catch (error) {
+      console.error("Failed to import session metadata:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Write agents, MCP servers, settings, execution profiles and session
+   * metadata to one archive file. Machine-specific settings are left out
+   * and MCP server env/header values are replaced with "[REDACTED]"
+   */
+  async exportWorkspace(filePath: string, include?: WorkspaceSections): Promise<SectionReport[]> {
+    try {
+      return await invoke<SectionReport[]>('export_workspace', { filePath, include });
+    } catch (error) {
+      console.error("Failed to export workspace:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Apply a workspace archive section by section
+   */
+  async importWorkspace(
+    filePath: string,
+    conflictStrategy: ImportConflictStrategy = "skip"
+  ): Promise<SectionReport[]> {
+    try {
+      return await invoke<SectionReport[]>('import_workspace', { filePath, conflictStrategy });
+    } catch (error) {
+      console.error("Failed to import workspace:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Export a session to a file, removing the filtered content in the backend
+   */
+  async exportSession(
+    projectId: string,
+    sessionId: string,
+    format: SessionExportFormat,
+    filters: SessionExportFilters,
+    path: string
+  ): Promise<SessionExportManifest> {
+    try {
+      return await invoke<SessionExportManifest>('export_session', {
+        projectId,
+        sessionId,
+        format,
+        filters,
+        path,
+      });
+    } catch (error) {
+      console.error("Failed to export session:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Import a session JSONL file into a project
+   * @param force - Import even when the file is above the size limit
+   */
+  async importSessionFile(filePath: string, targetProjectPath: string, force?: boolean): Promise<SessionImport> {
+    try {
+      return await invoke<SessionImport>('import_session_file', { filePath, targetProjectPath, force });
+    } catch (error) {
+      console.error("Failed to import session file:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Get the size in bytes above which session imports need force
+   */
+  async getSessionImportMaxBytes(): Promise<number> {
+    try {
+      return await invoke<number>('get_session_import_max_bytes');
+    } catch (error) {
+      console.error("Failed to get session import size limit:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Set the size in bytes above which session imports need force
+   */
+  async setSessionImportMaxBytes(maxBytes: number): Promise<void> {
+    try {
+      await invoke('set_session_import_max_bytes', { maxBytes });
+    } catch (error) {
+      console.error("Failed to set session import size limit:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Check DNS/HTTPS reachability of GitHub and Anthropic, the claude CLI and
+   * the configured MCP HTTP servers through the app's proxy environment
+   * @param projectPath - Also check the project's MCP servers
+   * @param pingClaude - Send a minimal claude request (default true)
+   */
+  async runConnectivityDoctor(projectPath?: string, pingClaude?: boolean): Promise<ConnectivityReport> {
+    try {
+      return await invoke<ConnectivityReport>('run_connectivity_doctor', { projectPath, pingClaude });
+    } catch (error) {
+      console.error("Failed to run connectivity doctor:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Estimate the prompt size and first-turn input cost of running an agent
+   */
+  async analyzeAgentPrompt(agentId: number, task: string, projectPath: string): Promise<PromptAnalysis> {
+    try {
+      return await invoke<PromptAnalysis>('analyze_agent_prompt', { agentId, task, projectPath });
+    } catch (error) {
+      console.error("Failed to analyze agent prompt:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Get the prompt soft limits
+   */
+  async getPromptSoftLimits(): Promise<PromptSoftLimits> {
+    try {
+      return await invoke<PromptSoftLimits>('get_prompt_soft_limits');
+    } catch (error) {
+      console.error("Failed to get prompt soft limits:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Save the prompt soft limits
+   */
+  async setPromptSoftLimits(limits: PromptSoftLimits): Promise<void> {
+    try {
+      await invoke('set_prompt_soft_limits', { limits });
+    } catch (error) {
+      console.error("Failed to save prompt soft limits:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Flag destructive intents in a prompt from local keyword lists; no network calls
+   */
+  async analyzePromptRisk(prompt: string): Promise<PromptRisk> {
+    try {
+      return await invoke<PromptRisk>('analyze_prompt_risk', { prompt });
+    } catch (error) {
+      console.error("Failed to analyze prompt risk:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Get the risky-prompt keywords per locale
+   */
+  async getPromptRiskKeywords(): Promise<PromptRiskKeywords> {
+    try {
+      return await invoke<PromptRiskKeywords>('get_prompt_risk_keywords');
+    } catch (error) {
+      console.error("Failed to get prompt risk keywords:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Save the risky-prompt keywords per locale
+   */
+  async setPromptRiskKeywords(keywords: PromptRiskKeywords): Promise<void> {
+    try {
+      await invoke('set_prompt_risk_keywords', { keywords });
+    } catch (error) {
+      console.error("Failed to save prompt risk keywords:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * List execution profiles
+   */
+  async listExecutionProfiles(): Promise<ExecutionProfile[]> {
+    try {
+      return await invoke<ExecutionProfile[]>('list_execution_profiles');
+    } catch (error) {
+      console.error("Failed to list execution profiles:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Create an execution profile
+   */
+  async createExecutionProfile(profile: ExecutionProfileInput): Promise<ExecutionProfile> {
+    try {
+      return await invoke<ExecutionProfile>('create_execution_profile', { profile });
+    } catch (error) {
+      console.error("Failed to create execution profile:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Replace the settings of an execution profile
+   */
+  async updateExecutionProfile(id: number, profile: ExecutionProfileInput): Promise<ExecutionProfile> {
+    try {
+      return await invoke<ExecutionProfile>('update_execution_profile', { id, profile });
+    } catch (error) {
+      console.error("Failed to update execution profile:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Delete an execution profile
+   */
+  async deleteExecutionProfile(id: number): Promise<void> {
+    try {
+      await invoke('delete_execution_profile', { id });
+    } catch (error) {
+      console.error("Failed to delete execution profile:", error);
+      throw error;
+    }
+  },
+
   /**
    * Fetch list of agents from GitHub repository
+   * @param source - Repository directory to list, the official one by default
    * @returns Promise resolving to list of available agents on GitHub
    */
-  async fetchGitHubAgents(): Promise<GitHubAgentFile[]> {
+  async fetchGitHubAgents(source?: AgentSource): Promise<GitHubAgentFile[]> {
     try {
-      return await invoke<GitHubAgentFile[]>('fetch_github_agents');
+      return await invoke<GitHubAgentFile[]>('fetch_github_agents', {
+        repo: source?.repo,
+        path: source?.path,
+      });
     } catch (error) {
       console.error("Failed to fetch GitHub agents:", error);
       throw error;
@@ -532,17 +1961,89 @@ export const api = {
   /**
    * Import an agent directly from GitHub
    * @param downloadUrl - The download URL for the agent file
+   * @param source - Repository directory the file was listed from, the official one by default
    * @returns Promise resolving to the imported agent
    */
-  async importAgentFromGitHub(downloadUrl: string): Promise<Agent> {
+  async importAgentFromGitHub(downloadUrl: string, source?: AgentSource): Promise<Agent> {
     try {
-      return await invoke<Agent>('import_agent_from_github', { downloadUrl });
+      return await invoke<Agent>('import_agent_from_github', {
+        downloadUrl,
+        repo: source?.repo,
+        path: source?.path,
+      });
     } catch (error) {
       console.error("Failed to import agent from GitHub:", error);
       throw error;
     }
   },
 
+  /**
+   * Whether a GitHub token is saved for fetching agents
+   */
+  async hasGitHubToken(): Promise<boolean> {
+    try {
+      return await invoke<boolean>('has_github_token');
+    } catch (error) {
+      console.error("Failed to check GitHub token:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves the token sent with GitHub requests, for private repositories and a
+   * higher rate limit. Rate-limited requests fail with "[rate_limited] ..."
+   * naming the time the limit resets.
+   * @param token - The token, or null to remove it
+   */
+  async setGitHubToken(token: string | null): Promise<void> {
+    try {
+      await invoke('set_github_token', { token });
+    } catch (error) {
+      console.error("Failed to save GitHub token:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the repositories agents can be imported from, the official one first
+   */
+  async listAgentSources(): Promise<AgentSource[]> {
+    try {
+      return await invoke<AgentSource[]>('list_agent_sources');
+    } catch (error) {
+      console.error("Failed to list agent sources:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Remembers a repository directory to import agents from
+   * @param repo - owner/name of the GitHub repository
+   * @param path - Directory of the agent files, the repository root by default
+   * @returns Promise resolving to all agent sources
+   */
+  async addAgentSource(repo: string, path?: string): Promise<AgentSource[]> {
+    try {
+      return await invoke<AgentSource[]>('add_agent_source', { repo, path });
+    } catch (error) {
+      console.error("Failed to add agent source:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Forgets a repository added with addAgentSource
+   * @returns Promise resolving to the remaining agent sources
+   */
+  async removeAgentSource(repo: string, path?: string): Promise<AgentSource[]> {
+    try {
+      return await invoke<AgentSource[]>('remove_agent_source', { repo, path });
+    } catch (error) {
+      console.error("Failed to remove agent source:", error);
+      throw error;
+    }
+  },
+
   /**
    * Reads the Claude settings file
    * @returns Promise resolving to the settings object
@@ -566,6 +2067,43 @@ export const api = {
     }
   },
 
+  /**
+   * Reads the claude CLI config via `claude config get/list`
+   * @param key - Key to read; every key when omitted
+   * @param projectPath - Project whose local config to read (home directory if omitted)
+   * @returns Promise resolving to the matching entries
+   */
+  async getClaudeConfig(key?: string, projectPath?: string): Promise<ClaudeConfigEntry[]> {
+    try {
+      return await invoke<ClaudeConfigEntry[]>("get_claude_config", { key, projectPath });
+    } catch (error) {
+      console.error("Failed to get Claude config:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets a claude CLI config key via `claude config set`
+   * @param key - Key to set
+   * @param value - New value, as it would be typed on the command line
+   * @param global - Set the user-wide value instead of the project's
+   * @param projectPath - Project whose local config to change (home directory if omitted)
+   * @returns Promise resolving to the key's new value
+   */
+  async setClaudeConfig(
+    key: string,
+    value: string,
+    global: boolean,
+    projectPath?: string
+  ): Promise<ClaudeConfigEntry[]> {
+    try {
+      return await invoke<ClaudeConfigEntry[]>("set_claude_config", { key, value, global, projectPath });
+    } catch (error) {
+      console.error("Failed to set Claude config:", error);
+      throw error;
+    }
+  },
+
   /**
    * Opens a new Claude Code session
    * @param path - Optional path to open the session in
@@ -580,6 +2118,189 @@ export const api = {
     }
   },
 
+  /**
+   * Opens the user's terminal in a directory
+   * @param path - Directory to open the terminal in
+   * @param command - Optional command to run there first, e.g. `claude --resume <id>`
+   * @returns Promise resolving to the terminal that was opened; the
+   *          `terminal_app` setting overrides the platform default
+   */
+  async openTerminalAt(path: string, command?: string): Promise<string> {
+    try {
+      return await invoke<string>("open_terminal_at", { path, command });
+    } catch (error) {
+      console.error("Failed to open terminal:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Opens a file inside a known project in the user's editor
+   * @param path - File to open, e.g. one mentioned in a run's output
+   * @param line - Optional line to jump to
+   * @param column - Optional column to jump to
+   * @returns Promise resolving once the editor was started; uses the
+   *          `editor_command` setting (e.g. `code -g {path}:{line}:{column}`)
+   *          or the OS default opener, and fails with "[editor_not_found] ..."
+   *          when the configured editor isn't installed
+   */
+  async openInEditor(path: string, line?: number, column?: number): Promise<void> {
+    try {
+      return await invoke("open_in_editor", { path, line, column });
+    } catch (error) {
+      console.error("Failed to open file in editor:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the known models with their capabilities
+   */
+  async getAvailableModels(): Promise<ModelCapabilities[]> {
+    try {
+      return await invoke<ModelCapabilities[]>("get_available_models");
+    } catch (error) {
+      console.error("Failed to get available models:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the capabilities of a model or alias such as "sonnet"
+   * @returns Promise resolving to null for models not in the registry
+   */
+  async getModelCapabilities(model: string): Promise<ModelCapabilities | null> {
+    try {
+      return await invoke<ModelCapabilities | null>("get_model_capabilities", { model });
+    } catch (error) {
+      console.error("Failed to get model capabilities:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets run counts, success rate and median duration and cost of an agent's runs per bucket
+   * @param range - How far back to look (default: all runs)
+   * @param bucket - Length of each bucket (default: week)
+   */
+  async getAgentPerformance(
+    agentId: number,
+    range?: ActivityRange,
+    bucket?: PerformanceBucket
+  ): Promise<AgentPerformancePoint[]> {
+    try {
+      return await invoke<AgentPerformancePoint[]>("get_agent_performance", { agentId, range, bucket });
+    } catch (error) {
+      console.error("Failed to get agent performance:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Compares all agents' performance over a range, best first
+   * @param sortBy - What to rank by (default: success rate)
+   */
+  async getAgentsLeaderboard(
+    range?: ActivityRange,
+    sortBy?: LeaderboardSort
+  ): Promise<AgentLeaderboardEntry[]> {
+    try {
+      return await invoke<AgentLeaderboardEntry[]>("get_agents_leaderboard", { range, sortBy });
+    } catch (error) {
+      console.error("Failed to get agents leaderboard:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Recent jobs, newest first
+   */
+  async listJobs(limit?: number): Promise<Job[]> {
+    try {
+      return await invoke<Job[]>("list_jobs", { limit });
+    } catch (error) {
+      console.error("Failed to list jobs:", error);
+      throw error;
+    }
+  },
+
+  async getJob(id: number): Promise<Job> {
+    try {
+      return await invoke<Job>("get_job", { id });
+    } catch (error) {
+      console.error("Failed to get job:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Ask a running job to stop at its next safe point
+   * @returns false if the job is not running
+   */
+  async cancelJob(id: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>("cancel_job", { id });
+    } catch (error) {
+      console.error("Failed to cancel job:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Watch a project and run an agent when matching files change
+   * @param debounceMs - Quiet time after the last change (default 2000)
+   * @param minIntervalSecs - Shortest time between two runs (default 60)
+   */
+  async createAgentWatcher(
+    agentId: number,
+    projectPath: string,
+    patterns: string[],
+    debounceMs?: number,
+    minIntervalSecs?: number,
+    taskTemplate?: string
+  ): Promise<AgentWatcher> {
+    try {
+      return await invoke<AgentWatcher>("create_agent_watcher", {
+        agentId,
+        projectPath,
+        patterns,
+        debounceMs,
+        minIntervalSecs,
+        taskTemplate,
+      });
+    } catch (error) {
+      console.error("Failed to create agent watcher:", error);
+      throw error;
+    }
+  },
+
+  async setAgentWatcherEnabled(id: number, enabled: boolean): Promise<AgentWatcher> {
+    try {
+      return await invoke<AgentWatcher>("set_agent_watcher_enabled", { id, enabled });
+    } catch (error) {
+      console.error("Failed to update agent watcher:", error);
+      throw error;
+    }
+  },
+
+  async listAgentWatchers(agentId?: number): Promise<AgentWatcher[]> {
+    try {
+      return await invoke<AgentWatcher[]>("list_agent_watchers", { agentId });
+    } catch (error) {
+      console.error("Failed to list agent watchers:", error);
+      throw error;
+    }
+  },
+
+  async deleteAgentWatcher(id: number): Promise<void> {
+    try {
+      await invoke("delete_agent_watcher", { id });
+    } catch (error) {
+      console.error("Failed to delete agent watcher:", error);
+      throw error;
+    }
+  },
+
   /**
    * Reads the CLAUDE.md system prompt file
    * @returns Promise resolving to the system prompt content
@@ -588,20 +2309,56 @@ export const api = {
     try {
       return await invoke<string>("get_system_prompt");
     } catch (error) {
-      console.error("Failed to get system prompt:", error);
+      console.error("Failed to get system prompt:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Checks if Claude Code is installed and gets its version
+   * @returns Promise resolving to the version status
+   */
+  async checkClaudeVersion(): Promise<ClaudeVersionStatus> {
+    try {
+      return await invoke<ClaudeVersionStatus>("check_claude_version");
+    } catch (error) {
+      console.error("Failed to check Claude version:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Checks whether ~/.claude and the files saved in it are writable
+   * @returns Promise resolving to the directory's health
+   */
+  async checkClaudeDirHealth(): Promise<ClaudeDirHealth> {
+    try {
+      return await invoke<ClaudeDirHealth>("check_claude_dir_health");
+    } catch (error) {
+      console.error("Failed to check ~/.claude health:", error);
+      throw error;
+    }
+  },
+
+  async getClaudeDirSettings(): Promise<ClaudeDirSettings> {
+    try {
+      return await invoke<ClaudeDirSettings>("get_claude_dir_settings");
+    } catch (error) {
+      console.error("Failed to get claude directory settings:", error);
       throw error;
     }
   },
 
   /**
-   * Checks if Claude Code is installed and gets its version
-   * @returns Promise resolving to the version status
+   * Point the app at another claude directory, e.g. a copied backup
+   * @param path - The directory to use, or null for ~/.claude
+   * @param readOnly - Refuse writes into the directory
    */
-  async checkClaudeVersion(): Promise<ClaudeVersionStatus> {
+  async switchClaudeDir(path: string | null, readOnly: boolean): Promise<ClaudeDirSettings> {
     try {
-      return await invoke<ClaudeVersionStatus>("check_claude_version");
+      return await invoke<ClaudeDirSettings>("switch_claude_dir", { path, readOnly });
     } catch (error) {
-      console.error("Failed to check Claude version:", error);
+      console.error("Failed to switch claude directory:", error);
       throw error;
     }
   },
@@ -700,6 +2457,14 @@ export const api = {
    * @param default_task - Optional default task
    * @param model - Optional model (defaults to 'sonnet')
    * @param hooks - Optional hooks configuration as JSON string
+   * @param auto_resume - Resume runs interrupted by an app restart
+   * @param mcp_servers - Optional JSON array of MCP servers injected into runs
+   * @param retry_on_rate_limit - Requeue runs that hit a rate limit, with backoff
+   * @param thinking_level - Extended thinking for the agent's runs
+   * @param output_retention - What happens to output beyond the in-memory cap
+   * @param auto_continue_on_truncation - Continue runs cut off by the output token limit
+   * @param startup_timeout_seconds - Seconds to wait for first output (app default if omitted, 0 never times out)
+   * @param additional_dirs - Directories outside the project passed to runs with --add-dir
    * @returns Promise resolving to the created agent
    */
   async createAgent(
@@ -708,7 +2473,15 @@ export const api = {
     system_prompt: string, 
     default_task?: string, 
     model?: string,
-    hooks?: string
+    hooks?: string,
+    auto_resume?: boolean,
+    mcp_servers?: string,
+    retry_on_rate_limit?: boolean,
+    thinking_level?: ThinkingLevel,
+    output_retention?: OutputRetention,
+    auto_continue_on_truncation?: boolean,
+    startup_timeout_seconds?: number,
+    additional_dirs?: string[]
   ): Promise<Agent> {
     try {
       return await invoke<Agent>('create_agent', { 
@@ -717,7 +2490,15 @@ export const api = {
         systemPrompt: system_prompt,
         defaultTask: default_task,
         model,
-        hooks
+        hooks,
+        autoResume: auto_resume,
+        mcpServers: mcp_servers,
+        retryOnRateLimit: retry_on_rate_limit,
+        thinkingLevel: thinking_level,
+        outputRetention: output_retention,
+        autoContinueOnTruncation: auto_continue_on_truncation,
+        startupTimeoutSeconds: startup_timeout_seconds,
+        additionalDirs: additional_dirs
       });
     } catch (error) {
       console.error("Failed to create agent:", error);
@@ -734,6 +2515,14 @@ export const api = {
    * @param default_task - Optional default task
    * @param model - Optional model
    * @param hooks - Optional hooks configuration as JSON string
+   * @param auto_resume - Resume runs interrupted by an app restart (unchanged if omitted)
+   * @param mcp_servers - Optional JSON array of MCP servers injected into runs
+   * @param retry_on_rate_limit - Requeue runs that hit a rate limit (unchanged if omitted)
+   * @param thinking_level - Extended thinking for the agent's runs
+   * @param output_retention - What happens to output beyond the in-memory cap (unchanged if omitted)
+   * @param auto_continue_on_truncation - Continue runs cut off by the output token limit (unchanged if omitted)
+   * @param startup_timeout_seconds - Seconds to wait for first output (unchanged if omitted, 0 never times out)
+   * @param additional_dirs - Directories outside the project passed to runs with --add-dir (unchanged if omitted)
    * @returns Promise resolving to the updated agent
    */
   async updateAgent(
@@ -743,7 +2532,15 @@ export const api = {
     system_prompt: string, 
     default_task?: string, 
     model?: string,
-    hooks?: string
+    hooks?: string,
+    auto_resume?: boolean,
+    mcp_servers?: string,
+    retry_on_rate_limit?: boolean,
+    thinking_level?: ThinkingLevel,
+    output_retention?: OutputRetention,
+    auto_continue_on_truncation?: boolean,
+    startup_timeout_seconds?: number,
+    additional_dirs?: string[]
   ): Promise<Agent> {
     try {
       return await invoke<Agent>('update_agent', { 
@@ -753,7 +2550,15 @@ export const api = {
         systemPrompt: system_prompt,
         defaultTask: default_task,
         model,
-        hooks
+        hooks,
+        autoResume: auto_resume,
+        mcpServers: mcp_servers,
+        retryOnRateLimit: retry_on_rate_limit,
+        thinkingLevel: thinking_level,
+        outputRetention: output_retention,
+        autoContinueOnTruncation: auto_continue_on_truncation,
+        startupTimeoutSeconds: startup_timeout_seconds,
+        additionalDirs: additional_dirs
       });
     } catch (error) {
       console.error("Failed to update agent:", error);
@@ -775,6 +2580,37 @@ export const api = {
     }
   },
 
+  /**
+   * Deletes an agent run and its recorded metrics
+   * @param runId - The run ID to delete
+   * @param force - Kill the run first if it is still running instead of failing
+   * @returns Promise resolving to the number of runs removed
+   */
+  async deleteAgentRun(runId: number, force?: boolean): Promise<number> {
+    try {
+      return await invoke<number>('delete_agent_run', { runId, force });
+    } catch (error) {
+      console.error("Failed to delete agent run:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Deletes the runs of an agent older than a number of days, keeping the agent
+   * @param agentId - The agent whose run history is pruned
+   * @param olderThanDays - Only runs created more than this many days ago are removed
+   * @param force - Kill runs that are still running instead of failing
+   * @returns Promise resolving to the number of runs removed
+   */
+  async deleteAgentRuns(agentId: number, olderThanDays: number, force?: boolean): Promise<number> {
+    try {
+      return await invoke<number>('delete_agent_runs', { agentId, olderThanDays, force });
+    } catch (error) {
+      console.error("Failed to delete agent runs:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets a single agent by ID
    * @param id - The agent ID
@@ -831,17 +2667,39 @@ export const api = {
     }
   },
 
+  /**
+   * Validates an agent run without spawning claude (draft mode)
+   * @param agentId - The agent ID to validate
+   * @param projectPath - The project path the agent would run in
+   * @param task - The task the agent would run
+   * @param model - Optional model override
+   * @returns Promise resolving to the list of checks
+   */
+  async validateAgentExecution(agentId: number, projectPath: string, task: string, model?: string): Promise<ValidationCheck[]> {
+    try {
+      return await invoke<ValidationCheck[]>('validate_agent_execution', { agentId, projectPath, task, model });
+    } catch (error) {
+      console.error("Failed to validate agent execution:", error);
+      throw error;
+    }
+  },
+
   /**
    * Executes an agent
    * @param agentId - The agent ID to execute
    * @param projectPath - The project path to run the agent in
    * @param task - The task description
    * @param model - Optional model override
+   * @param runInIsolatedWorktree - Run against a disposable worktree instead of the project
+   * @param envOverrides - Environment variables for this run only, applied over the project env
+   * @param disableRedaction - Keep secrets unmasked in this run's output, for debugging
+   * @param variables - Values for {{name}} placeholders in the task; when given, {{date}} and {{branch}} are filled in automatically
+   * @param thinkingLevel - Overrides the agent's thinking level; "none" turns thinking off
    * @returns Promise resolving to the run ID when execution starts
    */
-  async executeAgent(agentId: number, projectPath: string, task: string, model?: string): Promise<number> {
+  async executeAgent(agentId: number, projectPath: string, task: string, model?: string, outputFormat?: ClaudeOutputFormat, interactive?: boolean, validateFirst?: boolean, runInIsolatedWorktree?: boolean, envOverrides?: Record<string, string>, disableRedaction?: boolean, variables?: Record<string, string>, executionProfileId?: number, thinkingLevel?: ThinkingLevel): Promise<number> {
     try {
-      return await invoke<number>('execute_agent', { agentId, projectPath, task, model });
+      return await invoke<number>('execute_agent', { agentId, projectPath, task, model, outputFormat, interactive, validateFirst, runInIsolatedWorktree, envOverrides, disableRedaction, variables, executionProfileId, thinkingLevel });
     } catch (error) {
       console.error("Failed to execute agent:", error);
       // Return a sentinel value to indicate error
@@ -867,11 +2725,12 @@ export const api = {
   /**
    * Lists agent runs with metrics (includes token counts and duration)
    * @param agentId - Optional agent ID to filter runs
+   * @param strict - Fail on malformed JSONL lines instead of skipping them
    * @returns Promise resolving to an array of agent runs with metrics
    */
-  async listAgentRunsWithMetrics(agentId?: number): Promise<AgentRunWithMetrics[]> {
+  async listAgentRunsWithMetrics(agentId?: number, strict?: boolean): Promise<AgentRunWithMetrics[]> {
     try {
-      return await invoke<AgentRunWithMetrics[]>('list_agent_runs_with_metrics', { agentId });
+      return await invoke<AgentRunWithMetrics[]>('list_agent_runs_with_metrics', { agentId, strict });
     } catch (error) {
       console.error("Failed to list agent runs with metrics:", error);
       // Return empty array instead of throwing to prevent UI crashes
@@ -893,14 +2752,43 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the MCP tool calls of a run, per server and tool
+   * @param runId - The run ID
+   * @returns Promise resolving to the stats of each MCP tool the run used
+   */
+  async getRunMcpStats(runId: number): Promise<McpToolStats[]> {
+    try {
+      return await invoke<McpToolStats[]>('get_run_mcp_stats', { runId });
+    } catch (error) {
+      console.error("Failed to get run MCP stats:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the MCP tool calls of all runs of an agent, per server and tool
+   * @param agentId - The agent ID
+   * @returns Promise resolving to the stats of each MCP tool the agent used
+   */
+  async getAgentToolStats(agentId: number): Promise<McpToolStats[]> {
+    try {
+      return await invoke<McpToolStats[]>('get_agent_tool_stats', { agentId });
+    } catch (error) {
+      console.error("Failed to get agent tool stats:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets a single agent run by ID with real-time metrics from JSONL
    * @param id - The run ID
+   * @param strict - Fail on malformed JSONL lines instead of skipping them
    * @returns Promise resolving to the agent run with metrics
    */
-  async getAgentRunWithRealTimeMetrics(id: number): Promise<AgentRunWithMetrics> {
+  async getAgentRunWithRealTimeMetrics(id: number, strict?: boolean): Promise<AgentRunWithMetrics> {
     try {
-      return await invoke<AgentRunWithMetrics>('get_agent_run_with_real_time_metrics', { id });
+      return await invoke<AgentRunWithMetrics>('get_agent_run_with_real_time_metrics', { id, strict });
     } catch (error) {
       console.error("Failed to get agent run with real-time metrics:", error);
       throw new Error(`Failed to get agent run with real-time metrics: ${error instanceof Error ? error.message : 'Unknown error'}`);
@@ -920,6 +2808,19 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the counts of running and paused agent runs and sessions
+   * @returns Promise resolving to the current execution status
+   */
+  async getExecutionStatusSummary(): Promise<ExecutionStatusSummary> {
+    try {
+      return await invoke<ExecutionStatusSummary>('get_execution_status_summary');
+    } catch (error) {
+      console.error("Failed to get execution status summary:", error);
+      throw error;
+    }
+  },
+
   /**
    * Kills a running agent session
    * @param runId - The run ID to kill
@@ -934,6 +2835,46 @@ export const api = {
     }
   },
 
+  /**
+   * Suspends the process of a running agent run (not supported on Windows)
+   * @returns Promise resolving to whether the run's status changed
+   */
+  async pauseAgentRun(runId: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('pause_agent_run', { runId });
+    } catch (error) {
+      console.error("Failed to pause agent run:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Continues the process of a paused agent run
+   * @returns Promise resolving to whether the run's status changed
+   */
+  async resumeAgentRun(runId: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('resume_agent_run', { runId });
+    } catch (error) {
+      console.error("Failed to resume agent run:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Starts a stopped run over with the same agent, task, model and project
+   * @param runId - The run to retry
+   * @returns Promise resolving to the ID of the new run
+   */
+  async retryAgentRun(runId: number): Promise<number> {
+    try {
+      return await invoke<number>('retry_agent_run', { runId });
+    } catch (error) {
+      console.error("Failed to retry agent run:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets the status of a specific agent session
    * @param runId - The run ID to check
@@ -975,6 +2916,37 @@ export const api = {
     }
   },
 
+  /**
+   * Get only the output appended since the previous poll
+   * @param runId - The run ID to get output for
+   * @param cursor - Cursor returned by the previous call (omit on the first call)
+   * @param consumerId - Without a cursor, resume from the cursor saved for this consumer
+   * @returns Promise resolving to the new output and the cursor for the next call
+   */
+  async getSessionOutputDelta(runId: number, cursor?: string, consumerId?: string): Promise<SessionOutputDelta> {
+    try {
+      return await invoke<SessionOutputDelta>('get_session_output_delta', { runId, cursor, consumerId });
+    } catch (error) {
+      console.error("Failed to get session output delta:", error);
+      throw new Error(`Failed to get session output delta: ${error instanceof Error ? error.message : 'Unknown error'}`);
+    }
+  },
+
+  /**
+   * Save how far a consumer has read a run's output, so it resumes there after a restart
+   * @param runId - The run ID
+   * @param consumerId - Identifies the reader, e.g. a view
+   * @param cursor - Cursor of a delta whose content has been applied
+   */
+  async saveOutputCursor(runId: number, consumerId: string, cursor: string): Promise<void> {
+    try {
+      await invoke('save_output_cursor', { runId, consumerId, cursor });
+    } catch (error) {
+      console.error("Failed to save output cursor:", error);
+      throw error;
+    }
+  },
+
   /**
    * Get live output directly from process stdout buffer
    * @param runId - The run ID to get live output for
@@ -1005,20 +2977,22 @@ export const api = {
 
   /**
    * Loads the JSONL history for a specific session
+   * Skipped lines are reported on `session-parse-warnings:{sessionId}`
    */
-  async loadSessionHistory(sessionId: string, projectId: string): Promise<any[]> {
-    return invoke("load_session_history", { sessionId, projectId });
+  async loadSessionHistory(sessionId: string, projectId: string, strict?: boolean): Promise<any[]> {
+    return invoke("load_session_history", { sessionId, projectId, strict });
   },
 
   /**
    * Loads the JSONL history for a specific agent session
    * Similar to loadSessionHistory but searches across all project directories
    * @param sessionId - The session ID (UUID)
+   * @param strict - Fail on malformed JSONL lines instead of skipping them
    * @returns Promise resolving to array of session messages
    */
-  async loadAgentSessionHistory(sessionId: string): Promise<any[]> {
+  async loadAgentSessionHistory(sessionId: string, strict?: boolean): Promise<any[]> {
     try {
-      return await invoke<any[]>('load_agent_session_history', { sessionId });
+      return await invoke<any[]>('load_agent_session_history', { sessionId, strict });
     } catch (error) {
       console.error("Failed to load agent session history:", error);
       throw error;
@@ -1027,23 +3001,30 @@ export const api = {
 
   /**
    * Executes a new interactive Claude Code session with streaming output
+   *
+   * `additionalDirs` are passed to claude with `--add-dir` and covered by the
+   * session's checkpoints; each must be an existing directory.
    */
-  async executeClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return invoke("execute_claude_code", { projectPath, prompt, model });
+  async executeClaudeCode(projectPath: string, prompt: string, model?: string, outputFormat?: ClaudeOutputFormat, interactive?: boolean, envOverrides?: Record<string, string>, disableRedaction?: boolean, executionProfileId?: number, additionalDirs?: string[]): Promise<void> {
+    return invoke("execute_claude_code", { projectPath, prompt, model, outputFormat, interactive, envOverrides, disableRedaction, executionProfileId, additionalDirs });
   },
 
   /**
    * Continues an existing Claude Code conversation with streaming output
    */
-  async continueClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return invoke("continue_claude_code", { projectPath, prompt, model });
+  async continueClaudeCode(projectPath: string, prompt: string, model: string, outputFormat?: ClaudeOutputFormat, interactive?: boolean, envOverrides?: Record<string, string>, disableRedaction?: boolean): Promise<void> {
+    return invoke("continue_claude_code", { projectPath, prompt, model, outputFormat, interactive, envOverrides, disableRedaction });
   },
 
   /**
    * Resumes an existing Claude Code session by ID with streaming output
+   *
+   * Fails with `[session_in_other_project] ...: <path>` when the session
+   * belongs to another project; `force` skips that check. `additionalDirs`
+   * are passed with `--add-dir`, as for `executeClaudeCode`.
    */
-  async resumeClaudeCode(projectPath: string, sessionId: string, prompt: string, model: string): Promise<void> {
-    return invoke("resume_claude_code", { projectPath, sessionId, prompt, model });
+  async resumeClaudeCode(projectPath: string, sessionId: string, prompt: string, model: string, outputFormat?: ClaudeOutputFormat, interactive?: boolean, envOverrides?: Record<string, string>, disableRedaction?: boolean, force?: boolean, additionalDirs?: string[]): Promise<void> {
+    return invoke("resume_claude_code", { projectPath, sessionId, prompt, model, outputFormat, interactive, envOverrides, disableRedaction, force, additionalDirs });
   },
 
   /**
@@ -1054,6 +3035,22 @@ export const api = {
     return invoke("cancel_claude_execution", { sessionId });
   },
 
+  /**
+   * Sends a follow-up message to an interactive agent run or Claude session
+   * @param runOrSessionId - Agent run ID or Claude session ID
+   */
+  async sendSessionInput(runOrSessionId: string, text: string): Promise<void> {
+    return invoke("send_session_input", { runOrSessionId, text });
+  },
+
+  /**
+   * Closes the input of an interactive run so it finishes after the current turn
+   * @param runOrSessionId - Agent run ID or Claude session ID
+   */
+  async closeSessionInput(runOrSessionId: string): Promise<void> {
+    return invoke("close_session_input", { runOrSessionId });
+  },
+
   /**
    * Lists all currently running Claude sessions
    * @returns Promise resolving to list of running Claude sessions
@@ -1113,6 +3110,84 @@ export const api = {
     }
   },
 
+  /**
+   * Gets a project's agent runs, sessions and checkpoints as one feed, newest first
+   * @param projectPath - The project directory
+   * @param range - How far back to go (defaults to all time)
+   * @param limit - Maximum number of entries (defaults to 200)
+   * @returns Promise resolving to the activity entries
+   */
+  async getProjectActivity(
+    projectPath: string,
+    range?: ActivityRange,
+    limit?: number
+  ): Promise<ActivityEntry[]> {
+    try {
+      return await invoke<ActivityEntry[]>("get_project_activity", {
+        projectPath,
+        range,
+        limit,
+      });
+    } catch (error) {
+      console.error("Failed to get project activity:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the built-in and user saved queries
+   */
+  async listSavedQueries(): Promise<SavedQuery[]> {
+    try {
+      return await invoke<SavedQuery[]>("list_saved_queries");
+    } catch (error) {
+      console.error("Failed to list saved queries:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Creates or replaces a saved query; only a single SELECT statement is accepted
+   * @param name - Query name
+   * @param sql - The SELECT statement, with optional :named parameters
+   * @param description - Optional description
+   */
+  async saveSavedQuery(name: string, sql: string, description?: string): Promise<void> {
+    try {
+      return await invoke<void>("save_saved_query", { name, sql, description });
+    } catch (error) {
+      console.error("Failed to save query:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Deletes a user saved query
+   * @param name - Query name
+   */
+  async deleteSavedQuery(name: string): Promise<void> {
+    try {
+      return await invoke<void>("delete_saved_query", { name });
+    } catch (error) {
+      console.error("Failed to delete saved query:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Runs a saved query against a read-only connection (row and time limited)
+   * @param name - Query name
+   * @param params - Values for :named parameters, keyed without the colon
+   */
+  async runSavedQuery(name: string, params?: Record<string, string | number | boolean | null>): Promise<SavedQueryResult> {
+    try {
+      return await invoke<SavedQueryResult>("run_saved_query", { name, params });
+    } catch (error) {
+      console.error("Failed to run saved query:", error);
+      throw error;
+    }
+  },
+
   /**
    * Gets usage statistics grouped by session
    * @param since - Optional start date (YYYYMMDD)
@@ -1138,19 +3213,57 @@ export const api = {
   },
 
   /**
-   * Gets detailed usage entries with optional filtering
-   * @param limit - Optional limit for number of entries
-   * @returns Promise resolving to array of usage entries
+   * Gets detailed usage entries with optional filtering and paging
+   * @param options - Filters, plus limit/offset to page through the entries
+   * @returns Promise resolving to the requested page and the match totals
    */
-  async getUsageDetails(limit?: number): Promise<UsageEntry[]> {
+  async getUsageDetails(options: UsageDetailsOptions = {}): Promise<UsageDetailsPage> {
     try {
-      return await invoke<UsageEntry[]>("get_usage_details", { limit });
+      return await invoke<UsageDetailsPage>("get_usage_details", { ...options });
     } catch (error) {
       console.error("Failed to get usage details:", error);
       throw error;
     }
   },
 
+  /**
+   * Build or refresh the usage index in the background; an interrupted build
+   * resumes after its last committed batch
+   * @returns false when a build is already running
+   */
+  async buildUsageIndex(): Promise<boolean> {
+    try {
+      return await invoke<boolean>("build_usage_index");
+    } catch (error) {
+      console.error("Failed to start usage index build:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Stop the running usage index build after its current batch
+   */
+  async cancelUsageIndexBuild(): Promise<boolean> {
+    try {
+      return await invoke<boolean>("cancel_usage_index_build");
+    } catch (error) {
+      console.error("Failed to cancel usage index build:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Get the progress of the last usage index build
+   */
+  async getUsageIndexStatus(): Promise<UsageIndexStatus> {
+    try {
+      return await invoke<UsageIndexStatus>("get_usage_index_status");
+    } catch (error) {
+      console.error("Failed to get usage index status:", error);
+      throw error;
+    }
+  },
+
   /**
    * Creates a checkpoint for the current session state
    */
@@ -1159,14 +3272,16 @@ export const api = {
     projectId: string,
     projectPath: string,
     messageIndex?: number,
-    description?: string
+    description?: string,
+    kind?: CheckpointKind
   ): Promise<CheckpointResult> {
     return invoke("create_checkpoint", {
       sessionId,
       projectId,
       projectPath,
       messageIndex,
-      description
+      description,
+      kind
     });
   },
 
@@ -1246,14 +3361,16 @@ export const api = {
     projectId: string,
     projectPath: string,
     autoCheckpointEnabled: boolean,
-    checkpointStrategy: CheckpointStrategy
+    checkpointStrategy: CheckpointStrategy,
+    retention?: CheckpointRetention
   ): Promise<void> {
     return invoke("update_checkpoint_settings", {
       sessionId,
       projectId,
       projectPath,
       autoCheckpointEnabled,
-      checkpointStrategy
+      checkpointStrategy,
+      retention
     });
   },
 
@@ -1279,6 +3396,106 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the content diff of one file between two checkpoints
+   */
+  async getFileDiff(
+    fromCheckpointId: string,
+    toCheckpointId: string,
+    sessionId: string,
+    projectId: string,
+    path: string
+  ): Promise<FileDiff> {
+    try {
+      return await invoke<FileDiff>("get_file_diff", {
+        fromCheckpointId,
+        toCheckpointId,
+        sessionId,
+        projectId,
+        path
+      });
+    } catch (error) {
+      console.error("Failed to get file diff:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Finds the checkpoints of a session that touched files matching a glob
+   * @param sessionId - The session ID
+   * @param projectId - The project ID
+   * @param pathGlob - Path relative to the project root, e.g. "src/auth.rs" or "**\/*.sql"
+   * @param rebuild - Re-index the session's checkpoints first, for timelines
+   *                  created before the file index existed
+   * @returns Promise resolving to the matches, oldest first
+   */
+  async findCheckpointsTouchingFile(
+    sessionId: string,
+    projectId: string,
+    pathGlob: string,
+    rebuild?: boolean
+  ): Promise<CheckpointFileMatch[]> {
+    try {
+      return await invoke<CheckpointFileMatch[]>("find_checkpoints_touching_file", {
+        sessionId,
+        projectId,
+        pathGlob,
+        rebuild
+      });
+    } catch (error) {
+      console.error("Failed to find checkpoints touching file:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Reports how much the content pool of a session saves through deduplication
+   */
+  async getContentPoolStats(projectId: string, sessionId: string): Promise<ContentPoolStats> {
+    try {
+      return await invoke<ContentPoolStats>("get_content_pool_stats", { projectId, sessionId });
+    } catch (error) {
+      console.error("Failed to get content pool stats:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Removes content pool blobs no checkpoint refers to; returns how many were removed
+   */
+  async gcContentPool(projectId: string, sessionId: string): Promise<number> {
+    try {
+      return await invoke<number>("gc_content_pool", { projectId, sessionId });
+    } catch (error) {
+      console.error("Failed to garbage collect content pool:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Finds checkpoint data of sessions whose JSONL no longer exists
+   */
+  async findOrphanedCheckpointData(): Promise<OrphanedCheckpointData[]> {
+    try {
+      return await invoke<OrphanedCheckpointData[]>("find_orphaned_checkpoint_data");
+    } catch (error) {
+      console.error("Failed to find orphaned checkpoint data:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Deletes the selected orphaned checkpoint data, returning what was deleted
+   */
+  async purgeOrphanedCheckpointData(entries: OrphanedCheckpointData[]): Promise<OrphanedCheckpointData[]> {
+    try {
+      return await invoke<OrphanedCheckpointData[]>("purge_orphaned_checkpoint_data", { entries });
+    } catch (error) {
+      console.error("Failed to purge orphaned checkpoint data:", error);
+      throw error;
+    }
+  },
+
   /**
    * Tracks a message for checkpointing
    */
@@ -1330,14 +3547,16 @@ export const api = {
     sessionId: string,
     projectId: string,
     projectPath: string,
-    keepCount: number
+    keepCount?: number,
+    retention?: CheckpointRetention
   ): Promise<number> {
     try {
       return await invoke<number>("cleanup_old_checkpoints", {
         sessionId,
         projectId,
         projectPath,
-        keepCount
+        keepCount,
+        retention
       });
     } catch (error) {
       console.error("Failed to cleanup old checkpoints:", error);
@@ -1355,29 +3574,58 @@ export const api = {
   ): Promise<{
     auto_checkpoint_enabled: boolean;
     checkpoint_strategy: CheckpointStrategy;
+    retention: CheckpointRetention;
     total_checkpoints: number;
     current_checkpoint_id?: string;
+    last_checkpoint_at?: string; // Newest checkpoint on any branch
+    seconds_since_last_checkpoint?: number;
+    auto_checkpoint_pending: boolean; // Scheduled for when the project's current run ends
   }> {
     try {
-      return await invoke("get_checkpoint_settings", {
-        sessionId,
-        projectId,
-        projectPath
-      });
+      return await invoke("get_checkpoint_settings", {
+        sessionId,
+        projectId,
+        projectPath
+      });
+    } catch (error) {
+      console.error("Failed to get checkpoint settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Clears checkpoint manager for a session (cleanup on session end)
+   */
+  async clearCheckpointManager(sessionId: string): Promise<void> {
+    try {
+      await invoke("clear_checkpoint_manager", { sessionId });
+    } catch (error) {
+      console.error("Failed to clear checkpoint manager:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Cancels a checkpoint being created; no checkpoint is added to the timeline
+   * @returns Whether the session had a checkpoint manager to cancel
+   */
+  async cancelCheckpointCreation(sessionId: string): Promise<boolean> {
+    try {
+      return await invoke<boolean>("cancel_checkpoint_creation", { sessionId });
     } catch (error) {
-      console.error("Failed to get checkpoint settings:", error);
+      console.error("Failed to cancel checkpoint creation:", error);
       throw error;
     }
   },
 
   /**
-   * Clears checkpoint manager for a session (cleanup on session end)
+   * Repairs an unreadable timeline from its backup or the checkpoints on disk
    */
-  async clearCheckpointManager(sessionId: string): Promise<void> {
+  async repairTimeline(sessionId: string, projectId: string): Promise<TimelineRepairReport> {
     try {
-      await invoke("clear_checkpoint_manager", { sessionId });
+      return await invoke<TimelineRepairReport>("repair_timeline", { sessionId, projectId });
     } catch (error) {
-      console.error("Failed to clear checkpoint manager:", error);
+      console.error("Failed to repair timeline:", error);
       throw error;
     }
   },
@@ -1484,6 +3732,38 @@ export const api = {
     }
   },
 
+  /**
+   * Lists the servers in the Claude Desktop config, annotated with conflicts, without importing
+   */
+  async mcpPreviewClaudeDesktopImport(): Promise<DesktopImportPreview> {
+    try {
+      return await invoke<DesktopImportPreview>("mcp_preview_claude_desktop_import");
+    } catch (error) {
+      console.error("Failed to preview Claude Desktop import:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Imports only the selected Claude Desktop servers; rolls back if any of them fails
+   */
+  async mcpApplyClaudeDesktopImport(
+    selectedNames: string[],
+    scope: string = "local",
+    conflictStrategy: ImportConflictStrategy = "skip"
+  ): Promise<ImportResult> {
+    try {
+      return await invoke<ImportResult>("mcp_apply_claude_desktop_import", {
+        selectedNames,
+        scope,
+        conflictStrategy
+      });
+    } catch (error) {
+      console.error("Failed to apply Claude Desktop import:", error);
+      throw error;
+    }
+  },
+
   /**
    * Starts Claude Code as an MCP server
    */
@@ -1556,6 +3836,30 @@ export const api = {
     }
   },
 
+  /**
+   * Gets how agent MCP servers are injected into runs
+   */
+  async getAgentMcpSettings(): Promise<AgentMcpSettings> {
+    try {
+      return await invoke<AgentMcpSettings>("get_agent_mcp_settings");
+    } catch (error) {
+      console.error("Failed to get agent MCP settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves how agent MCP servers are injected into runs
+   */
+  async setAgentMcpSettings(settings: AgentMcpSettings): Promise<void> {
+    try {
+      return await invoke<void>("set_agent_mcp_settings", { settings });
+    } catch (error) {
+      console.error("Failed to save agent MCP settings:", error);
+      throw error;
+    }
+  },
+
   /**
    * Get the stored Claude binary path from settings
    * @returns Promise resolving to the path if set, null otherwise
@@ -1583,6 +3887,55 @@ export const api = {
     }
   },
 
+  /**
+   * Get how long a run may go without output before it is reported as stalled
+   */
+  async getStallWarningSeconds(): Promise<number> {
+    try {
+      return await invoke<number>("get_stall_warning_seconds");
+    } catch (error) {
+      console.error("Failed to get stall warning interval:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Set the stall warning interval in seconds (0 disables stall detection)
+   */
+  async setStallWarningSeconds(seconds: number): Promise<void> {
+    try {
+      return await invoke<void>("set_stall_warning_seconds", { seconds });
+    } catch (error) {
+      console.error("Failed to set stall warning interval:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Get how long runs may take to print their first output before they are
+   * killed, for agents without their own timeout
+   */
+  async getStartupTimeoutSeconds(): Promise<number> {
+    try {
+      return await invoke<number>("get_startup_timeout_seconds");
+    } catch (error) {
+      console.error("Failed to get startup timeout:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Set the default startup timeout in seconds (0 never times out)
+   */
+  async setStartupTimeoutSeconds(seconds: number): Promise<void> {
+    try {
+      return await invoke<void>("set_startup_timeout_seconds", { seconds });
+    } catch (error) {
+      console.error("Failed to set startup timeout:", error);
+      throw error;
+    }
+  },
+
   /**
    * List all available Claude installations on the system
    * @returns Promise resolving to an array of Claude installations
@@ -1942,4 +4295,306 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the locale used for backend messages
+   * @returns Promise resolving to the current backend locale
+   */
+  async getBackendLocale(): Promise<BackendLocale> {
+    try {
+      return await invoke<BackendLocale>("get_backend_locale");
+    } catch (error) {
+      console.error("Failed to get backend locale:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets and persists the locale used for backend messages
+   * @param locale - "en" or "zh-CN"
+   * @returns Promise resolving to the locale that was applied
+   */
+  async setBackendLocale(locale: BackendLocale): Promise<BackendLocale> {
+    try {
+      return await invoke<BackendLocale>("set_backend_locale", { locale });
+    } catch (error) {
+      console.error("Failed to set backend locale:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists saved project templates
+   * @returns Promise resolving to the saved templates
+   */
+  async listProjectTemplates(): Promise<ProjectTemplate[]> {
+    try {
+      return await invoke<ProjectTemplate[]>("list_project_templates");
+    } catch (error) {
+      console.error("Failed to list project templates:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves files of an existing project as a template
+   * @param name - Display name of the template
+   * @param sourceProjectPath - Project to copy the files from
+   * @param include - Which files to capture
+   * @returns Promise resolving to the saved template
+   */
+  async saveProjectTemplate(
+    name: string,
+    sourceProjectPath: string,
+    include: TemplateIncludes
+  ): Promise<ProjectTemplate> {
+    try {
+      return await invoke<ProjectTemplate>("save_project_template", {
+        name,
+        sourceProjectPath,
+        include
+      });
+    } catch (error) {
+      console.error("Failed to save project template:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Initializes a project directory from a template.
+   * Variables such as {{PROJECT_NAME}}, {{PROJECT_PATH}} and {{DATE}} are substituted.
+   * @param targetPath - Directory to initialize (created if missing)
+   * @param templateId - The template ID
+   * @param force - Overwrite files that already exist
+   * @returns Promise resolving to the files written
+   */
+  async createProjectFromTemplate(
+    targetPath: string,
+    templateId: string,
+    force?: boolean
+  ): Promise<TemplateApplyResult> {
+    try {
+      return await invoke<TemplateApplyResult>("create_project_from_template", {
+        targetPath,
+        templateId,
+        force
+      });
+    } catch (error) {
+      console.error("Failed to create project from template:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Reports whether a directory already has a claude setup. Never modifies anything.
+   * @param path - Directory to inspect
+   */
+  async inspectProjectDirectory(path: string): Promise<ProjectDirectoryInfo> {
+    try {
+      return await invoke<ProjectDirectoryInfo>("inspect_project_directory", { path });
+    } catch (error) {
+      console.error("Failed to inspect project directory:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Creates the selected starter files; existing files are kept
+   * @param path - Project directory
+   * @param options - Which files to create
+   */
+  async initializeProjectClaudeDir(path: string, options: ProjectInitOptions): Promise<ProjectInitResult> {
+    try {
+      return await invoke<ProjectInitResult>("initialize_project_claude_dir", { path, options });
+    } catch (error) {
+      console.error("Failed to initialize project:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the changes an isolated agent run made compared to its project
+   * @param runId - The run ID
+   */
+  async getWorktreeChanges(runId: number): Promise<WorktreeDiff> {
+    try {
+      return await invoke<WorktreeDiff>("get_worktree_changes", { runId });
+    } catch (error) {
+      console.error("Failed to get worktree changes:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Copies selected changes of an isolated run back into the project
+   * @param runId - The run ID
+   * @param paths - Relative paths taken from getWorktreeChanges
+   */
+  async applyWorktreeChanges(runId: number, paths: string[]): Promise<WorktreeApplyResult> {
+    try {
+      return await invoke<WorktreeApplyResult>("apply_worktree_changes", { runId, paths });
+    } catch (error) {
+      console.error("Failed to apply worktree changes:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Removes worktrees of runs that finished longer ago than the retention window
+   * @param retentionHours - Defaults to 72 hours
+   */
+  async cleanupAgentWorktrees(retentionHours?: number): Promise<WorktreeCleanupResult> {
+    try {
+      return await invoke<WorktreeCleanupResult>("cleanup_agent_worktrees", { retentionHours });
+    } catch (error) {
+      console.error("Failed to clean up agent worktrees:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the environment variables configured for a project
+   * @param projectPath - The project path
+   */
+  async getProjectEnv(projectPath: string): Promise<Record<string, string>> {
+    try {
+      return await invoke<Record<string, string>>("get_project_env", { projectPath });
+    } catch (error) {
+      console.error("Failed to get project env:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Replaces the environment variables configured for a project
+   * @param projectPath - The project path
+   * @param env - The full set of variables to keep
+   */
+  async setProjectEnv(projectPath: string, env: Record<string, string>): Promise<void> {
+    try {
+      return await invoke<void>("set_project_env", { projectPath, env });
+    } catch (error) {
+      console.error("Failed to set project env:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Previews the merged environment a claude process would get, with secrets masked
+   * @param projectPath - The project path
+   * @param envOverrides - Optional per-run variables
+   */
+  async previewClaudeEnv(projectPath: string, envOverrides?: Record<string, string>): Promise<EnvEntry[]> {
+    try {
+      return await invoke<EnvEntry[]>("preview_claude_env", { projectPath, envOverrides });
+    } catch (error) {
+      console.error("Failed to preview claude env:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists notices collected from claude's stderr, most recent first
+   * @param includeDismissed - Also return dismissed notices
+   */
+  async getAppNotices(includeDismissed?: boolean): Promise<AppNotice[]> {
+    try {
+      return await invoke<AppNotice[]>("get_app_notices", { includeDismissed });
+    } catch (error) {
+      console.error("Failed to get app notices:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Dismisses a notice; it stays hidden if the CLI prints it again
+   * @param id - The notice ID
+   */
+  async dismissAppNotice(id: number): Promise<void> {
+    try {
+      return await invoke<void>("dismiss_app_notice", { id });
+    } catch (error) {
+      console.error("Failed to dismiss app notice:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Describes every backend command, its parameters and payload types
+   */
+  async generateApiSchema(): Promise<ApiSchema> {
+    try {
+      return await invoke<ApiSchema>("generate_api_schema");
+    } catch (error) {
+      console.error("Failed to generate API schema:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the built-in and user output redaction patterns
+   */
+  async getRedactionSettings(): Promise<RedactionSettings> {
+    try {
+      return await invoke<RedactionSettings>("get_redaction_settings");
+    } catch (error) {
+      console.error("Failed to get redaction settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Replaces the user redaction patterns (regexes)
+   * @param patterns - Patterns to mask in addition to the built-in ones
+   */
+  async setRedactionPatterns(patterns: string[]): Promise<void> {
+    try {
+      return await invoke<void>("set_redaction_patterns", { patterns });
+    } catch (error) {
+      console.error("Failed to save redaction patterns:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Estimates how much context a session uses since its last compaction
+   * @param projectId - The project ID
+   * @param sessionId - The session ID
+   */
+  async estimateSessionContext(projectId: string, sessionId: string): Promise<ContextEstimate> {
+    try {
+      return await invoke<ContextEstimate>("estimate_session_context", { projectId, sessionId });
+    } catch (error) {
+      console.error("Failed to estimate session context:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Runs /compact on a session and reports the estimated reduction
+   * @param projectId - The project ID
+   * @param sessionId - The session ID
+   * @param instructions - Optional focus instructions for the summary
+   */
+  async compactSession(projectId: string, sessionId: string, instructions?: string): Promise<CompactionResult> {
+    try {
+      return await invoke<CompactionResult>("compact_session", { projectId, sessionId, instructions });
+    } catch (error) {
+      console.error("Failed to compact session:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets an agent's default task with the template variables it uses
+   * @param agentId - The agent ID
+   */
+  async getAgentTaskTemplate(agentId: number): Promise<TaskTemplate> {
+    try {
+      return await invoke<TaskTemplate>("get_agent_task_template", { agentId });
+    } catch (error) {
+      console.error("Failed to get agent task template:", error);
+      throw error;
+    }
+  },
+
 };